@@ -0,0 +1,106 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::Duration;
+
+/// Per-query control over the results cache, i.e. from a `Cache-Control` HTTP header or the
+/// `cache-control` Flight metadata key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// Don't serve the result from the cache (`no-cache` or `no-store`).
+    pub skip_read: bool,
+
+    /// Don't store the result in the cache (`no-store`).
+    pub skip_write: bool,
+
+    /// How long to keep the result in the cache instead of the configured `item_ttl`
+    /// (`max-age=<seconds>`).
+    pub ttl: Option<Duration>,
+}
+
+impl CacheControl {
+    /// Parses the directives of a `Cache-Control` value. Unknown directives are ignored.
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        let mut cache_control = Self::default();
+
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "no-cache" => cache_control.skip_read = true,
+                "no-store" => {
+                    cache_control.skip_read = true;
+                    cache_control.skip_write = true;
+                }
+                "max-age" => {
+                    if let Some(seconds) = argument.and_then(|argument| argument.parse().ok()) {
+                        cache_control.ttl = Some(Duration::from_secs(seconds));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        cache_control
+    }
+
+    /// Whether the cache is bypassed, either for reading or writing.
+    #[must_use]
+    pub fn bypasses_cache(&self) -> bool {
+        self.skip_read || self.skip_write
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cache_control() {
+        assert_eq!(CacheControl::parse(""), CacheControl::default());
+        assert_eq!(
+            CacheControl::parse("no-cache"),
+            CacheControl {
+                skip_read: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            CacheControl::parse("No-Store"),
+            CacheControl {
+                skip_read: true,
+                skip_write: true,
+                ttl: None,
+            }
+        );
+        assert_eq!(
+            CacheControl::parse("no-cache, max-age=60"),
+            CacheControl {
+                skip_read: true,
+                skip_write: false,
+                ttl: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(
+            CacheControl::parse("max-age=soon, private"),
+            CacheControl::default()
+        );
+    }
+}
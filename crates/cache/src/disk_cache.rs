@@ -0,0 +1,492 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::ipc;
+use crate::key_for_logical_plan;
+use crate::CachedQueryResult;
+use crate::FailedToCreateCacheDirectorySnafu;
+use crate::FailedToDecodeCacheEntrySnafu;
+use crate::FailedToEncodeCacheEntrySnafu;
+use crate::FailedToReadCacheEntrySnafu;
+use crate::FailedToRunCacheTaskSnafu;
+use crate::FailedToWriteCacheEntrySnafu;
+use crate::QueryResultCache;
+use crate::Result;
+use async_trait::async_trait;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::sql::TableReference;
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+const ENTRY_EXTENSION: &str = "arrow";
+
+struct DiskCacheEntry {
+    size_bytes: u64,
+    input_tables: Arc<HashSet<TableReference>>,
+    expires_at: SystemTime,
+    last_access: u64,
+}
+
+#[derive(Default)]
+struct DiskCacheIndex {
+    entries: HashMap<u64, DiskCacheEntry>,
+    size_bytes: u64,
+    access_counter: u64,
+}
+
+impl DiskCacheIndex {
+    fn touch(&mut self, key: u64) {
+        self.access_counter += 1;
+        let access = self.access_counter;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_access = access;
+        }
+    }
+
+    fn insert(&mut self, key: u64, mut entry: DiskCacheEntry) {
+        self.access_counter += 1;
+        entry.last_access = self.access_counter;
+        self.size_bytes += entry.size_bytes;
+        if let Some(previous) = self.entries.insert(key, entry) {
+            self.size_bytes -= previous.size_bytes;
+        }
+    }
+
+    fn remove(&mut self, key: u64) -> bool {
+        let Some(entry) = self.entries.remove(&key) else {
+            return false;
+        };
+        self.size_bytes -= entry.size_bytes;
+        true
+    }
+
+    /// Removes the least recently used entries until the cache fits in `max_size`.
+    fn evict_to(&mut self, max_size: u64) -> Vec<u64> {
+        let mut evicted = vec![];
+        while self.size_bytes > max_size {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.remove(key);
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+/// Stores cached query results on local disk as Arrow IPC files, so they survive a restart.
+///
+/// The files are indexed in memory by their key, input tables and expiry time. The index is
+/// rebuilt from the files in the cache directory on startup.
+pub struct DiskCache {
+    path: PathBuf,
+    cache_max_size: u64,
+    ttl: Duration,
+    index: Mutex<DiskCacheIndex>,
+}
+
+impl DiskCache {
+    /// # Errors
+    ///
+    /// Will return `Err` if the cache directory can't be created.
+    pub fn try_new(path: impl Into<PathBuf>, cache_max_size: u64, ttl: Duration) -> Result<Self> {
+        let path = path.into();
+        std::fs::create_dir_all(&path)
+            .context(FailedToCreateCacheDirectorySnafu { path: path.clone() })?;
+
+        let cache = DiskCache {
+            index: Mutex::new(load_index(&path)),
+            path,
+            cache_max_size,
+            ttl,
+        };
+        cache.evict();
+
+        Ok(cache)
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        entry_path(&self.path, key)
+    }
+
+    fn evict(&self) {
+        let evicted = match self.index.lock() {
+            Ok(mut index) => index.evict_to(self.cache_max_size),
+            Err(_) => return,
+        };
+
+        for key in evicted {
+            self.remove_file(key);
+        }
+    }
+
+    fn remove(&self, key: u64) {
+        if let Ok(mut index) = self.index.lock() {
+            index.remove(key);
+        }
+        self.remove_file(key);
+    }
+
+    fn remove_file(&self, key: u64) {
+        let path = self.entry_path(key);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(
+                    "Disk cache: Failed to remove cached result {}: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl QueryResultCache for DiskCache {
+    async fn get(&self, plan: &LogicalPlan) -> Result<Option<CachedQueryResult>> {
+        let key = key_for_logical_plan(plan);
+
+        let expires_at = match self.index.lock() {
+            Ok(mut index) => {
+                let Some(expires_at) = index.entries.get(&key).map(|entry| entry.expires_at) else {
+                    return Ok(None);
+                };
+                index.touch(key);
+                expires_at
+            }
+            Err(_) => return Ok(None),
+        };
+
+        if expires_at <= SystemTime::now() {
+            self.remove(key);
+            return Ok(None);
+        }
+
+        let path = self.entry_path(key);
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // Removed by a concurrent invalidation or eviction.
+                return Ok(None);
+            }
+            Err(e) => return Err(e).context(FailedToReadCacheEntrySnafu { path }),
+        };
+
+        let decoded = tokio::task::spawn_blocking(move || ipc::decode(bytes))
+            .await
+            .context(FailedToRunCacheTaskSnafu)?;
+
+        match decoded {
+            Ok((result, _)) => Ok(Some(result)),
+            Err(source) => {
+                self.remove(key);
+                Err(source).context(FailedToDecodeCacheEntrySnafu { path })
+            }
+        }
+    }
+
+    async fn put(&self, plan: &LogicalPlan, result: CachedQueryResult) -> Result<()> {
+        self.put_key(key_for_logical_plan(plan), result, None).await
+    }
+
+    async fn put_key(
+        &self,
+        plan_key: u64,
+        result: CachedQueryResult,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        let expires_at = SystemTime::now() + ttl.unwrap_or(self.ttl);
+        let input_tables = Arc::clone(&result.input_tables);
+
+        let bytes = tokio::task::spawn_blocking(move || ipc::encode(&result, expires_at))
+            .await
+            .context(FailedToRunCacheTaskSnafu)?
+            .context(FailedToEncodeCacheEntrySnafu)?;
+
+        let size_bytes = bytes.len() as u64;
+        if size_bytes > self.cache_max_size {
+            return Ok(());
+        }
+
+        // Write to a temporary file first, so a concurrent read never sees a partial file.
+        let path = self.entry_path(plan_key);
+        let temp_path = path.with_extension(format!("{ENTRY_EXTENSION}.tmp"));
+        tokio::fs::write(&temp_path, bytes)
+            .await
+            .context(FailedToWriteCacheEntrySnafu {
+                path: temp_path.clone(),
+            })?;
+        tokio::fs::rename(&temp_path, &path)
+            .await
+            .context(FailedToWriteCacheEntrySnafu { path: path.clone() })?;
+
+        if let Ok(mut index) = self.index.lock() {
+            index.insert(
+                plan_key,
+                DiskCacheEntry {
+                    size_bytes,
+                    input_tables,
+                    expires_at,
+                    last_access: 0,
+                },
+            );
+        }
+        self.evict();
+
+        Ok(())
+    }
+
+    async fn invalidate_for_table(&self, table_ref: TableReference) -> Result<()> {
+        let invalidated: Vec<u64> = match self.index.lock() {
+            Ok(mut index) => {
+                let keys: Vec<u64> = index
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| entry.input_tables.contains(&table_ref))
+                    .map(|(key, _)| *key)
+                    .collect();
+                for key in &keys {
+                    index.remove(*key);
+                }
+                keys
+            }
+            Err(_) => vec![],
+        };
+
+        for key in invalidated {
+            self.remove_file(key);
+        }
+
+        Ok(())
+    }
+
+    fn size_bytes(&self) -> u64 {
+        self.index.lock().map_or(0, |index| index.size_bytes)
+    }
+
+    fn item_count(&self) -> u64 {
+        self.index
+            .lock()
+            .map_or(0, |index| index.entries.len() as u64)
+    }
+}
+
+fn entry_path(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{key:016x}.{ENTRY_EXTENSION}"))
+}
+
+/// Indexes the cached results left in `dir` by a previous run, removing any that have expired or
+/// can't be read.
+fn load_index(dir: &Path) -> DiskCacheIndex {
+    let mut index = DiskCacheIndex::default();
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!(
+                "Disk cache: Failed to read cache directory {}: {e}",
+                dir.display()
+            );
+            return index;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(key) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|_| path.extension().is_some_and(|ext| ext == ENTRY_EXTENSION))
+            .and_then(|stem| u64::from_str_radix(stem, 16).ok())
+        else {
+            // Left over from an interrupted write.
+            if path.to_string_lossy().ends_with(".tmp") {
+                let _ = std::fs::remove_file(&path);
+            }
+            continue;
+        };
+
+        let header = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| ipc::decode_header(file).map_err(|e| e.to_string()));
+        let size_bytes = entry.metadata().map_or(0, |metadata| metadata.len());
+
+        match header {
+            Ok(header) if header.expires_at > SystemTime::now() => {
+                index.insert(
+                    key,
+                    DiskCacheEntry {
+                        size_bytes,
+                        input_tables: header.input_tables,
+                        expires_at: header.expires_at,
+                        last_access: 0,
+                    },
+                );
+            }
+            Ok(_) => {
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => {
+                tracing::debug!(
+                    "Disk cache: Removing unreadable cached result {}: {e}",
+                    path.display()
+                );
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::logical_expr::{lit, LogicalPlanBuilder};
+
+    use super::*;
+
+    fn cached_result(values: Vec<i32>, tables: &[&str]) -> CachedQueryResult {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int32Array::from(values))],
+        )
+        .expect("valid batch");
+
+        CachedQueryResult {
+            records: Arc::new(vec![batch]),
+            schema,
+            input_tables: Arc::new(
+                tables
+                    .iter()
+                    .map(|table| TableReference::from(*table))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn get_key(cache: &DiskCache, key: u64) -> Option<Vec<RecordBatch>> {
+        let bytes = std::fs::read(cache.entry_path(key)).ok()?;
+        let (result, _) = ipc::decode(bytes).ok()?;
+        Some(result.records.as_ref().clone())
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_survives_restart() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let ttl = Duration::from_secs(60);
+
+        let cache = DiskCache::try_new(dir.path(), 1024 * 1024, ttl).expect("disk cache");
+        cache
+            .put_key(1, cached_result(vec![1, 2, 3], &["customer"]), None)
+            .await
+            .expect("put");
+        assert_eq!(cache.item_count(), 1);
+        drop(cache);
+
+        let cache = DiskCache::try_new(dir.path(), 1024 * 1024, ttl).expect("disk cache");
+        assert_eq!(cache.item_count(), 1);
+        assert!(cache.size_bytes() > 0);
+
+        let records = get_key(&cache, 1).expect("cached result");
+        assert_eq!(records[0].num_rows(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_invalidate_for_table() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let cache = DiskCache::try_new(dir.path(), 1024 * 1024, Duration::from_secs(60))
+            .expect("disk cache");
+
+        cache
+            .put_key(1, cached_result(vec![1], &["customer"]), None)
+            .await
+            .expect("put");
+        cache
+            .put_key(2, cached_result(vec![2], &["orders", "customer"]), None)
+            .await
+            .expect("put");
+        cache
+            .put_key(3, cached_result(vec![3], &["orders"]), None)
+            .await
+            .expect("put");
+
+        cache
+            .invalidate_for_table(TableReference::from("customer"))
+            .await
+            .expect("invalidate");
+
+        assert_eq!(cache.item_count(), 1);
+        assert!(get_key(&cache, 1).is_none());
+        assert!(get_key(&cache, 2).is_none());
+        assert!(get_key(&cache, 3).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let entry_size = ipc::encode(
+            &cached_result(vec![1], &["t"]),
+            SystemTime::now() + Duration::from_secs(60),
+        )
+        .expect("encode")
+        .len() as u64;
+
+        let cache = DiskCache::try_new(dir.path(), entry_size * 2, Duration::from_secs(60))
+            .expect("disk cache");
+
+        let plans = (1..=3)
+            .map(|i| {
+                LogicalPlanBuilder::empty(true)
+                    .project(vec![lit(i)])
+                    .and_then(LogicalPlanBuilder::build)
+                    .expect("valid plan")
+            })
+            .collect::<Vec<_>>();
+
+        for plan in &plans[..2] {
+            cache
+                .put(plan, cached_result(vec![1], &["t"]))
+                .await
+                .expect("put");
+        }
+
+        // Reading the older entry makes the other one the least recently used.
+        assert!(cache.get(&plans[0]).await.expect("get").is_some());
+        cache
+            .put(&plans[2], cached_result(vec![1], &["t"]))
+            .await
+            .expect("put");
+
+        assert_eq!(cache.item_count(), 2);
+        assert!(cache.size_bytes() <= entry_size * 2);
+        assert!(cache.get(&plans[0]).await.expect("get").is_some());
+        assert!(cache.get(&plans[1]).await.expect("get").is_none());
+        assert!(cache.get(&plans[2]).await.expect("get").is_some());
+    }
+}
@@ -0,0 +1,143 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Serializes cached query results to self-describing Arrow IPC files, for cache backends that
+//! store results outside of the process (i.e. on disk, or in a remote key-value store).
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read, Seek},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use arrow::{
+    array::RecordBatch,
+    datatypes::Schema,
+    error::ArrowError,
+    ipc::{reader::FileReader, writer::FileWriter},
+};
+use datafusion::sql::TableReference;
+
+use crate::CachedQueryResult;
+
+const INPUT_TABLES_METADATA_KEY: &str = "spice.cache.input_tables";
+const EXPIRES_AT_METADATA_KEY: &str = "spice.cache.expires_at";
+
+/// What's needed to index a serialized result without reading its record batches.
+pub(crate) struct CachedResultHeader {
+    pub input_tables: Arc<HashSet<TableReference>>,
+    pub expires_at: SystemTime,
+}
+
+/// Encodes `result` as an Arrow IPC file. The input tables and expiry time are stored in the
+/// schema metadata, so the result can be indexed again after a restart.
+pub(crate) fn encode(
+    result: &CachedQueryResult,
+    expires_at: SystemTime,
+) -> Result<Vec<u8>, ArrowError> {
+    let mut metadata = result.schema.metadata().clone();
+    metadata.insert(
+        INPUT_TABLES_METADATA_KEY.to_string(),
+        result
+            .input_tables
+            .iter()
+            .map(TableReference::to_quoted_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    metadata.insert(
+        EXPIRES_AT_METADATA_KEY.to_string(),
+        expires_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string(),
+    );
+    let schema = Arc::new(Schema::new_with_metadata(
+        result.schema.fields().clone(),
+        metadata,
+    ));
+
+    let mut writer = FileWriter::try_new(Vec::new(), &schema)?;
+    for batch in result.records.iter() {
+        writer.write(&RecordBatch::try_new(
+            Arc::clone(&schema),
+            batch.columns().to_vec(),
+        )?)?;
+    }
+    writer.finish()?;
+    writer.into_inner()
+}
+
+/// Reads only the header of a result encoded by [`encode`], without decoding its record batches.
+pub(crate) fn decode_header<R: Read + Seek>(reader: R) -> Result<CachedResultHeader, ArrowError> {
+    let reader = FileReader::try_new(reader, None)?;
+    Ok(header_from_metadata(reader.schema().metadata()))
+}
+
+/// Decodes a result encoded by [`encode`], restoring its original schema.
+pub(crate) fn decode(bytes: Vec<u8>) -> Result<(CachedQueryResult, SystemTime), ArrowError> {
+    let reader = FileReader::try_new(Cursor::new(bytes), None)?;
+
+    let encoded_schema = reader.schema();
+    let header = header_from_metadata(encoded_schema.metadata());
+
+    let mut metadata = encoded_schema.metadata().clone();
+    metadata.remove(INPUT_TABLES_METADATA_KEY);
+    metadata.remove(EXPIRES_AT_METADATA_KEY);
+    let schema = Arc::new(Schema::new_with_metadata(
+        encoded_schema.fields().clone(),
+        metadata,
+    ));
+
+    let records = reader
+        .map(|batch| RecordBatch::try_new(Arc::clone(&schema), batch?.columns().to_vec()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((
+        CachedQueryResult {
+            records: Arc::new(records),
+            schema,
+            input_tables: header.input_tables,
+        },
+        header.expires_at,
+    ))
+}
+
+fn header_from_metadata(metadata: &HashMap<String, String>) -> CachedResultHeader {
+    let input_tables = metadata
+        .get(INPUT_TABLES_METADATA_KEY)
+        .map(|tables| {
+            tables
+                .lines()
+                .filter(|table| !table.is_empty())
+                .map(TableReference::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // A result without an expiry time is treated as expired.
+    let expires_at = metadata
+        .get(EXPIRES_AT_METADATA_KEY)
+        .and_then(|secs| secs.parse().ok())
+        .map_or(UNIX_EPOCH, |secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+    CachedResultHeader {
+        input_tables: Arc::new(input_tables),
+        expires_at,
+    }
+}
@@ -20,6 +20,7 @@ use std::fmt::Formatter;
 use std::hash::DefaultHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -27,20 +28,28 @@ use std::time::UNIX_EPOCH;
 
 use arrow::array::RecordBatch;
 use arrow::datatypes::Schema;
+use arrow::error::ArrowError;
 use async_trait::async_trait;
 use byte_unit::Byte;
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::logical_expr::LogicalPlan;
 use datafusion::sql::TableReference;
 use fundu::ParseError;
-use lru_cache::LruCache;
 use snafu::{ResultExt, Snafu};
-use spicepod::component::runtime::ResultsCache;
+use spicepod::component::runtime::{ResultsCache, ResultsCacheBackend};
 
+mod cache_control;
+mod disk_cache;
+mod ipc;
 mod lru_cache;
 mod metrics;
+mod normalize;
 mod utils;
 
+pub use cache_control::CacheControl;
+pub use disk_cache::DiskCache;
+pub use lru_cache::LruCache;
+
 pub use utils::get_logical_plan_input_tables;
 pub use utils::to_cached_record_batch_stream;
 
@@ -57,19 +66,61 @@ pub enum Error {
         source: moka::PredicateError,
         table_name: Arc<str>,
     },
+
+    #[snafu(display("Failed to create results cache directory {}: {source}", path.display()))]
+    FailedToCreateCacheDirectory {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    #[snafu(display("Failed to read cached result {}: {source}", path.display()))]
+    FailedToReadCacheEntry {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    #[snafu(display("Failed to write cached result {}: {source}", path.display()))]
+    FailedToWriteCacheEntry {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    #[snafu(display("Failed to encode cached result: {source}"))]
+    FailedToEncodeCacheEntry { source: ArrowError },
+
+    #[snafu(display("Failed to decode cached result {}: {source}", path.display()))]
+    FailedToDecodeCacheEntry { source: ArrowError, path: PathBuf },
+
+    #[snafu(display("Results cache task failed: {source}"))]
+    FailedToRunCacheTask { source: tokio::task::JoinError },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Where the `disk` backend stores results if no `path` is configured.
+const DEFAULT_DISK_CACHE_PATH: &str = ".spice/cache/results";
+
 pub struct QueryResult {
     pub data: SendableRecordBatchStream,
     pub from_cache: Option<bool>,
+    /// The number of rows the query is expected to return, from the statistics of its plan.
+    pub estimated_rows: Option<usize>,
 }
 
 impl QueryResult {
     #[must_use]
     pub fn new(data: SendableRecordBatchStream, from_cache: Option<bool>) -> Self {
-        QueryResult { data, from_cache }
+        QueryResult {
+            data,
+            from_cache,
+            estimated_rows: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_estimated_rows(mut self, estimated_rows: Option<usize>) -> Self {
+        self.estimated_rows = estimated_rows;
+        self
     }
 }
 
@@ -80,11 +131,23 @@ pub struct CachedQueryResult {
     pub input_tables: Arc<HashSet<TableReference>>,
 }
 
+/// A backend for storing query results, keyed by the hash of the query's logical plan.
+///
+/// Backends must keep track of the input tables of each result, so that results can be
+/// invalidated when one of their tables changes. Backends that store results outside of the
+/// process can use the Arrow IPC encoding used by [`DiskCache`], which carries the input tables
+/// with the result.
 #[async_trait]
 pub trait QueryResultCache {
     async fn get(&self, plan: &LogicalPlan) -> Result<Option<CachedQueryResult>>;
     async fn put(&self, plan: &LogicalPlan, result: CachedQueryResult) -> Result<()>;
-    async fn put_key(&self, key: u64, result: CachedQueryResult) -> Result<()>;
+    /// Stores `result` under `key`, expiring after `ttl` if set, or the backend's default TTL.
+    async fn put_key(
+        &self,
+        key: u64,
+        result: CachedQueryResult,
+        ttl: Option<std::time::Duration>,
+    ) -> Result<()>;
     async fn invalidate_for_table(&self, table_name: TableReference) -> Result<()>;
     fn size_bytes(&self) -> u64;
     fn item_count(&self) -> u64;
@@ -116,17 +179,41 @@ impl QueryResultsCacheProvider {
             None => std::time::Duration::from_secs(1),
         };
 
-        let cache_provider = QueryResultsCacheProvider {
-            cache: Arc::new(LruCache::new(cache_max_size, ttl)),
+        let cache: Arc<dyn QueryResultCache + Send + Sync> =
+            match config.backend.unwrap_or_default() {
+                ResultsCacheBackend::Memory => Arc::new(LruCache::new(cache_max_size, ttl)),
+                ResultsCacheBackend::Disk => Arc::new(DiskCache::try_new(
+                    config.path.as_deref().unwrap_or(DEFAULT_DISK_CACHE_PATH),
+                    cache_max_size,
+                    ttl,
+                )?),
+            };
+
+        Ok(Self::new_with_cache(
+            cache,
             cache_max_size,
             ttl,
-            metrics_reported_last_time: AtomicU64::new(0),
             ignore_schemas,
-        };
+        ))
+    }
 
+    /// Creates a provider around any [`QueryResultCache`] backend.
+    #[must_use]
+    pub fn new_with_cache(
+        cache: Arc<dyn QueryResultCache + Send + Sync>,
+        cache_max_size: u64,
+        ttl: std::time::Duration,
+        ignore_schemas: Box<[Box<str>]>,
+    ) -> Self {
         metrics::MAX_SIZE.record(cache_max_size, &[]);
 
-        Ok(cache_provider)
+        QueryResultsCacheProvider {
+            cache,
+            cache_max_size,
+            ttl,
+            metrics_reported_last_time: AtomicU64::new(0),
+            ignore_schemas,
+        }
     }
 
     /// # Errors
@@ -156,8 +243,13 @@ impl QueryResultsCacheProvider {
     /// # Errors
     ///
     /// Will return `Err` if method fails to access the cache
-    pub async fn put_key(&self, plan_key: u64, result: CachedQueryResult) -> Result<()> {
-        let res = self.cache.put_key(plan_key, result).await;
+    pub async fn put_key(
+        &self,
+        plan_key: u64,
+        result: CachedQueryResult,
+        ttl: Option<std::time::Duration>,
+    ) -> Result<()> {
+        let res = self.cache.put_key(plan_key, result, ttl).await;
         self.report_size_metrics();
         res
     }
@@ -244,10 +336,15 @@ fn current_time_secs() -> u64 {
         .as_secs()
 }
 
+/// Computes the cache key of a query from its normalized logical plan.
+///
+/// The output schema of the original plan is part of the key, so queries whose results only
+/// differ in column names (i.e. from an alias rename) never share a cached result.
 #[must_use]
 pub fn key_for_logical_plan(plan: &LogicalPlan) -> u64 {
     let mut hasher = DefaultHasher::new();
-    plan.hash(&mut hasher);
+    normalize::normalize_plan(plan).hash(&mut hasher);
+    plan.schema().as_arrow().hash(&mut hasher);
     hasher.finish()
 }
 
@@ -283,6 +380,44 @@ mod tests {
         assert!(!cache_provider.cache_is_enabled_for_plan(&logical_plan));
     }
 
+    #[tokio::test]
+    async fn test_key_for_logical_plan_normalizes_in_list_order() {
+        let first = parse_sql_to_logical_plan("SELECT * FROM customer WHERE id IN (3, 1, 2)").await;
+        let second =
+            parse_sql_to_logical_plan("SELECT *\n  FROM customer\n  WHERE id IN (1, 2, 3, 3)")
+                .await;
+        let different =
+            parse_sql_to_logical_plan("SELECT * FROM customer WHERE id IN (1, 2)").await;
+
+        assert_eq!(key_for_logical_plan(&first), key_for_logical_plan(&second));
+        assert_ne!(
+            key_for_logical_plan(&first),
+            key_for_logical_plan(&different)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_key_for_logical_plan_normalizes_aliases_and_operands() {
+        let first = parse_sql_to_logical_plan(
+            "SELECT c.first_name FROM customer c WHERE c.id = 1 AND c.state = 'NY'",
+        )
+        .await;
+        let second = parse_sql_to_logical_plan(
+            "SELECT x.first_name FROM customer x WHERE x.state = 'NY' AND 1 = x.id",
+        )
+        .await;
+
+        assert_eq!(key_for_logical_plan(&first), key_for_logical_plan(&second));
+    }
+
+    #[tokio::test]
+    async fn test_key_for_logical_plan_distinguishes_output_columns() {
+        let first = parse_sql_to_logical_plan("SELECT id AS a FROM customer").await;
+        let second = parse_sql_to_logical_plan("SELECT id AS b FROM customer").await;
+
+        assert_ne!(key_for_logical_plan(&first), key_for_logical_plan(&second));
+    }
+
     #[tokio::test]
     async fn test_cache_is_enabled_for_simple_select() {
         let sql = "SELECT * FROM customer";
@@ -22,20 +22,44 @@ use async_trait::async_trait;
 use datafusion::logical_expr::LogicalPlan;
 use datafusion::sql::TableReference;
 use moka::future::Cache;
+use moka::Expiry;
 use snafu::ResultExt;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+struct LruCacheEntry {
+    result: CachedQueryResult,
+    ttl: Duration,
+}
+
+/// Expires each entry after the TTL it was inserted with.
+struct LruCacheExpiry;
+
+impl Expiry<u64, LruCacheEntry> for LruCacheExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &u64,
+        value: &LruCacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+}
 
 pub struct LruCache {
-    cache: Cache<u64, CachedQueryResult>,
+    cache: Cache<u64, LruCacheEntry>,
+    ttl: Duration,
 }
 
 impl LruCache {
+    #[must_use]
     pub fn new(cache_max_size: u64, ttl: Duration) -> Self {
-        let cache: Cache<u64, CachedQueryResult> = Cache::builder()
-            .time_to_live(ttl)
-            .weigher(|_key, value: &CachedQueryResult| -> u32 {
+        let cache: Cache<u64, LruCacheEntry> = Cache::builder()
+            .expire_after(LruCacheExpiry)
+            .weigher(|_key, value: &LruCacheEntry| -> u32 {
                 let val: usize = value
+                    .result
                     .records
                     .iter()
                     .map(arrow::array::RecordBatch::get_array_memory_size)
@@ -59,7 +83,7 @@ impl LruCache {
             .support_invalidation_closures()
             .build();
 
-        LruCache { cache }
+        LruCache { cache, ttl }
     }
 }
 
@@ -68,19 +92,27 @@ impl QueryResultCache for LruCache {
     async fn get(&self, plan: &LogicalPlan) -> Result<Option<CachedQueryResult>> {
         let key = key_for_logical_plan(plan);
         match self.cache.get(&key).await {
-            Some(value) => Ok(Some(value)),
+            Some(value) => Ok(Some(value.result)),
             None => Ok(None),
         }
     }
 
     async fn put(&self, plan: &LogicalPlan, result: CachedQueryResult) -> Result<()> {
         let key = key_for_logical_plan(plan);
-        self.cache.insert(key, result).await;
-        Ok(())
+        self.put_key(key, result, None).await
     }
 
-    async fn put_key(&self, plan_key: u64, result: CachedQueryResult) -> Result<()> {
-        self.cache.insert(plan_key, result).await;
+    async fn put_key(
+        &self,
+        plan_key: u64,
+        result: CachedQueryResult,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        let entry = LruCacheEntry {
+            result,
+            ttl: ttl.unwrap_or(self.ttl),
+        };
+        self.cache.insert(plan_key, entry).await;
         Ok(())
     }
 
@@ -92,7 +124,9 @@ impl QueryResultCache for LruCache {
         };
         let table_name = Arc::clone(table_name);
         self.cache
-            .invalidate_entries_if(move |_key, value| value.input_tables.contains(&table_ref))
+            .invalidate_entries_if(move |_key, value| {
+                value.result.input_tables.contains(&table_ref)
+            })
             .context(FailedToInvalidateCacheSnafu { table_name })?;
 
         Ok(())
@@ -0,0 +1,159 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNode, TreeNodeRecursion},
+        Column,
+    },
+    error::Result,
+    logical_expr::{
+        expr::InList,
+        utils::{conjunction, disjunction, split_binary_owned},
+        BinaryExpr, Expr, LogicalPlan, Operator, SubqueryAlias,
+    },
+    sql::TableReference,
+};
+
+const CANONICAL_ALIAS_PREFIX: &str = "__spice_alias_";
+
+/// Rewrites `plan` into a canonical form, so that queries that only differ in the order of `IN`
+/// list items or commutative operands, or in their table aliases, produce the same cache key.
+///
+/// The normalized plan is only hashed, never executed. If it can't be normalized, the plan is
+/// returned as-is, which can only cause a cache miss.
+pub(crate) fn normalize_plan(plan: &LogicalPlan) -> LogicalPlan {
+    match try_normalize_plan(plan.clone()) {
+        Ok(normalized) => normalized,
+        Err(e) => {
+            tracing::trace!("Unable to normalize plan for the results cache key: {e}");
+            plan.clone()
+        }
+    }
+}
+
+fn try_normalize_plan(plan: LogicalPlan) -> Result<LogicalPlan> {
+    let aliases = canonical_aliases(&plan)?;
+
+    plan.transform_up_with_subqueries(|node| {
+        let node = match node {
+            LogicalPlan::SubqueryAlias(subquery_alias) => {
+                match aliases.get(&subquery_alias.alias) {
+                    Some(alias) => LogicalPlan::SubqueryAlias(SubqueryAlias::try_new(
+                        subquery_alias.input,
+                        alias.clone(),
+                    )?),
+                    None => LogicalPlan::SubqueryAlias(subquery_alias),
+                }
+            }
+            node => node
+                .map_expressions(|expr| expr.transform_up(|expr| normalize_expr(expr, &aliases)))?
+                .data
+                .recompute_schema()?,
+        };
+
+        Ok(Transformed::yes(node))
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Numbers the table aliases in the order they appear in the plan. Aliases are only renamed if
+/// they're all unique, so that a column qualifier can't refer to aliases in different scopes.
+fn canonical_aliases(plan: &LogicalPlan) -> Result<HashMap<TableReference, TableReference>> {
+    let mut aliases = vec![];
+    plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::SubqueryAlias(subquery_alias) = node {
+            aliases.push(subquery_alias.alias.clone());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+
+    let mut canonical = HashMap::with_capacity(aliases.len());
+    for (i, alias) in aliases.into_iter().enumerate() {
+        let canonical_alias = TableReference::bare(format!("{CANONICAL_ALIAS_PREFIX}{i}"));
+        if canonical.insert(alias, canonical_alias).is_some() {
+            return Ok(HashMap::new());
+        }
+    }
+
+    Ok(canonical)
+}
+
+fn normalize_expr(
+    expr: Expr,
+    aliases: &HashMap<TableReference, TableReference>,
+) -> Result<Transformed<Expr>> {
+    if let Expr::BinaryExpr(BinaryExpr {
+        op: op @ (Operator::And | Operator::Or),
+        ..
+    }) = &expr
+    {
+        let op = *op;
+        let mut operands = split_binary_owned(expr.clone(), op);
+        operands.sort_by_cached_key(ToString::to_string);
+
+        let combined = if op == Operator::And {
+            conjunction(operands)
+        } else {
+            disjunction(operands)
+        };
+
+        return Ok(Transformed::yes(combined.unwrap_or(expr)));
+    }
+
+    let expr = match expr {
+        Expr::Column(column) => Expr::Column(rename_qualifier(column, aliases)),
+        Expr::OuterReferenceColumn(data_type, column) => {
+            Expr::OuterReferenceColumn(data_type, rename_qualifier(column, aliases))
+        }
+        Expr::InList(InList {
+            expr,
+            mut list,
+            negated,
+        }) => {
+            list.sort_by_cached_key(ToString::to_string);
+            list.dedup();
+            Expr::InList(InList::new(expr, list, negated))
+        }
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: op @ (Operator::Eq | Operator::NotEq),
+            right,
+        }) if right.to_string() < left.to_string() => {
+            Expr::BinaryExpr(BinaryExpr::new(right, op, left))
+        }
+        expr => return Ok(Transformed::no(expr)),
+    };
+
+    Ok(Transformed::yes(expr))
+}
+
+fn rename_qualifier(
+    mut column: Column,
+    aliases: &HashMap<TableReference, TableReference>,
+) -> Column {
+    if let Some(alias) = column
+        .relation
+        .as_ref()
+        .and_then(|relation| aliases.get(relation))
+    {
+        column.relation = Some(alias.clone());
+    }
+
+    column
+}
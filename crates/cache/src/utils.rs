@@ -14,7 +14,7 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::{collections::HashSet, sync::Arc};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use arrow::array::RecordBatch;
 use datafusion::{
@@ -35,6 +35,7 @@ pub fn to_cached_record_batch_stream(
     mut stream: SendableRecordBatchStream,
     plan_key: u64,
     input_tables: Arc<HashSet<TableReference>>,
+    ttl: Option<Duration>,
 ) -> SendableRecordBatchStream {
     let schema = stream.schema();
     let schema_copy = Arc::clone(&schema);
@@ -62,7 +63,7 @@ pub fn to_cached_record_batch_stream(
                 input_tables,
             };
 
-            if let Err(e) = cache_provider.put_key(plan_key, cached_result).await {
+            if let Err(e) = cache_provider.put_key(plan_key, cached_result, ttl).await {
                 tracing::error!("Failed to cache query results: {e}");
             }
         }
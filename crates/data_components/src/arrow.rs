@@ -28,6 +28,7 @@ use crate::delete::DeletionTableProviderAdapter;
 
 use self::write::MemTable;
 
+pub mod stream_format;
 pub mod struct_builder;
 pub mod write;
 
@@ -0,0 +1,322 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A [`FileFormat`] for files in the Arrow IPC streaming format (`.arrows`).
+//!
+//! `DataFusion`'s `ArrowFormat` only reads the Arrow IPC file format, which has a footer that
+//! the streaming format doesn't.
+
+use arrow::{
+    array::{new_null_array, ArrayRef, RecordBatch, RecordBatchOptions},
+    datatypes::{Schema, SchemaRef},
+    ipc::reader::StreamDecoder,
+};
+use arrow_buffer::Buffer;
+use async_stream::try_stream;
+use async_trait::async_trait;
+use datafusion::{
+    common::{project_schema, Statistics},
+    datasource::{
+        file_format::{file_compression_type::FileCompressionType, FileFormat},
+        listing::PartitionedFile,
+        physical_plan::FileScanConfig,
+    },
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::{context::SessionState, SendableRecordBatchStream, TaskContext},
+    physical_expr::{EquivalenceProperties, PhysicalExpr},
+    physical_plan::{
+        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionMode,
+        ExecutionPlan, Partitioning, PlanProperties,
+    },
+};
+use futures::{Stream, StreamExt};
+use object_store::{ObjectMeta, ObjectStore};
+use std::{any::Any, fmt, sync::Arc};
+
+pub const DEFAULT_ARROW_STREAM_EXTENSION: &str = ".arrows";
+
+#[derive(Debug, Default)]
+pub struct ArrowStreamFormat {}
+
+#[async_trait]
+impl FileFormat for ArrowStreamFormat {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_ext(&self) -> String {
+        DEFAULT_ARROW_STREAM_EXTENSION.to_string()
+    }
+
+    fn get_ext_with_compression(
+        &self,
+        file_compression_type: &FileCompressionType,
+    ) -> DataFusionResult<String> {
+        if file_compression_type.is_compressed() {
+            return Err(DataFusionError::Internal(
+                "Arrow IPC stream files don't support file compression".to_string(),
+            ));
+        }
+
+        Ok(self.get_ext())
+    }
+
+    async fn infer_schema(
+        &self,
+        _state: &SessionState,
+        store: &Arc<dyn ObjectStore>,
+        objects: &[ObjectMeta],
+    ) -> DataFusionResult<SchemaRef> {
+        let mut schemas = Vec::with_capacity(objects.len());
+        for object in objects {
+            schemas.push(read_schema(store, object).await?.as_ref().clone());
+        }
+
+        Ok(Arc::new(Schema::try_merge(schemas)?))
+    }
+
+    async fn infer_stats(
+        &self,
+        _state: &SessionState,
+        _store: &Arc<dyn ObjectStore>,
+        table_schema: SchemaRef,
+        _object: &ObjectMeta,
+    ) -> DataFusionResult<Statistics> {
+        Ok(Statistics::new_unknown(&table_schema))
+    }
+
+    async fn create_physical_plan(
+        &self,
+        _state: &SessionState,
+        conf: FileScanConfig,
+        _filters: Option<&Arc<dyn PhysicalExpr>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(ArrowStreamExec::try_new(conf)?))
+    }
+}
+
+/// Reads only the schema message at the start of an Arrow IPC stream.
+async fn read_schema(
+    store: &Arc<dyn ObjectStore>,
+    object: &ObjectMeta,
+) -> DataFusionResult<SchemaRef> {
+    let mut chunks = store.get(&object.location).await?.into_stream();
+    let mut decoder = StreamDecoder::new();
+
+    while let Some(chunk) = chunks.next().await {
+        let mut buffer = Buffer::from_vec(chunk?.to_vec());
+        while !buffer.is_empty() {
+            decoder.decode(&mut buffer)?;
+            if let Some(schema) = decoder.schema() {
+                return Ok(schema);
+            }
+        }
+    }
+
+    Err(DataFusionError::Execution(format!(
+        "Unable to read the schema of {}: not an Arrow IPC stream",
+        object.location
+    )))
+}
+
+pub struct ArrowStreamExec {
+    conf: FileScanConfig,
+    projected_schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl ArrowStreamExec {
+    fn try_new(conf: FileScanConfig) -> DataFusionResult<Self> {
+        // Partition columns (i.e. from Hive-style paths) follow the columns read from the files.
+        let mut fields = conf.file_schema.fields().to_vec();
+        fields.extend(conf.table_partition_cols.iter().cloned().map(Arc::new));
+        let table_schema = Arc::new(Schema::new(fields));
+        let projected_schema = project_schema(&table_schema, conf.projection.as_ref())?;
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(Arc::clone(&projected_schema)),
+            Partitioning::UnknownPartitioning(conf.file_groups.len().max(1)),
+            ExecutionMode::Bounded,
+        );
+
+        Ok(Self {
+            conf,
+            projected_schema,
+            properties,
+        })
+    }
+}
+
+impl fmt::Debug for ArrowStreamExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} url={}", self.name(), self.conf.object_store_url)
+    }
+}
+
+impl DisplayAs for ArrowStreamExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} url={} files={}",
+            self.name(),
+            self.conf.object_store_url,
+            self.conf.file_groups.iter().map(Vec::len).sum::<usize>()
+        )
+    }
+}
+
+impl ExecutionPlan for ArrowStreamExec {
+    fn name(&self) -> &'static str {
+        "ArrowStreamExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.projected_schema)
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let store = context
+            .runtime_env()
+            .object_store(&self.conf.object_store_url)?;
+        let files = self
+            .conf
+            .file_groups
+            .get(partition)
+            .cloned()
+            .unwrap_or_default();
+
+        let projection = self.conf.projection.clone().unwrap_or_else(|| {
+            (0..self.conf.file_schema.fields().len() + self.conf.table_partition_cols.len())
+                .collect()
+        });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            to_sendable_stream(
+                store,
+                files,
+                Arc::clone(&self.conf.file_schema),
+                self.schema(),
+                projection,
+                self.conf.limit,
+            ),
+        )))
+    }
+}
+
+/// Projects a batch read from `file` onto the projected table schema. Columns are matched by
+/// name, since a file may only have some of the columns of the merged schema. Partition columns
+/// are filled with the partition values of `file`.
+fn project_batch(
+    batch: &RecordBatch,
+    file: &PartitionedFile,
+    file_schema: &Schema,
+    projected_schema: &SchemaRef,
+    projection: &[usize],
+) -> DataFusionResult<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let columns = projection
+        .iter()
+        .zip(projected_schema.fields())
+        .map(|(&i, field)| {
+            if i < file_schema.fields().len() {
+                return Ok(match batch.schema().index_of(field.name()) {
+                    Ok(column) => Arc::clone(batch.column(column)),
+                    Err(_) => new_null_array(field.data_type(), num_rows),
+                });
+            }
+
+            let Some(value) = file.partition_values.get(i - file_schema.fields().len()) else {
+                return Err(DataFusionError::Execution(format!(
+                    "Missing partition value for {} in {}",
+                    field.name(),
+                    file.object_meta.location
+                )));
+            };
+            value.to_array_of_size(num_rows)
+        })
+        .collect::<DataFusionResult<Vec<ArrayRef>>>()?;
+
+    Ok(RecordBatch::try_new_with_options(
+        Arc::clone(projected_schema),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?)
+}
+
+fn to_sendable_stream(
+    store: Arc<dyn ObjectStore>,
+    files: Vec<PartitionedFile>,
+    file_schema: SchemaRef,
+    projected_schema: SchemaRef,
+    projection: Vec<usize>,
+    limit: Option<usize>,
+) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
+    try_stream! {
+        let mut count = 0;
+
+        'files: for file in files {
+            let mut chunks = store.get(&file.object_meta.location).await?.into_stream();
+            let mut decoder = StreamDecoder::new();
+
+            while let Some(chunk) = chunks.next().await {
+                let mut buffer = Buffer::from_vec(chunk?.to_vec());
+                while !buffer.is_empty() {
+                    let Some(batch) = decoder.decode(&mut buffer)? else {
+                        continue;
+                    };
+                    let batch =
+                        project_batch(&batch, &file, &file_schema, &projected_schema, &projection)?;
+
+                    // Early exit on LIMIT clause
+                    if let Some(limit) = limit {
+                        if count + batch.num_rows() >= limit {
+                            yield batch.slice(0, limit - count);
+                            break 'files;
+                        }
+                    }
+
+                    count += batch.num_rows();
+                    yield batch;
+                }
+            }
+
+            decoder.finish()?;
+        }
+    }
+}
@@ -0,0 +1,321 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A catalog of the tables in a database, i.e. as listed in its `information_schema`. Table
+//! providers are only created when a table is first queried.
+//!
+//! The tables are only listed when the catalog is created, so tables created in the database
+//! afterwards aren't in the catalog until it is loaded again, i.e. when the runtime restarts.
+
+use arrow::{
+    array::{Array, AsArray},
+    compute::cast,
+    datatypes::DataType,
+};
+use async_trait::async_trait;
+use datafusion::{
+    catalog::{CatalogProvider, SchemaProvider},
+    datasource::TableProvider,
+    error::DataFusionError,
+    sql::TableReference,
+};
+use datafusion_table_providers::sql::db_connection_pool::dbconnection::AsyncDbConnection;
+use futures::TryStreamExt;
+use globset::GlobSet;
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tokio::sync::OnceCell;
+
+use crate::Read;
+
+/// Runs `sql` to list the tables in a database, as `(schema, table)` rows.
+pub async fn query_table_names<T: 'static, P: 'static>(
+    conn: &dyn AsyncDbConnection<T, P>,
+    sql: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let batches: Vec<_> = conn
+        .query_arrow(sql, &[], None)
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut tables = vec![];
+    for batch in batches {
+        if batch.num_columns() < 2 {
+            return Err(format!("Expected (schema, table) rows from: {sql}").into());
+        }
+
+        let schemas = cast(batch.column(0), &DataType::Utf8)?;
+        let names = cast(batch.column(1), &DataType::Utf8)?;
+        let (schemas, names) = (schemas.as_string::<i32>(), names.as_string::<i32>());
+        for i in 0..batch.num_rows() {
+            if schemas.is_null(i) || names.is_null(i) {
+                continue;
+            }
+            tables.push((schemas.value(i).to_string(), names.value(i).to_string()));
+        }
+    }
+
+    Ok(tables)
+}
+
+pub struct LazyCatalogProvider {
+    schemas: HashMap<String, Arc<dyn SchemaProvider>>,
+}
+
+impl LazyCatalogProvider {
+    /// Creates a catalog of `tables`, as `(schema, table)` pairs, that match the `include` patterns
+    /// (i.e. `public.*`). Table providers are created by `table_creator`.
+    ///
+    /// The catalog is fixed to `tables`: tables that are created or dropped later aren't added to
+    /// or removed from it.
+    #[must_use]
+    pub fn new(
+        tables: Vec<(String, String)>,
+        table_creator: Arc<dyn Read>,
+        include: Option<&GlobSet>,
+    ) -> Self {
+        let mut schema_tables: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (schema, table) in tables {
+            let schema_with_table = format!("{schema}.{table}");
+            if let Some(include) = include {
+                if !include.is_match(&schema_with_table) {
+                    tracing::debug!("Table {schema_with_table} is not included");
+                    continue;
+                }
+            }
+
+            schema_tables.entry(schema).or_default().push(table);
+        }
+
+        let schemas = schema_tables
+            .into_iter()
+            .map(|(schema, table_names)| {
+                let schema_provider = LazySchemaProvider {
+                    schema: schema.clone(),
+                    table_creator: Arc::clone(&table_creator),
+                    tables: table_names
+                        .into_iter()
+                        .map(|table| (table, OnceCell::new()))
+                        .collect(),
+                };
+                (schema, Arc::new(schema_provider) as Arc<dyn SchemaProvider>)
+            })
+            .collect();
+
+        Self { schemas }
+    }
+}
+
+impl CatalogProvider for LazyCatalogProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        self.schemas.keys().cloned().collect()
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        self.schemas.get(name).cloned()
+    }
+}
+
+pub struct LazySchemaProvider {
+    schema: String,
+    table_creator: Arc<dyn Read>,
+    tables: HashMap<String, OnceCell<Arc<dyn TableProvider>>>,
+}
+
+#[async_trait]
+impl SchemaProvider for LazySchemaProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    /// Creates the table provider the first time a table is queried, i.e. to resolve its schema.
+    /// Other tables can be created while it is being created.
+    async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>, DataFusionError> {
+        let Some(table) = self.tables.get(name) else {
+            return Ok(None);
+        };
+
+        let table = table
+            .get_or_try_init(|| async {
+                let table_reference = TableReference::partial(self.schema.as_str(), name);
+                self.table_creator
+                    .table_provider(table_reference, None)
+                    .await
+                    .map_err(DataFusionError::External)
+            })
+            .await?;
+
+        Ok(Some(Arc::clone(table)))
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.tables.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{Schema, SchemaRef};
+    use datafusion::datasource::empty::EmptyTable;
+    use globset::{Glob, GlobSetBuilder};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Creates empty tables, counting how many were created.
+    #[derive(Default)]
+    struct CountingRead {
+        created: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Read for CountingRead {
+        async fn table_provider(
+            &self,
+            _table_reference: TableReference,
+            _schema: Option<SchemaRef>,
+        ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>>
+        {
+            self.created.fetch_add(1, Ordering::SeqCst);
+            Ok(Arc::new(EmptyTable::new(Arc::new(Schema::empty()))))
+        }
+    }
+
+    fn tables() -> Vec<(String, String)> {
+        [
+            ("public", "orders"),
+            ("public", "customers"),
+            ("sales", "orders"),
+            ("internal", "audit"),
+        ]
+        .into_iter()
+        .map(|(schema, table)| (schema.to_string(), table.to_string()))
+        .collect()
+    }
+
+    fn sorted(mut names: Vec<String>) -> Vec<String> {
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_include_filters_tables() {
+        let include = GlobSetBuilder::new()
+            .add(Glob::new("public.*").expect("valid glob"))
+            .add(Glob::new("*.orders").expect("valid glob"))
+            .build()
+            .expect("valid globs");
+        let catalog =
+            LazyCatalogProvider::new(tables(), Arc::new(CountingRead::default()), Some(&include));
+
+        assert_eq!(sorted(catalog.schema_names()), vec!["public", "sales"]);
+        let public = catalog.schema("public").expect("public schema");
+        assert_eq!(sorted(public.table_names()), vec!["customers", "orders"]);
+        let sales = catalog.schema("sales").expect("sales schema");
+        assert_eq!(sales.table_names(), vec!["orders"]);
+        assert!(catalog.schema("internal").is_none());
+
+        let catalog = LazyCatalogProvider::new(tables(), Arc::new(CountingRead::default()), None);
+        assert_eq!(
+            sorted(catalog.schema_names()),
+            vec!["internal", "public", "sales"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tables_are_created_lazily() {
+        let read = Arc::new(CountingRead::default());
+        let catalog = LazyCatalogProvider::new(tables(), Arc::clone(&read) as Arc<dyn Read>, None);
+        let public = catalog.schema("public").expect("public schema");
+        assert!(public.table_exist("orders"));
+        assert_eq!(read.created.load(Ordering::SeqCst), 0);
+
+        for _ in 0..2 {
+            public
+                .table("orders")
+                .await
+                .expect("table created")
+                .expect("table exists");
+        }
+        assert_eq!(
+            read.created.load(Ordering::SeqCst),
+            1,
+            "the table provider is reused"
+        );
+
+        assert!(public.table("audit").await.expect("no error").is_none());
+        assert_eq!(read.created.load(Ordering::SeqCst), 1);
+    }
+
+    /// Waits to create the `orders` table until it is released.
+    #[derive(Default)]
+    struct BlockingRead {
+        release: tokio::sync::Notify,
+    }
+
+    #[async_trait]
+    impl Read for BlockingRead {
+        async fn table_provider(
+            &self,
+            table_reference: TableReference,
+            _schema: Option<SchemaRef>,
+        ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>>
+        {
+            if table_reference.table() == "orders" {
+                self.release.notified().await;
+            }
+            Ok(Arc::new(EmptyTable::new(Arc::new(Schema::empty()))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_creation_does_not_block_other_tables() {
+        let read = Arc::new(BlockingRead::default());
+        let catalog = LazyCatalogProvider::new(tables(), Arc::clone(&read) as Arc<dyn Read>, None);
+        let public = catalog.schema("public").expect("public schema");
+
+        let orders = tokio::spawn({
+            let public = Arc::clone(&public);
+            async move { public.table("orders").await }
+        });
+        tokio::task::yield_now().await;
+
+        tokio::time::timeout(Duration::from_secs(5), public.table("customers"))
+            .await
+            .expect("customers is created while orders is being created")
+            .expect("table created")
+            .expect("table exists");
+
+        read.release.notify_one();
+        orders
+            .await
+            .expect("task completed")
+            .expect("table created")
+            .expect("table exists");
+    }
+}
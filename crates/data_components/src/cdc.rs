@@ -14,10 +14,17 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::{fmt::Display, sync::Arc};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use arrow::{
     array::{Array, ListArray, RecordBatch, StringArray, StructArray},
+    buffer::OffsetBuffer,
     datatypes::{DataType, Field, Schema, SchemaRef},
 };
 use futures::stream::BoxStream;
@@ -25,12 +32,25 @@ use snafu::prelude::*;
 
 pub type ChangesStream = BoxStream<'static, Result<ChangeEnvelope, StreamError>>;
 
+/// Called with the position of a source, i.e. a table version or a log sequence number, once all
+/// of its changes up to that position are applied.
+pub type Checkpoint = Arc<dyn Fn(u64) + Send + Sync>;
+
 #[derive(Debug, Snafu)]
 pub enum CommitError {
     #[snafu(display("Unable to commit change: {source}"))]
     UnableToCommitChange {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[snafu(display(
+        "Only {applied} of the {expected} changes up to position {position} were applied"
+    ))]
+    ChangesNotApplied {
+        position: u64,
+        applied: usize,
+        expected: usize,
+    },
 }
 
 #[derive(Debug, Snafu)]
@@ -44,6 +64,8 @@ pub enum StreamError {
     Kafka(String),
     SerdeJsonError(String),
     Flight(String),
+    DeltaLake(String),
+    Postgres(String),
 }
 
 impl std::error::Error for StreamError {}
@@ -54,6 +76,8 @@ impl std::fmt::Display for StreamError {
             StreamError::Kafka(e) => write!(f, "Kafka error: {e}"),
             StreamError::SerdeJsonError(e) => write!(f, "Serde JSON error: {e}"),
             StreamError::Flight(e) => write!(f, "Arrow Flight error: {e}"),
+            StreamError::DeltaLake(e) => write!(f, "Delta Lake error: {e}"),
+            StreamError::Postgres(e) => write!(f, "Postgres error: {e}"),
         }
     }
 }
@@ -63,6 +87,33 @@ pub trait CommitChange {
     fn commit(&self) -> Result<(), CommitError>;
 }
 
+/// Counts the committed changes of a source position, i.e. a table version or a transaction, so
+/// that the position is only checkpointed once all of its changes are applied.
+#[derive(Clone, Default)]
+pub struct AppliedChanges(Arc<AtomicUsize>);
+
+impl AppliedChanges {
+    /// Records that one more change of the position was applied.
+    pub fn applied(&self) {
+        self.0.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Fails unless `expected` changes of the position were applied.
+    pub fn ensure_applied(&self, position: u64, expected: usize) -> Result<(), CommitError> {
+        let applied = self.0.load(Ordering::Acquire);
+        ensure!(
+            applied >= expected,
+            ChangesNotAppliedSnafu {
+                position,
+                applied,
+                expected,
+            }
+        );
+
+        Ok(())
+    }
+}
+
 pub struct ChangeEnvelope {
     change_committer: Box<dyn CommitChange + Send>,
     pub change_batch: ChangeBatch,
@@ -167,6 +218,42 @@ impl ChangeBatch {
         })
     }
 
+    /// Creates a change batch with one change per row of `data`, which all have the same primary keys.
+    pub fn try_from_data(
+        ops: StringArray,
+        primary_keys: &[String],
+        data: &RecordBatch,
+    ) -> Result<Self, ChangeBatchError> {
+        let num_rows = data.num_rows();
+        let primary_keys_values = StringArray::from_iter_values(
+            std::iter::repeat(primary_keys)
+                .take(num_rows)
+                .flat_map(|keys| keys.iter()),
+        );
+        let primary_keys_col = ListArray::new(
+            Arc::new(Field::new("item", DataType::Utf8, false)),
+            OffsetBuffer::from_lengths(std::iter::repeat(primary_keys.len()).take(num_rows)),
+            Arc::new(primary_keys_values),
+            None,
+        );
+
+        let schema = Arc::new(changes_schema(&data.schema()));
+        let record = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(ops),
+                Arc::new(primary_keys_col),
+                Arc::new(StructArray::from(data.clone())),
+            ],
+        )
+        .map_err(|e| ChangeBatchError::SchemaMismatch {
+            detail: e.to_string(),
+            schema,
+        })?;
+
+        Self::try_new(record)
+    }
+
     #[must_use]
     pub fn op(&self, row: usize) -> ChangeOperation {
         let Some(op_col) = self
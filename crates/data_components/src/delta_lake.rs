@@ -42,6 +42,8 @@ use delta_kernel::scan::state::{DvInfo, GlobalScanState, Stats};
 use delta_kernel::scan::ScanBuilder;
 use delta_kernel::snapshot::Snapshot;
 use delta_kernel::Table;
+use futures::StreamExt;
+use object_store::{path::Path, ObjectStore};
 use secrecy::{ExposeSecret, SecretString};
 use snafu::prelude::*;
 use std::collections::HashSet;
@@ -50,6 +52,8 @@ use url::Url;
 
 use crate::Read;
 
+pub mod changes;
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("An error occured with Delta Table: {source}"))]
@@ -60,18 +64,55 @@ pub enum Error {
 
     #[snafu(display("An error occured with handling partition columns: {message}"))]
     PartitionColumnMismatchError { message: String },
+
+    #[snafu(display("Failed to get object store for table location {location}"))]
+    UnableToGetObjectStore { location: String },
+
+    #[snafu(display("Unable to read the Delta transaction log: {source}"))]
+    UnableToReadLog { source: object_store::Error },
+
+    #[snafu(display("The Delta table has no version committed at or before {timestamp}"))]
+    NoVersionAsOf { timestamp: i64 },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// The folder of a Delta table with its transaction log.
+const DELTA_LOG_FOLDER: &str = "_delta_log";
+
+/// The version of a Delta table to read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSelection {
+    /// The latest version of the table, at the time of each query.
+    #[default]
+    Latest,
+    Version(u64),
+    /// The latest version committed at or before a timestamp, in milliseconds since the epoch.
+    AsOf(i64),
+}
+
 pub struct DeltaTableFactory {
     params: HashMap<String, SecretString>,
+    version: VersionSelection,
 }
 
 impl DeltaTableFactory {
     #[must_use]
     pub fn new(params: HashMap<String, SecretString>) -> Self {
-        Self { params }
+        Self {
+            params,
+            version: VersionSelection::Latest,
+        }
+    }
+
+    #[must_use]
+    pub fn with_version(mut self, version: VersionSelection) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub async fn create_table(&self, table_location: String) -> Result<DeltaTable> {
+        DeltaTable::from_version(table_location, self.params.clone(), self.version).await
     }
 }
 
@@ -83,7 +124,7 @@ impl Read for DeltaTableFactory {
         _schema: Option<SchemaRef>,
     ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>> {
         let delta_path = table_reference.table().to_string();
-        let delta: DeltaTable = DeltaTable::from(delta_path, self.params.clone()).boxed()?;
+        let delta: DeltaTable = self.create_table(delta_path).await.boxed()?;
         Ok(Arc::new(delta))
     }
 }
@@ -93,17 +134,46 @@ pub struct DeltaTable {
     engine: Arc<DefaultEngine<TokioBackgroundExecutor>>,
     arrow_schema: SchemaRef,
     delta_schema: delta_kernel::schema::SchemaRef,
+    /// The version to read, or `None` to read the latest version.
+    version: Option<u64>,
 }
 
 impl DeltaTable {
     pub fn from(table_location: String, options: HashMap<String, SecretString>) -> Result<Self> {
+        let (table, engine) = Self::open(table_location, options)?;
+        Self::try_new(table, engine, None)
+    }
+
+    pub async fn from_version(
+        table_location: String,
+        options: HashMap<String, SecretString>,
+        version: VersionSelection,
+    ) -> Result<Self> {
+        let (table, engine) = Self::open(table_location, options)?;
+        let version = match version {
+            VersionSelection::Latest => None,
+            VersionSelection::Version(version) => Some(version),
+            VersionSelection::AsOf(timestamp) => {
+                let store = table_object_store(&table, &engine)?;
+                let commits = list_commits(&store, table.location()).await?;
+                Some(version_as_of(&commits, timestamp).context(NoVersionAsOfSnafu { timestamp })?)
+            }
+        };
+
+        Self::try_new(table, engine, version)
+    }
+
+    fn open(
+        table_location: String,
+        options: HashMap<String, SecretString>,
+    ) -> Result<(Table, Arc<DefaultEngine<TokioBackgroundExecutor>>)> {
         let table =
             Table::try_from_uri(ensure_folder_location(table_location)).context(DeltaTableSnafu)?;
 
         let mut storage_options: HashMap<String, String> = HashMap::new();
         for (key, value) in options {
             match key.as_ref() {
-                "token" | "endpoint" => {
+                "token" | "endpoint" | "version" | "as_of" => {
                     continue;
                 }
                 "client_timeout" => {
@@ -124,8 +194,16 @@ impl DeltaTable {
             .context(DeltaTableSnafu)?,
         );
 
+        Ok((table, engine))
+    }
+
+    fn try_new(
+        table: Table,
+        engine: Arc<DefaultEngine<TokioBackgroundExecutor>>,
+        version: Option<u64>,
+    ) -> Result<Self> {
         let snapshot = table
-            .snapshot(engine.as_ref(), None)
+            .snapshot(engine.as_ref(), version)
             .context(DeltaTableSnafu)?;
 
         let arrow_schema = Self::get_schema(&snapshot);
@@ -136,6 +214,7 @@ impl DeltaTable {
             engine,
             arrow_schema: Arc::new(arrow_schema),
             delta_schema: Arc::new(delta_schema),
+            version,
         })
     }
 
@@ -205,6 +284,62 @@ impl DeltaTable {
     }
 }
 
+fn table_object_store(
+    table: &Table,
+    engine: &DefaultEngine<TokioBackgroundExecutor>,
+) -> Result<Arc<dyn ObjectStore>> {
+    engine
+        .get_object_store_for_url(table.location())
+        .context(UnableToGetObjectStoreSnafu {
+            location: table.location().to_string(),
+        })
+}
+
+/// The path of a file in the table's object store, from its location relative to the table root.
+fn table_path(table_root: &Url, relative_path: &str) -> Result<Path, object_store::path::Error> {
+    Path::from_url_path(format!(
+        "{}/{relative_path}",
+        table_root.path().trim_end_matches('/')
+    ))
+}
+
+/// Lists the commits in the transaction log, as their version and the time they were committed
+/// in milliseconds since the epoch.
+async fn list_commits(store: &Arc<dyn ObjectStore>, table_root: &Url) -> Result<Vec<(u64, i64)>> {
+    let log_path = table_path(table_root, DELTA_LOG_FOLDER)
+        .map_err(|e| Error::UnableToReadLog { source: e.into() })?;
+
+    let mut commits = vec![];
+    let mut files = store.list(Some(&log_path));
+    while let Some(file) = files.next().await {
+        let file = file.context(UnableToReadLogSnafu)?;
+        if let Some(version) = file.location.filename().and_then(commit_version) {
+            commits.push((version, file.last_modified.timestamp_millis()));
+        }
+    }
+
+    Ok(commits)
+}
+
+/// The version of a commit file in the transaction log, i.e. `00000000000000000010.json`.
+fn commit_version(filename: &str) -> Option<u64> {
+    let version = filename.strip_suffix(".json")?;
+    if version.len() != 20 || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    version.parse().ok()
+}
+
+/// The latest version committed at or before the timestamp.
+fn version_as_of(commits: &[(u64, i64)], timestamp: i64) -> Option<u64> {
+    commits
+        .iter()
+        .filter(|(_, committed_at)| *committed_at <= timestamp)
+        .map(|(version, _)| *version)
+        .max()
+}
+
 fn ensure_folder_location(table_location: String) -> String {
     if table_location.ends_with('/') {
         table_location
@@ -293,7 +428,7 @@ impl TableProvider for DeltaTable {
     ) -> Result<Arc<dyn ExecutionPlan>, datafusion::error::DataFusionError> {
         let snapshot = self
             .table
-            .snapshot(self.engine.as_ref(), None)
+            .snapshot(self.engine.as_ref(), self.version)
             .map_err(map_delta_error_to_datafusion_err)?;
 
         let df_schema = DFSchema::try_from(Arc::clone(&self.arrow_schema))?;
@@ -629,6 +764,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_commit_version() {
+        assert_eq!(commit_version("00000000000000000010.json"), Some(10));
+        assert_eq!(
+            commit_version("00000000000000000010.checkpoint.parquet"),
+            None
+        );
+        assert_eq!(commit_version("00000000000000000010.crc"), None);
+        assert_eq!(commit_version("_last_checkpoint"), None);
+        assert_eq!(commit_version("10.json"), None);
+    }
+
+    #[test]
+    fn test_version_as_of() {
+        let commits = [(0, 1_000), (1, 2_000), (2, 3_000)];
+        assert_eq!(version_as_of(&commits, 500), None);
+        assert_eq!(version_as_of(&commits, 2_000), Some(1));
+        assert_eq!(version_as_of(&commits, 2_500), Some(1));
+        assert_eq!(version_as_of(&commits, 10_000), Some(2));
+    }
+
     #[test]
     fn test_get_table_location() {
         assert_eq!(
@@ -0,0 +1,885 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Streams the changes of a Delta table from its transaction log, using the change data feed
+//! (the `_change_data` files) of the commits that have one.
+//!
+//! See <https://github.com/delta-io/delta/blob/master/PROTOCOL.md#change-data-files>
+
+use arrow::{
+    array::{
+        new_null_array, Array, ArrayRef, BooleanArray, RecordBatch, RecordBatchOptions, StringArray,
+    },
+    compute::{cast, filter_record_batch},
+    datatypes::{DataType, SchemaRef},
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use datafusion::{
+    catalog::Session,
+    datasource::{TableProvider, TableType},
+    error::Result as DataFusionResult,
+    execution::context::SessionContext,
+    logical_expr::{Expr, TableProviderFilterPushDown},
+    parquet::arrow::{
+        async_reader::{ParquetObjectReader, ParquetRecordBatchStream},
+        ParquetRecordBatchStreamBuilder,
+    },
+    physical_plan::{execute_stream, ExecutionPlan},
+    scalar::ScalarValue,
+};
+use delta_kernel::Table;
+use futures::StreamExt;
+use object_store::ObjectStore;
+use serde::Deserialize;
+use snafu::prelude::*;
+use std::{any::Any, collections::HashMap, sync::Arc, time::Duration};
+use url::Url;
+
+use super::{table_object_store, table_path, DeltaTable, DELTA_LOG_FOLDER};
+use crate::cdc::{
+    AppliedChanges, ChangeBatch, ChangeEnvelope, ChangesStream, Checkpoint, CommitChange,
+    CommitError, StreamError,
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The column of change data files with the type of each change.
+const CHANGE_TYPE_COLUMN: &str = "_change_type";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    DeltaLake { source: super::Error },
+
+    #[snafu(display("An error occured with Delta Table: {source}"))]
+    DeltaTableError { source: delta_kernel::Error },
+
+    #[snafu(display("Invalid path in the Delta transaction log: {source}"))]
+    InvalidPath { source: object_store::path::Error },
+
+    #[snafu(display("Unable to read commit {version} of the Delta table: {source}"))]
+    UnableToReadCommit {
+        version: u64,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("Unable to list the Delta transaction log: {source}"))]
+    UnableToListLog { source: object_store::Error },
+
+    #[snafu(display("Commit {version} of the Delta table was removed from its transaction log before its changes were applied, likely by log retention. Delete the accelerated data of the dataset to fully refresh it from the current version of the table."))]
+    CommitRemoved { version: u64 },
+
+    #[snafu(display("Unable to parse commit {version} of the Delta table: {source}"))]
+    UnableToParseCommit {
+        version: u64,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("Commit {version} of the Delta table adds files with deletion vectors, but has no change data. Enable the change data feed on the table with the 'delta.enableChangeDataFeed' property."))]
+    DeletionVectorsRequireChangeDataFeed { version: u64 },
+
+    #[snafu(display("Unable to read the changed file {path}: {source}"))]
+    UnableToReadChangeFile {
+        path: String,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("Unable to read Parquet data: {source}"))]
+    ParquetError {
+        source: datafusion::parquet::errors::ParquetError,
+    },
+
+    #[snafu(display("The change data file {path} has no {CHANGE_TYPE_COLUMN} column"))]
+    MissingChangeType { path: String },
+
+    #[snafu(display("An error occured with handling Arrow data: {source}"))]
+    ArrowError { source: arrow::error::ArrowError },
+
+    #[snafu(display("An error occured with reading the Delta table: {source}"))]
+    DataFusionError {
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("Unable to create the change batch: {source}"))]
+    ChangeBatchError {
+        source: crate::cdc::ChangeBatchError,
+    },
+}
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A Delta table whose changes can be streamed to keep an acceleration up to date.
+///
+/// The stream starts with the rows of the current version as `r` changes, unless it resumes after
+/// a version that was already applied, and then follows the commits of the table.
+pub struct DeltaChangeDataFeed {
+    table: Arc<DeltaTable>,
+    primary_keys: Vec<String>,
+    start_version: Option<u64>,
+    checkpoint: Option<Checkpoint>,
+    poll_interval: Duration,
+}
+
+impl DeltaChangeDataFeed {
+    #[must_use]
+    pub fn new(table: DeltaTable, primary_keys: Vec<String>) -> Self {
+        Self {
+            table: Arc::new(table),
+            primary_keys,
+            start_version: None,
+            checkpoint: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Resumes the stream after a version whose changes were already applied.
+    #[must_use]
+    pub fn with_start_version(mut self, version: Option<u64>) -> Self {
+        self.start_version = version;
+        self
+    }
+
+    #[must_use]
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// How often to check the transaction log for new commits.
+    #[must_use]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    #[must_use]
+    pub fn get_primary_keys(&self) -> &Vec<String> {
+        &self.primary_keys
+    }
+
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub fn stream_changes(&self) -> ChangesStream {
+        let table = Arc::clone(&self.table);
+        let primary_keys = self.primary_keys.clone();
+        let start_version = self.start_version;
+        let checkpoint = self.checkpoint.clone();
+        let poll_interval = self.poll_interval;
+
+        Box::pin(stream! {
+            let schema = table.schema();
+            let table_root = table.table.location().clone();
+            let store = match table_object_store(&table.table, &table.engine) {
+                Ok(store) => store,
+                Err(e) => {
+                    yield Err(StreamError::DeltaLake(e.to_string()));
+                    return;
+                }
+            };
+
+            let mut version = if let Some(version) = start_version {
+                version + 1
+            } else {
+                let snapshot = match table.pinned() {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        yield Err(StreamError::DeltaLake(e.to_string()));
+                        return;
+                    }
+                };
+                let Some(snapshot_version) = snapshot.version else {
+                    unreachable!("pinned tables have a version");
+                };
+
+                let mut batches = match read_snapshot(snapshot).await {
+                    Ok(batches) => batches,
+                    Err(e) => {
+                        yield Err(StreamError::DeltaLake(e.to_string()));
+                        return;
+                    }
+                };
+                let applied = AppliedChanges::default();
+                let mut changes = 0;
+                while let Some(batch) = batches.next().await {
+                    let change_batch = batch
+                        .context(DataFusionSnafu)
+                        .and_then(|batch| {
+                            let ops = StringArray::from(vec!["r"; batch.num_rows()]);
+                            let data = conform_to_schema(&schema, &batch, &HashMap::new())?;
+                            ChangeBatch::try_from_data(ops, &primary_keys, &data)
+                                .context(ChangeBatchSnafu)
+                        });
+                    match change_batch {
+                        Ok(change_batch) => {
+                            changes += 1;
+                            yield Ok(ChangeEnvelope::new(
+                                Box::new(VersionCommitter::change(applied.clone())),
+                                change_batch,
+                            ));
+                        }
+                        Err(e) => {
+                            yield Err(StreamError::DeltaLake(e.to_string()));
+                            return;
+                        }
+                    }
+                }
+
+                yield version_applied(
+                    &schema,
+                    snapshot_version,
+                    applied,
+                    changes,
+                    checkpoint.clone(),
+                );
+                snapshot_version + 1
+            };
+
+            loop {
+                let commit = match read_commit(&store, &table_root, version).await {
+                    Ok(Some(commit)) => commit,
+                    Ok(None) => {
+                        // A later version is only logged if this one was committed and removed
+                        // since, and its changes can't be read anymore.
+                        match later_version_logged(&store, &table_root, version).await {
+                            Ok(true) => {
+                                yield Err(StreamError::DeltaLake(
+                                    Error::CommitRemoved { version }.to_string(),
+                                ));
+                                return;
+                            }
+                            Ok(false) => {}
+                            Err(e) => yield Err(StreamError::DeltaLake(e.to_string())),
+                        }
+                        tokio::time::sleep(poll_interval).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        yield Err(StreamError::DeltaLake(e.to_string()));
+                        tokio::time::sleep(poll_interval).await;
+                        continue;
+                    }
+                };
+
+                let files = match change_files(version, &commit) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        yield Err(StreamError::DeltaLake(e.to_string()));
+                        tokio::time::sleep(poll_interval).await;
+                        continue;
+                    }
+                };
+
+                // The changes of a version that failed to be read are read again from the start,
+                // since applying them again is idempotent with the primary keys.
+                let applied = AppliedChanges::default();
+                let mut changes = 0;
+                let mut failed = false;
+                'files: for file in &files {
+                    let mut batches = match read_change_file(&store, &table_root, file).await {
+                        Ok(batches) => batches,
+                        Err(e) => {
+                            yield Err(StreamError::DeltaLake(e.to_string()));
+                            failed = true;
+                            break 'files;
+                        }
+                    };
+
+                    while let Some(batch) = batches.next().await {
+                        let change_batch = batch
+                            .context(ParquetSnafu)
+                            .and_then(|batch| file.to_change_batch(&schema, &primary_keys, &batch));
+                        match change_batch {
+                            Ok(change_batch) if change_batch.record.num_rows() == 0 => {}
+                            Ok(change_batch) => {
+                                changes += 1;
+                                yield Ok(ChangeEnvelope::new(
+                                    Box::new(VersionCommitter::change(applied.clone())),
+                                    change_batch,
+                                ));
+                            }
+                            Err(e) => {
+                                yield Err(StreamError::DeltaLake(e.to_string()));
+                                failed = true;
+                                break 'files;
+                            }
+                        }
+                    }
+                }
+
+                if failed {
+                    tokio::time::sleep(poll_interval).await;
+                    continue;
+                }
+
+                yield version_applied(&schema, version, applied, changes, checkpoint.clone());
+                version += 1;
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for DeltaChangeDataFeed {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.table.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.table.table_type()
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        self.table.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.table.scan(state, projection, filters, limit).await
+    }
+}
+
+impl DeltaTable {
+    /// The table at the version it currently reads, which stays the same for later scans.
+    fn pinned(&self) -> Result<Self> {
+        let snapshot = self
+            .table
+            .snapshot(self.engine.as_ref(), self.version)
+            .context(DeltaTableSnafu)?;
+
+        Ok(Self {
+            table: Table::new(self.table.location().clone()),
+            engine: Arc::clone(&self.engine),
+            arrow_schema: Arc::clone(&self.arrow_schema),
+            delta_schema: Arc::clone(&self.delta_schema),
+            version: Some(snapshot.version()),
+        })
+    }
+}
+
+/// Commits a change of a table version, or the version itself once all of its changes were
+/// committed, by recording it with the checkpoint.
+struct VersionCommitter {
+    applied: AppliedChanges,
+    version: Option<(u64, usize)>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl VersionCommitter {
+    fn change(applied: AppliedChanges) -> Self {
+        Self {
+            applied,
+            version: None,
+            checkpoint: None,
+        }
+    }
+}
+
+impl CommitChange for VersionCommitter {
+    fn commit(&self) -> Result<(), CommitError> {
+        let Some((version, changes)) = self.version else {
+            self.applied.applied();
+            return Ok(());
+        };
+
+        self.applied.ensure_applied(version, changes)?;
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint(version);
+        }
+
+        Ok(())
+    }
+}
+
+/// An empty change that marks all the `changes` of a version as applied.
+fn version_applied(
+    schema: &SchemaRef,
+    version: u64,
+    applied: AppliedChanges,
+    changes: usize,
+    checkpoint: Option<Checkpoint>,
+) -> Result<ChangeEnvelope, StreamError> {
+    let data = RecordBatch::new_empty(Arc::clone(schema));
+    let change_batch =
+        ChangeBatch::try_from_data(StringArray::from(Vec::<&str>::new()), &[], &data)
+            .map_err(|e| StreamError::DeltaLake(e.to_string()))?;
+
+    Ok(ChangeEnvelope::new(
+        Box::new(VersionCommitter {
+            applied,
+            version: Some((version, changes)),
+            checkpoint,
+        }),
+        change_batch,
+    ))
+}
+
+async fn read_snapshot(
+    snapshot: DeltaTable,
+) -> Result<datafusion::physical_plan::SendableRecordBatchStream> {
+    let ctx = SessionContext::new();
+    let plan = snapshot
+        .scan(&ctx.state(), None, &[], None)
+        .await
+        .context(DataFusionSnafu)?;
+
+    execute_stream(plan, ctx.task_ctx()).context(DataFusionSnafu)
+}
+
+/// Reads the actions of a commit, or `None` if the version isn't committed yet.
+async fn read_commit(
+    store: &Arc<dyn ObjectStore>,
+    table_root: &Url,
+    version: u64,
+) -> Result<Option<String>> {
+    let path = table_path(
+        table_root,
+        &format!("{DELTA_LOG_FOLDER}/{version:020}.json"),
+    )
+    .context(InvalidPathSnafu)?;
+
+    match store.get(&path).await {
+        Ok(result) => {
+            let commit = result
+                .bytes()
+                .await
+                .context(UnableToReadCommitSnafu { version })?;
+            Ok(Some(String::from_utf8_lossy(&commit).into_owned()))
+        }
+        Err(object_store::Error::NotFound { .. }) => Ok(None),
+        Err(source) => Err(Error::UnableToReadCommit { version, source }),
+    }
+}
+
+/// Whether the transaction log has a commit or checkpoint of a version after `version`.
+async fn later_version_logged(
+    store: &Arc<dyn ObjectStore>,
+    table_root: &Url,
+    version: u64,
+) -> Result<bool> {
+    let log_path = table_path(table_root, DELTA_LOG_FOLDER).context(InvalidPathSnafu)?;
+    let offset = table_path(
+        table_root,
+        &format!("{DELTA_LOG_FOLDER}/{version:020}.json"),
+    )
+    .context(InvalidPathSnafu)?;
+
+    let mut files = store.list_with_offset(Some(&log_path), &offset);
+    while let Some(file) = files.next().await {
+        let file = file.context(UnableToListLogSnafu)?;
+        if file
+            .location
+            .filename()
+            .and_then(logged_version)
+            .is_some_and(|logged| logged > version)
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// The version of a commit or checkpoint file in the transaction log, i.e.
+/// `00000000000000000010.json` or `00000000000000000010.checkpoint.parquet`.
+fn logged_version(filename: &str) -> Option<u64> {
+    let (version, _) = filename.split_once('.')?;
+    if version.len() != 20 || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    version.parse().ok()
+}
+
+/// The actions of a commit that change data files. Other actions are ignored.
+#[derive(Debug, Deserialize)]
+struct Action {
+    add: Option<FileAction>,
+    remove: Option<FileAction>,
+    cdc: Option<FileAction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileAction {
+    path: String,
+    #[serde(default)]
+    partition_values: HashMap<String, Option<String>>,
+    #[serde(default)]
+    data_change: bool,
+    #[serde(default)]
+    deletion_vector: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChangeFileKind {
+    /// A `_change_data` file, with the type of each change in its `_change_type` column.
+    ChangeData,
+    /// A data file added by a commit without change data, where every row is inserted.
+    Added,
+    /// A data file removed by a commit without change data, where every row is deleted.
+    Removed,
+}
+
+#[derive(Debug)]
+struct ChangeFile {
+    action: FileAction,
+    kind: ChangeFileKind,
+}
+
+/// The files with the changes of a commit, in the order to apply them.
+///
+/// Commits with change data only have their changes in the `cdc` files. Otherwise, the rows of
+/// added files are inserts and the rows of removed files are deletes, as Delta writers don't write
+/// change data for commits that only add or remove whole files.
+fn change_files(version: u64, commit: &str) -> Result<Vec<ChangeFile>> {
+    let mut change_data = vec![];
+    let mut removed = vec![];
+    let mut added = vec![];
+    for line in commit.lines().filter(|line| !line.trim().is_empty()) {
+        let action: Action =
+            serde_json::from_str(line).context(UnableToParseCommitSnafu { version })?;
+
+        if let Some(action) = action.cdc {
+            change_data.push(ChangeFile {
+                action,
+                kind: ChangeFileKind::ChangeData,
+            });
+        }
+        if let Some(action) = action.remove.filter(|action| action.data_change) {
+            removed.push(ChangeFile {
+                action,
+                kind: ChangeFileKind::Removed,
+            });
+        }
+        if let Some(action) = action.add.filter(|action| action.data_change) {
+            added.push(ChangeFile {
+                action,
+                kind: ChangeFileKind::Added,
+            });
+        }
+    }
+
+    if !change_data.is_empty() {
+        return Ok(change_data);
+    }
+
+    // The rows that are deleted by a deletion vector of an added file can't be told apart.
+    ensure!(
+        added
+            .iter()
+            .all(|file| file.action.deletion_vector.is_none()),
+        DeletionVectorsRequireChangeDataFeedSnafu { version }
+    );
+
+    // Removed files are deleted first, so that the rows of files that are rewritten are kept.
+    removed.extend(added);
+    Ok(removed)
+}
+
+async fn read_change_file(
+    store: &Arc<dyn ObjectStore>,
+    table_root: &Url,
+    file: &ChangeFile,
+) -> Result<ParquetRecordBatchStream<ParquetObjectReader>> {
+    let path = table_path(table_root, &file.action.path).context(InvalidPathSnafu)?;
+    let meta = store
+        .head(&path)
+        .await
+        .context(UnableToReadChangeFileSnafu {
+            path: file.action.path.clone(),
+        })?;
+
+    ParquetRecordBatchStreamBuilder::new(ParquetObjectReader::new(Arc::clone(store), meta))
+        .await
+        .context(ParquetSnafu)?
+        .build()
+        .context(ParquetSnafu)
+}
+
+impl ChangeFile {
+    fn to_change_batch(
+        &self,
+        schema: &SchemaRef,
+        primary_keys: &[String],
+        batch: &RecordBatch,
+    ) -> Result<ChangeBatch> {
+        let (ops, batch) = match self.kind {
+            ChangeFileKind::Added => (
+                StringArray::from(vec!["c"; batch.num_rows()]),
+                batch.clone(),
+            ),
+            ChangeFileKind::Removed => (
+                StringArray::from(vec!["d"; batch.num_rows()]),
+                batch.clone(),
+            ),
+            ChangeFileKind::ChangeData => {
+                let change_types =
+                    batch
+                        .column_by_name(CHANGE_TYPE_COLUMN)
+                        .context(MissingChangeTypeSnafu {
+                            path: self.action.path.clone(),
+                        })?;
+                let change_types = cast(change_types, &DataType::Utf8).context(ArrowSnafu)?;
+                let Some(change_types) = change_types.as_any().downcast_ref::<StringArray>() else {
+                    unreachable!("the change types were cast to Utf8");
+                };
+
+                let ops = change_types
+                    .iter()
+                    .map(|change_type| change_type.and_then(change_operation))
+                    .collect::<Vec<_>>();
+                let applied =
+                    BooleanArray::from(ops.iter().map(Option::is_some).collect::<Vec<_>>());
+                let batch = filter_record_batch(batch, &applied).context(ArrowSnafu)?;
+
+                (ops.into_iter().flatten().collect::<StringArray>(), batch)
+            }
+        };
+
+        let data = conform_to_schema(schema, &batch, &self.action.partition_values)?;
+        ChangeBatch::try_from_data(ops, primary_keys, &data).context(ChangeBatchSnafu)
+    }
+}
+
+/// The change operation of a `_change_type`. The pre-images of updates are skipped, as the
+/// post-images are upserted on the primary keys.
+fn change_operation(change_type: &str) -> Option<&'static str> {
+    match change_type {
+        "insert" => Some("c"),
+        "update_postimage" => Some("u"),
+        "delete" => Some("d"),
+        _ => None,
+    }
+}
+
+/// Projects the rows of a data file to the table schema. Partition columns aren't stored in the
+/// data files, and are filled in from the partition values of the file.
+fn conform_to_schema(
+    schema: &SchemaRef,
+    batch: &RecordBatch,
+    partition_values: &HashMap<String, Option<String>>,
+) -> Result<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| -> Result<ArrayRef> {
+            if let Some(column) = batch.column_by_name(field.name()) {
+                if column.data_type() == field.data_type() {
+                    return Ok(Arc::clone(column));
+                }
+                return cast(column, field.data_type()).context(ArrowSnafu);
+            }
+
+            match partition_values.get(field.name()) {
+                Some(Some(value)) => ScalarValue::try_from_string(value.clone(), field.data_type())
+                    .and_then(|value| value.to_array_of_size(num_rows))
+                    .context(DataFusionSnafu),
+                _ => Ok(new_null_array(field.data_type(), num_rows)),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    RecordBatch::try_new_with_options(
+        Arc::clone(schema),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )
+    .context(ArrowSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Int64Array,
+        datatypes::{Field, Schema},
+    };
+    use object_store::{memory::InMemory, path::Path};
+
+    #[tokio::test]
+    async fn test_later_version_logged() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let table_root = Url::parse("memory:///table/").expect("valid url");
+        for file in [
+            "_delta_log/_last_checkpoint",
+            "_delta_log/00000000000000000010.checkpoint.parquet",
+            "_delta_log/00000000000000000010.json",
+            "_delta_log/00000000000000000011.json",
+            "_delta_log/00000000000000000011.crc",
+        ] {
+            store
+                .put(
+                    &Path::from(format!("table/{file}")),
+                    Vec::<u8>::new().into(),
+                )
+                .await
+                .expect("file written");
+        }
+
+        // Commits up to 9 were removed by log retention
+        for removed in [0, 9] {
+            assert!(later_version_logged(&store, &table_root, removed)
+                .await
+                .expect("log listed"));
+        }
+        assert!(later_version_logged(&store, &table_root, 10)
+            .await
+            .expect("log listed"));
+        assert!(
+            !later_version_logged(&store, &table_root, 12)
+                .await
+                .expect("log listed"),
+            "version 12 isn't committed yet"
+        );
+        assert!(
+            !later_version_logged(&store, &table_root, 11)
+                .await
+                .expect("log listed"),
+            "version 11 is the latest commit"
+        );
+    }
+
+    #[test]
+    fn test_version_only_checkpointed_once_its_changes_are_applied() {
+        let checkpointed = Arc::new(std::sync::Mutex::new(vec![]));
+        let checkpoint: Checkpoint = {
+            let checkpointed = Arc::clone(&checkpointed);
+            Arc::new(move |version| checkpointed.lock().expect("lock").push(version))
+        };
+
+        let applied = AppliedChanges::default();
+        let changes = [
+            VersionCommitter::change(applied.clone()),
+            VersionCommitter::change(applied.clone()),
+        ];
+        let version = VersionCommitter {
+            applied,
+            version: Some((3, changes.len())),
+            checkpoint: Some(checkpoint),
+        };
+
+        changes[0].commit().expect("change committed");
+        assert!(matches!(
+            version.commit(),
+            Err(CommitError::ChangesNotApplied {
+                position: 3,
+                applied: 1,
+                expected: 2
+            })
+        ));
+        assert!(checkpointed.lock().expect("lock").is_empty());
+
+        changes[1].commit().expect("change committed");
+        version.commit().expect("version committed");
+        assert_eq!(*checkpointed.lock().expect("lock"), vec![3]);
+    }
+
+    #[test]
+    fn test_change_files() {
+        let commit = r#"{"commitInfo":{"timestamp":1724000000000,"operation":"MERGE"}}
+{"remove":{"path":"part-0.parquet","dataChange":true}}
+{"add":{"path":"part-1.parquet","partitionValues":{},"size":10,"dataChange":true}}
+{"cdc":{"path":"_change_data/cdc-0.parquet","partitionValues":{},"size":10,"dataChange":false}}"#;
+        let files = change_files(1, commit).expect("valid commit");
+        assert_eq!(files.len(), 1, "only the change data is read");
+        assert_eq!(files[0].kind, ChangeFileKind::ChangeData);
+        assert_eq!(files[0].action.path, "_change_data/cdc-0.parquet");
+
+        let commit = r#"{"add":{"path":"part-1.parquet","partitionValues":{"region":"us"},"size":10,"dataChange":true}}
+{"remove":{"path":"part-0.parquet","dataChange":true}}
+{"remove":{"path":"part-2.parquet","dataChange":false}}"#;
+        let files = change_files(2, commit).expect("valid commit");
+        let kinds = files.iter().map(|file| file.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![ChangeFileKind::Removed, ChangeFileKind::Added]);
+        assert_eq!(
+            files[1].action.partition_values.get("region"),
+            Some(&Some("us".to_string()))
+        );
+
+        let commit = r#"{"add":{"path":"part-1.parquet","partitionValues":{},"size":10,"dataChange":true,"deletionVector":{"storageType":"u","pathOrInlineDv":"ab","offset":1,"sizeInBytes":36,"cardinality":2}}}"#;
+        assert!(matches!(
+            change_files(3, commit),
+            Err(Error::DeletionVectorsRequireChangeDataFeed { version: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_change_data_to_change_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("region", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new(CHANGE_TYPE_COLUMN, DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 2, 3])),
+                Arc::new(StringArray::from(vec![
+                    "insert",
+                    "update_preimage",
+                    "update_postimage",
+                    "delete",
+                ])),
+            ],
+        )
+        .expect("valid batch");
+
+        let file = ChangeFile {
+            action: FileAction {
+                path: "_change_data/cdc-0.parquet".to_string(),
+                partition_values: HashMap::from([("region".to_string(), Some("us".to_string()))]),
+                data_change: false,
+                deletion_vector: None,
+            },
+            kind: ChangeFileKind::ChangeData,
+        };
+        let change_batch = file
+            .to_change_batch(&schema, &["id".to_string()], &batch)
+            .expect("valid change batch");
+
+        assert_eq!(change_batch.record.num_rows(), 3);
+        let ops = (0..3)
+            .map(|row| change_batch.op(row).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ops, vec!["c", "u", "d"]);
+        assert_eq!(change_batch.primary_keys(2), vec!["id".to_string()]);
+
+        let data = change_batch.data(1);
+        assert_eq!(data.schema(), schema);
+        let region = data
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .expect("region is a string column");
+        assert_eq!(region.value(0), "us");
+    }
+}
@@ -0,0 +1,561 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reads Apache Iceberg tables from an object store.
+//!
+//! The table metadata and manifests of a snapshot are read directly, and the Parquet data files
+//! that can match the filters of a query are scanned with a [`ParquetExec`]. Columns are matched
+//! by name, so tables with renamed columns are not supported. Tables with row-level deletes
+//! (format version 2 delete files) are not supported either.
+
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::DateTime;
+use datafusion::{
+    catalog::Session,
+    common::{project_schema, DFSchema},
+    datasource::{
+        listing::PartitionedFile,
+        physical_plan::{parquet::DefaultParquetFileReaderFactory, FileScanConfig, ParquetExec},
+        TableProvider, TableType,
+    },
+    error::DataFusionError,
+    execution::{object_store::ObjectStoreUrl, runtime_env::RuntimeEnv},
+    logical_expr::{utils::conjunction, Expr, TableProviderFilterPushDown},
+    physical_optimizer::pruning::PruningPredicate,
+    physical_plan::{empty::EmptyExec, ExecutionPlan},
+    sql::TableReference,
+};
+use futures::{future::try_join_all, StreamExt};
+use object_store::{path::Path, ObjectMeta, ObjectStore};
+use secrecy::{ExposeSecret, SecretString};
+use snafu::prelude::*;
+use std::{collections::HashMap, sync::Arc};
+use url::{form_urlencoded, Url};
+
+use crate::Read;
+
+use self::{
+    manifest::{
+        read_manifest, read_manifest_list, DataFile, ManifestFile, MANIFEST_CONTENT_DELETES,
+    },
+    metadata::{Schema, Snapshot, SnapshotSelection, TableMetadata},
+    pruning::{ContainerStatistics, IcebergPruningStatistics},
+};
+
+pub mod manifest;
+pub mod metadata;
+mod pruning;
+pub mod rest;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid Iceberg table location {location}: {message}"))]
+    InvalidTableLocation { location: String, message: String },
+
+    #[snafu(display("Unable to get the object store for {location}: {source}"))]
+    UnableToGetObjectStore {
+        location: String,
+        source: DataFusionError,
+    },
+
+    #[snafu(display("Unable to read {location}: {source}"))]
+    UnableToReadFile {
+        location: String,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("No Iceberg table metadata found in {location}"))]
+    NoMetadataFound { location: String },
+
+    #[snafu(display("Unable to parse the Iceberg table metadata: {source}"))]
+    UnableToParseMetadata { source: serde_json::Error },
+
+    #[snafu(display("The Iceberg snapshot {snapshot} doesn't exist"))]
+    SnapshotNotFound { snapshot: String },
+
+    #[snafu(display("The Iceberg type {type_name} is not supported"))]
+    UnsupportedType { type_name: String },
+
+    #[snafu(display("Unable to read the Iceberg manifest: {source}"))]
+    UnableToReadManifest { source: apache_avro::Error },
+
+    #[snafu(display("Invalid Iceberg manifest: {message}"))]
+    InvalidManifest { message: String },
+
+    #[snafu(display("The Iceberg table has row-level deletes, which are not supported"))]
+    DeleteFilesNotSupported,
+
+    #[snafu(display(
+        "The Iceberg table has {file_format} data files, only Parquet data files are supported"
+    ))]
+    UnsupportedFileFormat { file_format: String },
+
+    #[snafu(display("Iceberg REST catalog request failed: {source}"))]
+    ConnectionError { source: reqwest::Error },
+
+    #[snafu(display("Iceberg REST catalog request to {url} failed: {status}"))]
+    UnexpectedStatusCode {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+
+    #[snafu(display("The Iceberg REST catalog returned no metadata location for {table}"))]
+    NoMetadataLocation { table: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Creates [`IcebergTable`]s from the location of a table, or of one of its metadata files.
+pub struct IcebergTableFactory {
+    runtime_env: Arc<RuntimeEnv>,
+    storage_options: HashMap<String, SecretString>,
+    snapshot: SnapshotSelection,
+}
+
+impl IcebergTableFactory {
+    /// Object stores are resolved with the object store registry of `runtime_env`. The
+    /// `storage_options` are passed to the registry as the URL fragment, i.e. `region` or `key`.
+    #[must_use]
+    pub fn new(
+        runtime_env: Arc<RuntimeEnv>,
+        storage_options: HashMap<String, SecretString>,
+        snapshot: SnapshotSelection,
+    ) -> Self {
+        Self {
+            runtime_env,
+            storage_options,
+            snapshot,
+        }
+    }
+}
+
+#[async_trait]
+impl Read for IcebergTableFactory {
+    async fn table_provider(
+        &self,
+        table_reference: TableReference,
+        _schema: Option<SchemaRef>,
+    ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>> {
+        let table = IcebergTable::try_new(
+            &self.runtime_env,
+            table_reference.table(),
+            &self.storage_options,
+            self.snapshot,
+        )
+        .await?;
+
+        Ok(Arc::new(table))
+    }
+}
+
+pub struct IcebergTable {
+    store: Arc<dyn ObjectStore>,
+    metadata: TableMetadata,
+    snapshot: Option<Snapshot>,
+    schema: Schema,
+    arrow_schema: SchemaRef,
+}
+
+impl IcebergTable {
+    pub async fn try_new(
+        runtime_env: &RuntimeEnv,
+        location: &str,
+        storage_options: &HashMap<String, SecretString>,
+        selection: SnapshotSelection,
+    ) -> Result<Self> {
+        let url = parse_location(location)?;
+
+        let mut store_url = url.clone();
+        if !storage_options.is_empty() {
+            let mut fragment = form_urlencoded::Serializer::new(String::new());
+            for (key, value) in storage_options {
+                fragment.append_pair(key, value.expose_secret());
+            }
+            store_url.set_fragment(Some(&fragment.finish()));
+        }
+        let store = runtime_env
+            .object_store(&store_url)
+            .context(UnableToGetObjectStoreSnafu { location })?;
+
+        let metadata_path = find_metadata(&store, &url).await?;
+        let raw = read_file(&store, &metadata_path).await?;
+        let metadata: TableMetadata =
+            serde_json::from_slice(&raw).context(UnableToParseMetadataSnafu)?;
+
+        let snapshot = metadata.snapshot(selection)?.cloned();
+        let schema = metadata.schema(snapshot.as_ref())?.clone();
+        let arrow_schema = Arc::new(schema.to_arrow()?);
+
+        tracing::debug!(
+            "Loaded Iceberg table {} from {metadata_path}, snapshot {:?}",
+            metadata.location,
+            snapshot.as_ref().map(|snapshot| snapshot.snapshot_id)
+        );
+
+        Ok(Self {
+            store,
+            metadata,
+            snapshot,
+            schema,
+            arrow_schema,
+        })
+    }
+
+    /// The Parquet data files of the snapshot that may match `predicate`.
+    async fn data_files(&self, predicate: Option<&PruningPredicate>) -> Result<Vec<DataFile>> {
+        let Some(snapshot) = &self.snapshot else {
+            return Ok(vec![]);
+        };
+
+        let manifests = match &snapshot.manifest_list {
+            Some(manifest_list) => {
+                read_manifest_list(&read_file(&self.store, &to_store_path(manifest_list)?).await?)?
+            }
+            None => snapshot
+                .manifests
+                .iter()
+                .map(|manifest_path| ManifestFile {
+                    manifest_path: manifest_path.clone(),
+                    partition_spec_id: 0,
+                    content: 0,
+                    partitions: vec![],
+                })
+                .collect(),
+        };
+
+        let (delete_manifests, data_manifests): (Vec<_>, Vec<_>) = manifests
+            .into_iter()
+            .partition(|manifest| manifest.content == MANIFEST_CONTENT_DELETES);
+        let delete_files = try_join_all(
+            delete_manifests
+                .iter()
+                .map(|manifest| self.read_manifest(manifest)),
+        )
+        .await?;
+        ensure!(
+            delete_files.iter().all(Vec::is_empty),
+            DeleteFilesNotSupportedSnafu
+        );
+
+        let data_manifests = self.prune(data_manifests, predicate, |manifest| {
+            ContainerStatistics::from_manifest(
+                manifest,
+                self.metadata
+                    .partition_spec(manifest.partition_spec_id)
+                    .unwrap_or_default(),
+                &self.schema,
+                &self.arrow_schema,
+            )
+        });
+
+        let mut files = vec![];
+        let manifest_files = try_join_all(
+            data_manifests
+                .iter()
+                .map(|manifest| self.read_manifest(manifest)),
+        )
+        .await?;
+        for (manifest, data_files) in data_manifests.iter().zip(manifest_files) {
+            let partition_spec = self
+                .metadata
+                .partition_spec(manifest.partition_spec_id)
+                .unwrap_or_default();
+            files.extend(self.prune(data_files, predicate, |file| {
+                ContainerStatistics::from_data_file(
+                    file,
+                    partition_spec,
+                    &self.schema,
+                    &self.arrow_schema,
+                )
+            }));
+        }
+
+        if let Some(file) = files
+            .iter()
+            .find(|file| !file.file_format.eq_ignore_ascii_case("parquet"))
+        {
+            return UnsupportedFileFormatSnafu {
+                file_format: file.file_format.clone(),
+            }
+            .fail();
+        }
+
+        Ok(files)
+    }
+
+    async fn read_manifest(&self, manifest: &ManifestFile) -> Result<Vec<DataFile>> {
+        let raw = read_file(&self.store, &to_store_path(&manifest.manifest_path)?).await?;
+        read_manifest(&raw)
+    }
+
+    /// Keeps the manifests or data files whose statistics may match `predicate`.
+    fn prune<T>(
+        &self,
+        items: Vec<T>,
+        predicate: Option<&PruningPredicate>,
+        statistics: impl Fn(&T) -> ContainerStatistics,
+    ) -> Vec<T> {
+        let Some(predicate) = predicate else {
+            return items;
+        };
+
+        let containers = items.iter().map(statistics).collect();
+        let statistics = IcebergPruningStatistics::new(&self.arrow_schema, containers);
+        match predicate.prune(&statistics) {
+            Ok(keep) => items
+                .into_iter()
+                .zip(keep)
+                .filter_map(|(item, keep)| keep.then_some(item))
+                .collect(),
+            Err(e) => {
+                tracing::debug!("Unable to prune Iceberg files: {e}");
+                items
+            }
+        }
+    }
+
+    fn partitioned_file(&self, file: &DataFile) -> Result<PartitionedFile> {
+        let last_modified = self
+            .snapshot
+            .as_ref()
+            .and_then(|snapshot| DateTime::from_timestamp_millis(snapshot.timestamp_ms))
+            .unwrap_or_default();
+
+        Ok(PartitionedFile::from(ObjectMeta {
+            location: to_store_path(&file.file_path)?,
+            last_modified,
+            size: usize::try_from(file.file_size_in_bytes).unwrap_or_default(),
+            e_tag: None,
+            version: None,
+        }))
+    }
+}
+
+/// Parses a table location, which is either a URL or a local path.
+fn parse_location(location: &str) -> Result<Url> {
+    if let Ok(url) = Url::parse(location) {
+        return Ok(url);
+    }
+
+    let path = std::path::absolute(location).map_err(|e| Error::InvalidTableLocation {
+        location: location.to_string(),
+        message: e.to_string(),
+    })?;
+    Url::from_file_path(&path).map_err(|()| Error::InvalidTableLocation {
+        location: location.to_string(),
+        message: "Expected a URL or a local path".to_string(),
+    })
+}
+
+/// The path of a file in the table's object store. Iceberg records files by their full URL,
+/// although older tables may use plain paths.
+fn to_store_path(location: &str) -> Result<Path> {
+    match Url::parse(location) {
+        Ok(url) => Path::from_url_path(url.path()).map_err(|e| Error::InvalidTableLocation {
+            location: location.to_string(),
+            message: e.to_string(),
+        }),
+        Err(_) => Ok(Path::from(location)),
+    }
+}
+
+async fn read_file(store: &Arc<dyn ObjectStore>, path: &Path) -> Result<bytes::Bytes> {
+    let location = path.to_string();
+    store
+        .get(path)
+        .await
+        .context(UnableToReadFileSnafu {
+            location: location.clone(),
+        })?
+        .bytes()
+        .await
+        .context(UnableToReadFileSnafu { location })
+}
+
+/// Finds the current metadata file of a table. The location is either a metadata file, or the
+/// table directory, in which case `metadata/version-hint.text` or the metadata file with the
+/// highest version is used.
+async fn find_metadata(store: &Arc<dyn ObjectStore>, url: &Url) -> Result<Path> {
+    let location = url.to_string();
+    let path = to_store_path(&location)?;
+    if url.path().ends_with(".metadata.json") {
+        return Ok(path);
+    }
+
+    let metadata_dir = path.child("metadata");
+    match store.get(&metadata_dir.child("version-hint.text")).await {
+        Ok(result) => {
+            let hint = result.bytes().await.context(UnableToReadFileSnafu {
+                location: location.clone(),
+            })?;
+            let hint = String::from_utf8_lossy(&hint).trim().to_string();
+            if hint.ends_with(".metadata.json") {
+                return Ok(metadata_dir.child(hint));
+            }
+            if hint.parse::<u64>().is_ok() {
+                return Ok(metadata_dir.child(format!("v{hint}.metadata.json")));
+            }
+            tracing::debug!("Ignoring invalid version hint {hint} in {location}");
+        }
+        Err(object_store::Error::NotFound { .. }) => {}
+        Err(source) => return Err(Error::UnableToReadFile { location, source }),
+    }
+
+    let mut latest: Option<(u64, Path)> = None;
+    let mut list = store.list(Some(&metadata_dir));
+    while let Some(meta) = list.next().await {
+        let meta = meta.context(UnableToReadFileSnafu {
+            location: location.clone(),
+        })?;
+        let Some(version) = meta.location.filename().and_then(metadata_version) else {
+            continue;
+        };
+        if latest
+            .as_ref()
+            .map_or(true, |(latest, _)| version > *latest)
+        {
+            latest = Some((version, meta.location));
+        }
+    }
+
+    latest
+        .map(|(_, path)| path)
+        .context(NoMetadataFoundSnafu { location })
+}
+
+/// The version of a metadata file name, i.e. `v3.metadata.json` or `00003-<uuid>.metadata.json`.
+fn metadata_version(filename: &str) -> Option<u64> {
+    let name = filename.strip_suffix(".metadata.json")?;
+    let version = name.split('-').next()?;
+    version.strip_prefix('v').unwrap_or(version).parse().ok()
+}
+
+#[async_trait]
+impl TableProvider for IcebergTable {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.arrow_schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>, DataFusionError> {
+        Ok(vec![TableProviderFilterPushDown::Inexact; filters.len()])
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let df_schema = DFSchema::try_from(Arc::clone(&self.arrow_schema))?;
+        let predicate = conjunction(filters.to_vec())
+            .map(|filter| state.create_physical_expr(filter, &df_schema))
+            .transpose()?;
+        let pruning_predicate = predicate
+            .as_ref()
+            .and_then(|predicate| {
+                PruningPredicate::try_new(Arc::clone(predicate), Arc::clone(&self.arrow_schema))
+                    .ok()
+            })
+            .filter(|predicate| !predicate.always_true());
+
+        let files = self
+            .data_files(pruning_predicate.as_ref())
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        if files.is_empty() {
+            return Ok(Arc::new(EmptyExec::new(project_schema(
+                &self.arrow_schema,
+                projection,
+            )?)));
+        }
+
+        // Spread the files over the target partitions, to read them in parallel.
+        let num_groups = state.config().target_partitions().clamp(1, files.len());
+        let mut file_groups = vec![vec![]; num_groups];
+        for (i, file) in files.iter().enumerate() {
+            file_groups[i % num_groups].push(
+                self.partitioned_file(file)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?,
+            );
+        }
+
+        let mut file_scan_config = FileScanConfig::new(
+            ObjectStoreUrl::local_filesystem(),
+            Arc::clone(&self.arrow_schema),
+        )
+        .with_projection(projection.cloned())
+        .with_limit(limit);
+        for file_group in file_groups {
+            file_scan_config = file_scan_config.with_file_group(file_group);
+        }
+
+        // The object store is provided by the reader factory, the object store URL is unused.
+        let mut builder =
+            ParquetExec::builder(file_scan_config).with_parquet_file_reader_factory(Arc::new(
+                DefaultParquetFileReaderFactory::new(Arc::clone(&self.store)),
+            ));
+        if let Some(predicate) = predicate {
+            builder = builder.with_predicate(predicate);
+        }
+
+        Ok(Arc::new(builder.build()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_version() {
+        assert_eq!(metadata_version("v3.metadata.json"), Some(3));
+        assert_eq!(
+            metadata_version("00012-9c12d441-03fe-4693-9a96-a0705ddf69c1.metadata.json"),
+            Some(12)
+        );
+        assert_eq!(metadata_version("version-hint.text"), None);
+        assert_eq!(metadata_version("snap-1.avro"), None);
+    }
+
+    #[test]
+    fn test_to_store_path() {
+        assert_eq!(
+            to_store_path("s3://bucket/warehouse/db/t/data/00000-0-a.parquet")
+                .expect("valid location")
+                .as_ref(),
+            "warehouse/db/t/data/00000-0-a.parquet"
+        );
+        assert_eq!(
+            to_store_path("file:/tmp/warehouse/t/metadata/v1.metadata.json")
+                .expect("valid location")
+                .as_ref(),
+            "tmp/warehouse/t/metadata/v1.metadata.json"
+        );
+    }
+}
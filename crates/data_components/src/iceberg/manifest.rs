@@ -0,0 +1,337 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The Avro manifest lists and manifests of an Iceberg snapshot.
+//!
+//! Records are read as generic Avro values rather than deserialized, since the schemas differ
+//! between format versions and the partition record depends on the partition spec.
+//!
+//! See <https://iceberg.apache.org/spec/#manifests>
+
+use apache_avro::{types::Value, Reader};
+use snafu::prelude::*;
+use std::collections::HashMap;
+
+use super::{InvalidManifestSnafu, Result, UnableToReadManifestSnafu};
+
+/// Manifests with `content` set to this track delete files, rather than data files.
+pub const MANIFEST_CONTENT_DELETES: i32 = 1;
+
+/// Manifest entries with `status` set to this are files removed by the snapshot.
+const ENTRY_STATUS_DELETED: i32 = 2;
+
+/// An entry in a snapshot's manifest list.
+#[derive(Debug, Clone)]
+pub struct ManifestFile {
+    pub manifest_path: String,
+    pub partition_spec_id: i32,
+    pub content: i32,
+    /// Summaries of the partition values in the manifest, one per partition field.
+    pub partitions: Vec<FieldSummary>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldSummary {
+    pub contains_null: bool,
+    pub lower_bound: Option<Vec<u8>>,
+    pub upper_bound: Option<Vec<u8>>,
+}
+
+/// A data (or delete) file that is live in a snapshot.
+#[derive(Debug, Clone)]
+pub struct DataFile {
+    /// 0 for data files, 1 for position deletes and 2 for equality deletes.
+    pub content: i32,
+    pub file_path: String,
+    pub file_format: String,
+    /// The partition values of the file, by partition field name.
+    pub partition: HashMap<String, Value>,
+    pub record_count: i64,
+    pub file_size_in_bytes: i64,
+    /// Column statistics, keyed by field ID. Bounds use Iceberg's single-value serialization.
+    pub null_value_counts: HashMap<i32, i64>,
+    pub lower_bounds: HashMap<i32, Vec<u8>>,
+    pub upper_bounds: HashMap<i32, Vec<u8>>,
+}
+
+pub fn read_manifest_list(raw: &[u8]) -> Result<Vec<ManifestFile>> {
+    let reader = Reader::new(raw).context(UnableToReadManifestSnafu)?;
+
+    let mut manifests = vec![];
+    for record in reader {
+        let record = record.context(UnableToReadManifestSnafu)?;
+        let manifest_path = field(&record, "manifest_path")
+            .and_then(as_string)
+            .context(InvalidManifestSnafu {
+                message: "The manifest list has an entry without a manifest_path",
+            })?;
+
+        let partitions = match field(&record, "partitions") {
+            Some(Value::Array(summaries)) => summaries
+                .iter()
+                .map(|summary| FieldSummary {
+                    contains_null: field(summary, "contains_null")
+                        .and_then(as_bool)
+                        .unwrap_or(true),
+                    lower_bound: field(summary, "lower_bound").and_then(as_bytes),
+                    upper_bound: field(summary, "upper_bound").and_then(as_bytes),
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        manifests.push(ManifestFile {
+            manifest_path,
+            partition_spec_id: field(&record, "partition_spec_id")
+                .and_then(as_i32)
+                .unwrap_or_default(),
+            // Format version 1 manifests only track data files.
+            content: field(&record, "content")
+                .and_then(as_i32)
+                .unwrap_or_default(),
+            partitions,
+        });
+    }
+
+    Ok(manifests)
+}
+
+/// Reads the files of a manifest that are live in its snapshot, i.e. skipping deleted entries.
+pub fn read_manifest(raw: &[u8]) -> Result<Vec<DataFile>> {
+    let reader = Reader::new(raw).context(UnableToReadManifestSnafu)?;
+
+    let mut files = vec![];
+    for entry in reader {
+        let entry = entry.context(UnableToReadManifestSnafu)?;
+        if field(&entry, "status").and_then(as_i32) == Some(ENTRY_STATUS_DELETED) {
+            continue;
+        }
+
+        let data_file = field(&entry, "data_file").context(InvalidManifestSnafu {
+            message: "The manifest has an entry without a data_file",
+        })?;
+        let file_path =
+            field(data_file, "file_path")
+                .and_then(as_string)
+                .context(InvalidManifestSnafu {
+                    message: "The manifest has a data file without a file_path",
+                })?;
+
+        let partition = match field(data_file, "partition") {
+            Some(Value::Record(values)) => values
+                .iter()
+                .map(|(name, value)| (name.clone(), unwrap_union(value).clone()))
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        files.push(DataFile {
+            content: field(data_file, "content")
+                .and_then(as_i32)
+                .unwrap_or_default(),
+            file_format: field(data_file, "file_format")
+                .and_then(as_string)
+                .unwrap_or_else(|| "PARQUET".to_string()),
+            partition,
+            record_count: field(data_file, "record_count")
+                .and_then(as_i64)
+                .unwrap_or_default(),
+            file_size_in_bytes: field(data_file, "file_size_in_bytes")
+                .and_then(as_i64)
+                .unwrap_or_default(),
+            null_value_counts: id_map(field(data_file, "null_value_counts"), as_i64),
+            lower_bounds: id_map(field(data_file, "lower_bounds"), as_bytes),
+            upper_bounds: id_map(field(data_file, "upper_bounds"), as_bytes),
+            file_path,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Optional fields are unions with null.
+fn unwrap_union(value: &Value) -> &Value {
+    match value {
+        Value::Union(_, value) => value,
+        value => value,
+    }
+}
+
+fn field<'a>(record: &'a Value, name: &str) -> Option<&'a Value> {
+    let Value::Record(fields) = unwrap_union(record) else {
+        return None;
+    };
+
+    fields
+        .iter()
+        .find(|(field_name, _)| field_name == name)
+        .map(|(_, value)| unwrap_union(value))
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) | Value::Enum(_, value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Boolean(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn as_i32(value: &Value) -> Option<i32> {
+    match value {
+        Value::Int(value) => Some(*value),
+        Value::Long(value) => i32::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(value) => Some(i64::from(*value)),
+        Value::Long(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn as_bytes(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Bytes(value) | Value::Fixed(_, value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Reads a map keyed by field ID, which Avro encodes as an array of key-value records.
+fn id_map<T>(value: Option<&Value>, convert: fn(&Value) -> Option<T>) -> HashMap<i32, T> {
+    let Some(Value::Array(entries)) = value else {
+        return HashMap::new();
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let key = field(entry, "key").and_then(as_i32)?;
+            let value = field(entry, "value").and_then(convert)?;
+            Some((key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apache_avro::{Schema, Writer};
+
+    const MANIFEST_SCHEMA: &str = r#"{
+        "type": "record", "name": "manifest_entry", "fields": [
+            {"name": "status", "type": "int"},
+            {"name": "snapshot_id", "type": ["null", "long"]},
+            {"name": "data_file", "type": {"type": "record", "name": "r2", "fields": [
+                {"name": "content", "type": "int"},
+                {"name": "file_path", "type": "string"},
+                {"name": "file_format", "type": "string"},
+                {"name": "partition", "type": {"type": "record", "name": "r102", "fields": [
+                    {"name": "region", "type": ["null", "string"]}
+                ]}},
+                {"name": "record_count", "type": "long"},
+                {"name": "file_size_in_bytes", "type": "long"},
+                {"name": "lower_bounds", "type": ["null", {"type": "array", "items": {
+                    "type": "record", "name": "k126_v127", "fields": [
+                        {"name": "key", "type": "int"},
+                        {"name": "value", "type": "bytes"}
+                    ]}}]}
+            ]}}
+        ]
+    }"#;
+
+    fn entry(status: i32, file_path: &str, region: &str) -> Value {
+        Value::Record(vec![
+            ("status".to_string(), Value::Int(status)),
+            (
+                "snapshot_id".to_string(),
+                Value::Union(1, Box::new(Value::Long(1))),
+            ),
+            (
+                "data_file".to_string(),
+                Value::Record(vec![
+                    ("content".to_string(), Value::Int(0)),
+                    (
+                        "file_path".to_string(),
+                        Value::String(file_path.to_string()),
+                    ),
+                    (
+                        "file_format".to_string(),
+                        Value::String("PARQUET".to_string()),
+                    ),
+                    (
+                        "partition".to_string(),
+                        Value::Record(vec![(
+                            "region".to_string(),
+                            Value::Union(1, Box::new(Value::String(region.to_string()))),
+                        )]),
+                    ),
+                    ("record_count".to_string(), Value::Long(10)),
+                    ("file_size_in_bytes".to_string(), Value::Long(1024)),
+                    (
+                        "lower_bounds".to_string(),
+                        Value::Union(
+                            1,
+                            Box::new(Value::Array(vec![Value::Record(vec![
+                                ("key".to_string(), Value::Int(1)),
+                                (
+                                    "value".to_string(),
+                                    Value::Bytes(5_i64.to_le_bytes().to_vec()),
+                                ),
+                            ])])),
+                        ),
+                    ),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let schema = Schema::parse_str(MANIFEST_SCHEMA).expect("valid schema");
+        let mut writer = Writer::new(&schema, Vec::new());
+        writer
+            .append(entry(1, "s3://bucket/data/a.parquet", "us"))
+            .expect("valid entry");
+        writer
+            .append(entry(2, "s3://bucket/data/b.parquet", "eu"))
+            .expect("valid entry");
+        let raw = writer.into_inner().expect("valid manifest");
+
+        let files = read_manifest(&raw).expect("valid manifest");
+        assert_eq!(files.len(), 1, "deleted entries are skipped");
+
+        let file = &files[0];
+        assert_eq!(file.file_path, "s3://bucket/data/a.parquet");
+        assert_eq!(file.record_count, 10);
+        assert_eq!(
+            file.partition.get("region"),
+            Some(&Value::String("us".to_string()))
+        );
+        assert_eq!(
+            file.lower_bounds.get(&1),
+            Some(&5_i64.to_le_bytes().to_vec())
+        );
+    }
+}
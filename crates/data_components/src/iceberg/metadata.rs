@@ -0,0 +1,422 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The Iceberg table metadata file (`metadata/*.metadata.json`), for format versions 1 and 2.
+//!
+//! See <https://iceberg.apache.org/spec/#table-metadata-fields>
+
+use arrow::datatypes::{DataType, Field, Fields, Schema as ArrowSchema, TimeUnit};
+use serde::Deserialize;
+use snafu::prelude::*;
+use std::sync::Arc;
+
+use super::{Result, SnapshotNotFoundSnafu, UnsupportedTypeSnafu};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TableMetadata {
+    pub format_version: u8,
+    pub location: String,
+    #[serde(default)]
+    pub current_schema_id: Option<i32>,
+    #[serde(default)]
+    pub schemas: Vec<Schema>,
+    /// The table schema, only in format version 1.
+    #[serde(default)]
+    pub schema: Option<Schema>,
+    #[serde(default)]
+    pub partition_specs: Vec<PartitionSpec>,
+    /// The partition fields of the table, only in format version 1.
+    #[serde(default)]
+    pub partition_spec: Vec<PartitionField>,
+    #[serde(default)]
+    pub current_snapshot_id: Option<i64>,
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Schema {
+    #[serde(default)]
+    pub schema_id: i32,
+    pub fields: Vec<NestedField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NestedField {
+    pub id: i32,
+    pub name: String,
+    pub required: bool,
+    /// Either the name of a primitive type, or a `struct`, `list` or `map` object.
+    #[serde(rename = "type")]
+    pub field_type: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PartitionSpec {
+    pub spec_id: i32,
+    pub fields: Vec<PartitionField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PartitionField {
+    pub source_id: i32,
+    pub name: String,
+    pub transform: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snapshot {
+    pub snapshot_id: i64,
+    pub timestamp_ms: i64,
+    #[serde(default)]
+    pub schema_id: Option<i32>,
+    /// The Avro file listing the manifests of the snapshot.
+    #[serde(default)]
+    pub manifest_list: Option<String>,
+    /// The manifests of the snapshot, in format version 1 tables written without a manifest list.
+    #[serde(default)]
+    pub manifests: Vec<String>,
+}
+
+/// The snapshot of an Iceberg table to read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SnapshotSelection {
+    #[default]
+    Current,
+    Id(i64),
+    /// The latest snapshot at or before a timestamp, in milliseconds since the epoch.
+    AsOf(i64),
+}
+
+impl TableMetadata {
+    /// Selects a snapshot, or returns `None` if the table has no snapshots yet (i.e. it was just
+    /// created).
+    pub fn snapshot(&self, selection: SnapshotSelection) -> Result<Option<&Snapshot>> {
+        match selection {
+            SnapshotSelection::Current => {
+                // Format version 1 tables use -1 when there is no current snapshot.
+                let Some(snapshot_id) = self.current_snapshot_id.filter(|id| *id >= 0) else {
+                    return Ok(None);
+                };
+                self.snapshot_by_id(snapshot_id).map(Some)
+            }
+            SnapshotSelection::Id(snapshot_id) => self.snapshot_by_id(snapshot_id).map(Some),
+            SnapshotSelection::AsOf(timestamp_ms) => self
+                .snapshots
+                .iter()
+                .filter(|snapshot| snapshot.timestamp_ms <= timestamp_ms)
+                .max_by_key(|snapshot| snapshot.timestamp_ms)
+                .map(Some)
+                .context(SnapshotNotFoundSnafu {
+                    snapshot: format!("as of {timestamp_ms}"),
+                }),
+        }
+    }
+
+    fn snapshot_by_id(&self, snapshot_id: i64) -> Result<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.snapshot_id == snapshot_id)
+            .context(SnapshotNotFoundSnafu {
+                snapshot: snapshot_id.to_string(),
+            })
+    }
+
+    /// The schema of a snapshot, which is the current schema for snapshots that don't record
+    /// their schema.
+    pub fn schema(&self, snapshot: Option<&Snapshot>) -> Result<&Schema> {
+        let schema_id = snapshot
+            .and_then(|snapshot| snapshot.schema_id)
+            .or(self.current_schema_id);
+
+        let schema = match schema_id {
+            Some(schema_id) => self
+                .schemas
+                .iter()
+                .find(|schema| schema.schema_id == schema_id)
+                .or(self.schema.as_ref()),
+            None => self.schema.as_ref().or(self.schemas.last()),
+        };
+
+        schema.context(UnsupportedTypeSnafu {
+            type_name: "table metadata without a schema",
+        })
+    }
+
+    pub fn partition_spec(&self, spec_id: i32) -> Option<&[PartitionField]> {
+        self.partition_specs
+            .iter()
+            .find(|spec| spec.spec_id == spec_id)
+            .map(|spec| spec.fields.as_slice())
+            .or_else(|| (spec_id == 0).then_some(self.partition_spec.as_slice()))
+    }
+}
+
+impl Schema {
+    pub fn to_arrow(&self) -> Result<ArrowSchema> {
+        let fields = self
+            .fields
+            .iter()
+            .map(NestedField::to_arrow)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ArrowSchema::new(fields))
+    }
+
+    pub fn field_by_id(&self, id: i32) -> Option<&NestedField> {
+        self.fields.iter().find(|field| field.id == id)
+    }
+
+    pub fn field_by_name(&self, name: &str) -> Option<&NestedField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl NestedField {
+    pub fn to_arrow(&self) -> Result<Field> {
+        Ok(Field::new(
+            &self.name,
+            to_arrow_type(&self.field_type)?,
+            !self.required,
+        ))
+    }
+}
+
+/// Maps an Iceberg type to an Arrow type.
+///
+/// See <https://iceberg.apache.org/spec/#schemas-and-data-types>
+pub fn to_arrow_type(field_type: &serde_json::Value) -> Result<DataType> {
+    match field_type {
+        serde_json::Value::String(name) => primitive_to_arrow_type(name),
+        serde_json::Value::Object(object) => {
+            let type_name = object
+                .get("type")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default();
+            match type_name {
+                "struct" => {
+                    let fields: Vec<NestedField> = object
+                        .get("fields")
+                        .cloned()
+                        .and_then(|fields| serde_json::from_value(fields).ok())
+                        .context(UnsupportedTypeSnafu {
+                            type_name: field_type.to_string(),
+                        })?;
+                    let fields = fields
+                        .iter()
+                        .map(NestedField::to_arrow)
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(DataType::Struct(Fields::from(fields)))
+                }
+                "list" => {
+                    let element = object.get("element").context(UnsupportedTypeSnafu {
+                        type_name: field_type.to_string(),
+                    })?;
+                    let element_required = object
+                        .get("element-required")
+                        .and_then(serde_json::Value::as_bool)
+                        .unwrap_or_default();
+                    Ok(DataType::List(Arc::new(Field::new(
+                        "element",
+                        to_arrow_type(element)?,
+                        !element_required,
+                    ))))
+                }
+                "map" => {
+                    let (Some(key), Some(value)) = (object.get("key"), object.get("value")) else {
+                        return UnsupportedTypeSnafu {
+                            type_name: field_type.to_string(),
+                        }
+                        .fail();
+                    };
+                    let value_required = object
+                        .get("value-required")
+                        .and_then(serde_json::Value::as_bool)
+                        .unwrap_or_default();
+                    let entries = Field::new(
+                        "key_value",
+                        DataType::Struct(Fields::from(vec![
+                            Field::new("key", to_arrow_type(key)?, false),
+                            Field::new("value", to_arrow_type(value)?, !value_required),
+                        ])),
+                        false,
+                    );
+                    Ok(DataType::Map(Arc::new(entries), false))
+                }
+                _ => UnsupportedTypeSnafu {
+                    type_name: field_type.to_string(),
+                }
+                .fail(),
+            }
+        }
+        _ => UnsupportedTypeSnafu {
+            type_name: field_type.to_string(),
+        }
+        .fail(),
+    }
+}
+
+fn primitive_to_arrow_type(name: &str) -> Result<DataType> {
+    let data_type = match name {
+        "boolean" => DataType::Boolean,
+        "int" => DataType::Int32,
+        "long" => DataType::Int64,
+        "float" => DataType::Float32,
+        "double" => DataType::Float64,
+        "date" => DataType::Date32,
+        "time" => DataType::Time64(TimeUnit::Microsecond),
+        "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+        "timestamptz" => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        "timestamp_ns" => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        "timestamptz_ns" => DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        "string" => DataType::Utf8,
+        "uuid" => DataType::FixedSizeBinary(16),
+        "binary" => DataType::Binary,
+        _ => {
+            if let Some(length) = name
+                .strip_prefix("fixed[")
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                let length = length.trim().parse().ok().context(UnsupportedTypeSnafu {
+                    type_name: name.to_string(),
+                })?;
+                return Ok(DataType::FixedSizeBinary(length));
+            }
+
+            if let Some(precision_scale) = name
+                .strip_prefix("decimal(")
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                let (precision, scale) =
+                    precision_scale
+                        .split_once(',')
+                        .context(UnsupportedTypeSnafu {
+                            type_name: name.to_string(),
+                        })?;
+                let (Ok(precision), Ok(scale)) = (precision.trim().parse(), scale.trim().parse())
+                else {
+                    return UnsupportedTypeSnafu {
+                        type_name: name.to_string(),
+                    }
+                    .fail();
+                };
+                return Ok(DataType::Decimal128(precision, scale));
+            }
+
+            return UnsupportedTypeSnafu {
+                type_name: name.to_string(),
+            }
+            .fail();
+        }
+    };
+
+    Ok(data_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "format-version": 2,
+        "table-uuid": "9c12d441-03fe-4693-9a96-a0705ddf69c1",
+        "location": "s3://bucket/warehouse/db/events",
+        "last-sequence-number": 2,
+        "last-updated-ms": 1726000000000,
+        "last-column-id": 4,
+        "current-schema-id": 1,
+        "schemas": [
+            {"type": "struct", "schema-id": 0, "fields": [
+                {"id": 1, "name": "id", "required": true, "type": "long"}
+            ]},
+            {"type": "struct", "schema-id": 1, "fields": [
+                {"id": 1, "name": "id", "required": true, "type": "long"},
+                {"id": 2, "name": "ts", "required": false, "type": "timestamptz"},
+                {"id": 3, "name": "amount", "required": false, "type": "decimal(10, 2)"},
+                {"id": 4, "name": "tags", "required": false, "type":
+                    {"type": "list", "element-id": 5, "element": "string", "element-required": false}}
+            ]}
+        ],
+        "default-spec-id": 0,
+        "partition-specs": [{"spec-id": 0, "fields": [
+            {"source-id": 2, "field-id": 1000, "name": "ts_day", "transform": "day"}
+        ]}],
+        "current-snapshot-id": 2,
+        "snapshots": [
+            {"snapshot-id": 1, "timestamp-ms": 1725000000000, "schema-id": 0,
+             "manifest-list": "s3://bucket/warehouse/db/events/metadata/snap-1.avro"},
+            {"snapshot-id": 2, "timestamp-ms": 1726000000000, "schema-id": 1,
+             "manifest-list": "s3://bucket/warehouse/db/events/metadata/snap-2.avro"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_table_metadata_snapshots() {
+        let metadata: TableMetadata = serde_json::from_str(METADATA).expect("valid metadata");
+
+        let current = metadata
+            .snapshot(SnapshotSelection::Current)
+            .expect("snapshot exists")
+            .expect("table has snapshots");
+        assert_eq!(current.snapshot_id, 2);
+
+        let as_of = metadata
+            .snapshot(SnapshotSelection::AsOf(1_725_500_000_000))
+            .expect("snapshot exists")
+            .expect("table has snapshots");
+        assert_eq!(as_of.snapshot_id, 1);
+        assert_eq!(
+            metadata
+                .schema(Some(as_of))
+                .expect("schema exists")
+                .fields
+                .len(),
+            1
+        );
+
+        assert!(metadata.snapshot(SnapshotSelection::Id(3)).is_err());
+        assert!(metadata
+            .snapshot(SnapshotSelection::AsOf(1_000_000_000_000))
+            .is_err());
+    }
+
+    #[test]
+    fn test_schema_to_arrow() {
+        let metadata: TableMetadata = serde_json::from_str(METADATA).expect("valid metadata");
+        let schema = metadata
+            .schema(None)
+            .expect("schema exists")
+            .to_arrow()
+            .expect("supported types");
+
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert!(!schema.field(0).is_nullable());
+        assert_eq!(
+            schema.field(1).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        );
+        assert_eq!(schema.field(2).data_type(), &DataType::Decimal128(10, 2));
+        assert_eq!(
+            schema.field(3).data_type(),
+            &DataType::List(Arc::new(Field::new("element", DataType::Utf8, true)))
+        );
+    }
+}
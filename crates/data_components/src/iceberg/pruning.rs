@@ -0,0 +1,326 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Statistics of Iceberg manifests and data files, to skip the ones that can't match the
+//! filters of a query with a [`PruningPredicate`](datafusion::physical_optimizer::pruning::PruningPredicate).
+
+use apache_avro::types::Value;
+use arrow::{
+    array::{ArrayRef, BooleanArray, UInt64Array},
+    datatypes::{DataType, Schema as ArrowSchema, TimeUnit},
+};
+use datafusion::{
+    common::Column, physical_optimizer::pruning::PruningStatistics, scalar::ScalarValue,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use super::{
+    manifest::{DataFile, ManifestFile},
+    metadata::{PartitionField, Schema},
+};
+
+/// Only identity partitions have values of their source column. Other transforms (i.e. `day` or
+/// `bucket[16]`) are only used through the column bounds of the data files.
+const IDENTITY_TRANSFORM: &str = "identity";
+
+/// The statistics of one manifest or data file, by column name.
+#[derive(Debug, Default)]
+pub(crate) struct ContainerStatistics {
+    bounds: HashMap<String, (ScalarValue, ScalarValue)>,
+    null_counts: HashMap<String, u64>,
+    row_count: Option<u64>,
+}
+
+impl ContainerStatistics {
+    /// The statistics of a manifest, from the summaries of its identity partition values.
+    pub(crate) fn from_manifest(
+        manifest: &ManifestFile,
+        partition_spec: &[PartitionField],
+        schema: &Schema,
+        arrow_schema: &ArrowSchema,
+    ) -> Self {
+        let mut statistics = Self::default();
+        for (partition_field, summary) in partition_spec.iter().zip(&manifest.partitions) {
+            let Some((name, data_type)) = identity_source(partition_field, schema, arrow_schema)
+            else {
+                continue;
+            };
+
+            if let (Some(lower), Some(upper)) = (&summary.lower_bound, &summary.upper_bound) {
+                if let (Some(min), Some(max)) = (
+                    decode_bound(lower, data_type),
+                    decode_bound(upper, data_type),
+                ) {
+                    statistics.bounds.insert(name.to_string(), (min, max));
+                }
+            }
+        }
+
+        statistics
+    }
+
+    /// The statistics of a data file, from its column metrics and identity partition values.
+    pub(crate) fn from_data_file(
+        file: &DataFile,
+        partition_spec: &[PartitionField],
+        schema: &Schema,
+        arrow_schema: &ArrowSchema,
+    ) -> Self {
+        let row_count = u64::try_from(file.record_count).ok();
+        let mut statistics = Self {
+            row_count,
+            ..Default::default()
+        };
+
+        for field in &schema.fields {
+            let Ok(arrow_field) = arrow_schema.field_with_name(&field.name) else {
+                continue;
+            };
+
+            if let Some(null_count) = file
+                .null_value_counts
+                .get(&field.id)
+                .and_then(|count| u64::try_from(*count).ok())
+            {
+                statistics
+                    .null_counts
+                    .insert(field.name.clone(), null_count);
+            }
+
+            if let (Some(lower), Some(upper)) = (
+                file.lower_bounds.get(&field.id),
+                file.upper_bounds.get(&field.id),
+            ) {
+                if let (Some(min), Some(max)) = (
+                    decode_bound(lower, arrow_field.data_type()),
+                    decode_bound(upper, arrow_field.data_type()),
+                ) {
+                    statistics.bounds.insert(field.name.clone(), (min, max));
+                }
+            }
+        }
+
+        for partition_field in partition_spec {
+            let Some((name, data_type)) = identity_source(partition_field, schema, arrow_schema)
+            else {
+                continue;
+            };
+            let Some(value) = file.partition.get(&partition_field.name) else {
+                continue;
+            };
+
+            if *value == Value::Null {
+                if let Some(row_count) = row_count {
+                    statistics.null_counts.insert(name.to_string(), row_count);
+                }
+            } else if let Some(value) = avro_to_scalar(value, data_type) {
+                statistics
+                    .bounds
+                    .insert(name.to_string(), (value.clone(), value));
+                statistics.null_counts.insert(name.to_string(), 0);
+            }
+        }
+
+        statistics
+    }
+}
+
+/// The name and type of the source column of an identity partition field.
+fn identity_source<'a>(
+    partition_field: &PartitionField,
+    schema: &'a Schema,
+    arrow_schema: &'a ArrowSchema,
+) -> Option<(&'a str, &'a DataType)> {
+    if partition_field.transform != IDENTITY_TRANSFORM {
+        return None;
+    }
+
+    let source = schema.field_by_id(partition_field.source_id)?;
+    let arrow_field = arrow_schema.field_with_name(&source.name).ok()?;
+    Some((source.name.as_str(), arrow_field.data_type()))
+}
+
+pub(crate) struct IcebergPruningStatistics<'a> {
+    schema: &'a ArrowSchema,
+    containers: Vec<ContainerStatistics>,
+}
+
+impl<'a> IcebergPruningStatistics<'a> {
+    pub(crate) fn new(schema: &'a ArrowSchema, containers: Vec<ContainerStatistics>) -> Self {
+        Self { schema, containers }
+    }
+
+    fn bounds(
+        &self,
+        column: &Column,
+        select: fn(&(ScalarValue, ScalarValue)) -> &ScalarValue,
+    ) -> Option<ArrayRef> {
+        if !self
+            .containers
+            .iter()
+            .any(|container| container.bounds.contains_key(&column.name))
+        {
+            return None;
+        }
+
+        let data_type = self.schema.field_with_name(&column.name).ok()?.data_type();
+        let null = ScalarValue::try_from(data_type).ok()?;
+        ScalarValue::iter_to_array(self.containers.iter().map(|container| {
+            container
+                .bounds
+                .get(&column.name)
+                .map_or_else(|| null.clone(), |bounds| select(bounds).clone())
+        }))
+        .ok()
+    }
+}
+
+impl PruningStatistics for IcebergPruningStatistics<'_> {
+    fn min_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.bounds(column, |(min, _)| min)
+    }
+
+    fn max_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.bounds(column, |(_, max)| max)
+    }
+
+    fn num_containers(&self) -> usize {
+        self.containers.len()
+    }
+
+    fn null_counts(&self, column: &Column) -> Option<ArrayRef> {
+        if !self
+            .containers
+            .iter()
+            .any(|container| container.null_counts.contains_key(&column.name))
+        {
+            return None;
+        }
+
+        Some(Arc::new(UInt64Array::from_iter(
+            self.containers
+                .iter()
+                .map(|container| container.null_counts.get(&column.name).copied()),
+        )))
+    }
+
+    fn row_counts(&self, _column: &Column) -> Option<ArrayRef> {
+        if self
+            .containers
+            .iter()
+            .all(|container| container.row_count.is_none())
+        {
+            return None;
+        }
+
+        Some(Arc::new(UInt64Array::from_iter(
+            self.containers.iter().map(|container| container.row_count),
+        )))
+    }
+
+    fn contained(&self, _column: &Column, _values: &HashSet<ScalarValue>) -> Option<BooleanArray> {
+        None
+    }
+}
+
+/// Converts an identity partition value to the type of its source column.
+fn avro_to_scalar(value: &Value, data_type: &DataType) -> Option<ScalarValue> {
+    let scalar = match value {
+        Value::Boolean(value) => ScalarValue::Boolean(Some(*value)),
+        Value::Int(value) | Value::Date(value) => ScalarValue::Int32(Some(*value)),
+        Value::Long(value) | Value::TimeMicros(value) | Value::TimestampMicros(value) => {
+            ScalarValue::Int64(Some(*value))
+        }
+        Value::Float(value) => ScalarValue::Float32(Some(*value)),
+        Value::Double(value) => ScalarValue::Float64(Some(*value)),
+        Value::String(value) => ScalarValue::Utf8(Some(value.clone())),
+        Value::Bytes(value) | Value::Fixed(_, value) => ScalarValue::Binary(Some(value.clone())),
+        _ => return None,
+    };
+
+    scalar.cast_to(data_type).ok()
+}
+
+/// Decodes a bound in Iceberg's single-value binary serialization.
+///
+/// See <https://iceberg.apache.org/spec/#binary-single-value-serialization>
+pub(crate) fn decode_bound(raw: &[u8], data_type: &DataType) -> Option<ScalarValue> {
+    let value = match data_type {
+        DataType::Boolean => ScalarValue::Boolean(Some(*raw.first()? != 0)),
+        DataType::Int32 => ScalarValue::Int32(Some(i32::from_le_bytes(raw.try_into().ok()?))),
+        DataType::Date32 => ScalarValue::Date32(Some(i32::from_le_bytes(raw.try_into().ok()?))),
+        DataType::Int64 => ScalarValue::Int64(Some(i64::from_le_bytes(raw.try_into().ok()?))),
+        DataType::Time64(TimeUnit::Microsecond) => {
+            ScalarValue::Time64Microsecond(Some(i64::from_le_bytes(raw.try_into().ok()?)))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => ScalarValue::TimestampMicrosecond(
+            Some(i64::from_le_bytes(raw.try_into().ok()?)),
+            tz.clone(),
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => ScalarValue::TimestampNanosecond(
+            Some(i64::from_le_bytes(raw.try_into().ok()?)),
+            tz.clone(),
+        ),
+        DataType::Float32 => ScalarValue::Float32(Some(f32::from_le_bytes(raw.try_into().ok()?))),
+        DataType::Float64 => ScalarValue::Float64(Some(f64::from_le_bytes(raw.try_into().ok()?))),
+        DataType::Utf8 => ScalarValue::Utf8(Some(String::from_utf8(raw.to_vec()).ok()?)),
+        DataType::Binary => ScalarValue::Binary(Some(raw.to_vec())),
+        DataType::FixedSizeBinary(size) => ScalarValue::FixedSizeBinary(*size, Some(raw.to_vec())),
+        DataType::Decimal128(precision, scale) => {
+            // The unscaled value, as big-endian two's complement in the minimum number of bytes.
+            if raw.is_empty() || raw.len() > 16 {
+                return None;
+            }
+            let sign: i128 = if raw[0] & 0x80 == 0 { 0 } else { -1 };
+            let unscaled = raw
+                .iter()
+                .fold(sign, |value, byte| (value << 8) | i128::from(*byte));
+            ScalarValue::Decimal128(Some(unscaled), *precision, *scale)
+        }
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bound() {
+        assert_eq!(
+            decode_bound(&42_i64.to_le_bytes(), &DataType::Int64),
+            Some(ScalarValue::Int64(Some(42)))
+        );
+        assert_eq!(
+            decode_bound(b"abc", &DataType::Utf8),
+            Some(ScalarValue::Utf8(Some("abc".to_string())))
+        );
+        assert_eq!(
+            decode_bound(&[0xff, 0x38], &DataType::Decimal128(10, 2)),
+            Some(ScalarValue::Decimal128(Some(-200), 10, 2))
+        );
+        assert_eq!(
+            decode_bound(&[0x01, 0x00], &DataType::Decimal128(10, 2)),
+            Some(ScalarValue::Decimal128(Some(256), 10, 2))
+        );
+        assert_eq!(decode_bound(&[1, 2], &DataType::Int32), None);
+    }
+}
@@ -0,0 +1,526 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A client for the Iceberg REST catalog protocol, and a [`CatalogProvider`] of its tables.
+//!
+//! See <https://github.com/apache/iceberg/blob/main/open-api/rest-catalog-open-api.yaml>
+
+use async_trait::async_trait;
+use datafusion::{
+    catalog::{CatalogProvider, SchemaProvider},
+    datasource::TableProvider,
+    error::DataFusionError,
+    sql::TableReference,
+};
+use globset::GlobSet;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de::DeserializeOwned, Deserialize};
+use snafu::prelude::*;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+use tokio::sync::OnceCell;
+use url::form_urlencoded;
+
+use super::{ConnectionSnafu, NoMetadataLocationSnafu, Result, UnexpectedStatusCodeSnafu};
+use crate::Read;
+
+/// Multi-level namespaces are joined with the unit separator in request paths.
+const NAMESPACE_SEPARATOR: &str = "%1F";
+
+pub struct RestCatalog {
+    endpoint: String,
+    /// The path prefix for a warehouse, as returned by the catalog's `/v1/config`.
+    prefix: Option<String>,
+    token: Option<SecretString>,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigResponse {
+    #[serde(default)]
+    defaults: HashMap<String, String>,
+    #[serde(default)]
+    overrides: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ListNamespacesResponse {
+    #[serde(default)]
+    namespaces: Vec<Vec<String>>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ListTablesResponse {
+    #[serde(default)]
+    identifiers: Vec<TableIdentifier>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TableIdentifier {
+    pub namespace: Vec<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LoadTableResponse {
+    #[serde(default)]
+    metadata_location: Option<String>,
+}
+
+impl RestCatalog {
+    /// Connects to the REST catalog at `endpoint` (i.e. `http://localhost:8181`), and reads the
+    /// configuration of `warehouse`.
+    pub async fn try_new(
+        endpoint: &str,
+        warehouse: Option<&str>,
+        token: Option<SecretString>,
+    ) -> Result<Self> {
+        let mut catalog = Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            prefix: None,
+            token,
+            client: reqwest::Client::new(),
+        };
+
+        let query = warehouse
+            .map(|warehouse| {
+                form_urlencoded::Serializer::new(String::new())
+                    .append_pair("warehouse", warehouse)
+                    .finish()
+            })
+            .unwrap_or_default();
+        let config: ConfigResponse = catalog.get("/v1/config", &query).await?;
+        catalog.prefix = config
+            .overrides
+            .get("prefix")
+            .or_else(|| config.defaults.get("prefix"))
+            .map(|prefix| prefix.trim_matches('/').to_string())
+            .filter(|prefix| !prefix.is_empty());
+
+        Ok(catalog)
+    }
+
+    /// Lists all namespaces, including nested namespaces.
+    pub async fn list_namespaces(&self) -> Result<Vec<Vec<String>>> {
+        let mut namespaces = vec![];
+        let mut seen = HashSet::new();
+        let mut parents = VecDeque::from([None::<Vec<String>>]);
+
+        while let Some(parent) = parents.pop_front() {
+            let mut page_token: Option<String> = None;
+            loop {
+                let mut query = form_urlencoded::Serializer::new(String::new());
+                if let Some(parent) = &parent {
+                    query.append_pair("parent", &parent.join("\u{1f}"));
+                }
+                if let Some(page_token) = &page_token {
+                    query.append_pair("pageToken", page_token);
+                }
+
+                let response: ListNamespacesResponse =
+                    self.get(&self.path("namespaces"), &query.finish()).await?;
+                for namespace in response.namespaces {
+                    // Catalogs that don't support nested namespaces ignore `parent`.
+                    if seen.insert(namespace.clone()) {
+                        parents.push_back(Some(namespace.clone()));
+                        namespaces.push(namespace);
+                    }
+                }
+
+                page_token = response.next_page_token;
+                if page_token.is_none() {
+                    break;
+                }
+            }
+        }
+
+        Ok(namespaces)
+    }
+
+    pub async fn list_tables(&self, namespace: &[String]) -> Result<Vec<TableIdentifier>> {
+        let path = self.path(&format!(
+            "namespaces/{}/tables",
+            encode_namespace(namespace)
+        ));
+
+        let mut tables = vec![];
+        let mut page_token: Option<String> = None;
+        loop {
+            let query = page_token
+                .map(|page_token| {
+                    form_urlencoded::Serializer::new(String::new())
+                        .append_pair("pageToken", &page_token)
+                        .finish()
+                })
+                .unwrap_or_default();
+
+            let response: ListTablesResponse = self.get(&path, &query).await?;
+            tables.extend(response.identifiers);
+
+            page_token = response.next_page_token;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(tables)
+    }
+
+    /// Returns the location of the current metadata file of a table.
+    pub async fn load_table(&self, table: &TableIdentifier) -> Result<String> {
+        let path = self.path(&format!(
+            "namespaces/{}/tables/{}",
+            encode_namespace(&table.namespace),
+            form_urlencoded::byte_serialize(table.name.as_bytes()).collect::<String>()
+        ));
+
+        let response: LoadTableResponse = self.get(&path, "").await?;
+        response.metadata_location.context(NoMetadataLocationSnafu {
+            table: format!("{}.{}", table.namespace.join("."), table.name),
+        })
+    }
+
+    fn path(&self, path: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("/v1/{prefix}/{path}"),
+            None => format!("/v1/{path}"),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &str) -> Result<T> {
+        let url = if query.is_empty() {
+            format!("{}{path}", self.endpoint)
+        } else {
+            format!("{}{path}?{query}", self.endpoint)
+        };
+        tracing::debug!("Sending request to {url}");
+
+        let mut builder = self.client.get(&url);
+        if let Some(token) = &self.token {
+            builder = builder.bearer_auth(token.expose_secret());
+        }
+
+        let response = builder.send().await.context(ConnectionSnafu)?;
+        if !response.status().is_success() {
+            return UnexpectedStatusCodeSnafu {
+                url,
+                status: response.status(),
+            }
+            .fail();
+        }
+
+        response.json().await.context(ConnectionSnafu)
+    }
+}
+
+fn encode_namespace(namespace: &[String]) -> String {
+    namespace
+        .iter()
+        .map(|level| form_urlencoded::byte_serialize(level.as_bytes()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(NAMESPACE_SEPARATOR)
+}
+
+/// The tables of an Iceberg REST catalog, with one schema per namespace. Nested namespaces are
+/// joined with `.`, i.e. `sales.emea`.
+pub struct IcebergCatalogProvider {
+    schemas: HashMap<String, Arc<dyn SchemaProvider>>,
+}
+
+impl IcebergCatalogProvider {
+    /// Lists the tables of the catalog that match the `include` patterns. Tables are only loaded,
+    /// and their table providers created with `table_creator` from the location of their metadata
+    /// file, when they are first queried.
+    pub async fn try_new(
+        client: Arc<RestCatalog>,
+        table_creator: Arc<dyn Read>,
+        include: Option<GlobSet>,
+    ) -> Result<Self> {
+        let mut schemas = HashMap::new();
+        for namespace in client.list_namespaces().await? {
+            let schema_name = namespace.join(".");
+
+            let mut tables = HashMap::new();
+            for table in client.list_tables(&namespace).await? {
+                let schema_with_table = format!("{schema_name}.{}", table.name);
+                if let Some(include) = &include {
+                    if !include.is_match(&schema_with_table) {
+                        tracing::debug!("Table {schema_with_table} is not included");
+                        continue;
+                    }
+                }
+
+                tables.insert(table.name.clone(), (table, OnceCell::new()));
+            }
+
+            schemas.insert(
+                schema_name,
+                Arc::new(IcebergSchemaProvider {
+                    client: Arc::clone(&client),
+                    table_creator: Arc::clone(&table_creator),
+                    tables,
+                }) as Arc<dyn SchemaProvider>,
+            );
+        }
+
+        Ok(Self { schemas })
+    }
+}
+
+impl CatalogProvider for IcebergCatalogProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        self.schemas.keys().cloned().collect()
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        self.schemas.get(name).cloned()
+    }
+}
+
+pub struct IcebergSchemaProvider {
+    client: Arc<RestCatalog>,
+    table_creator: Arc<dyn Read>,
+    /// The identifier of each table, and its table provider once it is loaded.
+    tables: HashMap<String, (TableIdentifier, OnceCell<Arc<dyn TableProvider>>)>,
+}
+
+#[async_trait]
+impl SchemaProvider for IcebergSchemaProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    /// Loads the table the first time it is queried, i.e. to resolve its schema.
+    async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>, DataFusionError> {
+        let Some((table, provider)) = self.tables.get(name) else {
+            return Ok(None);
+        };
+
+        let provider = provider
+            .get_or_try_init(|| async {
+                let metadata_location = self
+                    .client
+                    .load_table(table)
+                    .await
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                self.table_creator
+                    .table_provider(TableReference::bare(metadata_location), None)
+                    .await
+                    .map_err(DataFusionError::External)
+            })
+            .await?;
+
+        Ok(Some(Arc::clone(provider)))
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.tables.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{Schema, SchemaRef};
+    use datafusion::datasource::empty::EmptyTable;
+    use std::sync::Mutex;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// A stand-in for a REST catalog, that answers each request path with a canned JSON body.
+    async fn start_server(routes: Vec<(&'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind to a local port");
+        let endpoint = format!("http://{}", listener.local_addr().expect("local address"));
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![0; 8192];
+                let read = socket.read(&mut request).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&request[..read]).to_string();
+                let target = request.split_whitespace().nth(1).unwrap_or_default();
+
+                let response = match routes.iter().find(|(route, _)| *route == target) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        .to_string(),
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_rest_catalog() {
+        let endpoint = start_server(vec![
+            (
+                "/v1/config?warehouse=lake",
+                r#"{"defaults": {}, "overrides": {"prefix": "lake"}}"#,
+            ),
+            (
+                "/v1/lake/namespaces",
+                r#"{"namespaces": [["sales"], ["sales", "emea"]]}"#,
+            ),
+            ("/v1/lake/namespaces?parent=sales", r#"{"namespaces": []}"#),
+            (
+                "/v1/lake/namespaces?parent=sales%1Femea",
+                r#"{"namespaces": []}"#,
+            ),
+            (
+                "/v1/lake/namespaces/sales%1Femea/tables",
+                r#"{"identifiers": [{"namespace": ["sales", "emea"], "name": "orders"}]}"#,
+            ),
+            (
+                "/v1/lake/namespaces/sales%1Femea/tables/orders",
+                r#"{"metadata-location": "s3://lake/sales/emea/orders/metadata/00001.metadata.json", "metadata": {}}"#,
+            ),
+        ])
+        .await;
+
+        let catalog = RestCatalog::try_new(&endpoint, Some("lake"), None)
+            .await
+            .expect("catalog config");
+
+        let namespaces = catalog.list_namespaces().await.expect("namespaces");
+        assert_eq!(
+            namespaces,
+            vec![
+                vec!["sales".to_string()],
+                vec!["sales".to_string(), "emea".to_string()]
+            ]
+        );
+
+        let tables = catalog.list_tables(&namespaces[1]).await.expect("tables");
+        assert_eq!(
+            tables,
+            vec![TableIdentifier {
+                namespace: vec!["sales".to_string(), "emea".to_string()],
+                name: "orders".to_string(),
+            }]
+        );
+
+        assert_eq!(
+            catalog.load_table(&tables[0]).await.expect("table"),
+            "s3://lake/sales/emea/orders/metadata/00001.metadata.json"
+        );
+        assert!(catalog.list_tables(&namespaces[0]).await.is_err());
+    }
+
+    /// Creates empty tables, recording the metadata locations they were created from.
+    #[derive(Default)]
+    struct RecordingRead {
+        created: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Read for RecordingRead {
+        async fn table_provider(
+            &self,
+            table_reference: TableReference,
+            _schema: Option<SchemaRef>,
+        ) -> Result<Arc<dyn TableProvider + 'static>, Box<dyn std::error::Error + Send + Sync>>
+        {
+            self.created
+                .lock()
+                .expect("lock")
+                .push(table_reference.table().to_string());
+            Ok(Arc::new(EmptyTable::new(Arc::new(Schema::empty()))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tables_are_loaded_lazily() {
+        let endpoint = start_server(vec![
+            ("/v1/config", "{}"),
+            ("/v1/namespaces", r#"{"namespaces": [["sales"]]}"#),
+            ("/v1/namespaces?parent=sales", r#"{"namespaces": []}"#),
+            (
+                "/v1/namespaces/sales/tables",
+                r#"{"identifiers": [{"namespace": ["sales"], "name": "orders"}, {"namespace": ["sales"], "name": "broken"}]}"#,
+            ),
+            (
+                "/v1/namespaces/sales/tables/orders",
+                r#"{"metadata-location": "s3://lake/sales/orders/metadata/00001.metadata.json", "metadata": {}}"#,
+            ),
+        ])
+        .await;
+
+        let client = Arc::new(
+            RestCatalog::try_new(&endpoint, None, None)
+                .await
+                .expect("catalog config"),
+        );
+        let read = Arc::new(RecordingRead::default());
+        let catalog =
+            IcebergCatalogProvider::try_new(client, Arc::clone(&read) as Arc<dyn Read>, None)
+                .await
+                .expect("catalog listed");
+
+        let sales = catalog.schema("sales").expect("sales schema");
+        let mut table_names = sales.table_names();
+        table_names.sort();
+        assert_eq!(table_names, vec!["broken", "orders"]);
+        assert!(
+            read.created.lock().expect("lock").is_empty(),
+            "no table is loaded before it is queried"
+        );
+
+        for _ in 0..2 {
+            sales
+                .table("orders")
+                .await
+                .expect("table loaded")
+                .expect("table exists");
+        }
+        assert_eq!(
+            *read.created.lock().expect("lock"),
+            vec!["s3://lake/sales/orders/metadata/00001.metadata.json"],
+            "the table is loaded once"
+        );
+
+        assert!(
+            sales.table("broken").await.is_err(),
+            "a table that can't be loaded fails when it is queried"
+        );
+        assert!(sales.table("missing").await.expect("no error").is_none());
+    }
+}
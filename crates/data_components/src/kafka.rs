@@ -53,12 +53,38 @@ pub enum Error {
         message: String,
     },
 
+    #[snafu(display("Unable to seek to offset {offset} of partition {partition}: {source}"))]
+    UnableToSeekPartition {
+        source: rdkafka::error::KafkaError,
+        partition: i32,
+        offset: i64,
+    },
+
     #[snafu(display("The metadata for topic {topic} was not found."))]
     MetadataTopicNotFound { topic: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Where a consumer group without committed offsets starts reading a topic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StartOffset {
+    /// Read the topic from the beginning.
+    #[default]
+    Earliest,
+    /// Only read messages produced after the consumer joined the group.
+    Latest,
+}
+
+impl StartOffset {
+    fn auto_offset_reset(self) -> &'static str {
+        match self {
+            StartOffset::Earliest => "smallest",
+            StartOffset::Latest => "largest",
+        }
+    }
+}
+
 pub struct KafkaConsumer {
     group_id: String,
     consumer: StreamConsumer,
@@ -69,11 +95,25 @@ impl KafkaConsumer {
         group_id: impl Into<String>,
         brokers: String,
     ) -> Result<Self> {
-        Self::create(group_id.into(), brokers)
+        Self::create(group_id.into(), brokers, StartOffset::Earliest)
     }
 
     pub fn create_with_generated_group_id(dataset: &str, brokers: String) -> Result<Self> {
-        Self::create(Self::generate_group_id(dataset), brokers)
+        Self::create(
+            Self::generate_group_id(dataset),
+            brokers,
+            StartOffset::Earliest,
+        )
+    }
+
+    /// Creates a consumer that starts reading at `start_offset` if `group_id` has no committed
+    /// offsets yet.
+    pub fn create_with_start_offset(
+        group_id: impl Into<String>,
+        brokers: String,
+        start_offset: StartOffset,
+    ) -> Result<Self> {
+        Self::create(group_id.into(), brokers, start_offset)
     }
 
     #[must_use]
@@ -122,6 +162,44 @@ impl KafkaConsumer {
         })
     }
 
+    /// Receive a message from the Kafka topic without deserializing it.
+    pub async fn next_payload(&self) -> Result<Option<KafkaMessage<Option<Vec<u8>>, Vec<u8>>>> {
+        let mut stream = Box::pin(self.stream_payloads());
+        stream.next().await.transpose()
+    }
+
+    /// Stream the raw keys and payloads of messages from the Kafka topic.
+    ///
+    /// Messages without a payload, i.e. tombstones, are skipped.
+    pub fn stream_payloads(
+        &self,
+    ) -> impl Stream<Item = Result<KafkaMessage<Option<Vec<u8>>, Vec<u8>>>> {
+        self.consumer.stream().filter_map(move |msg| async move {
+            let msg = match msg {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(Error::UnableToReceiveMessage { source: e })),
+            };
+
+            let key = msg.key().map(<[u8]>::to_vec);
+            let value = msg.payload()?.to_vec();
+
+            Some(Ok(KafkaMessage::new(&self.consumer, msg, key, value)))
+        })
+    }
+
+    /// Moves the position of the consumer in a partition of `topic` back (or forward) to `offset`,
+    /// so that the message at `offset` is the next one received from that partition.
+    pub fn seek(&self, topic: &str, partition: i32, offset: i64) -> Result<()> {
+        self.consumer
+            .seek(
+                topic,
+                partition,
+                Offset::Offset(offset),
+                std::time::Duration::from_secs(1),
+            )
+            .context(UnableToSeekPartitionSnafu { partition, offset })
+    }
+
     pub fn restart_topic(&self, topic: &str) -> Result<()> {
         let mut assignment = self
             .consumer
@@ -174,15 +252,15 @@ impl KafkaConsumer {
         Ok(())
     }
 
-    fn create(group_id: String, brokers: String) -> Result<Self> {
+    fn create(group_id: String, brokers: String, start_offset: StartOffset) -> Result<Self> {
         let (_, version) = get_rdkafka_version();
         tracing::debug!("rd_kafka_version: {}", version);
 
         let consumer: StreamConsumer = ClientConfig::new()
             .set("group.id", group_id.clone())
             .set("bootstrap.servers", brokers)
-            // For new consumer groups, start reading at the beginning of the topic
-            .set("auto.offset.reset", "smallest")
+            // Where new consumer groups start reading the topic
+            .set("auto.offset.reset", start_offset.auto_offset_reset())
             // Commit offsets automatically
             .set("enable.auto.commit", "true")
             // Commit offsets every 5 seconds
@@ -197,7 +275,8 @@ impl KafkaConsumer {
         Ok(Self { group_id, consumer })
     }
 
-    fn generate_group_id(dataset: &str) -> String {
+    #[must_use]
+    pub fn generate_group_id(dataset: &str) -> String {
         format!("spice.ai-{dataset}-{}", uuid::Uuid::new_v4())
     }
 }
@@ -227,6 +306,16 @@ impl<'a, K, V> KafkaMessage<'a, K, V> {
         &self.value
     }
 
+    #[must_use]
+    pub fn partition(&self) -> i32 {
+        self.msg.partition()
+    }
+
+    #[must_use]
+    pub fn offset(&self) -> i64 {
+        self.msg.offset()
+    }
+
     pub fn mark_processed(&self) -> Result<()> {
         self.consumer
             .store_offset_from_message(&self.msg)
@@ -0,0 +1,669 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reads the records of an arbitrary Kafka topic as rows of a table.
+//!
+//! Every message is decoded into one or more JSON records (a message that holds a JSON array yields
+//! one record per element), which are converted to Arrow with the schema of the table. Avro
+//! messages are decoded either with a fixed writer schema, or with the schema registered under the
+//! id in the Confluent wire format header of the message.
+//!
+//! The schema of the table is derived from the Avro schema of Avro topics, and inferred from
+//! sample messages of JSON topics.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+};
+
+use apache_avro::schema::{Name, ResolvedSchema, Schema as AvroSchema};
+use arrow::{
+    array::{RecordBatch, StringArray},
+    datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit},
+    error::ArrowError,
+    json::{reader::infer_json_schema_from_iterator, ReaderBuilder},
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use datafusion::{
+    catalog::Session,
+    datasource::{TableProvider, TableType},
+    error::Result as DataFusionResult,
+    logical_expr::Expr,
+    physical_plan::{empty::EmptyExec, ExecutionPlan},
+};
+use futures::StreamExt;
+use opentelemetry::{
+    global,
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use serde::Deserialize;
+use snafu::prelude::*;
+use tokio::sync::RwLock;
+
+use crate::{
+    cdc::{self, ChangeBatch, ChangeEnvelope, ChangesStream},
+    kafka::KafkaConsumer,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to deserialize JSON message: {source}"))]
+    UnableToDeserializeJson { source: serde_json::Error },
+
+    #[snafu(display("Unable to decode Avro message: {source}"))]
+    UnableToDecodeAvro { source: apache_avro::Error },
+
+    #[snafu(display("Unable to parse Avro schema: {source}"))]
+    UnableToParseAvroSchema { source: apache_avro::Error },
+
+    #[snafu(display(
+        "The message doesn't start with the schema registry header (a zero byte and a 4 byte schema id)"
+    ))]
+    MissingSchemaRegistryHeader,
+
+    #[snafu(display("Unable to fetch schema {id} from the schema registry at {url}: {source}"))]
+    UnableToFetchSchema {
+        id: u32,
+        url: String,
+        source: reqwest::Error,
+    },
+
+    #[snafu(display("Unable to infer the schema of the topic: {source}"))]
+    UnableToInferSchema { source: ArrowError },
+
+    #[snafu(display("The Avro schema of the topic isn't a record"))]
+    AvroSchemaNotRecord,
+
+    #[snafu(display("The Avro type of field {field} isn't supported: {detail}"))]
+    UnsupportedAvroType { field: String, detail: String },
+
+    #[snafu(display("Unable to convert the message to the schema of the topic: {source}"))]
+    UnableToConvertMessage { source: ArrowError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+static METER: LazyLock<Meter> = LazyLock::new(|| global::meter("kafka"));
+
+static SKIPPED_MESSAGES: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    METER
+        .u64_counter("kafka_skipped_messages")
+        .with_description("Number of messages skipped because they couldn't be read.")
+        .init()
+});
+
+/// The first byte of messages framed in the Confluent wire format, followed by the 4 byte schema id.
+const SCHEMA_REGISTRY_MAGIC_BYTE: u8 = 0;
+
+/// Decodes the payload of Kafka messages into JSON records.
+pub enum MessageDecoder {
+    Json,
+    /// Avro datums that are all written with the same schema.
+    Avro(AvroSchema),
+    /// Avro datums in the Confluent wire format, written with a schema of the schema registry.
+    AvroSchemaRegistry(SchemaRegistry),
+}
+
+impl MessageDecoder {
+    pub fn avro(schema: &str) -> Result<Self> {
+        let schema = AvroSchema::parse_str(schema).context(UnableToParseAvroSchemaSnafu)?;
+        Ok(Self::Avro(schema))
+    }
+
+    #[must_use]
+    pub fn avro_schema_registry(url: impl Into<String>) -> Self {
+        Self::AvroSchemaRegistry(SchemaRegistry::new(url))
+    }
+
+    pub async fn decode(&self, payload: &[u8]) -> Result<serde_json::Value> {
+        match self {
+            MessageDecoder::Json => {
+                serde_json::from_slice(payload).context(UnableToDeserializeJsonSnafu)
+            }
+            MessageDecoder::Avro(schema) => decode_avro(schema, payload),
+            MessageDecoder::AvroSchemaRegistry(registry) => {
+                let (id, datum) = split_schema_registry_header(payload)?;
+                let schema = registry.schema(id).await?;
+                decode_avro(&schema, datum)
+            }
+        }
+    }
+}
+
+fn decode_avro(schema: &AvroSchema, mut datum: &[u8]) -> Result<serde_json::Value> {
+    let value =
+        apache_avro::from_avro_datum(schema, &mut datum, None).context(UnableToDecodeAvroSnafu)?;
+    serde_json::Value::try_from(value).context(UnableToDecodeAvroSnafu)
+}
+
+/// The id of the registered schema a message in the Confluent wire format is written with.
+pub fn schema_id(payload: &[u8]) -> Result<u32> {
+    split_schema_registry_header(payload).map(|(id, _)| id)
+}
+
+fn split_schema_registry_header(payload: &[u8]) -> Result<(u32, &[u8])> {
+    match payload {
+        [SCHEMA_REGISTRY_MAGIC_BYTE, a, b, c, d, datum @ ..] => {
+            Ok((u32::from_be_bytes([*a, *b, *c, *d]), datum))
+        }
+        _ => MissingSchemaRegistryHeaderSnafu.fail(),
+    }
+}
+
+/// Fetches and caches the Avro schemas of a Confluent compatible schema registry.
+pub struct SchemaRegistry {
+    url: String,
+    client: reqwest::Client,
+    schemas: RwLock<HashMap<u32, Arc<AvroSchema>>>,
+}
+
+#[derive(Deserialize)]
+struct RegisteredSchema {
+    schema: String,
+}
+
+impl SchemaRegistry {
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            schemas: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The Arrow schema of the records written with the registered schema `id`.
+    pub async fn arrow_schema(&self, id: u32) -> Result<SchemaRef> {
+        avro_to_arrow_schema(self.schema(id).await?.as_ref())
+    }
+
+    pub async fn schema(&self, id: u32) -> Result<Arc<AvroSchema>> {
+        if let Some(schema) = self.schemas.read().await.get(&id) {
+            return Ok(Arc::clone(schema));
+        }
+
+        let url = format!("{}/schemas/ids/{id}", self.url);
+        let registered = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(UnableToFetchSchemaSnafu { id, url: &self.url })?
+            .json::<RegisteredSchema>()
+            .await
+            .context(UnableToFetchSchemaSnafu { id, url: &self.url })?;
+        let schema = Arc::new(
+            AvroSchema::parse_str(&registered.schema).context(UnableToParseAvroSchemaSnafu)?,
+        );
+
+        self.schemas.write().await.insert(id, Arc::clone(&schema));
+        Ok(schema)
+    }
+}
+
+/// The records in a decoded message.
+fn records(value: &serde_json::Value) -> &[serde_json::Value] {
+    match value {
+        serde_json::Value::Array(records) => records,
+        record => std::slice::from_ref(record),
+    }
+}
+
+/// Infers the Arrow schema of a topic from decoded sample messages.
+///
+/// Fields that are null in every sample are inferred as strings, so that later values of any
+/// primitive type are kept as text.
+pub fn infer_schema(messages: &[serde_json::Value]) -> Result<SchemaRef> {
+    let schema = infer_json_schema_from_iterator(
+        messages
+            .iter()
+            .flat_map(|message| records(message).iter())
+            .map(Ok),
+    )
+    .context(UnableToInferSchemaSnafu)?;
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Null => Arc::new(Field::new(field.name(), DataType::Utf8, true)),
+            _ => Arc::clone(field),
+        })
+        .collect::<Vec<_>>();
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// Derives the Arrow schema of a topic from the Avro record schema its messages are written with,
+/// for the JSON records the messages are decoded into.
+pub fn avro_to_arrow_schema(schema: &AvroSchema) -> Result<SchemaRef> {
+    let resolved = ResolvedSchema::try_from(schema).context(UnableToParseAvroSchemaSnafu)?;
+    let AvroSchema::Record(record) = schema else {
+        return AvroSchemaNotRecordSnafu.fail();
+    };
+
+    let mut names = vec![record.name.clone()];
+    let fields = record
+        .fields
+        .iter()
+        .map(|field| {
+            avro_to_arrow_field(&field.name, &field.schema, resolved.get_names(), &mut names)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+fn avro_to_arrow_field(
+    name: &str,
+    schema: &AvroSchema,
+    named: &HashMap<Name, &AvroSchema>,
+    names: &mut Vec<Name>,
+) -> Result<Field> {
+    let unsupported = |detail: &str| {
+        UnsupportedAvroTypeSnafu {
+            field: name,
+            detail,
+        }
+        .fail()
+    };
+
+    // Bytes are decoded into arrays of their values.
+    let bytes = || DataType::List(Arc::new(Field::new("item", DataType::UInt8, false)));
+
+    let data_type = match schema {
+        AvroSchema::Null => DataType::Null,
+        AvroSchema::Boolean => DataType::Boolean,
+        AvroSchema::Int => DataType::Int32,
+        AvroSchema::Long => DataType::Int64,
+        AvroSchema::Float => DataType::Float32,
+        AvroSchema::Double => DataType::Float64,
+        AvroSchema::String | AvroSchema::Enum(_) | AvroSchema::Uuid => DataType::Utf8,
+        AvroSchema::Bytes
+        | AvroSchema::Fixed(_)
+        | AvroSchema::Decimal(_)
+        | AvroSchema::Duration => bytes(),
+        AvroSchema::Date => DataType::Date32,
+        AvroSchema::TimeMillis => DataType::Time32(TimeUnit::Millisecond),
+        AvroSchema::TimeMicros => DataType::Time64(TimeUnit::Microsecond),
+        AvroSchema::TimestampMillis => {
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+        }
+        AvroSchema::TimestampMicros => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        }
+        AvroSchema::LocalTimestampMillis => DataType::Timestamp(TimeUnit::Millisecond, None),
+        AvroSchema::LocalTimestampMicros => DataType::Timestamp(TimeUnit::Microsecond, None),
+        AvroSchema::Array(items) => {
+            DataType::List(Arc::new(avro_to_arrow_field("item", items, named, names)?))
+        }
+        AvroSchema::Map(values) => DataType::Map(
+            Arc::new(Field::new(
+                "entries",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("keys", DataType::Utf8, false),
+                    avro_to_arrow_field("values", values, named, names)?,
+                ])),
+                false,
+            )),
+            false,
+        ),
+        AvroSchema::Record(record) => {
+            if names.contains(&record.name) {
+                return unsupported("recursive records can't be converted to Arrow");
+            }
+            names.push(record.name.clone());
+            let fields = record
+                .fields
+                .iter()
+                .map(|field| avro_to_arrow_field(&field.name, &field.schema, named, names))
+                .collect::<Result<Vec<_>>>();
+            names.pop();
+            DataType::Struct(Fields::from(fields?))
+        }
+        AvroSchema::Union(union) => {
+            let variants = union
+                .variants()
+                .iter()
+                .filter(|variant| !matches!(variant, AvroSchema::Null))
+                .collect::<Vec<_>>();
+            let [variant] = variants.as_slice() else {
+                return unsupported("only unions of null and one other type are supported");
+            };
+            return Ok(avro_to_arrow_field(name, variant, named, names)?.with_nullable(true));
+        }
+        AvroSchema::Ref { name: reference } => {
+            let Some(schema) = named.get(reference) else {
+                return unsupported(&format!("the type {reference} isn't defined"));
+            };
+            return avro_to_arrow_field(name, schema, named, names);
+        }
+        schema => return unsupported(&format!("{schema:?}")),
+    };
+
+    let nullable = data_type == DataType::Null;
+    Ok(Field::new(name, data_type, nullable))
+}
+
+/// Converts the records of a decoded message to a record batch with the given schema.
+///
+/// Fields that aren't part of the schema are ignored, and missing fields are null. Primitive
+/// values of string fields are converted to text.
+pub fn to_record_batch(schema: &SchemaRef, message: &serde_json::Value) -> Result<RecordBatch> {
+    let mut decoder = ReaderBuilder::new(Arc::clone(schema))
+        .with_coerce_primitive(true)
+        .build_decoder()
+        .context(UnableToConvertMessageSnafu)?;
+    decoder
+        .serialize(records(message))
+        .context(UnableToConvertMessageSnafu)?;
+    let batch = decoder.flush().context(UnableToConvertMessageSnafu)?;
+    Ok(batch.unwrap_or_else(|| RecordBatch::new_empty(Arc::clone(schema))))
+}
+
+/// What to do with a message that can't be decoded, or converted to the schema of the topic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidMessagePolicy {
+    /// Stop reading the topic before the message, so that its offset is never committed.
+    #[default]
+    Stop,
+    /// Skip the message and commit its offset, with a warning and the `kafka_skipped_messages`
+    /// metric.
+    Skip,
+}
+
+/// A Kafka topic whose messages are appended to an accelerated table.
+///
+/// The topic can't be queried directly, its rows are only available through `stream_appends`.
+pub struct KafkaTopic {
+    schema: SchemaRef,
+    decoder: Arc<MessageDecoder>,
+    consumer: &'static KafkaConsumer,
+    invalid_messages: InvalidMessagePolicy,
+}
+
+impl KafkaTopic {
+    #[must_use]
+    pub fn new(schema: SchemaRef, decoder: MessageDecoder, consumer: KafkaConsumer) -> Self {
+        Self {
+            schema,
+            decoder: Arc::new(decoder),
+            consumer: Box::leak(Box::new(consumer)),
+            invalid_messages: InvalidMessagePolicy::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_invalid_message_policy(mut self, invalid_messages: InvalidMessagePolicy) -> Self {
+        self.invalid_messages = invalid_messages;
+        self
+    }
+
+    /// Streams the records of every message as inserts, which commit the offset of the message once
+    /// they are written.
+    ///
+    /// A message that can't be read ends the stream, or is skipped, according to the
+    /// [`InvalidMessagePolicy`] of the topic. Offsets are committed in order, so the stream stops
+    /// before a message that can't be read to never commit past it.
+    #[must_use]
+    pub fn stream_appends(&self) -> ChangesStream {
+        let schema = Arc::clone(&self.schema);
+        let decoder = Arc::clone(&self.decoder);
+        let consumer = self.consumer;
+        let invalid_messages = self.invalid_messages;
+
+        Box::pin(stream! {
+            let mut messages = Box::pin(consumer.stream_payloads());
+            while let Some(msg) = messages.next().await {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        yield Err(cdc::StreamError::Kafka(e.to_string()));
+                        continue;
+                    }
+                };
+
+                let data = match decoder.decode(msg.value()).await {
+                    Ok(message) => to_record_batch(&schema, &message),
+                    Err(e) => Err(e),
+                };
+                let change_batch = data
+                    .map_err(|e| cdc::StreamError::Kafka(format!(
+                        "Unable to read message at offset {} of partition {}: {e}",
+                        msg.offset(),
+                        msg.partition()
+                    )))
+                    .and_then(|data| {
+                        let ops = StringArray::from(vec!["c"; data.num_rows()]);
+                        ChangeBatch::try_from_data(ops, &[], &data)
+                            .map_err(|e| cdc::StreamError::Kafka(e.to_string()))
+                    });
+
+                match (change_batch, invalid_messages) {
+                    (Ok(change_batch), _) => yield Ok(ChangeEnvelope::new(Box::new(msg), change_batch)),
+                    (Err(e), InvalidMessagePolicy::Stop) => {
+                        yield Err(cdc::StreamError::Kafka(format!(
+                            "{e}. Stopped reading the topic before the message, set kafka_invalid_messages to skip to skip messages that can't be read."
+                        )));
+                        return;
+                    }
+                    (Err(e), InvalidMessagePolicy::Skip) => {
+                        tracing::warn!("Skipping message: {e}");
+                        SKIPPED_MESSAGES.add(1, &[KeyValue::new("partition", i64::from(msg.partition()))]);
+                        if let Err(e) = msg.mark_processed() {
+                            tracing::warn!("Unable to commit the offset of the skipped message: {e}");
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for KafkaTopic {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        _projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(EmptyExec::new(Arc::clone(&self.schema))) as Arc<dyn ExecutionPlan>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apache_avro::types::Value as AvroValue;
+    use arrow::array::{Array, Int64Array};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_infer_schema_and_convert_messages() {
+        let messages = vec![
+            json!({"id": 1, "name": "a"}),
+            json!([{"id": 2, "name": "b", "score": 0.5}, {"id": 3}]),
+        ];
+        let schema = infer_schema(&messages).expect("schema");
+        assert_eq!(schema.fields().len(), 3);
+        assert_eq!(
+            schema.field_with_name("id").expect("id").data_type(),
+            &DataType::Int64
+        );
+        assert_eq!(
+            schema.field_with_name("score").expect("score").data_type(),
+            &DataType::Float64
+        );
+
+        let batch = to_record_batch(&schema, &messages[1]).expect("batch");
+        assert_eq!(batch.num_rows(), 2);
+        let ids = batch
+            .column_by_name("id")
+            .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            .expect("id column");
+        assert_eq!(ids.values(), &[2, 3]);
+        assert!(batch.column_by_name("score").expect("score").is_null(1));
+
+        let batch = to_record_batch(&schema, &json!({"id": 4, "extra": true})).expect("batch");
+        assert_eq!(batch.num_rows(), 1);
+        assert!(to_record_batch(&schema, &json!({"id": "x"})).is_err());
+    }
+
+    #[test]
+    fn test_infer_schema_of_null_fields() {
+        let schema = infer_schema(&[json!({"id": 1, "note": null})]).expect("schema");
+        assert_eq!(
+            schema.field_with_name("note").expect("note").data_type(),
+            &DataType::Utf8
+        );
+
+        let batch = to_record_batch(&schema, &json!({"id": 2, "note": 7})).expect("batch");
+        let notes = batch
+            .column_by_name("note")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .expect("note column");
+        assert_eq!(notes.value(0), "7");
+    }
+
+    #[test]
+    fn test_avro_to_arrow_schema() {
+        let schema = AvroSchema::parse_str(
+            r#"{
+                "type": "record",
+                "name": "event",
+                "fields": [
+                    {"name": "id", "type": "long"},
+                    {"name": "name", "type": ["null", "string"], "default": null},
+                    {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                    {"name": "tags", "type": {"type": "array", "items": "string"}},
+                    {"name": "origin", "type": {
+                        "type": "record",
+                        "name": "point",
+                        "fields": [{"name": "x", "type": "double"}]
+                    }},
+                    {"name": "target", "type": ["null", "point"], "default": null}
+                ]
+            }"#,
+        )
+        .expect("valid schema");
+        let arrow_schema = avro_to_arrow_schema(&schema).expect("arrow schema");
+
+        let id = arrow_schema.field_with_name("id").expect("id");
+        assert_eq!(id.data_type(), &DataType::Int64);
+        assert!(!id.is_nullable());
+        let name = arrow_schema.field_with_name("name").expect("name");
+        assert_eq!(name.data_type(), &DataType::Utf8);
+        assert!(name.is_nullable());
+        assert_eq!(
+            arrow_schema.field_with_name("at").expect("at").data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+        );
+        let point = DataType::Struct(Fields::from(vec![Field::new(
+            "x",
+            DataType::Float64,
+            false,
+        )]));
+        assert_eq!(
+            arrow_schema
+                .field_with_name("origin")
+                .expect("origin")
+                .data_type(),
+            &point
+        );
+        let target = arrow_schema.field_with_name("target").expect("target");
+        assert_eq!(target.data_type(), &point);
+        assert!(target.is_nullable());
+
+        // A message without a value for a nullable field is converted with the derived schema
+        let message = json!({"id": 1, "name": null, "at": 1_724_000_000_000_i64, "tags": ["a"], "origin": {"x": 0.5}, "target": null});
+        let batch = to_record_batch(&arrow_schema, &message).expect("batch");
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch.column_by_name("target").expect("target").is_null(0));
+
+        let schema = AvroSchema::parse_str(r#"["string", "long"]"#).expect("valid schema");
+        assert!(matches!(
+            avro_to_arrow_schema(&schema),
+            Err(Error::AvroSchemaNotRecord)
+        ));
+        let schema = AvroSchema::parse_str(
+            r#"{"type": "record", "name": "event", "fields": [{"name": "value", "type": ["string", "long"]}]}"#,
+        )
+        .expect("valid schema");
+        assert!(matches!(
+            avro_to_arrow_schema(&schema),
+            Err(Error::UnsupportedAvroType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_split_schema_registry_header() {
+        let payload = [0, 0, 0, 1, 2, 42, 43];
+        let (id, datum) = split_schema_registry_header(&payload).expect("header");
+        assert_eq!(id, 258);
+        assert_eq!(datum, &[42, 43]);
+
+        assert!(split_schema_registry_header(&[1, 0, 0, 0, 1]).is_err());
+        assert!(split_schema_registry_header(&[0, 0, 1]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode_avro() {
+        let schema = r#"{
+            "type": "record",
+            "name": "event",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "name", "type": ["null", "string"]}
+            ]
+        }"#;
+        let decoder = MessageDecoder::avro(schema).expect("decoder");
+        let MessageDecoder::Avro(ref avro_schema) = decoder else {
+            panic!("expected an Avro decoder");
+        };
+
+        let datum = apache_avro::to_avro_datum(
+            avro_schema,
+            AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(7)),
+                (
+                    "name".to_string(),
+                    AvroValue::Union(1, Box::new(AvroValue::String("seven".to_string()))),
+                ),
+            ]),
+        )
+        .expect("datum");
+
+        let message = decoder.decode(&datum).await.expect("message");
+        assert_eq!(message, json!({"id": 7, "name": "seven"}));
+    }
+}
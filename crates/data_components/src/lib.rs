@@ -22,6 +22,7 @@ use async_trait::async_trait;
 use datafusion::{datasource::TableProvider, sql::TableReference};
 
 pub mod arrow;
+pub mod catalog;
 #[cfg(feature = "clickhouse")]
 pub mod clickhouse;
 #[cfg(feature = "databricks")]
@@ -39,8 +40,12 @@ pub mod duckdb;
 pub mod flight;
 #[cfg(feature = "flightsql")]
 pub mod flightsql;
-#[cfg(feature = "debezium")]
+#[cfg(feature = "iceberg")]
+pub mod iceberg;
+#[cfg(any(feature = "debezium", feature = "kafka"))]
 pub mod kafka;
+#[cfg(feature = "kafka")]
+pub mod kafka_topic;
 #[cfg(feature = "mysql")]
 pub mod mysql;
 #[cfg(feature = "odbc")]
@@ -0,0 +1,483 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Extracts the plain text content, and metadata, of documents in an object store.
+
+use std::{
+    any::Any,
+    io::{Cursor, Read},
+    panic::AssertUnwindSafe,
+    str::Utf8Error,
+    sync::{Arc, LazyLock},
+};
+
+use bytes::Bytes;
+use pdf_extract::{OutputError, PlainTextOutput};
+use quick_xml::events::Event;
+use regex::Regex;
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The document is not valid UTF-8: {source}"))]
+    InvalidUtf8 { source: Utf8Error },
+
+    #[snafu(display("Unable to extract text from the PDF document: {source}"))]
+    UnableToExtractPdf { source: OutputError },
+
+    #[snafu(display("Unable to read the DOCX document: {source}"))]
+    UnableToReadDocx { source: zip::result::ZipError },
+
+    #[snafu(display("Unable to read {entry} from the DOCX document: {source}"))]
+    UnableToReadDocxEntry {
+        entry: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Unable to parse the DOCX document: {source}"))]
+    UnableToParseDocx { source: quick_xml::Error },
+
+    #[snafu(display("The extractor failed on the document: {message}"))]
+    ExtractorPanicked { message: String },
+
+    #[snafu(display("Unable to run the extractor: {source}"))]
+    UnableToRunExtractor { source: tokio::task::JoinError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The text content of a document, and the metadata found in it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub content: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub page_count: Option<u32>,
+}
+
+pub trait DocumentExtractor: Send + Sync + std::fmt::Debug {
+    /// Whether documents have metadata, in which case the `title`, `author` and `page_count`
+    /// columns are added to the table.
+    fn has_metadata(&self) -> bool {
+        true
+    }
+
+    fn extract(&self, raw: &[u8]) -> Result<Document>;
+}
+
+/// Extracts the document `raw` on the blocking thread pool, as extraction is CPU bound and can take
+/// long for large documents. A panic of the extractor, i.e. on a malformed document, becomes an
+/// error for the document instead of failing the whole scan.
+pub async fn extract_document(
+    extractor: Arc<dyn DocumentExtractor>,
+    raw: Bytes,
+) -> Result<Document> {
+    tokio::task::spawn_blocking(move || {
+        std::panic::catch_unwind(AssertUnwindSafe(|| extractor.extract(&raw))).unwrap_or_else(
+            |panic| {
+                ExtractorPanickedSnafu {
+                    message: panic_message(panic.as_ref()),
+                }
+                .fail()
+            },
+        )
+    })
+    .await
+    .context(UnableToRunExtractorSnafu)?
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Selects the extractor for a `file_format`. Unknown formats are read as UTF-8 text.
+#[must_use]
+pub fn extractor_for_format(format: &str) -> Arc<dyn DocumentExtractor> {
+    match format.to_ascii_lowercase().as_str() {
+        "pdf" => Arc::new(PdfExtractor {}),
+        "docx" => Arc::new(DocxExtractor {}),
+        "html" | "htm" => Arc::new(HtmlExtractor {}),
+        _ => Arc::new(TextExtractor {}),
+    }
+}
+
+/// Reads documents as UTF-8 text, as-is.
+#[derive(Debug, Default)]
+pub struct TextExtractor {}
+
+impl DocumentExtractor for TextExtractor {
+    fn has_metadata(&self) -> bool {
+        false
+    }
+
+    fn extract(&self, raw: &[u8]) -> Result<Document> {
+        Ok(Document {
+            content: std::str::from_utf8(raw)
+                .context(InvalidUtf8Snafu)?
+                .to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PdfExtractor {}
+
+impl DocumentExtractor for PdfExtractor {
+    fn extract(&self, raw: &[u8]) -> Result<Document> {
+        let mut doc = pdf_extract::Document::load_mem(raw)
+            .map_err(OutputError::from)
+            .context(UnableToExtractPdfSnafu)?;
+        if doc.is_encrypted() {
+            // Documents that are only encrypted to restrict permissions have an empty password.
+            doc.decrypt("")
+                .map_err(OutputError::from)
+                .context(UnableToExtractPdfSnafu)?;
+        }
+
+        let mut content = String::new();
+        pdf_extract::output_doc(&doc, &mut PlainTextOutput::new(&mut content))
+            .context(UnableToExtractPdfSnafu)?;
+
+        let info = doc
+            .trailer
+            .get_deref(b"Info", &doc)
+            .and_then(pdf_extract::Object::as_dict)
+            .ok();
+        let info_string = |key: &[u8]| {
+            info.and_then(|info| info.get_deref(key, &doc).ok())
+                .and_then(|value| pdf_extract::decode_text_string(value).ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok(Document {
+            title: info_string(b"Title"),
+            author: info_string(b"Author"),
+            page_count: u32::try_from(doc.get_pages().len()).ok(),
+            content,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DocxExtractor {}
+
+impl DocxExtractor {
+    fn read_entry(
+        archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+        entry: &str,
+    ) -> Result<Option<String>> {
+        let mut file = match archive.by_name(entry) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(source) => return Err(Error::UnableToReadDocx { source }),
+        };
+
+        let mut xml = String::new();
+        file.read_to_string(&mut xml)
+            .context(UnableToReadDocxEntrySnafu { entry })?;
+        Ok(Some(xml))
+    }
+
+    /// Extracts the text of the `<w:t>` runs of `word/document.xml`, one line per paragraph.
+    fn document_text(xml: &str) -> Result<String> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut content = String::new();
+        let mut in_text = false;
+
+        loop {
+            match reader.read_event().context(UnableToParseDocxSnafu)? {
+                Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+                Event::End(e) if e.name().as_ref() == b"w:t" => in_text = false,
+                Event::End(e) if e.name().as_ref() == b"w:p" => content.push('\n'),
+                Event::Empty(e) if e.name().as_ref() == b"w:tab" => content.push('\t'),
+                Event::Empty(e) if e.name().as_ref() == b"w:br" => content.push('\n'),
+                Event::Text(text) if in_text => {
+                    content.push_str(&text.unescape().context(UnableToParseDocxSnafu)?);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(content)
+    }
+
+    /// Reads the text of the first element named `name` in one of the `docProps` parts.
+    fn property(xml: &str, name: &[u8]) -> Result<Option<String>> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut in_property = false;
+
+        loop {
+            match reader.read_event().context(UnableToParseDocxSnafu)? {
+                Event::Start(e) if e.name().as_ref() == name => in_property = true,
+                Event::End(e) if e.name().as_ref() == name => return Ok(None),
+                Event::Text(text) if in_property => {
+                    let value = text.unescape().context(UnableToParseDocxSnafu)?;
+                    let value = value.trim();
+                    return Ok((!value.is_empty()).then(|| value.to_string()));
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl DocumentExtractor for DocxExtractor {
+    fn extract(&self, raw: &[u8]) -> Result<Document> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(raw)).context(UnableToReadDocxSnafu)?;
+
+        let content = match Self::read_entry(&mut archive, "word/document.xml")? {
+            Some(xml) => Self::document_text(&xml)?,
+            None => String::new(),
+        };
+
+        let (title, author) = match Self::read_entry(&mut archive, "docProps/core.xml")? {
+            Some(xml) => (
+                Self::property(&xml, b"dc:title")?,
+                Self::property(&xml, b"dc:creator")?,
+            ),
+            None => (None, None),
+        };
+
+        // The page count is only as accurate as the application that last saved the document.
+        let page_count = match Self::read_entry(&mut archive, "docProps/app.xml")? {
+            Some(xml) => Self::property(&xml, b"Pages")?.and_then(|pages| pages.parse().ok()),
+            None => None,
+        };
+
+        Ok(Document {
+            content,
+            title,
+            author,
+            page_count,
+        })
+    }
+}
+
+static HTML_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap_or_else(|_| unreachable!())
+});
+
+static HTML_AUTHOR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<meta\s+name\s*=\s*["']author["']\s+content\s*=\s*["']([^"']*)["']"#)
+        .unwrap_or_else(|_| unreachable!())
+});
+
+/// Converts HTML to plain text, dropping the markup.
+#[derive(Debug, Default)]
+pub struct HtmlExtractor {}
+
+impl HtmlExtractor {
+    /// Wide enough that paragraphs aren't wrapped, so they stay intact for chunking and embedding.
+    const TEXT_WIDTH: usize = 100_000;
+}
+
+impl DocumentExtractor for HtmlExtractor {
+    fn extract(&self, raw: &[u8]) -> Result<Document> {
+        let html = std::str::from_utf8(raw).context(InvalidUtf8Snafu)?;
+        let capture = |regex: &Regex| {
+            regex
+                .captures(html)
+                .and_then(|captures| captures.get(1))
+                .map(|value| value.as_str().trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok(Document {
+            content: html2text::from_read(raw, Self::TEXT_WIDTH),
+            title: capture(&HTML_TITLE),
+            author: capture(&HTML_AUTHOR),
+            page_count: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// A single page PDF document with `text` in the standard Helvetica font.
+    fn pdf(text: &str, title: &str, author: &str) -> Vec<u8> {
+        let content = format!("BT /F1 24 Tf 72 720 Td ({text}) Tj ET");
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R \
+             /Resources << /Font << /F1 5 0 R >> >> >>"
+                .to_string(),
+            format!(
+                "<< /Length {} >>\nstream\n{content}\nendstream",
+                content.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            format!("<< /Title ({title}) /Author ({author}) >>"),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (id, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{object}\nendobj\n", id + 1).into_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+        for offset in offsets {
+            pdf.extend(format!("{offset:010} 00000 n \n").into_bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R /Info 6 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .into_bytes(),
+        );
+        pdf
+    }
+
+    /// A DOCX document with the paragraphs `paragraphs`.
+    fn docx(paragraphs: &[&str], title: &str, pages: u32) -> Vec<u8> {
+        let body: String = paragraphs
+            .iter()
+            .map(|paragraph| format!("<w:p><w:r><w:t>{paragraph}</w:t></w:r></w:p>"))
+            .collect();
+        let entries = [
+            (
+                "word/document.xml",
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?><w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{body}</w:body></w:document>"#
+                ),
+            ),
+            (
+                "docProps/core.xml",
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?><cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>{title}</dc:title></cp:coreProperties>"#
+                ),
+            ),
+            (
+                "docProps/app.xml",
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?><Properties><Pages>{pages}</Pages></Properties>"#
+                ),
+            ),
+        ];
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, xml) in entries {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .expect("entry started");
+            writer.write_all(xml.as_bytes()).expect("entry written");
+        }
+        writer.finish().expect("archive written").into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_pdf_extract() {
+        let raw = pdf("Revenue grew", "Quarterly report", "Finance team");
+        let document = extract_document(Arc::new(PdfExtractor {}), Bytes::from(raw))
+            .await
+            .expect("valid document");
+
+        assert!(
+            document.content.contains("Revenue grew"),
+            "unexpected content {:?}",
+            document.content
+        );
+        assert_eq!(document.title.as_deref(), Some("Quarterly report"));
+        assert_eq!(document.author.as_deref(), Some("Finance team"));
+        assert_eq!(document.page_count, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_docx_extract() {
+        let raw = docx(&["Summary", "Revenue grew"], "Quarterly report", 2);
+        let document = extract_document(Arc::new(DocxExtractor {}), Bytes::from(raw))
+            .await
+            .expect("valid document");
+
+        assert_eq!(document.content, "Summary\nRevenue grew\n");
+        assert_eq!(document.title.as_deref(), Some("Quarterly report"));
+        assert_eq!(document.author, None);
+        assert_eq!(document.page_count, Some(2));
+    }
+
+    #[derive(Debug)]
+    struct PanickingExtractor {}
+
+    impl DocumentExtractor for PanickingExtractor {
+        fn extract(&self, _raw: &[u8]) -> Result<Document> {
+            panic!("malformed document");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_document_catches_panics() {
+        let error = extract_document(Arc::new(PanickingExtractor {}), Bytes::new())
+            .await
+            .expect_err("the extractor panics");
+        assert!(
+            matches!(&error, Error::ExtractorPanicked { message } if message == "malformed document"),
+            "unexpected error {error}"
+        );
+
+        // Invalid documents fail without a panic
+        assert!(
+            extract_document(Arc::new(PdfExtractor {}), Bytes::from_static(b"not a PDF"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_docx_document_text() {
+        let xml = r#"<w:document><w:body>
+            <w:p><w:r><w:t>Hello</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">world &amp; all</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Second</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+
+        assert_eq!(
+            DocxExtractor::document_text(xml).expect("valid document"),
+            "Hello\tworld & all\nSecond\n"
+        );
+    }
+
+    #[test]
+    fn test_html_extract() {
+        let html = r#"<html><head><title> Quarterly report </title>
+            <meta name="author" content="Finance team"></head>
+            <body><h1>Summary</h1><p>Revenue grew.</p></body></html>"#;
+
+        let document = HtmlExtractor {}
+            .extract(html.as_bytes())
+            .expect("valid document");
+        assert_eq!(document.title.as_deref(), Some("Quarterly report"));
+        assert_eq!(document.author.as_deref(), Some("Finance team"));
+        assert!(document.content.contains("Revenue grew."));
+        assert!(!document.content.contains("<p>"));
+    }
+}
@@ -16,6 +16,7 @@ limitations under the License.
 
 use std::{path::PathBuf, sync::Arc};
 
+pub mod document;
 pub mod metadata;
 pub mod text;
 
@@ -15,13 +15,12 @@ limitations under the License.
 */
 
 use arrow::{
-    array::{ArrayRef, RecordBatch, StringArray},
+    array::{ArrayRef, RecordBatch, StringArray, UInt32Array},
     datatypes::{DataType, Field, Schema, SchemaRef},
     error::ArrowError,
 };
 use async_stream::stream;
 use async_trait::async_trait;
-use bytes::Bytes;
 use datafusion::{
     catalog::Session,
     common::{project_schema, Constraints},
@@ -38,13 +37,19 @@ use datafusion::{
 use futures::Stream;
 use futures::StreamExt;
 use object_store::{path::Path, GetResult, ObjectMeta, ObjectStore};
-use std::{any::Any, fmt, str::Utf8Error, sync::Arc};
+use std::{any::Any, fmt, sync::Arc};
 
-use super::ObjectStoreContext;
+use super::{
+    document::{extract_document, Document, DocumentExtractor},
+    ObjectStoreContext,
+};
 use url::Url;
 
+/// The content of the documents in an object store, one row per document. The content is
+/// extracted as plain text by a [`DocumentExtractor`], selected by the dataset's `file_format`.
 pub struct ObjectStoreTextTable {
     ctx: ObjectStoreContext,
+    extractor: Arc<dyn DocumentExtractor>,
 }
 
 impl ObjectStoreTextTable {
@@ -52,25 +57,40 @@ impl ObjectStoreTextTable {
         store: Arc<dyn ObjectStore>,
         url: &Url,
         extension: Option<String>,
+        extractor: Arc<dyn DocumentExtractor>,
     ) -> Result<Arc<Self>, Box<dyn std::error::Error + Send + Sync>> {
         Ok(Arc::new(Self {
             ctx: ObjectStoreContext::try_new(store, url, extension)?,
+            extractor,
         }))
     }
 
-    fn table_schema() -> Schema {
-        Schema::new(vec![
+    fn table_schema(extractor: &dyn DocumentExtractor) -> Schema {
+        let mut fields = vec![
             Field::new("location", DataType::Utf8, false),
             Field::new("content", DataType::Utf8, false),
-        ])
+        ];
+        if extractor.has_metadata() {
+            fields.extend([
+                Field::new("title", DataType::Utf8, true),
+                Field::new("author", DataType::Utf8, true),
+                Field::new("page_count", DataType::UInt32, true),
+            ]);
+        }
+
+        Schema::new(fields)
     }
 
-    fn to_record_batch(meta_list: &[ObjectMeta], raw: &[Bytes]) -> Result<RecordBatch, ArrowError> {
-        if meta_list.len() != raw.len() {
+    fn to_record_batch(
+        extractor: &dyn DocumentExtractor,
+        meta_list: &[ObjectMeta],
+        documents: &[Document],
+    ) -> Result<RecordBatch, ArrowError> {
+        if meta_list.len() != documents.len() {
             return Err(ArrowError::ParseError("Length mismatch".to_string()));
         }
 
-        let schema = Self::table_schema();
+        let schema = Self::table_schema(extractor);
 
         let location_array: ArrayRef = Arc::new(StringArray::from(
             meta_list
@@ -79,17 +99,27 @@ impl ObjectStoreTextTable {
                 .collect::<Vec<_>>(),
         ));
 
-        let utf8_strings: Result<Vec<_>, Utf8Error> =
-            raw.iter().map(|bytes| std::str::from_utf8(bytes)).collect();
-
-        let content_array: ArrayRef = Arc::new(StringArray::from(
-            utf8_strings?
-                .into_iter()
-                .map(ToString::to_string)
-                .collect::<Vec<_>>(),
-        ));
+        let mut columns: Vec<ArrayRef> = vec![
+            location_array,
+            Arc::new(StringArray::from_iter_values(
+                documents.iter().map(|document| document.content.as_str()),
+            )),
+        ];
+        if extractor.has_metadata() {
+            columns.extend([
+                Arc::new(StringArray::from_iter(
+                    documents.iter().map(|document| document.title.as_deref()),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter(
+                    documents.iter().map(|document| document.author.as_deref()),
+                )),
+                Arc::new(UInt32Array::from_iter(
+                    documents.iter().map(|document| document.page_count),
+                )),
+            ]);
+        }
 
-        RecordBatch::try_new(Arc::new(schema), vec![location_array, content_array])
+        RecordBatch::try_new(Arc::new(schema), columns)
     }
 }
 
@@ -100,7 +130,7 @@ impl TableProvider for ObjectStoreTextTable {
     }
 
     fn schema(&self) -> SchemaRef {
-        Arc::new(Self::table_schema())
+        Arc::new(Self::table_schema(self.extractor.as_ref()))
     }
 
     fn constraints(&self) -> Option<&Constraints> {
@@ -121,9 +151,11 @@ impl TableProvider for ObjectStoreTextTable {
         let projected_schema = project_schema(&self.schema(), projection)?;
         Ok(Arc::new(ObjectStoreTextExec::new(
             projected_schema,
+            projection.cloned(),
             filters,
             limit,
             self.ctx.clone(),
+            Arc::clone(&self.extractor),
         )))
     }
 
@@ -140,11 +172,13 @@ impl TableProvider for ObjectStoreTextTable {
 
 pub struct ObjectStoreTextExec {
     projected_schema: SchemaRef,
+    projection: Option<Vec<usize>>,
     _filters: Vec<Expr>,
     limit: Option<usize>,
     properties: PlanProperties,
 
     ctx: ObjectStoreContext,
+    extractor: Arc<dyn DocumentExtractor>,
 }
 
 impl std::fmt::Debug for ObjectStoreTextExec {
@@ -199,7 +233,12 @@ impl ExecutionPlan for ObjectStoreTextExec {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
-            to_sendable_stream(self.ctx.clone(), self.limit), // TODO get prefix from filters
+            to_sendable_stream(
+                self.ctx.clone(),
+                Arc::clone(&self.extractor),
+                self.projection.clone(),
+                self.limit,
+            ), // TODO get prefix from filters
         )))
     }
 }
@@ -207,12 +246,15 @@ impl ExecutionPlan for ObjectStoreTextExec {
 impl ObjectStoreTextExec {
     pub(crate) fn new(
         projected_schema: SchemaRef,
+        projection: Option<Vec<usize>>,
         filters: &[Expr],
         limit: Option<usize>,
         ctx: ObjectStoreContext,
+        extractor: Arc<dyn DocumentExtractor>,
     ) -> Self {
         Self {
             projected_schema: Arc::clone(&projected_schema),
+            projection,
             _filters: filters.to_vec(),
             limit,
             properties: PlanProperties::new(
@@ -221,12 +263,15 @@ impl ObjectStoreTextExec {
                 ExecutionMode::Bounded,
             ),
             ctx,
+            extractor,
         }
     }
 }
 
 pub(crate) fn to_sendable_stream(
     ctx: ObjectStoreContext,
+    extractor: Arc<dyn DocumentExtractor>,
+    projection: Option<Vec<usize>>,
     limit: Option<usize>,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
     stream! {
@@ -244,7 +289,30 @@ pub(crate) fn to_sendable_stream(
                     let result: GetResult = ctx.store.get(&object_meta.location).await?;
                     let bytz = result.bytes().await?;
 
-                    match ObjectStoreTextTable::to_record_batch(&[object_meta], &[bytz]) {
+                    // A document that can't be extracted fails its own batch only.
+                    let batch = extract_document(Arc::clone(&extractor), bytz)
+                        .await
+                        .map_err(|e| {
+                            ArrowError::ExternalError(
+                                format!(
+                                    "Unable to extract the content of {}: {e}",
+                                    object_meta.location
+                                )
+                                .into(),
+                            )
+                        })
+                        .and_then(|document| {
+                            ObjectStoreTextTable::to_record_batch(
+                                extractor.as_ref(),
+                                &[object_meta],
+                                &[document],
+                            )
+                        })
+                        .and_then(|batch| match &projection {
+                            Some(projection) => batch.project(projection),
+                            None => Ok(batch),
+                        });
+                    match batch {
                         Ok(batch) => {
                             let n = batch.num_rows();
                             yield Ok(batch);
@@ -33,6 +33,9 @@ use datafusion_table_providers::{
     util,
 };
 
+mod pgoutput;
+pub mod replication;
+
 #[async_trait]
 impl Read for PostgresTableFactory {
     async fn table_provider(
@@ -0,0 +1,375 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Decodes the messages of the `pgoutput` logical decoding plugin, protocol version 1.
+//!
+//! See <https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html>
+
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The pgoutput message ended unexpectedly"))]
+    UnexpectedEnd,
+
+    #[snafu(display("The pgoutput message has an invalid string"))]
+    InvalidString,
+
+    #[snafu(display("The pgoutput message has an unexpected tag '{tag}'"))]
+    UnexpectedTag { tag: char },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Begin,
+    /// The end of a transaction, with the LSN after its commit record.
+    Commit {
+        end_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation_id: u32,
+        new: Tuple,
+    },
+    Update {
+        relation_id: u32,
+        /// The old values of the replica identity, or of the whole row with
+        /// `REPLICA IDENTITY FULL`.
+        old: Option<Tuple>,
+        new: Tuple,
+    },
+    Delete {
+        relation_id: u32,
+        old: Tuple,
+    },
+    Truncate {
+        relation_ids: Vec<u32>,
+    },
+    /// Messages that don't change rows, i.e. types and origins.
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+pub type Tuple = Vec<TupleValue>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TupleValue {
+    Null,
+    /// An unchanged TOASTed value, which isn't sent.
+    Unchanged,
+    /// The value in the text format of its type.
+    Text(String),
+}
+
+pub fn decode(message: &[u8]) -> Result<Message> {
+    let mut reader = Reader(message);
+    let decoded = match reader.u8()? {
+        b'B' => Message::Begin,
+        b'C' => {
+            let _flags = reader.u8()?;
+            let _commit_lsn = reader.u64()?;
+            Message::Commit {
+                end_lsn: reader.u64()?,
+            }
+        }
+        b'R' => {
+            let id = reader.u32()?;
+            let namespace = reader.string()?;
+            let name = reader.string()?;
+            let _replica_identity = reader.u8()?;
+            let num_columns = reader.u16()?;
+            let mut columns = Vec::with_capacity(num_columns.into());
+            for _ in 0..num_columns {
+                let _flags = reader.u8()?;
+                columns.push(reader.string()?);
+                let _type_id = reader.u32()?;
+                let _type_modifier = reader.u32()?;
+            }
+
+            Message::Relation(Relation {
+                id,
+                // The pg_catalog namespace is sent as an empty string.
+                namespace: if namespace.is_empty() {
+                    "pg_catalog".to_string()
+                } else {
+                    namespace
+                },
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation_id = reader.u32()?;
+            reader.expect_tag(b'N')?;
+            Message::Insert {
+                relation_id,
+                new: reader.tuple()?,
+            }
+        }
+        b'U' => {
+            let relation_id = reader.u32()?;
+            let (old, new) = match reader.u8()? {
+                b'K' | b'O' => {
+                    let old = reader.tuple()?;
+                    reader.expect_tag(b'N')?;
+                    (Some(old), reader.tuple()?)
+                }
+                b'N' => (None, reader.tuple()?),
+                tag => {
+                    return UnexpectedTagSnafu {
+                        tag: char::from(tag),
+                    }
+                    .fail()
+                }
+            };
+            Message::Update {
+                relation_id,
+                old,
+                new,
+            }
+        }
+        b'D' => {
+            let relation_id = reader.u32()?;
+            match reader.u8()? {
+                b'K' | b'O' => {}
+                tag => {
+                    return UnexpectedTagSnafu {
+                        tag: char::from(tag),
+                    }
+                    .fail()
+                }
+            }
+            Message::Delete {
+                relation_id,
+                old: reader.tuple()?,
+            }
+        }
+        b'T' => {
+            let num_relations = reader.u32()?;
+            let _options = reader.u8()?;
+            let relation_ids = (0..num_relations)
+                .map(|_| reader.u32())
+                .collect::<Result<Vec<_>>>()?;
+            Message::Truncate { relation_ids }
+        }
+        _ => Message::Other,
+    };
+
+    Ok(decoded)
+}
+
+/// Parses an LSN in its text format, i.e. `16/B374D848`.
+#[must_use]
+pub fn parse_lsn(lsn: &str) -> Option<u64> {
+    let (high, low) = lsn.split_once('/')?;
+    let high = u64::from_str_radix(high, 16).ok()?;
+    let low = u64::from_str_radix(low, 16).ok()?;
+    Some((high << 32) | low)
+}
+
+#[must_use]
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        ensure!(self.0.len() >= len, UnexpectedEndSnafu);
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok((u64::from(self.u32()?) << 32) | u64::from(self.u32()?))
+    }
+
+    /// A null-terminated string.
+    fn string(&mut self) -> Result<String> {
+        let end = self
+            .0
+            .iter()
+            .position(|byte| *byte == 0)
+            .context(InvalidStringSnafu)?;
+        let value =
+            String::from_utf8(self.take(end)?.to_vec()).map_err(|_| Error::InvalidString)?;
+        self.take(1)?;
+        Ok(value)
+    }
+
+    fn expect_tag(&mut self, expected: u8) -> Result<()> {
+        let tag = self.u8()?;
+        ensure!(
+            tag == expected,
+            UnexpectedTagSnafu {
+                tag: char::from(tag)
+            }
+        );
+        Ok(())
+    }
+
+    fn tuple(&mut self) -> Result<Tuple> {
+        let num_columns = self.u16()?;
+        (0..num_columns)
+            .map(|_| match self.u8()? {
+                b'n' => Ok(TupleValue::Null),
+                b'u' => Ok(TupleValue::Unchanged),
+                b't' => {
+                    let len = self.u32()? as usize;
+                    let value = String::from_utf8(self.take(len)?.to_vec())
+                        .map_err(|_| Error::InvalidString)?;
+                    Ok(TupleValue::Text(value))
+                }
+                tag => UnexpectedTagSnafu {
+                    tag: char::from(tag),
+                }
+                .fail(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuple(values: &[Option<&str>]) -> Vec<u8> {
+        let mut raw = u16::try_from(values.len())
+            .expect("few columns")
+            .to_be_bytes()
+            .to_vec();
+        for value in values {
+            match value {
+                Some(value) => {
+                    raw.push(b't');
+                    raw.extend(
+                        u32::try_from(value.len())
+                            .expect("short value")
+                            .to_be_bytes(),
+                    );
+                    raw.extend(value.as_bytes());
+                }
+                None => raw.push(b'n'),
+            }
+        }
+        raw
+    }
+
+    #[test]
+    fn test_decode_relation() {
+        let mut raw = vec![b'R'];
+        raw.extend(16385_u32.to_be_bytes());
+        raw.extend(b"public\0orders\0");
+        raw.push(b'd');
+        raw.extend(2_u16.to_be_bytes());
+        for column in ["id", "amount"] {
+            raw.push(1);
+            raw.extend(column.as_bytes());
+            raw.push(0);
+            raw.extend(23_u32.to_be_bytes());
+            raw.extend(u32::MAX.to_be_bytes());
+        }
+
+        assert_eq!(
+            decode(&raw).expect("valid message"),
+            Message::Relation(Relation {
+                id: 16385,
+                namespace: "public".to_string(),
+                name: "orders".to_string(),
+                columns: vec!["id".to_string(), "amount".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_changes() {
+        let mut insert = vec![b'I'];
+        insert.extend(16385_u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend(tuple(&[Some("1"), None]));
+        assert_eq!(
+            decode(&insert).expect("valid message"),
+            Message::Insert {
+                relation_id: 16385,
+                new: vec![TupleValue::Text("1".to_string()), TupleValue::Null],
+            }
+        );
+
+        let mut update = vec![b'U'];
+        update.extend(16385_u32.to_be_bytes());
+        update.push(b'K');
+        update.extend(tuple(&[Some("1")]));
+        update.push(b'N');
+        update.extend(tuple(&[Some("2"), Some("9.99")]));
+        assert_eq!(
+            decode(&update).expect("valid message"),
+            Message::Update {
+                relation_id: 16385,
+                old: Some(vec![TupleValue::Text("1".to_string())]),
+                new: vec![
+                    TupleValue::Text("2".to_string()),
+                    TupleValue::Text("9.99".to_string())
+                ],
+            }
+        );
+
+        let mut commit = vec![b'C', 0];
+        commit.extend(0x16_B374_D848_u64.to_be_bytes());
+        commit.extend(0x16_B374_D878_u64.to_be_bytes());
+        commit.extend(0_u64.to_be_bytes());
+        assert_eq!(
+            decode(&commit).expect("valid message"),
+            Message::Commit {
+                end_lsn: 0x16_B374_D878
+            }
+        );
+
+        assert!(matches!(decode(&[b'I', 0, 0]), Err(Error::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn test_lsn() {
+        assert_eq!(parse_lsn("16/B374D848"), Some(0x16_B374_D848));
+        assert_eq!(parse_lsn("0/0"), Some(0));
+        assert_eq!(parse_lsn("invalid"), None);
+        assert_eq!(format_lsn(0x16_B374_D848), "16/B374D848");
+    }
+}
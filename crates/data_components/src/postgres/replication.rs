@@ -0,0 +1,873 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Streams the changes of a Postgres table from a logical replication slot with the `pgoutput`
+//! plugin, without Debezium or Kafka.
+//!
+//! The changes are read with `pg_logical_slot_peek_binary_changes` and the slot is only advanced
+//! once they are applied, so that no changes are lost on restarts.
+
+use arrow::{
+    array::{ArrayRef, BinaryArray, RecordBatch, StringArray},
+    compute::{cast_with_options, CastOptions},
+    datatypes::{DataType, Schema, SchemaRef},
+};
+use async_stream::stream;
+use async_trait::async_trait;
+use datafusion::{
+    catalog::Session,
+    datasource::{TableProvider, TableType},
+    error::Result as DataFusionResult,
+    execution::context::SessionContext,
+    logical_expr::{Expr, TableProviderFilterPushDown},
+    physical_plan::{execute_stream, ExecutionPlan},
+    sql::TableReference,
+};
+use datafusion_table_providers::sql::db_connection_pool::postgrespool::PostgresConnectionPool;
+use futures::StreamExt;
+use snafu::prelude::*;
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::pgoutput::{self, format_lsn, parse_lsn, Message, Relation, Tuple, TupleValue};
+use crate::cdc::{
+    AppliedChanges, ChangeBatch, ChangeEnvelope, ChangesStream, Checkpoint, CommitChange,
+    CommitError, StreamError,
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of changes to read at once. Transactions aren't split, so more changes are
+/// read when a transaction is larger.
+const MAX_CHANGES_PER_READ: i32 = 10_000;
+
+const DEFAULT_SCHEMA: &str = "public";
+
+/// The number of consecutive reads of the slot that may fail to decode or convert its changes
+/// before the stream stops. The slot isn't advanced past a change that fails, so it is read again
+/// until it succeeds, i.e. once the table schema in the acceleration is fixed.
+const MAX_FAILED_READS: usize = 10;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to connect to Postgres: {source}"))]
+    UnableToConnect {
+        source: datafusion_table_providers::sql::db_connection_pool::postgrespool::Error,
+    },
+
+    #[snafu(display("Unable to query the replication slot {slot}: {source}"))]
+    ReplicationSlotError {
+        slot: String,
+        source: tokio_postgres::Error,
+    },
+
+    #[snafu(display("Unable to query the replica identity of the table {table}: {source}"))]
+    ReplicaIdentityError {
+        table: String,
+        source: tokio_postgres::Error,
+    },
+
+    #[snafu(display("The replication slot {slot} returned an invalid LSN {lsn}"))]
+    InvalidLsn { slot: String, lsn: String },
+
+    #[snafu(display("Unable to decode a change of the replication slot {slot}: {source}"))]
+    UnableToDecodeChange {
+        slot: String,
+        source: pgoutput::Error,
+    },
+
+    #[snafu(display("A change references the unknown relation {relation_id}"))]
+    UnknownRelation { relation_id: u32 },
+
+    #[snafu(display("Unable to convert the value of column {column} to {data_type}: {source}"))]
+    UnableToConvertValue {
+        column: String,
+        data_type: DataType,
+        source: arrow::error::ArrowError,
+    },
+
+    #[snafu(display("The value of column {column} isn't a hex encoded bytea value"))]
+    InvalidBytea { column: String },
+
+    #[snafu(display(
+        "An update of the table doesn't include the unchanged value of column {column}. Run `ALTER TABLE ... REPLICA IDENTITY FULL` on the table and refresh the dataset."
+    ))]
+    UnchangedValueMissing { column: String },
+
+    #[snafu(display("An error occured with handling Arrow data: {source}"))]
+    ArrowError { source: arrow::error::ArrowError },
+
+    #[snafu(display("An error occured with reading the Postgres table: {source}"))]
+    DataFusionError {
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("Unable to create the change batch: {source}"))]
+    ChangeBatchError {
+        source: crate::cdc::ChangeBatchError,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Creates a logical replication slot with the `pgoutput` plugin, if it doesn't exist yet.
+///
+/// Returns `true` if the slot was created.
+pub async fn create_replication_slot_if_not_exists(
+    pool: &PostgresConnectionPool,
+    slot: &str,
+) -> Result<bool> {
+    let conn = pool.connect_direct().await.context(UnableToConnectSnafu)?;
+    let existing = conn
+        .conn
+        .query(
+            "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+            &[&slot],
+        )
+        .await
+        .context(ReplicationSlotSnafu { slot })?;
+    if !existing.is_empty() {
+        return Ok(false);
+    }
+
+    conn.conn
+        .execute(
+            "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+            &[&slot],
+        )
+        .await
+        .context(ReplicationSlotSnafu { slot })?;
+
+    Ok(true)
+}
+
+/// Whether the table has `REPLICA IDENTITY FULL`, so that updates include the old row.
+///
+/// Without it, the unchanged TOASTed values (i.e. large text or bytea values) of an updated row
+/// aren't sent, and can't be applied to the acceleration.
+pub async fn has_full_replica_identity(
+    pool: &PostgresConnectionPool,
+    table_reference: &TableReference,
+) -> Result<bool> {
+    let table = table_reference.to_string();
+    let conn = pool.connect_direct().await.context(UnableToConnectSnafu)?;
+    let rows = conn
+        .conn
+        .query(
+            "SELECT c.relreplident::text FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace WHERE n.nspname = $1 AND c.relname = $2",
+            &[
+                &table_reference.schema().unwrap_or(DEFAULT_SCHEMA),
+                &table_reference.table(),
+            ],
+        )
+        .await
+        .context(ReplicaIdentitySnafu { table })?;
+
+    Ok(rows
+        .first()
+        .is_some_and(|row| row.get::<_, String>(0) == "f"))
+}
+
+/// A Postgres table whose changes are streamed from a logical replication slot, to keep an
+/// acceleration up to date.
+///
+/// The stream starts with the rows of the table as `r` changes, unless it resumes after an LSN
+/// whose changes were already applied.
+pub struct PostgresReplication {
+    table_provider: Arc<dyn TableProvider>,
+    pool: Arc<PostgresConnectionPool>,
+    table_reference: TableReference,
+    slot: String,
+    publication: String,
+    primary_keys: Vec<String>,
+    start_lsn: Option<u64>,
+    checkpoint: Option<Checkpoint>,
+    poll_interval: Duration,
+}
+
+impl PostgresReplication {
+    #[must_use]
+    pub fn new(
+        table_provider: Arc<dyn TableProvider>,
+        pool: Arc<PostgresConnectionPool>,
+        table_reference: TableReference,
+        slot: String,
+        publication: String,
+        primary_keys: Vec<String>,
+    ) -> Self {
+        Self {
+            table_provider,
+            pool,
+            table_reference,
+            slot,
+            publication,
+            primary_keys,
+            start_lsn: None,
+            checkpoint: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Resumes the stream after an LSN whose changes were already applied.
+    #[must_use]
+    pub fn with_start_lsn(mut self, lsn: Option<u64>) -> Self {
+        self.start_lsn = lsn;
+        self
+    }
+
+    #[must_use]
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// How often to check the replication slot for new changes.
+    #[must_use]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    #[must_use]
+    pub fn get_primary_keys(&self) -> &Vec<String> {
+        &self.primary_keys
+    }
+
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub fn stream_changes(&self) -> ChangesStream {
+        let table_provider = Arc::clone(&self.table_provider);
+        let schema = table_provider.schema();
+        let pool = Arc::clone(&self.pool);
+        let slot = self.slot.clone();
+        let publication = self.publication.clone();
+        let namespace = self
+            .table_reference
+            .schema()
+            .unwrap_or(DEFAULT_SCHEMA)
+            .to_string();
+        let table_name = self.table_reference.table().to_string();
+        let primary_keys = self.primary_keys.clone();
+        let start_lsn = self.start_lsn;
+        let checkpoint = self.checkpoint.clone();
+        let poll_interval = self.poll_interval;
+
+        Box::pin(stream! {
+            if start_lsn.is_none() {
+                let mut batches = match read_snapshot(&table_provider).await {
+                    Ok(batches) => batches,
+                    Err(e) => {
+                        yield Err(StreamError::Postgres(e.to_string()));
+                        return;
+                    }
+                };
+                while let Some(batch) = batches.next().await {
+                    let change_batch = batch.context(DataFusionSnafu).and_then(|batch| {
+                        let ops = StringArray::from(vec!["r"; batch.num_rows()]);
+                        ChangeBatch::try_from_data(ops, &primary_keys, &batch)
+                            .context(ChangeBatchSnafu)
+                    });
+                    match change_batch {
+                        Ok(change_batch) => yield Ok(ChangeEnvelope::new(
+                            Box::new(LsnCommitter::change(AppliedChanges::default())),
+                            change_batch,
+                        )),
+                        Err(e) => {
+                            yield Err(StreamError::Postgres(e.to_string()));
+                            return;
+                        }
+                    }
+                }
+            }
+
+            // The LSN of the last transaction that was streamed, and of the last one that was
+            // applied. The slot is advanced to the applied LSN before reading more changes.
+            let mut streamed_lsn = start_lsn.unwrap_or_default();
+            let applied_lsn = Arc::new(AtomicU64::new(streamed_lsn));
+            let mut advanced_lsn = 0;
+            let mut failed_reads = 0;
+            let mut relation: Option<Relation> = None;
+
+            loop {
+                let lsn = applied_lsn.load(Ordering::Acquire);
+                if lsn > advanced_lsn {
+                    match advance_slot(&pool, &slot, lsn).await {
+                        Ok(()) => advanced_lsn = lsn,
+                        Err(e) => yield Err(StreamError::Postgres(e.to_string())),
+                    }
+                }
+
+                let changes = match peek_changes(&pool, &slot, &publication).await {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        yield Err(StreamError::Postgres(e.to_string()));
+                        tokio::time::sleep(poll_interval).await;
+                        continue;
+                    }
+                };
+
+                let mut rows: Vec<(&'static str, Tuple)> = vec![];
+                let mut streamed = false;
+                let mut failed = false;
+                'changes: for change in &changes {
+                    let message = match pgoutput::decode(change)
+                        .context(UnableToDecodeChangeSnafu { slot: slot.as_str() })
+                    {
+                        Ok(message) => message,
+                        Err(e) => {
+                            yield Err(StreamError::Postgres(e.to_string()));
+                            failed = true;
+                            break;
+                        }
+                    };
+
+                    let relation_id = relation.as_ref().map(|relation| relation.id);
+                    match message {
+                        Message::Begin => rows.clear(),
+                        Message::Relation(changed) => {
+                            if changed.namespace == namespace && changed.name == table_name {
+                                relation = Some(changed);
+                            }
+                        }
+                        Message::Insert { relation_id: id, new } if Some(id) == relation_id => {
+                            rows.push(("c", new));
+                        }
+                        Message::Update {
+                            relation_id: id,
+                            old,
+                            new,
+                        } if Some(id) == relation_id => {
+                            // The old row is only sent when its replica identity changed, or
+                            // with `REPLICA IDENTITY FULL`, and is deleted in case its primary
+                            // key changed.
+                            let new = with_unchanged_values(new, old.as_ref());
+                            if let Some(old) = old {
+                                rows.push(("d", old));
+                            }
+                            rows.push(("u", new));
+                        }
+                        Message::Delete { relation_id: id, old } if Some(id) == relation_id => {
+                            rows.push(("d", old));
+                        }
+                        Message::Truncate { relation_ids } => {
+                            if relation_ids.iter().any(|id| Some(*id) == relation_id) {
+                                tracing::warn!(
+                                    "The table {namespace}.{table_name} was truncated, which isn't applied to the acceleration. Refresh the dataset to remove the truncated rows."
+                                );
+                            }
+                        }
+                        Message::Commit { end_lsn } => {
+                            let transaction_rows = std::mem::take(&mut rows);
+                            if end_lsn <= streamed_lsn {
+                                continue;
+                            }
+
+                            let change_batches = match &relation {
+                                Some(relation) => to_change_batches(
+                                    &schema,
+                                    &primary_keys,
+                                    relation,
+                                    transaction_rows,
+                                ),
+                                None => Ok(vec![]),
+                            };
+                            let change_batches = match change_batches {
+                                Ok(change_batches) => change_batches,
+                                Err(e) => {
+                                    yield Err(StreamError::Postgres(e.to_string()));
+                                    failed = true;
+                                    break 'changes;
+                                }
+                            };
+                            let applied = AppliedChanges::default();
+                            let changes = change_batches.len();
+                            for change_batch in change_batches {
+                                yield Ok(ChangeEnvelope::new(
+                                    Box::new(LsnCommitter::change(applied.clone())),
+                                    change_batch,
+                                ));
+                            }
+
+                            // Transactions without changes to the table are committed too, so
+                            // that the slot doesn't hold back the WAL of other tables.
+                            yield transaction_applied(
+                                &schema,
+                                LsnCommitter {
+                                    applied,
+                                    transaction: Some((end_lsn, changes)),
+                                    applied_lsn: Some(Arc::clone(&applied_lsn)),
+                                    checkpoint: checkpoint.clone(),
+                                },
+                            );
+                            streamed_lsn = end_lsn;
+                            streamed = true;
+                        }
+                        _ => {}
+                    }
+                }
+
+                if failed {
+                    failed_reads += 1;
+                    if failed_reads >= MAX_FAILED_READS {
+                        yield Err(StreamError::Postgres(format!(
+                            "Stopped streaming the changes of the replication slot {slot} after {failed_reads} failed attempts to read them"
+                        )));
+                        return;
+                    }
+                } else {
+                    failed_reads = 0;
+                }
+
+                if !streamed || failed {
+                    tokio::time::sleep(poll_interval).await;
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for PostgresReplication {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.table_provider.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.table_provider.table_type()
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        self.table_provider.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.table_provider
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
+
+/// Commits a change of a transaction, or the transaction itself once all of its changes were
+/// committed, by recording its LSN so that the slot is advanced past it.
+struct LsnCommitter {
+    applied: AppliedChanges,
+    transaction: Option<(u64, usize)>,
+    applied_lsn: Option<Arc<AtomicU64>>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl LsnCommitter {
+    fn change(applied: AppliedChanges) -> Self {
+        Self {
+            applied,
+            transaction: None,
+            applied_lsn: None,
+            checkpoint: None,
+        }
+    }
+}
+
+impl CommitChange for LsnCommitter {
+    fn commit(&self) -> Result<(), CommitError> {
+        let Some((lsn, changes)) = self.transaction else {
+            self.applied.applied();
+            return Ok(());
+        };
+
+        self.applied.ensure_applied(lsn, changes)?;
+        if let Some(applied_lsn) = &self.applied_lsn {
+            applied_lsn.fetch_max(lsn, Ordering::AcqRel);
+        }
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint(lsn);
+        }
+
+        Ok(())
+    }
+}
+
+async fn read_snapshot(
+    table_provider: &Arc<dyn TableProvider>,
+) -> Result<datafusion::physical_plan::SendableRecordBatchStream> {
+    let ctx = SessionContext::new();
+    let plan = table_provider
+        .scan(&ctx.state(), None, &[], None)
+        .await
+        .context(DataFusionSnafu)?;
+
+    execute_stream(plan, ctx.task_ctx()).context(DataFusionSnafu)
+}
+
+/// Reads the pending changes of the slot, as `pgoutput` messages, without consuming them.
+async fn peek_changes(
+    pool: &PostgresConnectionPool,
+    slot: &str,
+    publication: &str,
+) -> Result<Vec<Vec<u8>>> {
+    let conn = pool.connect_direct().await.context(UnableToConnectSnafu)?;
+    let rows = conn
+        .conn
+        .query(
+            "SELECT data FROM pg_logical_slot_peek_binary_changes($1, NULL, $2, 'proto_version', '1', 'publication_names', $3)",
+            &[&slot, &MAX_CHANGES_PER_READ, &publication],
+        )
+        .await
+        .context(ReplicationSlotSnafu { slot })?;
+
+    Ok(rows.iter().map(|row| row.get::<_, Vec<u8>>(0)).collect())
+}
+
+async fn advance_slot(pool: &PostgresConnectionPool, slot: &str, lsn: u64) -> Result<()> {
+    let conn = pool.connect_direct().await.context(UnableToConnectSnafu)?;
+    let row = conn
+        .conn
+        .query_one(
+            "SELECT end_lsn::text FROM pg_replication_slot_advance($1, $2::text::pg_lsn)",
+            &[&slot, &format_lsn(lsn)],
+        )
+        .await
+        .context(ReplicationSlotSnafu { slot })?;
+
+    let end_lsn: String = row.get(0);
+    ensure!(
+        parse_lsn(&end_lsn).is_some(),
+        InvalidLsnSnafu { slot, lsn: end_lsn }
+    );
+
+    Ok(())
+}
+
+/// Fills in the unchanged TOASTed values of an update from the old row, which has them with
+/// `REPLICA IDENTITY FULL`. Values that remain unchanged fail the conversion to a change batch,
+/// rather than being written as `NULL`.
+fn with_unchanged_values(new: Tuple, old: Option<&Tuple>) -> Tuple {
+    new.into_iter()
+        .enumerate()
+        .map(|(i, value)| match (value, old.and_then(|old| old.get(i))) {
+            (TupleValue::Unchanged, Some(old_value)) => old_value.clone(),
+            (value, _) => value,
+        })
+        .collect()
+}
+
+/// An empty change that marks all the changes of a transaction as applied.
+fn transaction_applied(
+    schema: &SchemaRef,
+    committer: LsnCommitter,
+) -> Result<ChangeEnvelope, StreamError> {
+    let data = RecordBatch::new_empty(Arc::clone(schema));
+    let change_batch =
+        ChangeBatch::try_from_data(StringArray::from(Vec::<&str>::new()), &[], &data)
+            .map_err(|e| StreamError::Postgres(e.to_string()))?;
+
+    Ok(ChangeEnvelope::new(Box::new(committer), change_batch))
+}
+
+/// Converts the changed rows of a transaction, in the text format of their types, to change
+/// batches in the same order.
+///
+/// Deleted rows may only have the values of the replica identity, so they are in separate batches
+/// where all the columns are nullable.
+fn to_change_batches(
+    schema: &SchemaRef,
+    primary_keys: &[String],
+    relation: &Relation,
+    rows: Vec<(&'static str, Tuple)>,
+) -> Result<Vec<ChangeBatch>> {
+    let deletion_schema = Arc::new(Schema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| field.as_ref().clone().with_nullable(true))
+            .collect::<Vec<_>>(),
+    ));
+
+    let mut change_batches = vec![];
+    let mut run: Vec<(&'static str, Tuple)> = vec![];
+    for row in rows {
+        if run
+            .first()
+            .is_some_and(|(op, _)| (*op == "d") != (row.0 == "d"))
+        {
+            change_batches.push(std::mem::take(&mut run));
+        }
+        run.push(row);
+    }
+    if !run.is_empty() {
+        change_batches.push(run);
+    }
+
+    change_batches
+        .into_iter()
+        .map(|rows| {
+            let is_deletion = rows.first().is_some_and(|(op, _)| *op == "d");
+            let schema = if is_deletion {
+                &deletion_schema
+            } else {
+                schema
+            };
+            to_change_batch(schema, primary_keys, relation, &rows)
+        })
+        .collect()
+}
+
+fn to_change_batch(
+    schema: &SchemaRef,
+    primary_keys: &[String],
+    relation: &Relation,
+    rows: &[(&'static str, Tuple)],
+) -> Result<ChangeBatch> {
+    let column_indexes = relation
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| (column.as_str(), i))
+        .collect::<HashMap<_, _>>();
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let column_index = column_indexes.get(field.name().as_str());
+            let values = rows
+                .iter()
+                .map(
+                    |(_, tuple)| match column_index.and_then(|i| tuple.get(*i)) {
+                        Some(TupleValue::Text(value)) => Ok(Some(value.as_str())),
+                        Some(TupleValue::Unchanged) => UnchangedValueMissingSnafu {
+                            column: field.name(),
+                        }
+                        .fail(),
+                        _ => Ok(None),
+                    },
+                )
+                .collect::<Result<StringArray>>()?;
+
+            from_text(&values, field.name(), field.data_type())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let ops = rows.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+    let data = RecordBatch::try_new(Arc::clone(schema), columns).context(ArrowSnafu)?;
+
+    ChangeBatch::try_from_data(StringArray::from(ops), primary_keys, &data)
+        .context(ChangeBatchSnafu)
+}
+
+/// Converts values in the text format of their Postgres type to an Arrow type.
+fn from_text(values: &StringArray, column: &str, data_type: &DataType) -> Result<ArrayRef> {
+    if matches!(data_type, DataType::Binary) {
+        // bytea values are hex encoded, i.e. `\x0102`
+        return Ok(Arc::new(
+            values
+                .iter()
+                .map(|value| {
+                    value
+                        .map(|value| decode_bytea(value).context(InvalidByteaSnafu { column }))
+                        .transpose()
+                })
+                .collect::<Result<BinaryArray>>()?,
+        ));
+    }
+
+    let cast_options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    cast_with_options(values, data_type, &cast_options).context(UnableToConvertValueSnafu {
+        column,
+        data_type: data_type.clone(),
+    })
+}
+
+fn decode_bytea(value: &str) -> Option<Vec<u8>> {
+    let hex = value.strip_prefix("\\x")?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Array, Int64Array},
+        datatypes::Field,
+    };
+
+    #[test]
+    fn test_lsn_only_applied_once_the_transaction_is_written() {
+        let applied_lsn = Arc::new(AtomicU64::new(0));
+        let applied = AppliedChanges::default();
+        let change = LsnCommitter::change(applied.clone());
+        let transaction = LsnCommitter {
+            applied,
+            transaction: Some((0x16B_3748, 1)),
+            applied_lsn: Some(Arc::clone(&applied_lsn)),
+            checkpoint: None,
+        };
+
+        assert!(matches!(
+            transaction.commit(),
+            Err(CommitError::ChangesNotApplied { .. })
+        ));
+        assert_eq!(applied_lsn.load(Ordering::Acquire), 0);
+
+        change.commit().expect("change committed");
+        transaction.commit().expect("transaction committed");
+        assert_eq!(applied_lsn.load(Ordering::Acquire), 0x16B_3748);
+    }
+
+    #[test]
+    fn test_to_change_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("payload", DataType::Binary, true),
+        ]));
+        let relation = Relation {
+            id: 16385,
+            namespace: "public".to_string(),
+            name: "users".to_string(),
+            columns: vec!["payload".to_string(), "id".to_string(), "name".to_string()],
+        };
+        let rows = vec![
+            (
+                "c",
+                vec![
+                    TupleValue::Text("\\x0102".to_string()),
+                    TupleValue::Text("1".to_string()),
+                    TupleValue::Text("alice".to_string()),
+                ],
+            ),
+            (
+                "d",
+                vec![
+                    TupleValue::Null,
+                    TupleValue::Text("2".to_string()),
+                    TupleValue::Null,
+                ],
+            ),
+        ];
+
+        let change_batches = to_change_batches(&schema, &["id".to_string()], &relation, rows)
+            .expect("valid change batches");
+        assert_eq!(change_batches.len(), 2, "deletions are in a separate batch");
+        assert_eq!(change_batches[0].op(0).to_string(), "c");
+        assert_eq!(change_batches[1].op(0).to_string(), "d");
+        assert_eq!(change_batches[1].primary_keys(0), vec!["id".to_string()]);
+
+        let deleted = change_batches[1].data(0);
+        let id = deleted
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("id is an Int64 column");
+        assert_eq!(id.value(0), 2);
+        assert!(deleted.column(1).is_null(0));
+
+        let created = change_batches[0].data(0);
+        let payload = created
+            .column(2)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .expect("payload is a Binary column");
+        assert_eq!(payload.value(0), &[1, 2]);
+    }
+
+    #[test]
+    fn test_invalid_bytea() {
+        let values = StringArray::from(vec![Some("\\x0102"), None, Some("\\x01zz")]);
+        let result = from_text(&values, "payload", &DataType::Binary);
+        assert!(
+            matches!(result, Err(Error::InvalidBytea { ref column }) if column == "payload"),
+            "invalid values must not be written as NULL"
+        );
+
+        let values = StringArray::from(vec![Some("\\x0102"), None, Some("\\x")]);
+        let payload = from_text(&values, "payload", &DataType::Binary).expect("valid values");
+        let payload = payload
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .expect("payload is a Binary column");
+        assert_eq!(payload.value(0), &[1, 2]);
+        assert!(payload.is_null(1));
+        assert!(payload.value(2).is_empty());
+    }
+
+    #[test]
+    fn test_with_unchanged_values() {
+        let old = vec![
+            TupleValue::Text("1".to_string()),
+            TupleValue::Text("large".to_string()),
+        ];
+        let new = vec![TupleValue::Text("2".to_string()), TupleValue::Unchanged];
+        assert_eq!(
+            with_unchanged_values(new, Some(&old)),
+            vec![
+                TupleValue::Text("2".to_string()),
+                TupleValue::Text("large".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_value_without_old_row() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("document", DataType::Utf8, true),
+        ]));
+        let relation = Relation {
+            id: 16385,
+            namespace: "public".to_string(),
+            name: "documents".to_string(),
+            columns: vec!["id".to_string(), "document".to_string()],
+        };
+        // With the default replica identity, an update that doesn't change a TOASTed value has
+        // no old row to take it from.
+        let new = with_unchanged_values(
+            vec![TupleValue::Text("1".to_string()), TupleValue::Unchanged],
+            None,
+        );
+
+        let result = to_change_batches(&schema, &["id".to_string()], &relation, vec![("u", new)]);
+        assert!(
+            matches!(result, Err(Error::UnchangedValueMissing { ref column }) if column == "document"),
+            "the unchanged value must not be written as NULL"
+        );
+    }
+}
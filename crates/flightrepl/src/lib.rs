@@ -67,6 +67,10 @@ pub struct ReplConfig {
         help_heading = "SQL REPL"
     )]
     pub tls_root_certificate_file: Option<String>,
+
+    /// The API key used to authenticate with the Spice.ai runtime
+    #[arg(long, value_name = "API_KEY", help_heading = "SQL REPL")]
+    pub api_key: Option<String>,
 }
 
 const NQL_LINE_PREFIX: &str = "nql ";
@@ -76,10 +80,16 @@ async fn send_nsql_request(
     base_url: String,
     query: String,
     runtime: LlmRuntime,
+    api_key: Option<&str>,
 ) -> Result<String, reqwest::Error> {
-    client
+    let mut request = client
         .post(format!("{base_url}/v1/nsql"))
-        .header("Content-Type", "application/json")
+        .header("Content-Type", "application/json");
+    if let Some(api_key) = api_key {
+        request = request.header("X-API-Key", api_key);
+    }
+
+    request
         .json(&json!({
             "query": query,
             "model": runtime,
@@ -178,7 +188,8 @@ pub async fn run(repl_config: ReplConfig) -> Result<(), Box<dyn std::error::Erro
                 let _ = rl.add_history_entry(line);
                 get_and_display_nql_records(
                     repl_config.http_endpoint.clone(),
-                     line.strip_prefix(NQL_LINE_PREFIX).unwrap_or(line).to_string()
+                     line.strip_prefix(NQL_LINE_PREFIX).unwrap_or(line).to_string(),
+                     repl_config.api_key.as_deref(),
                 ).await.map_err(|e| format!("Error occured on NQL request: {e}"))?;
                 continue;
             }
@@ -188,7 +199,7 @@ pub async fn run(repl_config: ReplConfig) -> Result<(), Box<dyn std::error::Erro
         let _ = rl.add_history_entry(line);
 
         let start_time = Instant::now();
-        match get_records(client.clone(), line).await {
+        match get_records(client.clone(), line, repl_config.api_key.as_deref()).await {
             Ok((_, 0, from_cache)) => {
                 println!("No results{}.", if from_cache { " (cached)" } else { "" });
             }
@@ -212,6 +223,21 @@ pub async fn run(repl_config: ReplConfig) -> Result<(), Box<dyn std::error::Erro
     Ok(())
 }
 
+fn add_authorization<T>(
+    request: &mut tonic::Request<T>,
+    api_key: Option<&str>,
+) -> Result<(), FlightError> {
+    let Some(api_key) = api_key else {
+        return Ok(());
+    };
+
+    let value = format!("Bearer {api_key}")
+        .parse()
+        .map_err(|_| FlightError::Tonic(Status::invalid_argument("Invalid API key")))?;
+    request.metadata_mut().insert("authorization", value);
+    Ok(())
+}
+
 /// Send a SQL query to the Flight service and return the resulting record batches.
 ///
 /// # Errors
@@ -220,6 +246,7 @@ pub async fn run(repl_config: ReplConfig) -> Result<(), Box<dyn std::error::Erro
 async fn get_records(
     mut client: FlightServiceClient<Channel>,
     line: &str,
+    api_key: Option<&str>,
 ) -> Result<(Vec<RecordBatch>, usize, bool), FlightError> {
     let sql_command = CommandStatementQuery {
         query: line.to_string(),
@@ -227,7 +254,8 @@ async fn get_records(
     };
     let sql_command_bytes = sql_command.as_any().encode_to_vec();
 
-    let request = FlightDescriptor::new_cmd(sql_command_bytes);
+    let mut request = FlightDescriptor::new_cmd(sql_command_bytes).into_request();
+    add_authorization(&mut request, api_key)?;
 
     let mut flight_info = client.get_flight_info(request).await?.into_inner();
     let Some(endpoint) = flight_info.endpoint.pop() else {
@@ -236,7 +264,8 @@ async fn get_records(
     let Some(ticket) = endpoint.ticket else {
         return Err(FlightError::Tonic(Status::internal("No ticket")));
     };
-    let request = ticket.into_request();
+    let mut request = ticket.into_request();
+    add_authorization(&mut request, api_key)?;
 
     let response = client.do_get(request).await?;
     let from_cache = response
@@ -312,10 +341,12 @@ async fn display_records(
 async fn get_and_display_nql_records(
     endpoint: String,
     query: String,
+    api_key: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let start_time = Instant::now();
 
-    let resp = send_nsql_request(&Client::new(), endpoint, query, LlmRuntime::Openai).await?;
+    let resp =
+        send_nsql_request(&Client::new(), endpoint, query, LlmRuntime::Openai, api_key).await?;
 
     let jsonl_resp = json_array_to_jsonl(&resp)?;
 
@@ -36,6 +36,7 @@ use async_openai::{
 
 #[cfg(feature = "mistralrs")]
 pub mod mistral;
+pub mod rerank;
 use indexmap::IndexMap;
 use mistralrs::MessageContent;
 
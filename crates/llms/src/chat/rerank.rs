@@ -0,0 +1,141 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+     https://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+#![allow(clippy::missing_errors_doc)]
+
+//! Reranking of documents with a chat model, for when no cross-encoder is available.
+
+use super::{Chat, Error, Result};
+
+/// The most tokens of a single document in a rerank prompt. Longer documents are truncated, as
+/// their start is usually enough to grade their relevance.
+const MAX_DOCUMENT_TOKENS: usize = 512;
+
+/// The most tokens of all the documents in a rerank prompt, so that it fits in the context window
+/// of small models along with the instructions and the response.
+const MAX_DOCUMENTS_TOKENS: usize = 6_000;
+
+/// Chat models don't expose their tokenizer, so tokens are approximated with characters, of which
+/// English text has about four per token.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Scores how relevant each of `documents` is to `query`, from 0 (irrelevant) to 10 (a perfect
+/// answer), by asking `model` to grade all of them in a single prompt.
+pub async fn rerank(model: &dyn Chat, query: &str, documents: &[String]) -> Result<Vec<f32>> {
+    if documents.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let response = model
+        .run(rerank_prompt(query, documents))
+        .await?
+        .unwrap_or_default();
+
+    parse_scores(&response, documents.len()).ok_or_else(|| Error::FailedToRunModel {
+        source: format!(
+            "Expected a JSON array of {} relevance scores, got: {response}",
+            documents.len()
+        )
+        .into(),
+    })
+}
+
+/// The prompt to grade `documents`, each truncated so that all of them fit in
+/// [`MAX_DOCUMENTS_TOKENS`].
+fn rerank_prompt(query: &str, documents: &[String]) -> String {
+    let count = documents.len();
+    let max_chars = CHARS_PER_TOKEN * MAX_DOCUMENT_TOKENS.min(MAX_DOCUMENTS_TOKENS / count.max(1));
+    let documents = documents
+        .iter()
+        .enumerate()
+        .map(|(i, document)| {
+            let document = truncate(document, max_chars);
+            format!("<document index=\"{i}\">\n{document}\n</document>")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "Rate how relevant each document is to the query, from 0 (irrelevant) to 10 (answers the query completely).\n\
+        Respond with only a JSON array of {count} numbers, the rating of each document in order.\n\n\
+        <query>\n{query}\n</query>\n\n{documents}"
+    )
+}
+
+/// The first `max_chars` characters of `document`.
+fn truncate(document: &str, max_chars: usize) -> &str {
+    match document.char_indices().nth(max_chars) {
+        Some((end, _)) => &document[..end],
+        None => document,
+    }
+}
+
+/// The first JSON array of `expected` numbers in a response, ignoring any text around it.
+fn parse_scores(response: &str, expected: usize) -> Option<Vec<f32>> {
+    let start = response.find('[')?;
+    let end = start + response[start..].find(']')?;
+    let scores: Vec<f32> = serde_json::from_str(&response[start..=end]).ok()?;
+    (scores.len() == expected).then_some(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scores() {
+        assert_eq!(parse_scores("[1, 7.5, 0]", 3), Some(vec![1.0, 7.5, 0.0]));
+        assert_eq!(
+            parse_scores("Here are the ratings:\n```json\n[3, 10]\n```", 2),
+            Some(vec![3.0, 10.0])
+        );
+        assert_eq!(parse_scores("[1, 2]", 3), None, "a score is missing");
+        assert_eq!(parse_scores("[1, \"high\"]", 2), None);
+        assert_eq!(parse_scores("The first document is relevant.", 1), None);
+    }
+
+    #[test]
+    fn test_rerank_prompt() {
+        let documents = vec![
+            "Refunds take 5 days.".to_string(),
+            "Shipping is free.".to_string(),
+        ];
+        let prompt = rerank_prompt("How long do refunds take?", &documents);
+
+        assert!(prompt.contains("JSON array of 2 numbers"));
+        assert!(prompt.contains("<query>\nHow long do refunds take?\n</query>"));
+        assert!(prompt.contains("<document index=\"0\">\nRefunds take 5 days.\n</document>"));
+        assert!(prompt.contains("<document index=\"1\">\nShipping is free.\n</document>"));
+    }
+
+    #[test]
+    fn test_rerank_prompt_truncates_documents() {
+        let long = "é".repeat(CHARS_PER_TOKEN * MAX_DOCUMENT_TOKENS * 2);
+        let prompt = rerank_prompt("query", &[long]);
+        let expected = "é".repeat(CHARS_PER_TOKEN * MAX_DOCUMENT_TOKENS);
+        assert!(prompt.contains(&format!("\n{expected}\n</document>")));
+
+        // Many documents share the token budget of the prompt
+        let documents = vec!["word ".repeat(1_000); 100];
+        let prompt = rerank_prompt("query", &documents);
+        assert!(prompt.len() < CHARS_PER_TOKEN * MAX_DOCUMENTS_TOKENS + 10_000);
+        assert!(prompt.contains("<document index=\"99\">"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("héllo", 2), "hé");
+        assert_eq!(truncate("héllo", 5), "héllo");
+        assert_eq!(truncate("héllo", 10), "héllo");
+        assert_eq!(truncate("héllo", 0), "");
+    }
+}
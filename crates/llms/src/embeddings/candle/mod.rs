@@ -17,7 +17,7 @@ limitations under the License.
 
 use super::{
     Embed, FailedToCreateEmbeddingSnafu, FailedToInstantiateEmbeddingModelSnafu,
-    FailedToPrepareInputSnafu, Result,
+    FailedToPrepareInputSnafu, FailedToRerankSnafu, HealthCheckSnafu, Result, UnsupportedTaskSnafu,
 };
 use std::{
     collections::HashMap,
@@ -41,8 +41,10 @@ use snafu::ResultExt;
 use tei_backend_core::{Backend, ModelType, Pool};
 use tei_candle::{batch, sort_embeddings, CandleBackend};
 use tempfile::tempdir;
-use tokenizers::{Encoding, Tokenizer};
+use tokenizers::{Encoding, Tokenizer, TruncationParams};
 
+/// An embedding model, or a cross-encoder that reranks documents when the model is a sequence
+/// classifier.
 pub struct CandleEmbedding {
     backend: Arc<Mutex<CandleBackend>>,
     tok: Tokenizer,
@@ -53,6 +55,21 @@ pub struct CandleEmbedding {
 #[derive(Debug, Deserialize)]
 pub struct ModelConfig {
     pub hidden_size: i32,
+
+    #[serde(default)]
+    pub architectures: Vec<String>,
+
+    pub max_position_embeddings: Option<usize>,
+}
+
+impl ModelConfig {
+    /// Whether the model is a cross-encoder, which scores pairs of texts instead of embedding them.
+    #[must_use]
+    pub fn is_cross_encoder(&self) -> bool {
+        self.architectures
+            .iter()
+            .any(|a| a.ends_with("ForSequenceClassification"))
+    }
 }
 
 static F32_DTYPE: &str = "float32";
@@ -83,19 +100,35 @@ impl CandleEmbedding {
 
     /// Attempt to create a new `CandleEmbedding` instance. Requires all model artifacts to be within a single folder.
     pub fn try_new(model_root: &Path, dtype: &str) -> Result<Self> {
+        let model_cfg = Self::model_config(model_root)?;
+        let model_type = if model_cfg.is_cross_encoder() {
+            ModelType::Classifier
+        } else {
+            ModelType::Embedding(Pool::Cls)
+        };
+
+        let mut tok = Tokenizer::from_file(model_root.join("tokenizer.json"))
+            .context(FailedToInstantiateEmbeddingModelSnafu)?;
+        let rerank_max_length = model_cfg
+            .max_position_embeddings
+            .filter(|_| model_cfg.is_cross_encoder());
+        if let Some(max_length) = rerank_max_length {
+            // Long documents are truncated rather than failing the whole rerank.
+            tok.with_truncation(Some(TruncationParams {
+                max_length,
+                ..TruncationParams::default()
+            }))
+            .context(FailedToInstantiateEmbeddingModelSnafu)?;
+        }
+
         Ok(Self {
             backend: Arc::new(Mutex::new(
-                CandleBackend::new(
-                    model_root.to_path_buf(),
-                    dtype.to_string(),
-                    ModelType::Embedding(Pool::Cls),
-                )
-                .boxed()
-                .context(FailedToInstantiateEmbeddingModelSnafu)?,
+                CandleBackend::new(model_root.to_path_buf(), dtype.to_string(), model_type)
+                    .boxed()
+                    .context(FailedToInstantiateEmbeddingModelSnafu)?,
             )),
-            tok: Tokenizer::from_file(model_root.join("tokenizer.json"))
-                .context(FailedToInstantiateEmbeddingModelSnafu)?,
-            model_cfg: Self::model_config(model_root)?,
+            tok,
+            model_cfg,
         })
     }
 
@@ -114,6 +147,10 @@ impl CandleEmbedding {
 #[async_trait]
 impl Embed for CandleEmbedding {
     async fn embed(&mut self, input: EmbeddingInput) -> Result<Vec<Vec<f32>>> {
+        if self.model_cfg.is_cross_encoder() {
+            return UnsupportedTaskSnafu { task: "embed" }.fail();
+        }
+
         let add_special_tokens = true;
 
         let encodings: Vec<Encoding> = match input {
@@ -156,6 +193,62 @@ impl Embed for CandleEmbedding {
     fn size(&self) -> i32 {
         self.model_cfg.hidden_size
     }
+
+    async fn health(&mut self) -> Result<()> {
+        if self.model_cfg.is_cross_encoder() {
+            self.rerank("health", &["health".to_string()])
+                .await
+                .boxed()
+                .context(HealthCheckSnafu)?;
+        } else {
+            self.embed(EmbeddingInput::String("health".to_string()))
+                .await
+                .boxed()
+                .context(HealthCheckSnafu)?;
+        }
+        Ok(())
+    }
+
+    async fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<f32>> {
+        if !self.model_cfg.is_cross_encoder() {
+            return UnsupportedTaskSnafu { task: "rerank" }.fail();
+        }
+
+        let encodings: Vec<Encoding> = documents
+            .iter()
+            .map(|document| {
+                self.tok
+                    .encode((query.to_string(), document.clone()), true)
+                    .context(FailedToPrepareInputSnafu)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let pooled_idx = (0..encodings.len()).map(|i| i as u32).collect::<Vec<_>>();
+        let b = batch(encodings, pooled_idx, vec![]);
+
+        let predictions = match self.backend.lock() {
+            Ok(r) => r.predict(b).boxed().context(FailedToRerankSnafu)?,
+            Err(e) => {
+                tracing::error!("Failed to lock backend: {:?}", e);
+                return Err(super::Error::FailedToRerank {
+                    source: "Failed to lock backend".into(),
+                });
+            }
+        };
+
+        // Cross-encoders have a single logit, the relevance of the document to the query.
+        (0..documents.len())
+            .map(|i| {
+                predictions
+                    .get(&i)
+                    .and_then(|logits| logits.first().copied())
+                    .ok_or_else(|| super::Error::FailedToRerank {
+                        source: format!("No score for document {i}").into(),
+                    })
+            })
+            .collect()
+    }
 }
 
 /// For a given `HuggingFace` repo, download the needed files to create a `CandleEmbedding`.
@@ -233,3 +326,47 @@ fn link_files_into_tmp_dir(files: HashMap<String, &Path>) -> Result<PathBuf> {
 
     Ok(temp_dir.into_path())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_cross_encoder() {
+        let config: ModelConfig = serde_json::from_str(
+            r#"{"hidden_size": 384, "architectures": ["BertForSequenceClassification"]}"#,
+        )
+        .expect("valid config");
+        assert!(config.is_cross_encoder());
+
+        let config: ModelConfig =
+            serde_json::from_str(r#"{"hidden_size": 384, "architectures": ["BertModel"]}"#)
+                .expect("valid config");
+        assert!(!config.is_cross_encoder());
+
+        let config: ModelConfig =
+            serde_json::from_str(r#"{"hidden_size": 384}"#).expect("valid config");
+        assert!(!config.is_cross_encoder());
+    }
+
+    #[tokio::test]
+    #[ignore = "downloads the model from Hugging Face"]
+    async fn test_cross_encoder_rerank() {
+        let mut model = CandleEmbedding::from_hf("cross-encoder/ms-marco-MiniLM-L-6-v2", None)
+            .expect("model loaded");
+        let documents = vec![
+            "Shipping is free for orders over $50.".to_string(),
+            "Refunds are issued within 5 business days of receiving the return.".to_string(),
+        ];
+
+        let scores = model
+            .rerank("How long does a refund take?", &documents)
+            .await
+            .expect("documents reranked");
+        assert_eq!(scores.len(), 2);
+        assert!(
+            scores[1] > scores[0],
+            "the refund document is more relevant, got {scores:?}"
+        );
+    }
+}
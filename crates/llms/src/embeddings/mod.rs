@@ -50,6 +50,14 @@ pub enum Error {
 
     #[snafu(display("No model from {from} currently supports {task}"))]
     UnsupportedTaskForModel { from: String, task: String },
+
+    #[snafu(display("The model doesn't support {task}"))]
+    UnsupportedTask { task: String },
+
+    #[snafu(display("Failed to rerank documents: {source}"))]
+    FailedToRerank {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -71,6 +79,12 @@ pub trait Embed: Sync + Send {
     /// Returns the size of the embedding vector returned by the model.
     fn size(&self) -> i32;
 
+    /// Scores how relevant each of `documents` is to `query`, higher is more relevant. Only
+    /// cross-encoder models support reranking; they can't [`Self::embed`].
+    async fn rerank(&mut self, _query: &str, _documents: &[String]) -> Result<Vec<f32>> {
+        UnsupportedTaskSnafu { task: "rerank" }.fail()
+    }
+
     /// An OpenAI-compatible interface for the embedding trait. If not implemented, the default
     /// implementation will be constructed based on the trait's [`embed`] method.
     #[allow(clippy::cast_possible_truncation)]
@@ -19,6 +19,7 @@ use std::{any::Any, sync::Arc, time::Duration};
 
 use crate::component::dataset::acceleration::{RefreshMode, ZeroResultsAction};
 use crate::component::dataset::TimeFormat;
+use crate::dataaccelerator::snapshot::Snapshotter;
 use crate::datafusion::SPICE_RUNTIME_SCHEMA;
 use arrow::array::UInt64Array;
 use arrow::datatypes::SchemaRef;
@@ -172,6 +173,7 @@ pub struct Builder {
     changes_stream: Option<ChangesStream>,
     append_stream: Option<ChangesStream>,
     disable_query_push_down: bool,
+    snapshotter: Option<Arc<Snapshotter>>,
 }
 
 impl Builder {
@@ -192,6 +194,7 @@ impl Builder {
             changes_stream: None,
             append_stream: None,
             disable_query_push_down: false,
+            snapshotter: None,
         }
     }
 
@@ -218,6 +221,12 @@ impl Builder {
         self
     }
 
+    /// Snapshot the accelerator after each successful refresh
+    pub fn snapshotter(&mut self, snapshotter: Option<Arc<Snapshotter>>) -> &mut Self {
+        self.snapshotter = snapshotter;
+        self
+    }
+
     /// Set the changes stream for the accelerated table
     ///
     /// # Panics
@@ -268,7 +277,7 @@ impl Builder {
                     )
                 }
             }
-            RefreshMode::Full => {
+            RefreshMode::Full | RefreshMode::Upsert => {
                 let (start_refresh, on_start_refresh) = mpsc::channel::<()>(1);
                 (
                     refresh::AccelerationRefreshMode::Full(on_start_refresh),
@@ -295,6 +304,7 @@ impl Builder {
             Arc::clone(&self.accelerator),
         );
         refresher.cache_provider(self.cache_provider.clone());
+        refresher.snapshotter(self.snapshotter);
 
         let refresh_handle = refresher
             .start(acceleration_refresh_mode, ready_sender)
@@ -46,6 +46,14 @@ pub(crate) static LOAD_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(||
         .init()
 });
 
+pub(crate) static UPSERT_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    METER
+        .f64_histogram("datasets_acceleration_upsert_duration_ms")
+        .with_description("Duration in milliseconds to upsert into an acceleration.")
+        .with_unit("ms")
+        .init()
+});
+
 pub(crate) static APPEND_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
     METER
         .f64_histogram("datasets_acceleration_append_duration_ms")
@@ -21,8 +21,12 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use crate::accelerated_table::refresh_task::RefreshTask;
 use crate::component::dataset::acceleration::RefreshMode;
 use crate::component::dataset::TimeFormat;
+use crate::dataaccelerator::snapshot::Snapshotter;
+use crate::status;
 use arrow::datatypes::Schema;
 use cache::QueryResultsCacheProvider;
+use chrono::Utc;
+use cron::Schedule;
 use data_components::cdc::ChangesStream;
 use datafusion::common::TableReference;
 use datafusion::datasource::TableProvider;
@@ -33,6 +37,7 @@ use snafu::prelude::*;
 use tokio::select;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 
@@ -61,6 +66,7 @@ pub struct Refresh {
     pub(crate) time_column: Option<String>,
     pub(crate) time_format: Option<TimeFormat>,
     pub(crate) check_interval: Option<Duration>,
+    pub(crate) cron: Option<Schedule>,
     pub(crate) max_jitter: Option<Duration>,
     pub(crate) sql: Option<String>,
     pub(crate) mode: RefreshMode,
@@ -68,6 +74,9 @@ pub struct Refresh {
     pub(crate) append_overlap: Option<Duration>,
     pub(crate) refresh_retry_enabled: bool,
     pub(crate) refresh_retry_max_attempts: Option<usize>,
+    pub(crate) primary_key: Vec<String>,
+    pub(crate) delete_check_interval: Option<Duration>,
+    pub(crate) restored_from_snapshot: bool,
 }
 
 impl Refresh {
@@ -98,6 +107,13 @@ impl Refresh {
         self
     }
 
+    /// Refreshes on a cron schedule (in UTC) instead of every `check_interval`.
+    #[must_use]
+    pub fn cron(mut self, cron: Schedule) -> Self {
+        self.cron = Some(cron);
+        self
+    }
+
     #[must_use]
     pub fn max_jitter(mut self, max_jitter: Duration) -> Self {
         self.max_jitter = Some(max_jitter);
@@ -122,6 +138,27 @@ impl Refresh {
         self
     }
 
+    /// The columns rows are upserted on in `RefreshMode::Upsert`.
+    #[must_use]
+    pub fn primary_key(mut self, primary_key: Vec<String>) -> Self {
+        self.primary_key = primary_key;
+        self
+    }
+
+    /// How often to remove rows that no longer exist in the source in `RefreshMode::Upsert`.
+    #[must_use]
+    pub fn delete_check_interval(mut self, delete_check_interval: Duration) -> Self {
+        self.delete_check_interval = Some(delete_check_interval);
+        self
+    }
+
+    /// The accelerator was restored from a snapshot, so a full refresh isn't needed on start.
+    #[must_use]
+    pub fn restored_from_snapshot(mut self, restored_from_snapshot: bool) -> Self {
+        self.restored_from_snapshot = restored_from_snapshot;
+        self
+    }
+
     #[must_use]
     pub fn with_retry(mut self, enabled: bool, max_attempts: Option<usize>) -> Self {
         self.refresh_retry_enabled = enabled;
@@ -228,6 +265,7 @@ impl Default for Refresh {
             time_column: None,
             time_format: None,
             check_interval: None,
+            cron: None,
             max_jitter: None,
             sql: None,
             mode: RefreshMode::Full,
@@ -235,6 +273,9 @@ impl Default for Refresh {
             append_overlap: None,
             refresh_retry_enabled: false,
             refresh_retry_max_attempts: None,
+            primary_key: vec![],
+            delete_check_interval: None,
+            restored_from_snapshot: false,
         }
     }
 }
@@ -253,6 +294,9 @@ pub struct Refresher {
     accelerator: Arc<dyn TableProvider>,
     cache_provider: Option<Arc<QueryResultsCacheProvider>>,
     refresh_task_runner: RefreshTaskRunner,
+    next_scheduled_refresh: Arc<std::sync::RwLock<Option<SystemTime>>>,
+    snapshotter: Option<Arc<Snapshotter>>,
+    data_updates: Arc<watch::Sender<()>>,
 }
 
 impl Refresher {
@@ -276,6 +320,9 @@ impl Refresher {
             accelerator,
             cache_provider: None,
             refresh_task_runner,
+            next_scheduled_refresh: Arc::new(std::sync::RwLock::new(None)),
+            snapshotter: None,
+            data_updates: Arc::new(watch::channel(()).0),
         }
     }
 
@@ -287,6 +334,44 @@ impl Refresher {
         self
     }
 
+    pub fn snapshotter(&mut self, snapshotter: Option<Arc<Snapshotter>>) -> &mut Self {
+        self.snapshotter = snapshotter;
+        self
+    }
+
+    /// Notifies the receiver every time data is written to the accelerator.
+    #[must_use]
+    pub fn subscribe_to_data_updates(&self) -> watch::Receiver<()> {
+        self.data_updates.subscribe()
+    }
+
+    /// When the next scheduled refresh will start, if one is scheduled.
+    #[must_use]
+    pub fn next_scheduled_refresh(&self) -> Option<SystemTime> {
+        self.next_scheduled_refresh
+            .read()
+            .map(|next| *next)
+            .unwrap_or_default()
+    }
+
+    /// The delay until the next refresh on `cron`, or after `check_interval` if there's no cron
+    /// schedule.
+    fn next_refresh_delay(
+        cron: Option<&Schedule>,
+        check_interval: Option<Duration>,
+        max_jitter: Option<Duration>,
+    ) -> Option<Duration> {
+        let period = match cron {
+            Some(cron) => {
+                let next = cron.upcoming(Utc).next()?;
+                (next - Utc::now()).to_std().unwrap_or_default()
+            }
+            None => check_interval?,
+        };
+
+        Some(Self::compute_delay(period, max_jitter))
+    }
+
     fn compute_delay(period: Duration, max_jitter: Option<Duration>) -> Duration {
         match max_jitter {
             Some(max_jitter) => {
@@ -327,15 +412,46 @@ impl Refresher {
 
         let cache_provider = self.cache_provider.clone();
 
+        let snapshotter = self.snapshotter.clone();
+
+        let data_updates = Arc::clone(&self.data_updates);
+
         let refresh_check_interval = self.refresh.read().await.check_interval;
+        let refresh_cron = self.refresh.read().await.cron.clone();
         let max_jitter = self.refresh.read().await.max_jitter;
+        let skip_initial_refresh = {
+            let refresh = self.refresh.read().await;
+            refresh.restored_from_snapshot && refresh.mode == RefreshMode::Full
+        };
+
+        let next_scheduled_refresh = Arc::clone(&self.next_scheduled_refresh);
+        let schedule_refresh = move |delay: Option<Duration>| {
+            if let Ok(mut next) = next_scheduled_refresh.write() {
+                *next = delay.map(|delay| SystemTime::now() + delay);
+            }
+            delay.map(sleep)
+        };
 
         Some(tokio::spawn(async move {
-            // first refresh is on start, thus duration is 0
-            let mut next_scheduled_refresh_timer = Some(sleep(Self::compute_delay(
-                Duration::from_secs(0),
-                max_jitter,
-            )));
+            let mut next_scheduled_refresh_timer = if skip_initial_refresh {
+                // an accelerator restored from a snapshot is ready as-is, so the first full
+                // refresh waits for the schedule
+                status::update_dataset(&dataset_name, status::ComponentStatus::Ready);
+                if let Some(sender) = ready_sender.take() {
+                    sender.send(()).ok();
+                }
+                schedule_refresh(Self::next_refresh_delay(
+                    refresh_cron.as_ref(),
+                    refresh_check_interval,
+                    max_jitter,
+                ))
+            } else {
+                // first refresh is on start, thus duration is 0
+                schedule_refresh(Some(Self::compute_delay(
+                    Duration::from_secs(0),
+                    max_jitter,
+                )))
+            };
 
             loop {
                 let scheduled_refresh_future: BoxFuture<()> =
@@ -347,12 +463,14 @@ impl Refresher {
                 select! {
                     () = scheduled_refresh_future => {
                         tracing::debug!("Starting scheduled refresh");
+                        next_scheduled_refresh_timer = schedule_refresh(None);
                         if let Err(err) = start_refresh.send(()).await {
                             tracing::error!("Failed to execute refresh: {err}");
                         }
                     },
                     _ = on_start_refresh_external.recv() => {
                         tracing::debug!("Received external trigger to start refresh");
+                        next_scheduled_refresh_timer = schedule_refresh(None);
 
                         if let Err(err) = start_refresh.send(()).await {
                             tracing::error!("Failed to execute refresh: {err}");
@@ -363,6 +481,7 @@ impl Refresher {
 
                         if let Ok(()) = res {
                             notify_refresh_done(&dataset_name, &refresh, &mut ready_sender).await;
+                            data_updates.send_replace(());
 
                             if let Some(cache_provider) = &cache_provider {
                                 if let Err(e) = cache_provider
@@ -372,14 +491,17 @@ impl Refresher {
                                     tracing::error!("Failed to invalidate cached results for dataset {}: {e}", &dataset_name.to_string());
                                 }
                             }
-                        }
 
-                        if let Some(refresh_check_interval) = refresh_check_interval {
-                            next_scheduled_refresh_timer = Some(sleep(Self::compute_delay(
-                                refresh_check_interval,
-                                max_jitter,
-                            )));
+                            if let Some(snapshotter) = &snapshotter {
+                                snapshotter.snapshot_if_due().await;
+                            }
                         }
+
+                        next_scheduled_refresh_timer = schedule_refresh(Self::next_refresh_delay(
+                            refresh_cron.as_ref(),
+                            refresh_check_interval,
+                            max_jitter,
+                        ));
                     }
                 }
             }
@@ -398,10 +520,17 @@ impl Refresher {
         ));
 
         let cache_provider = self.cache_provider.clone();
+        let snapshotter = self.snapshotter.clone();
+        let data_updates = Arc::clone(&self.data_updates);
 
         tokio::spawn(async move {
             if let Err(err) = refresh_task
-                .start_streaming_append(cache_provider, Some(ready_sender))
+                .start_streaming_append(
+                    cache_provider,
+                    snapshotter,
+                    data_updates,
+                    Some(ready_sender),
+                )
                 .await
             {
                 tracing::error!("Append refresh failed with error: {err}");
@@ -422,10 +551,18 @@ impl Refresher {
         ));
 
         let cache_provider = self.cache_provider.clone();
+        let snapshotter = self.snapshotter.clone();
+        let data_updates = Arc::clone(&self.data_updates);
 
         tokio::spawn(async move {
             if let Err(err) = refresh_task
-                .start_changes_stream(changes_stream, cache_provider, Some(ready_sender))
+                .start_changes_stream(
+                    changes_stream,
+                    cache_provider,
+                    snapshotter,
+                    data_updates,
+                    Some(ready_sender),
+                )
                 .await
             {
                 tracing::error!("Changes stream failed with error: {err}");
@@ -22,6 +22,7 @@ use arrow::{
 use arrow_schema::SchemaRef;
 use async_stream::stream;
 use cache::QueryResultsCacheProvider;
+use data_components::delete::get_deletion_provider;
 use datafusion_table_providers::util::retriable_error::{
     check_and_mark_retriable_error, is_retriable_error,
 };
@@ -36,6 +37,7 @@ use crate::datafusion::schema::BaseSchema;
 use crate::dataupdate::StreamingDataUpdateExecutionPlan;
 use crate::{
     component::dataset::acceleration::RefreshMode,
+    dataaccelerator::snapshot::Snapshotter,
     dataconnector::get_data,
     datafusion::{filter_converter::TimestampFilterConvert, schema, SPICE_RUNTIME_SCHEMA},
     dataupdate::{DataUpdate, StreamingDataUpdate, UpdateType},
@@ -51,13 +53,14 @@ use super::{metrics, UnableToCreateMemTableFromUpdateSnafu};
 use crate::component::dataset::TimeFormat;
 use std::time::UNIX_EPOCH;
 use std::{cmp::Ordering, sync::Arc, time::SystemTime};
-use tokio::sync::{oneshot, RwLock};
+use tokio::sync::{oneshot, watch, RwLock};
 
 use datafusion::{
+    common::ScalarValue,
     dataframe::DataFrame,
     datasource::TableProvider,
     error::DataFusionError,
-    logical_expr::{cast, col, Expr, Operator},
+    logical_expr::{cast, col, lit, utils::conjunction, Expr, Operator},
     physical_plan::{stream::RecordBatchStreamAdapter, ExecutionPlanProperties},
     prelude::SessionConfig,
     sql::TableReference,
@@ -68,6 +71,9 @@ use super::refresh::Refresh;
 
 mod changes;
 
+/// The number of primary keys deleted per delete operation when removing deleted rows.
+const DELETE_BATCH_SIZE: usize = 1000;
+
 #[derive(Debug, Clone, Default)]
 struct RefreshStat {
     pub num_rows: usize,
@@ -79,6 +85,7 @@ pub struct RefreshTask {
     federated: Arc<dyn TableProvider>,
     refresh: Arc<RwLock<Refresh>>,
     accelerator: Arc<dyn TableProvider>,
+    last_delete_check: RwLock<Option<SystemTime>>,
 }
 
 impl RefreshTask {
@@ -94,12 +101,15 @@ impl RefreshTask {
             federated,
             refresh,
             accelerator,
+            last_delete_check: RwLock::new(None),
         }
     }
 
     pub async fn start_streaming_append(
         &self,
         cache_provider: Option<Arc<QueryResultsCacheProvider>>,
+        snapshotter: Option<Arc<Snapshotter>>,
+        data_updates: Arc<watch::Sender<()>>,
         ready_sender: Option<oneshot::Sender<()>>,
     ) -> super::Result<()> {
         self.mark_dataset_status(status::ComponentStatus::Refreshing)
@@ -123,6 +133,7 @@ impl RefreshTask {
                         if let Some(ready_sender) = ready_sender.take() {
                             ready_sender.send(()).ok();
                         }
+                        data_updates.send_replace(());
 
                         if let Some(cache_provider) = &cache_provider {
                             if let Err(e) = cache_provider
@@ -135,6 +146,10 @@ impl RefreshTask {
                                 );
                             }
                         }
+
+                        if let Some(snapshotter) = &snapshotter {
+                            snapshotter.snapshot_if_due().await;
+                        }
                     }
                 }
                 Err(e) => {
@@ -202,6 +217,7 @@ impl RefreshTask {
                 }
                 RefreshMode::Full => &metrics::LOAD_DURATION_MS,
                 RefreshMode::Append => &metrics::APPEND_DURATION_MS,
+                RefreshMode::Upsert => &metrics::UPSERT_DURATION_MS,
                 RefreshMode::Changes => unreachable!("changes are handled upstream"),
             },
             vec![Key::from_static_str("dataset").string(dataset_name.to_string())],
@@ -215,6 +231,7 @@ impl RefreshTask {
             }
             RefreshMode::Full => self.get_full_update().await,
             RefreshMode::Append => self.get_incremental_append_update().await,
+            RefreshMode::Upsert => self.get_incremental_upsert_update().await,
             RefreshMode::Changes => unreachable!("changes are handled upstream"),
         };
 
@@ -229,7 +246,13 @@ impl RefreshTask {
         };
 
         self.write_streaming_data_update(Some(start_time), streaming_data_update)
-            .await
+            .await?;
+
+        if mode == RefreshMode::Upsert {
+            self.delete_rows_removed_from_source().await;
+        }
+
+        Ok(())
     }
 
     async fn write_streaming_data_update(
@@ -416,6 +439,123 @@ impl RefreshTask {
         }
     }
 
+    /// Gets the rows updated since the latest `time_column` value in the acceleration, or all rows
+    /// if there's no `time_column`. Unlike appends, rows that already exist aren't filtered out
+    /// since they're upserted.
+    async fn get_incremental_upsert_update(
+        &self,
+    ) -> Result<StreamingDataUpdate, RetryError<super::Error>> {
+        let timestamp = if self.refresh.read().await.time_column.is_some() {
+            self.timestamp_nanos_for_append_query()
+                .await
+                .map_err(RetryError::permanent)?
+        } else {
+            None
+        };
+
+        self.get_full_or_incremental_append_update(timestamp).await
+    }
+
+    /// Deletes the rows whose primary key no longer exists in the source, at most once every
+    /// `delete_check_interval`. Failures are logged and don't fail the refresh.
+    async fn delete_rows_removed_from_source(&self) {
+        let (primary_key, delete_check_interval) = {
+            let refresh = self.refresh.read().await;
+            (refresh.primary_key.clone(), refresh.delete_check_interval)
+        };
+        let Some(delete_check_interval) = delete_check_interval else {
+            return;
+        };
+        if primary_key.is_empty() {
+            return;
+        }
+
+        {
+            let mut last_delete_check = self.last_delete_check.write().await;
+            if last_delete_check
+                .and_then(|last| last.elapsed().ok())
+                .is_some_and(|elapsed| elapsed < delete_check_interval)
+            {
+                return;
+            }
+            *last_delete_check = Some(SystemTime::now());
+        }
+
+        let dataset_name = &self.dataset_name;
+        match self.delete_removed_keys(&primary_key).await {
+            Ok(0) => tracing::debug!("No deleted rows found for dataset {dataset_name}"),
+            Ok(num_rows) => tracing::info!(
+                "Removed {} rows deleted from the source of dataset {dataset_name}",
+                util::pretty_print_number(num_rows)
+            ),
+            Err(e) => {
+                tracing::warn!("Failed to remove deleted rows for dataset {dataset_name}: {e}");
+            }
+        }
+    }
+
+    async fn delete_removed_keys(&self, primary_key: &[String]) -> super::Result<usize> {
+        let deletion_provider = get_deletion_provider(Arc::clone(&self.accelerator))
+            .context(super::AcceleratedTableDoesntSupportDeleteSnafu)?;
+
+        let ctx = self.refresh_df_context();
+        let key_columns = primary_key
+            .iter()
+            .map(|column| col(format!(r#""{column}""#)))
+            .collect::<Vec<_>>();
+
+        let accelerated_keys = ctx
+            .read_table(Arc::new(EnsureSchema::new(Arc::clone(&self.accelerator))))
+            .and_then(|df| df.select(key_columns.clone()))
+            .context(super::UnableToScanTableProviderSnafu)?;
+        let source_keys = ctx
+            .read_table(Arc::clone(&self.federated))
+            .and_then(|df| df.select(key_columns))
+            .context(super::UnableToScanTableProviderSnafu)?;
+
+        let removed_keys = accelerated_keys
+            .except(source_keys)
+            .context(super::UnableToScanTableProviderSnafu)?
+            .collect()
+            .await
+            .context(super::UnableToScanTableProviderSnafu)?;
+
+        let mut key_filters = vec![];
+        for batch in &removed_keys {
+            for row in 0..batch.num_rows() {
+                let key_values = batch
+                    .columns()
+                    .iter()
+                    .map(|column| ScalarValue::try_from_array(column, row))
+                    .collect::<Result<Vec<_>, _>>()
+                    .context(super::FailedToWriteDataSnafu)?;
+
+                let key_filter = primary_key
+                    .iter()
+                    .zip(key_values)
+                    .map(|(column, value)| col(format!(r#""{column}""#)).eq(lit(value)));
+                key_filters.extend(conjunction(key_filter));
+            }
+        }
+
+        let num_rows = key_filters.len();
+        for key_filters in key_filters.chunks(DELETE_BATCH_SIZE) {
+            let Some(filter) = balanced_disjunction(key_filters) else {
+                continue;
+            };
+
+            let plan = deletion_provider
+                .delete_from(&ctx.state(), &[filter])
+                .await
+                .context(super::FailedToWriteDataSnafu)?;
+            collect(plan, ctx.task_ctx())
+                .await
+                .context(super::FailedToWriteDataSnafu)?;
+        }
+
+        Ok(num_rows)
+    }
+
     fn get_append_stream(
         &self,
     ) -> impl Stream<Item = super::Result<(Option<SystemTime>, DataUpdate)>> {
@@ -504,7 +644,8 @@ impl RefreshTask {
                         unreachable!("Refresh cannot be called when acceleration is disabled")
                     }
                     RefreshMode::Full => UpdateType::Overwrite,
-                    RefreshMode::Append => UpdateType::Append,
+                    // Upserted rows replace existing rows through the accelerator's `on_conflict`
+                    RefreshMode::Append | RefreshMode::Upsert => UpdateType::Append,
                     RefreshMode::Changes => unreachable!("changes are handled upstream"),
                 },
             )
@@ -819,6 +960,19 @@ fn filter_records(
     filter_record_batch(update_data, &predicates.into()).context(super::FailedToFilterUpdatesSnafu)
 }
 
+/// ORs `filters` as a balanced tree, since a chain of `DELETE_BATCH_SIZE` ORs is nested deeply
+/// enough to overflow the stack when it is planned or converted to SQL.
+fn balanced_disjunction(filters: &[Expr]) -> Option<Expr> {
+    match filters {
+        [] => None,
+        [filter] => Some(filter.clone()),
+        _ => {
+            let (left, right) = filters.split_at(filters.len() / 2);
+            Some(balanced_disjunction(left)?.or(balanced_disjunction(right)?))
+        }
+    }
+}
+
 fn retry_from_df_error(error: DataFusionError) -> RetryError<super::Error> {
     if is_retriable_error(&error) {
         return RetryError::transient(super::Error::UnableToGetDataFromConnector { source: error });
@@ -833,3 +987,200 @@ fn inner_err_from_retry(error: RetryError<super::Error>) -> super::Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::time::Duration;
+
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{Field, Schema};
+    use async_trait::async_trait;
+    use data_components::arrow::write::MemTable;
+    use data_components::delete::{DeletionTableProvider, DeletionTableProviderAdapter};
+    use datafusion::catalog::Session;
+    use datafusion::datasource::TableType;
+    use datafusion::physical_plan::ExecutionPlan;
+
+    use super::*;
+
+    /// An in-memory accelerator that counts its delete operations.
+    struct CountingDeletes {
+        table: MemTable,
+        deletes: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TableProvider for CountingDeletes {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn schema(&self) -> SchemaRef {
+            self.table.schema()
+        }
+
+        fn table_type(&self) -> TableType {
+            self.table.table_type()
+        }
+
+        async fn scan(
+            &self,
+            state: &dyn Session,
+            projection: Option<&Vec<usize>>,
+            filters: &[Expr],
+            limit: Option<usize>,
+        ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+            self.table.scan(state, projection, filters, limit).await
+        }
+    }
+
+    #[async_trait]
+    impl DeletionTableProvider for CountingDeletes {
+        async fn delete_from(
+            &self,
+            state: &dyn Session,
+            filters: &[Expr],
+        ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+            self.deletes.fetch_add(1, AtomicOrdering::SeqCst);
+            DeletionTableProvider::delete_from(&self.table, state, filters).await
+        }
+    }
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("time", DataType::Int64, false),
+        ]))
+    }
+
+    fn mem_table(rows: &[(i64, i64)]) -> MemTable {
+        let batch = RecordBatch::try_new(
+            schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(rows.iter().map(|(id, _)| *id))),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|(_, time)| *time),
+                )),
+            ],
+        )
+        .expect("valid batch");
+        MemTable::try_new(schema(), vec![vec![batch]]).expect("valid table")
+    }
+
+    async fn table_ids(table: Arc<dyn TableProvider>) -> Vec<i64> {
+        let batches = SessionContext::new()
+            .read_table(table)
+            .and_then(|df| df.select(vec![col("id")]))
+            .expect("valid query")
+            .collect()
+            .await
+            .expect("table scanned");
+        ids(&batches)
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<i64> {
+        let mut ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .expect("id is an Int64 column")
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_incremental_upsert_update() {
+        let federated = Arc::new(mem_table(&[(1, 10), (2, 20), (3, 30)]));
+        let accelerator = Arc::new(mem_table(&[(1, 10), (2, 20)]));
+
+        let refresh = Refresh::new(RefreshMode::Upsert)
+            .time_column("time".to_string())
+            .time_format(TimeFormat::UnixSeconds)
+            .append_overlap(Duration::from_secs(5));
+        let task = RefreshTask::new(
+            TableReference::bare("test"),
+            Arc::clone(&federated) as Arc<dyn TableProvider>,
+            Arc::new(RwLock::new(refresh)),
+            Arc::clone(&accelerator) as Arc<dyn TableProvider>,
+        );
+        let update = task
+            .get_incremental_upsert_update()
+            .await
+            .map_err(inner_err_from_retry)
+            .expect("update")
+            .collect_data()
+            .await
+            .expect("update data");
+        assert!(matches!(update.update_type, UpdateType::Append));
+        // Rows after the latest time minus the overlap, including the existing row 2
+        assert_eq!(ids(&update.data), vec![2, 3]);
+
+        let task = RefreshTask::new(
+            TableReference::bare("test"),
+            federated,
+            Arc::new(RwLock::new(Refresh::new(RefreshMode::Upsert))),
+            accelerator,
+        );
+        let update = task
+            .get_incremental_upsert_update()
+            .await
+            .map_err(inner_err_from_retry)
+            .expect("update")
+            .collect_data()
+            .await
+            .expect("update data");
+        assert_eq!(
+            ids(&update.data),
+            vec![1, 2, 3],
+            "all rows are upserted without a time column"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_removed_keys_in_batches() {
+        let num_rows = 2 * DELETE_BATCH_SIZE + 500;
+        let rows: Vec<(i64, i64)> = (0..i64::try_from(num_rows).expect("row count fits"))
+            .map(|id| (id, 0))
+            .collect();
+        let source_rows: Vec<(i64, i64)> =
+            rows.iter().copied().filter(|(id, _)| id % 2 == 0).collect();
+
+        let counting = Arc::new(CountingDeletes {
+            table: mem_table(&rows),
+            deletes: AtomicUsize::new(0),
+        });
+        let accelerator: Arc<dyn TableProvider> = Arc::new(DeletionTableProviderAdapter::new(
+            Arc::clone(&counting) as Arc<dyn DeletionTableProvider>,
+        ));
+        let task = RefreshTask::new(
+            TableReference::bare("test"),
+            Arc::new(mem_table(&source_rows)),
+            Arc::new(RwLock::new(
+                Refresh::new(RefreshMode::Upsert).primary_key(vec!["id".to_string()]),
+            )),
+            Arc::clone(&accelerator),
+        );
+
+        let deleted = task
+            .delete_removed_keys(&["id".to_string()])
+            .await
+            .expect("removed keys deleted");
+        assert_eq!(deleted, num_rows / 2);
+        assert_eq!(
+            counting.deletes.load(AtomicOrdering::SeqCst),
+            deleted.div_ceil(DELETE_BATCH_SIZE)
+        );
+
+        let expected: Vec<i64> = source_rows.iter().map(|(id, _)| *id).collect();
+        assert_eq!(table_ids(accelerator).await, expected);
+    }
+}
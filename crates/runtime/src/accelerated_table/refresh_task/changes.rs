@@ -15,7 +15,9 @@ limitations under the License.
 */
 
 use super::RefreshTask;
-use crate::{dataupdate::StreamingDataUpdateExecutionPlan, status};
+use crate::{
+    dataaccelerator::snapshot::Snapshotter, dataupdate::StreamingDataUpdateExecutionPlan, status,
+};
 use arrow::array::{Int32Array, Int64Array, RecordBatch, StringArray};
 use arrow::datatypes::DataType;
 use cache::QueryResultsCacheProvider;
@@ -28,7 +30,15 @@ use datafusion::{execution::context::SessionContext, physical_plan::collect};
 use futures::{stream, StreamExt};
 use snafu::{OptionExt, ResultExt};
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use util::fibonacci_backoff::FibonacciBackoffBuilder;
+use util::{retry_notify, RetryError};
+
+/// How long to wait before writing a change again once its retries are exhausted. The stream is
+/// kept alive and no later change is written, so that the source resumes where it stopped once the
+/// accelerator accepts writes again.
+const CHANGE_WRITE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Extracts the primary key value from the data, as a tuple of (String, Expr).
 ///
@@ -62,6 +72,8 @@ impl RefreshTask {
         &self,
         mut changes_stream: ChangesStream,
         cache_provider: Option<Arc<QueryResultsCacheProvider>>,
+        snapshotter: Option<Arc<Snapshotter>>,
+        data_updates: Arc<watch::Sender<()>>,
         ready_sender: Option<oneshot::Sender<()>>,
     ) -> crate::accelerated_table::Result<()> {
         self.mark_dataset_status(status::ComponentStatus::Refreshing)
@@ -74,37 +86,54 @@ impl RefreshTask {
         while let Some(update) = changes_stream.next().await {
             match update {
                 Ok(change_envelope) => {
-                    match self
-                        .write_change(change_envelope.change_batch.clone())
+                    // A change is only committed once it is written, and no later changes are
+                    // written or committed before it, so that the source doesn't acknowledge
+                    // changes that were never applied.
+                    let mut failed = false;
+                    while let Err(e) = self
+                        .write_change_with_retry(&change_envelope.change_batch)
                         .await
                     {
-                        Ok(()) => {
-                            if let Some(ready_sender) = ready_sender.take() {
-                                ready_sender.send(()).ok();
-                            }
-
-                            if let Err(e) = change_envelope.commit() {
-                                tracing::debug!("Failed to commit CDC change envelope: {e}");
-                            }
-
-                            if let Some(cache_provider) = &cache_provider {
-                                if let Err(e) = cache_provider
-                                    .invalidate_for_table(dataset_name.clone())
-                                    .await
-                                {
-                                    tracing::error!(
-                                        "Failed to invalidate cached results for dataset {}: {e}",
-                                        &dataset_name.to_string()
-                                    );
-                                }
-                            }
-                        }
-                        Err(e) => {
+                        if !failed {
                             self.mark_dataset_status(status::ComponentStatus::Error)
                                 .await;
-                            tracing::error!("Error writing change for {dataset_name}: {e}");
+                            failed = true;
+                        }
+                        tracing::error!(
+                            "Error writing change for {dataset_name}, retrying in {}s: {e}",
+                            CHANGE_WRITE_RETRY_INTERVAL.as_secs()
+                        );
+                        tokio::time::sleep(CHANGE_WRITE_RETRY_INTERVAL).await;
+                    }
+                    if failed {
+                        self.mark_dataset_status(status::ComponentStatus::Refreshing)
+                            .await;
+                    }
+
+                    if let Some(ready_sender) = ready_sender.take() {
+                        ready_sender.send(()).ok();
+                    }
+                    data_updates.send_replace(());
+
+                    if let Err(e) = change_envelope.commit() {
+                        tracing::error!("Failed to commit CDC change envelope: {e}");
+                    }
+
+                    if let Some(cache_provider) = &cache_provider {
+                        if let Err(e) = cache_provider
+                            .invalidate_for_table(dataset_name.clone())
+                            .await
+                        {
+                            tracing::error!(
+                                "Failed to invalidate cached results for dataset {}: {e}",
+                                &dataset_name.to_string()
+                            );
                         }
                     }
+
+                    if let Some(snapshotter) = &snapshotter {
+                        snapshotter.snapshot_if_due().await;
+                    }
                 }
                 Err(e) => {
                     tracing::error!("Changes stream error for {dataset_name}: {e}");
@@ -119,6 +148,44 @@ impl RefreshTask {
         Ok(())
     }
 
+    /// Writes a change, retrying with the refresh retry settings of the dataset.
+    async fn write_change_with_retry(
+        &self,
+        change_batch: &ChangeBatch,
+    ) -> crate::accelerated_table::Result<()> {
+        let (refresh_retry_enabled, refresh_retry_max_attempts) = {
+            let refresh = self.refresh.read().await;
+            (
+                refresh.refresh_retry_enabled,
+                refresh.refresh_retry_max_attempts,
+            )
+        };
+
+        let retry_strategy = FibonacciBackoffBuilder::new()
+            .max_retries(if refresh_retry_enabled {
+                refresh_retry_max_attempts
+            } else {
+                Some(0)
+            })
+            .build();
+
+        let dataset_name = &self.dataset_name;
+        retry_notify(
+            retry_strategy,
+            || async {
+                self.write_change(change_batch.clone())
+                    .await
+                    .map_err(RetryError::transient)
+            },
+            |e, backoff: Duration| {
+                tracing::warn!(
+                    "Failed to write change for {dataset_name}, retrying in {backoff:?}: {e}"
+                );
+            },
+        )
+        .await
+    }
+
     async fn write_change(
         &self,
         change_batch: ChangeBatch,
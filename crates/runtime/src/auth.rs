@@ -0,0 +1,400 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose, Engine};
+use dashmap::DashMap;
+use secrecy::{ExposeSecret, SecretString};
+use snafu::prelude::*;
+use spicepod::component::runtime::{ApiKeyScope, AuthConfig};
+use uuid::Uuid;
+
+use crate::secrets::{ParamStr, Secrets};
+
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to parse auth token_ttl '{token_ttl}': {source}"))]
+    UnableToParseTokenTtl {
+        token_ttl: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("Auth is enabled, but no API keys or basic auth users are configured"))]
+    NoCredentialsConfigured,
+
+    #[snafu(display("An API key configured for auth resolved to an empty value"))]
+    EmptyApiKey,
+
+    #[snafu(display("The password for basic auth user '{username}' resolved to an empty value"))]
+    EmptyPassword { username: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The credentials presented by a client in an `authorization` header.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Credentials {
+    /// `Bearer <token>` - either an API key or a token issued by [`Auth::issue_token`].
+    Bearer(String),
+    /// `Basic <base64(username:password)>`
+    Basic { username: String, password: String },
+}
+
+impl Credentials {
+    /// Parses the value of an `authorization` header.
+    #[must_use]
+    pub fn from_header(value: &str) -> Option<Self> {
+        let (scheme, rest) = value.trim().split_once(' ')?;
+        let rest = rest.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(Credentials::Bearer(rest.to_string()));
+        }
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = general_purpose::STANDARD.decode(rest).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            return Some(Credentials::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// What an authenticated client is allowed to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    scopes: HashSet<ApiKeyScope>,
+}
+
+impl Grant {
+    #[must_use]
+    pub fn new(scopes: impl IntoIterator<Item = ApiKeyScope>) -> Self {
+        Self {
+            scopes: scopes.into_iter().collect(),
+        }
+    }
+
+    /// A grant for every scope, used for basic auth users and API keys without explicit scopes.
+    #[must_use]
+    pub fn all() -> Self {
+        Self::new([
+            ApiKeyScope::Sql,
+            ApiKeyScope::Refresh,
+            ApiKeyScope::Inference,
+            ApiKeyScope::Write,
+        ])
+    }
+
+    #[must_use]
+    pub fn allows(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+pub struct ApiKey {
+    key: SecretString,
+    grant: Grant,
+}
+
+impl ApiKey {
+    #[must_use]
+    pub fn new(key: SecretString, grant: Grant) -> Self {
+        Self { key, grant }
+    }
+}
+
+struct IssuedToken {
+    expires_at: Instant,
+    grant: Grant,
+}
+
+/// Validates client credentials against the API keys and basic auth users configured in the spicepod,
+/// and tracks the bearer tokens handed out by the Flight handshake.
+pub struct Auth {
+    api_keys: Vec<ApiKey>,
+    basic_auth: HashMap<String, SecretString>,
+    token_ttl: Duration,
+    tokens: DashMap<String, IssuedToken>,
+}
+
+impl Auth {
+    /// Builds the runtime auth from the spicepod configuration, resolving any secret references.
+    ///
+    /// Returns `None` if auth isn't enabled.
+    pub async fn try_from_config(config: &AuthConfig, secrets: &Secrets) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let token_ttl = match &config.token_ttl {
+            Some(token_ttl) => {
+                fundu::parse_duration(token_ttl).context(UnableToParseTokenTtlSnafu {
+                    token_ttl: token_ttl.clone(),
+                })?
+            }
+            None => DEFAULT_TOKEN_TTL,
+        };
+
+        let mut api_keys = Vec::with_capacity(config.api_keys.len());
+        for api_key in &config.api_keys {
+            let key = secrets
+                .inject_secrets("api_key", ParamStr(&api_key.key))
+                .await;
+            ensure!(!key.expose_secret().is_empty(), EmptyApiKeySnafu);
+            let grant = api_key
+                .scopes
+                .as_ref()
+                .map_or_else(Grant::all, |scopes| Grant::new(scopes.iter().copied()));
+            api_keys.push(ApiKey::new(key, grant));
+        }
+
+        let mut basic_auth = HashMap::with_capacity(config.basic_auth.len());
+        for user in &config.basic_auth {
+            let password = secrets
+                .inject_secrets("password", ParamStr(&user.password))
+                .await;
+            ensure!(
+                !password.expose_secret().is_empty(),
+                EmptyPasswordSnafu {
+                    username: user.username.clone()
+                }
+            );
+            basic_auth.insert(user.username.clone(), password);
+        }
+
+        ensure!(
+            !api_keys.is_empty() || !basic_auth.is_empty(),
+            NoCredentialsConfiguredSnafu
+        );
+
+        Ok(Some(Self::new(api_keys, basic_auth, token_ttl)))
+    }
+
+    #[must_use]
+    pub fn new(
+        api_keys: Vec<ApiKey>,
+        basic_auth: HashMap<String, SecretString>,
+        token_ttl: Duration,
+    ) -> Self {
+        Self {
+            api_keys,
+            basic_auth,
+            token_ttl,
+            tokens: DashMap::new(),
+        }
+    }
+
+    /// Checks the credentials used to start a session, i.e. in the Flight handshake.
+    ///
+    /// Bearer credentials must be a configured API key, a previously issued token is not accepted.
+    #[must_use]
+    pub fn validate_login(&self, credentials: &Credentials) -> Option<Grant> {
+        match credentials {
+            Credentials::Bearer(key) => self.api_key_grant(key),
+            Credentials::Basic { username, password } => self.basic_user_grant(username, password),
+        }
+    }
+
+    /// Checks the credentials attached to a request. Bearer credentials can either be an API key
+    /// or an unexpired token issued by [`Auth::issue_token`].
+    #[must_use]
+    pub fn validate_request(&self, credentials: &Credentials) -> Option<Grant> {
+        match credentials {
+            Credentials::Bearer(token) => self
+                .token_grant(token)
+                .or_else(|| self.api_key_grant(token)),
+            Credentials::Basic { username, password } => self.basic_user_grant(username, password),
+        }
+    }
+
+    /// Issues a new bearer token carrying `grant` that expires after the configured `token_ttl`.
+    #[must_use]
+    pub fn issue_token(&self, grant: Grant) -> String {
+        let now = Instant::now();
+        self.tokens.retain(|_, issued| issued.expires_at > now);
+
+        let token = Uuid::new_v4().to_string();
+        self.tokens.insert(
+            token.clone(),
+            IssuedToken {
+                expires_at: now + self.token_ttl,
+                grant,
+            },
+        );
+        token
+    }
+
+    fn token_grant(&self, token: &str) -> Option<Grant> {
+        let (expires_at, grant) = self
+            .tokens
+            .get(token)
+            .map(|issued| (issued.expires_at, issued.grant.clone()))?;
+
+        if expires_at > Instant::now() {
+            return Some(grant);
+        }
+
+        self.tokens.remove(token);
+        None
+    }
+
+    fn api_key_grant(&self, key: &str) -> Option<Grant> {
+        // Check every key so the time taken doesn't reveal which key matched.
+        self.api_keys.iter().fold(None, |matched, api_key| {
+            if constant_time_eq(api_key.key.expose_secret().as_bytes(), key.as_bytes()) {
+                Some(api_key.grant.clone())
+            } else {
+                matched
+            }
+        })
+    }
+
+    fn basic_user_grant(&self, username: &str, password: &str) -> Option<Grant> {
+        self.basic_auth
+            .get(username)
+            .filter(|expected| {
+                constant_time_eq(expected.expose_secret().as_bytes(), password.as_bytes())
+            })
+            .map(|_| Grant::all())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_auth(token_ttl: Duration) -> Auth {
+        Auth::new(
+            vec![
+                ApiKey::new(SecretString::new("my-api-key".to_string()), Grant::all()),
+                ApiKey::new(
+                    SecretString::new("my-sql-key".to_string()),
+                    Grant::new([ApiKeyScope::Sql]),
+                ),
+            ],
+            HashMap::from([(
+                "spice".to_string(),
+                SecretString::new("hunter2".to_string()),
+            )]),
+            token_ttl,
+        )
+    }
+
+    #[test]
+    fn test_credentials_from_header() {
+        assert_eq!(
+            Credentials::from_header("Bearer abc"),
+            Some(Credentials::Bearer("abc".to_string()))
+        );
+        assert_eq!(
+            Credentials::from_header(&format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode("spice:hunter2")
+            )),
+            Some(Credentials::Basic {
+                username: "spice".to_string(),
+                password: "hunter2".to_string()
+            })
+        );
+        assert_eq!(Credentials::from_header("Digest abc"), None);
+        assert_eq!(Credentials::from_header("Basic not-base64"), None);
+    }
+
+    #[test]
+    fn test_validate_login() {
+        let auth = test_auth(DEFAULT_TOKEN_TTL);
+
+        assert_eq!(
+            auth.validate_login(&Credentials::Bearer("my-api-key".to_string())),
+            Some(Grant::all())
+        );
+        assert_eq!(
+            auth.validate_login(&Credentials::Bearer("wrong".to_string())),
+            None
+        );
+        assert_eq!(
+            auth.validate_login(&Credentials::Basic {
+                username: "spice".to_string(),
+                password: "hunter2".to_string()
+            }),
+            Some(Grant::all())
+        );
+        assert_eq!(
+            auth.validate_login(&Credentials::Basic {
+                username: "spice".to_string(),
+                password: "hunter3".to_string()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        let auth = test_auth(DEFAULT_TOKEN_TTL);
+
+        let grant = auth
+            .validate_request(&Credentials::Bearer("my-sql-key".to_string()))
+            .expect("valid api key");
+        assert!(grant.allows(ApiKeyScope::Sql));
+        assert!(!grant.allows(ApiKeyScope::Refresh));
+        assert!(!grant.allows(ApiKeyScope::Inference));
+        assert!(!grant.allows(ApiKeyScope::Write));
+    }
+
+    #[test]
+    fn test_issued_token() {
+        let auth = test_auth(DEFAULT_TOKEN_TTL);
+        let token = auth.issue_token(Grant::new([ApiKeyScope::Sql]));
+
+        assert_eq!(
+            auth.validate_request(&Credentials::Bearer(token.clone())),
+            Some(Grant::new([ApiKeyScope::Sql]))
+        );
+        // An issued token can't be used to start a new session.
+        assert_eq!(auth.validate_login(&Credentials::Bearer(token)), None);
+        assert_eq!(
+            auth.validate_request(&Credentials::Bearer(Uuid::new_v4().to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let auth = test_auth(Duration::ZERO);
+        let token = auth.issue_token(Grant::all());
+
+        assert_eq!(auth.validate_request(&Credentials::Bearer(token)), None);
+    }
+}
@@ -23,6 +23,7 @@ use crate::{
     dataaccelerator, dataconnector,
     datafusion::DataFusion,
     datasets_health_monitor::DatasetsHealthMonitor,
+    embeddings::search_function::{VectorSearchTableFunction, VECTOR_SEARCH_FUNCTION_NAME},
     extension::{Extension, ExtensionFactory},
     metrics, podswatcher,
     secrets::{self, Secrets},
@@ -151,6 +152,15 @@ impl RuntimeBuilder {
             prometheus_registry: self.prometheus_registry,
         };
 
+        rt.df.ctx.register_udtf(
+            VECTOR_SEARCH_FUNCTION_NAME,
+            Arc::new(VectorSearchTableFunction::new(
+                Arc::downgrade(&rt.df),
+                Arc::clone(&rt.embeds),
+                Arc::clone(&rt.app),
+            )),
+        );
+
         let mut extensions: HashMap<String, Arc<dyn Extension>> = HashMap::new();
         for factory in self.extensions {
             let mut extension = factory.create();
@@ -61,6 +61,21 @@ pub enum Error {
         field: String,
         source: fundu::ParseError,
     },
+
+    #[snafu(display("Error parsing snapshots location '{location}': {source}"))]
+    UnableToParseSnapshotsLocation {
+        location: String,
+        source: url::ParseError,
+    },
+
+    #[snafu(display("Invalid refresh_mode: upsert configuration, {reason}"))]
+    InvalidUpsertRefresh { reason: String },
+
+    #[snafu(display("Error parsing refresh_cron '{cron}' as a cron expression: {source}"))]
+    UnableToParseRefreshCron {
+        cron: String,
+        source: cron::error::Error,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -306,6 +321,13 @@ impl Dataset {
         None
     }
 
+    #[must_use]
+    pub fn refresh_cron(&self) -> Option<cron::Schedule> {
+        self.acceleration
+            .as_ref()
+            .and_then(|acceleration| acceleration.refresh_cron.clone())
+    }
+
     #[must_use]
     pub fn refresh_max_jitter(&self) -> Option<Duration> {
         if let Some(acceleration) = &self.acceleration {
@@ -459,11 +481,17 @@ pub mod replication {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
+
+    use chrono::{Datelike, Weekday};
 
     use datafusion_table_providers::util::column_reference::ColumnReference;
 
-    use super::acceleration::{Acceleration, IndexType};
+    use spicepod::component::dataset::acceleration as spicepod_acceleration;
+
+    use super::acceleration::{
+        parse_cron_schedule, Acceleration, IndexType, OnConflictBehavior, RefreshMode,
+    };
 
     #[test]
     fn test_indexes_roundtrip() {
@@ -535,4 +563,128 @@ mod tests {
             "The column reference \"(foo,bar\" is missing a closing parenthensis."
         );
     }
+
+    fn upsert_acceleration(
+        engine: &str,
+        primary_key: Option<&str>,
+        on_conflict: &[(&str, spicepod_acceleration::OnConflictBehavior)],
+    ) -> spicepod_acceleration::Acceleration {
+        spicepod_acceleration::Acceleration {
+            enabled: true,
+            engine: Some(engine.to_string()),
+            refresh_mode: Some(spicepod_acceleration::RefreshMode::Upsert),
+            primary_key: primary_key.map(ToString::to_string),
+            on_conflict: on_conflict
+                .iter()
+                .map(|(column, behavior)| ((*column).to_string(), behavior.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_upsert_refresh_validation() {
+        let acceleration = Acceleration::try_from(upsert_acceleration("duckdb", Some("id"), &[]))
+            .expect("valid upsert acceleration");
+        assert_eq!(acceleration.refresh_mode, Some(RefreshMode::Upsert));
+        assert_eq!(
+            acceleration.on_conflict,
+            HashMap::from([(
+                ColumnReference::try_from("id").expect("valid column"),
+                OnConflictBehavior::Upsert
+            )]),
+            "rows are upserted on the primary key by default"
+        );
+
+        Acceleration::try_from(upsert_acceleration(
+            "sqlite",
+            Some("id"),
+            &[("id", spicepod_acceleration::OnConflictBehavior::Upsert)],
+        ))
+        .expect("explicit upsert on_conflict is valid");
+
+        let invalid = [
+            (
+                upsert_acceleration("arrow", Some("id"), &[]),
+                "arrow engine",
+            ),
+            (upsert_acceleration("duckdb", None, &[]), "primary_key"),
+            (
+                upsert_acceleration(
+                    "duckdb",
+                    Some("id"),
+                    &[("id", spicepod_acceleration::OnConflictBehavior::Drop)],
+                ),
+                "on_conflict",
+            ),
+        ];
+        for (acceleration, reason) in invalid {
+            let err =
+                Acceleration::try_from(acceleration).expect_err("invalid upsert acceleration");
+            assert!(
+                err.to_string().contains(reason),
+                "expected {reason} in error: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cron_schedule() {
+        let schedule = parse_cron_schedule("30 6 * * *").expect("valid 5-field cron expression");
+        assert_eq!(
+            schedule,
+            parse_cron_schedule("0 30 6 * * *").expect("valid 6-field cron expression")
+        );
+
+        let next = schedule
+            .upcoming(chrono::Utc)
+            .next()
+            .expect("schedule has an upcoming time");
+        assert_eq!(next.format("%H:%M:%S").to_string(), "06:30:00");
+
+        parse_cron_schedule("every morning").expect_err("invalid cron expression");
+    }
+
+    fn upcoming_weekdays(cron: &str) -> HashSet<Weekday> {
+        parse_cron_schedule(cron)
+            .expect("valid cron expression")
+            .upcoming(chrono::Utc)
+            .take(14)
+            .map(|time| time.weekday())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_cron_schedule_days_of_week() {
+        assert_eq!(
+            upcoming_weekdays("0 9 * * 1-5"),
+            HashSet::from([
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri
+            ])
+        );
+        assert_eq!(
+            upcoming_weekdays("0 9 * * 0"),
+            HashSet::from([Weekday::Sun])
+        );
+        assert_eq!(
+            upcoming_weekdays("0 9 * * 7"),
+            HashSet::from([Weekday::Sun])
+        );
+        assert_eq!(
+            upcoming_weekdays("0 9 * * sun"),
+            HashSet::from([Weekday::Sun])
+        );
+        assert_eq!(
+            upcoming_weekdays("0 9 * * 5-7"),
+            HashSet::from([Weekday::Fri, Weekday::Sat, Weekday::Sun])
+        );
+        assert_eq!(
+            upcoming_weekdays("0 0 9 * * 1,3"),
+            HashSet::from([Weekday::Mon, Weekday::Wed])
+        );
+    }
 }
@@ -14,9 +14,11 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use cron::Schedule;
 use datafusion_table_providers::util::column_reference::ColumnReference;
 use spicepod::component::{dataset::acceleration as spicepod_acceleration, params::Params};
-use std::{collections::HashMap, fmt::Display, time::Duration};
+use std::{collections::HashMap, fmt::Display, str::FromStr, time::Duration};
+use url::Url;
 
 pub mod constraints;
 pub mod on_conflict;
@@ -27,6 +29,7 @@ pub enum RefreshMode {
     Full,
     Append,
     Changes,
+    Upsert,
 }
 
 impl From<spicepod_acceleration::RefreshMode> for RefreshMode {
@@ -35,6 +38,7 @@ impl From<spicepod_acceleration::RefreshMode> for RefreshMode {
             spicepod_acceleration::RefreshMode::Full => RefreshMode::Full,
             spicepod_acceleration::RefreshMode::Append => RefreshMode::Append,
             spicepod_acceleration::RefreshMode::Changes => RefreshMode::Changes,
+            spicepod_acceleration::RefreshMode::Upsert => RefreshMode::Upsert,
         }
     }
 }
@@ -218,6 +222,8 @@ pub struct Acceleration {
 
     pub refresh_check_interval: Option<Duration>,
 
+    pub refresh_cron: Option<Schedule>,
+
     pub refresh_sql: Option<String>,
 
     pub refresh_data_window: Option<String>,
@@ -232,6 +238,8 @@ pub struct Acceleration {
 
     pub refresh_jitter_max: Option<Duration>,
 
+    pub refresh_delete_check_interval: Option<Duration>,
+
     pub params: HashMap<String, String>,
 
     pub retention_period: Option<String>,
@@ -249,6 +257,22 @@ pub struct Acceleration {
     pub on_conflict: HashMap<ColumnReference, OnConflictBehavior>,
 
     pub disable_query_push_down: bool,
+
+    pub snapshots: Option<Snapshots>,
+}
+
+/// The number of snapshots kept when `retain` isn't set.
+const DEFAULT_SNAPSHOTS_RETAIN: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshots {
+    pub location: Url,
+
+    pub interval: Option<Duration>,
+
+    pub retain: usize,
+
+    pub bootstrap: bool,
 }
 
 impl TryFrom<spicepod_acceleration::Acceleration> for Acceleration {
@@ -300,6 +324,36 @@ impl TryFrom<spicepod_acceleration::Acceleration> for Acceleration {
 
         let engine = Engine::try_from(acceleration.engine.unwrap_or_else(|| "arrow".to_string()))?;
 
+        let refresh_mode = acceleration.refresh_mode.map(RefreshMode::from);
+        if refresh_mode == Some(RefreshMode::Upsert) {
+            let invalid_upsert_refresh = |reason: &str| crate::Error::InvalidSpicepodDataset {
+                source: super::Error::InvalidUpsertRefresh {
+                    reason: reason.to_string(),
+                },
+            };
+
+            if engine == Engine::Arrow {
+                return Err(invalid_upsert_refresh(
+                    "the arrow engine doesn't support upserts, use duckdb, sqlite or postgres",
+                ));
+            }
+            let Some(primary_key) = &primary_key else {
+                return Err(invalid_upsert_refresh(
+                    "a primary_key is required to upsert refreshed rows on",
+                ));
+            };
+            if on_conflict.is_empty() {
+                on_conflict.insert(primary_key.clone(), OnConflictBehavior::Upsert);
+            } else if on_conflict
+                .values()
+                .any(|behavior| *behavior != OnConflictBehavior::Upsert)
+            {
+                return Err(invalid_upsert_refresh(
+                    "on_conflict must be set to upsert, or left unset",
+                ));
+            }
+        }
+
         if engine == Engine::Arrow && !indexes.is_empty() {
             tracing::warn!(
                 "Indexes are not supported for Arrow engine acceleration. Ignoring indexes."
@@ -334,12 +388,56 @@ impl TryFrom<spicepod_acceleration::Acceleration> for Acceleration {
         let refresh_jitter_max =
             try_parse_duration("refresh_jitter_max", acceleration.refresh_jitter_max)?;
 
+        let snapshots = match acceleration.snapshots {
+            Some(snapshots) => {
+                if engine == Engine::Arrow || engine == Engine::PostgreSQL {
+                    tracing::warn!(
+                        "Snapshots are only supported for file-mode duckdb and sqlite acceleration. Ignoring snapshots."
+                    );
+                    None
+                } else {
+                    let location = Url::parse(&snapshots.location).map_err(|e| {
+                        crate::Error::InvalidSpicepodDataset {
+                            source: super::Error::UnableToParseSnapshotsLocation {
+                                location: snapshots.location.clone(),
+                                source: e,
+                            },
+                        }
+                    })?;
+
+                    Some(Snapshots {
+                        location,
+                        interval: try_parse_duration("snapshots.interval", snapshots.interval)?,
+                        retain: snapshots.retain.unwrap_or(DEFAULT_SNAPSHOTS_RETAIN).max(1),
+                        bootstrap: snapshots.bootstrap,
+                    })
+                }
+            }
+            None => None,
+        };
+
+        let refresh_cron = match acceleration.refresh_cron {
+            Some(cron) => Some(parse_cron_schedule(&cron).map_err(|e| {
+                crate::Error::InvalidSpicepodDataset {
+                    source: super::Error::UnableToParseRefreshCron { cron, source: e },
+                }
+            })?),
+            None => None,
+        };
+
+        if refresh_cron.is_some() && refresh_check_interval.is_some() {
+            tracing::warn!(
+                "Both refresh_cron and refresh_check_interval are set. Ignoring refresh_check_interval."
+            );
+        }
+
         Ok(Acceleration {
             enabled: acceleration.enabled,
             mode: Mode::from(acceleration.mode),
             engine,
-            refresh_mode: acceleration.refresh_mode.map(RefreshMode::from),
+            refresh_mode,
             refresh_check_interval,
+            refresh_cron,
             refresh_sql: acceleration.refresh_sql,
             refresh_data_window: acceleration.refresh_data_window,
             refresh_append_overlap: try_parse_duration(
@@ -350,6 +448,10 @@ impl TryFrom<spicepod_acceleration::Acceleration> for Acceleration {
             refresh_retry_max_attempts: acceleration.refresh_retry_max_attempts,
             refresh_jitter_max,
             refresh_jitter_enabled: acceleration.refresh_jitter_enabled,
+            refresh_delete_check_interval: try_parse_duration(
+                "refresh_delete_check_interval",
+                acceleration.refresh_delete_check_interval,
+            )?,
             params: params
                 .as_ref()
                 .map(Params::as_string_map)
@@ -362,10 +464,75 @@ impl TryFrom<spicepod_acceleration::Acceleration> for Acceleration {
             indexes,
             primary_key,
             on_conflict,
+            snapshots,
         })
     }
 }
 
+/// Parses a cron expression. Standard 5-field expressions (minute, hour, day of month, month, day
+/// of week) are accepted as well as the 6 and 7-field forms with seconds and years.
+///
+/// Numeric days of the week are numbered as in standard cron, from 0 (Sunday) to 6 (Saturday),
+/// with 7 also being Sunday.
+pub(crate) fn parse_cron_schedule(cron: &str) -> Result<Schedule, cron::error::Error> {
+    let mut fields = cron
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if fields.len() == 5 {
+        fields.insert(0, "0".to_string());
+    }
+    if let Some(day_of_week) = fields.get_mut(5) {
+        *day_of_week = to_cron_days_of_week(day_of_week);
+    }
+
+    Schedule::from_str(&fields.join(" "))
+}
+
+/// Translates the numeric days of the week of a cron field to the numbering of the `cron` crate,
+/// from 1 (Sunday) to 7 (Saturday). Named days (i.e. `mon-fri`) are kept, as are values that
+/// can't be translated, for the `cron` crate to report.
+fn to_cron_days_of_week(field: &str) -> String {
+    field
+        .split(',')
+        .map(|item| to_cron_days(item).unwrap_or_else(|| item.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn to_cron_days(item: &str) -> Option<String> {
+    if item == "*" || item == "?" || item.chars().any(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, Some(step.parse::<usize>().ok().filter(|s| *s > 0)?)),
+        None => (item, None),
+    };
+    let (start, end) = match (range, range.split_once('-')) {
+        ("*", _) => (0, 6),
+        (_, Some((start, end))) => (start.parse::<u8>().ok()?, end.parse::<u8>().ok()?),
+        // `n/step` is from `n` to the last day of the week
+        (start, None) if step.is_some() => (start.parse::<u8>().ok()?, 6),
+        (day, None) => (day.parse::<u8>().ok()?, day.parse::<u8>().ok()?),
+    };
+    if start > end || end > 7 {
+        return None;
+    }
+
+    let days = (start..=end)
+        .step_by(step.unwrap_or(1))
+        .map(|day| day % 7 + 1)
+        .collect::<std::collections::BTreeSet<_>>();
+
+    Some(
+        days.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
 impl Default for Acceleration {
     fn default() -> Self {
         Self {
@@ -374,6 +541,7 @@ impl Default for Acceleration {
             engine: Engine::default(),
             refresh_mode: None,
             refresh_check_interval: None,
+            refresh_cron: None,
             refresh_sql: None,
             refresh_data_window: None,
             refresh_append_overlap: None,
@@ -381,6 +549,7 @@ impl Default for Acceleration {
             refresh_retry_max_attempts: None,
             refresh_jitter_enabled: false,
             refresh_jitter_max: None,
+            refresh_delete_check_interval: None,
             params: HashMap::default(),
             retention_period: None,
             retention_check_interval: None,
@@ -390,6 +559,7 @@ impl Default for Acceleration {
             primary_key: None,
             on_conflict: HashMap::default(),
             disable_query_push_down: false,
+            snapshots: None,
         }
     }
 }
@@ -55,6 +55,7 @@ pub mod postgres;
 pub mod sqlite;
 
 pub mod metadata;
+pub mod snapshot;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -150,6 +151,16 @@ pub trait DataAccelerator: Send + Sync {
         None
     }
 
+    /// For file-based accelerators, write a consistent copy of the acceleration to `path`, for
+    /// snapshots. Must be called while the acceleration isn't being written to.
+    async fn snapshot_to(
+        &self,
+        _dataset: &Dataset,
+        _path: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("The {} accelerator doesn't support snapshots", self.name()).into())
+    }
+
     /// Check if the file path is valid
     fn is_valid_file(&self, dataset: &Dataset) -> bool {
         if let Some(path) = self.file_path(dataset) {
@@ -21,7 +21,7 @@ use datafusion::{
     logical_expr::CreateExternalTable,
 };
 use datafusion_table_providers::{
-    duckdb::{write::DuckDBTableWriter, DuckDBTableProviderFactory},
+    duckdb::{write::DuckDBTableWriter, DuckDB, DuckDBTableProviderFactory},
     sql::db_connection_pool::duckdbpool::DuckDbConnectionPool,
 };
 use duckdb::AccessMode;
@@ -130,6 +130,31 @@ impl DataAccelerator for DuckDBAccelerator {
         self.duckdb_file_path(dataset)
     }
 
+    /// Checkpoints the write-ahead log into the database file, then copies the file. Nothing else
+    /// writes to the file while the refresh task takes the snapshot, so the copy is consistent.
+    async fn snapshot_to(
+        &self,
+        dataset: &Dataset,
+        path: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let duckdb_file = self
+            .duckdb_file_path(dataset)
+            .ok_or("Acceleration mode is not file-based.")?;
+
+        let pool = DuckDbConnectionPool::new_file(&duckdb_file, &AccessMode::ReadWrite)
+            .map_err(|e| e.to_string())?;
+        let mut db_conn = Arc::new(pool).connect_sync().map_err(|e| e.to_string())?;
+        DuckDB::duckdb_conn(&mut db_conn)
+            .map_err(|e| e.to_string())?
+            .get_underlying_conn_mut()
+            .execute_batch("CHECKPOINT")?;
+        drop(db_conn);
+
+        tokio::fs::copy(&duckdb_file, path).await?;
+
+        Ok(())
+    }
+
     fn is_initialized(&self, dataset: &Dataset) -> bool {
         if !dataset.is_file_accelerated() {
             return true; // memory mode DuckDB is always initialized
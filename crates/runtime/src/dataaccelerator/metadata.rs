@@ -21,6 +21,7 @@ limitations under the License.
 //! - `metadata` (TEXT): The metadata entry in JSON format
 
 use serde::de::DeserializeOwned;
+use tokio::sync::watch;
 
 use crate::component::dataset::{acceleration::Engine, Dataset};
 
@@ -79,6 +80,41 @@ impl AcceleratedMetadata {
             .set_metadata(&self.dataset_name, &metadata)
             .await
     }
+
+    /// Spawns a task that saves the metadata sent on the returned channel, in the order it is sent.
+    /// Metadata that is replaced before it could be saved is skipped.
+    pub fn spawn_writer<T: serde::Serialize + Send + Sync + 'static>(
+        dataset: Dataset,
+    ) -> watch::Sender<Option<T>> {
+        let (sender, mut receiver) = watch::channel(None::<T>);
+
+        tokio::spawn(async move {
+            while receiver.changed().await.is_ok() {
+                let metadata = match &*receiver.borrow_and_update() {
+                    Some(metadata) => serde_json::to_string(metadata),
+                    None => continue,
+                };
+
+                let result = match metadata {
+                    Ok(metadata) => match Self::new_create_if_not_exists(&dataset).await {
+                        Ok(accelerated_metadata) => {
+                            accelerated_metadata
+                                .metadata_provider
+                                .set_metadata(&accelerated_metadata.dataset_name, &metadata)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = result {
+                    tracing::error!("Failed to save metadata for dataset {}: {e}", dataset.name);
+                }
+            }
+        });
+
+        sender
+    }
 }
 
 #[async_trait::async_trait]
@@ -0,0 +1,603 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Snapshots of file-mode accelerations in an object store.
+//!
+//! Each snapshot is stored as `<location>/<dataset>/<timestamp>/db`, a consistent copy of the
+//! acceleration file written by the accelerator (i.e. with `VACUUM INTO` for `SQLite`). A snapshot
+//! without the `db` object is incomplete and is skipped.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use chrono::Utc;
+use datafusion::{
+    error::DataFusionError, execution::object_store::ObjectStoreRegistry, sql::TableReference,
+};
+use futures::{StreamExt, TryStreamExt};
+use object_store::{path::Path, ObjectStore, WriteMultipart};
+use snafu::prelude::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    component::dataset::{acceleration::Snapshots, Dataset},
+    object_store_registry::SpiceObjectStoreRegistry,
+};
+
+use super::DataAccelerator;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to access the snapshots location {location}: {source}"))]
+    UnableToGetObjectStore {
+        location: String,
+        source: DataFusionError,
+    },
+
+    #[snafu(display("Invalid snapshots location {location}: {source}"))]
+    InvalidLocation {
+        location: String,
+        source: object_store::path::Error,
+    },
+
+    #[snafu(display("Unable to list snapshots: {source}"))]
+    UnableToListSnapshots { source: object_store::Error },
+
+    #[snafu(display("Unable to download snapshot file {object}: {source}"))]
+    UnableToDownloadSnapshot {
+        object: String,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("Unable to upload snapshot file {object}: {source}"))]
+    UnableToUploadSnapshot {
+        object: String,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("Unable to delete snapshot {snapshot}: {source}"))]
+    UnableToDeleteSnapshot {
+        snapshot: String,
+        source: object_store::Error,
+    },
+
+    #[snafu(display("Unable to access acceleration file {path}: {source}"))]
+    UnableToAccessFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Unable to copy the acceleration for a snapshot: {source}"))]
+    UnableToCopyAcceleration {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+const SNAPSHOT_FILE_NAME: &str = "db";
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+const MAX_CONCURRENT_UPLOAD_PARTS: usize = 4;
+
+struct SnapshotLocation {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl SnapshotLocation {
+    fn try_new(snapshots: &Snapshots, dataset_name: &TableReference) -> Result<Self> {
+        let location = snapshots.location.to_string();
+        let store = SpiceObjectStoreRegistry::new()
+            .get_store(&snapshots.location)
+            .context(UnableToGetObjectStoreSnafu {
+                location: location.clone(),
+            })?;
+        let prefix = Path::from_url_path(snapshots.location.path())
+            .context(InvalidLocationSnafu { location })?
+            .child(dataset_name.to_string());
+
+        Ok(Self { store, prefix })
+    }
+
+    /// Lists the snapshots, oldest first. Snapshots are named after the time they were taken, so
+    /// they sort chronologically.
+    async fn list(&self) -> Result<Vec<Path>> {
+        let mut snapshots = self
+            .store
+            .list_with_delimiter(Some(&self.prefix))
+            .await
+            .context(UnableToListSnapshotsSnafu)?
+            .common_prefixes;
+        snapshots.sort();
+
+        Ok(snapshots)
+    }
+
+    /// Downloads `object` to `path`. Returns `false` if the object doesn't exist.
+    async fn download(&self, object: &Path, path: &str) -> Result<bool> {
+        let result = match self.store.get(object).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(false),
+            Err(source) => {
+                return Err(Error::UnableToDownloadSnapshot {
+                    object: object.to_string(),
+                    source,
+                })
+            }
+        };
+
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .context(UnableToAccessFileSnafu { path })?;
+        let mut stream = result.into_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.context(UnableToDownloadSnapshotSnafu {
+                object: object.to_string(),
+            })?;
+            file.write_all(&chunk)
+                .await
+                .context(UnableToAccessFileSnafu { path })?;
+        }
+        file.flush()
+            .await
+            .context(UnableToAccessFileSnafu { path })?;
+
+        Ok(true)
+    }
+
+    async fn upload(&self, path: &str, object: &Path) -> Result<()> {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .context(UnableToAccessFileSnafu { path })?;
+        let upload =
+            self.store
+                .put_multipart(object)
+                .await
+                .context(UnableToUploadSnapshotSnafu {
+                    object: object.to_string(),
+                })?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, UPLOAD_CHUNK_SIZE);
+
+        let mut buffer = vec![0; UPLOAD_CHUNK_SIZE];
+        loop {
+            let read = match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(source) => {
+                    writer.abort().await.ok();
+                    return Err(Error::UnableToAccessFile {
+                        path: path.to_string(),
+                        source,
+                    });
+                }
+            };
+
+            writer
+                .wait_for_capacity(MAX_CONCURRENT_UPLOAD_PARTS)
+                .await
+                .context(UnableToUploadSnapshotSnafu {
+                    object: object.to_string(),
+                })?;
+            writer.write(&buffer[..read]);
+        }
+
+        writer.finish().await.context(UnableToUploadSnapshotSnafu {
+            object: object.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Whether `snapshot` has its acceleration file, i.e. it was fully uploaded.
+    async fn is_complete(&self, snapshot: &Path) -> Result<bool> {
+        match self.store.head(&snapshot.child(SNAPSHOT_FILE_NAME)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(source) => Err(Error::UnableToDownloadSnapshot {
+                object: snapshot.to_string(),
+                source,
+            }),
+        }
+    }
+
+    async fn delete(&self, snapshot: &Path) -> Result<()> {
+        let objects = self
+            .store
+            .list(Some(snapshot))
+            .try_collect::<Vec<_>>()
+            .await
+            .context(UnableToDeleteSnapshotSnafu {
+                snapshot: snapshot.to_string(),
+            })?;
+
+        for object in objects {
+            self.store
+                .delete(&object.location)
+                .await
+                .context(UnableToDeleteSnapshotSnafu {
+                    snapshot: snapshot.to_string(),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Restores the newest snapshot of `dataset` to its acceleration file, if it's configured to
+/// bootstrap from snapshots and there's no acceleration file yet. Must be called before the
+/// accelerator is initialized. Returns whether a snapshot was restored, for the caller to skip the
+/// initial refresh.
+pub async fn bootstrap(dataset: &Dataset, accelerator: &dyn DataAccelerator) -> Result<bool> {
+    let Some(snapshots) = dataset
+        .acceleration
+        .as_ref()
+        .and_then(|acceleration| acceleration.snapshots.as_ref())
+    else {
+        return Ok(false);
+    };
+    if !snapshots.bootstrap || accelerator.has_existing_file(dataset) {
+        return Ok(false);
+    }
+    let Some(file_path) = accelerator.file_path(dataset) else {
+        return Ok(false);
+    };
+
+    let location = SnapshotLocation::try_new(snapshots, &dataset.name)?;
+    let Some(snapshot) = newest_complete_snapshot(&location).await? else {
+        tracing::debug!("No snapshot found for dataset {}", dataset.name);
+        return Ok(false);
+    };
+
+    if let Some(parent) = std::path::Path::new(&file_path).parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context(UnableToAccessFileSnafu {
+                path: parent.to_string_lossy(),
+            })?;
+    }
+
+    // Download to a temporary file first, so that a failed download doesn't leave a partial
+    // acceleration behind.
+    let partial_path = format!("{file_path}.partial");
+    match location
+        .download(&snapshot.child(SNAPSHOT_FILE_NAME), &partial_path)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Ok(false),
+        Err(e) => {
+            tokio::fs::remove_file(&partial_path).await.ok();
+            return Err(e);
+        }
+    }
+
+    tokio::fs::rename(&partial_path, &file_path)
+        .await
+        .context(UnableToAccessFileSnafu { path: file_path })?;
+
+    tracing::info!("Restored dataset {} from snapshot {snapshot}", dataset.name);
+
+    Ok(true)
+}
+
+async fn newest_complete_snapshot(location: &SnapshotLocation) -> Result<Option<Path>> {
+    for snapshot in location.list().await?.into_iter().rev() {
+        if location.is_complete(&snapshot).await? {
+            return Ok(Some(snapshot));
+        }
+        tracing::debug!("Skipping incomplete snapshot {snapshot}");
+    }
+
+    Ok(None)
+}
+
+/// Takes snapshots of an acceleration after it's refreshed.
+pub struct Snapshotter {
+    dataset: Dataset,
+    accelerator: Arc<dyn DataAccelerator>,
+    file_path: String,
+    location: SnapshotLocation,
+    interval: Option<Duration>,
+    retain: usize,
+    last_snapshot: Mutex<Option<SystemTime>>,
+}
+
+impl Snapshotter {
+    /// Returns `None` if `dataset` isn't configured with snapshots, or isn't accelerated to a file.
+    pub fn try_new(
+        dataset: &Dataset,
+        accelerator: Arc<dyn DataAccelerator>,
+    ) -> Result<Option<Self>> {
+        let Some(snapshots) = dataset
+            .acceleration
+            .as_ref()
+            .and_then(|acceleration| acceleration.snapshots.as_ref())
+        else {
+            return Ok(None);
+        };
+        let Some(file_path) = accelerator.file_path(dataset) else {
+            tracing::warn!(
+                "Snapshots are only supported for file-mode acceleration. Ignoring snapshots for dataset {}.",
+                dataset.name
+            );
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            dataset: dataset.clone(),
+            accelerator,
+            file_path,
+            location: SnapshotLocation::try_new(snapshots, &dataset.name)?,
+            interval: snapshots.interval,
+            retain: snapshots.retain,
+            last_snapshot: Mutex::new(None),
+        }))
+    }
+
+    /// Takes a snapshot, unless one was taken less than `interval` ago. Must be called by the
+    /// refresh task, while the acceleration isn't being written to: the accelerator copies the
+    /// acceleration before returning, and the copy is then uploaded in the background.
+    pub async fn snapshot_if_due(self: &Arc<Self>) {
+        if !self.is_due() {
+            return;
+        }
+
+        let name = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let staged_path = match self.stage(&name).await {
+            Ok(staged_path) => staged_path,
+            Err(e) => {
+                tracing::warn!("Failed to snapshot dataset {}: {e}", self.dataset.name);
+                return;
+            }
+        };
+
+        let snapshotter = Arc::clone(self);
+        tokio::spawn(async move {
+            let result = snapshotter.upload(&name, &staged_path).await;
+            tokio::fs::remove_file(&staged_path).await.ok();
+
+            let dataset_name = &snapshotter.dataset.name;
+            match result {
+                Ok(()) => {
+                    tracing::debug!("Uploaded snapshot {name} for dataset {dataset_name}");
+                    if let Err(e) = snapshotter.prune().await {
+                        tracing::warn!(
+                            "Failed to remove old snapshots for dataset {dataset_name}: {e}"
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to upload snapshot for dataset {dataset_name}: {e}");
+                }
+            }
+        });
+    }
+
+    fn is_due(&self) -> bool {
+        let Ok(mut last_snapshot) = self.last_snapshot.lock() else {
+            return false;
+        };
+
+        if let (Some(interval), Some(last)) = (self.interval, *last_snapshot) {
+            if last.elapsed().is_ok_and(|elapsed| elapsed < interval) {
+                return false;
+            }
+        }
+
+        *last_snapshot = Some(SystemTime::now());
+        true
+    }
+
+    /// Has the accelerator write a consistent copy of the acceleration, returning its path.
+    async fn stage(&self, name: &str) -> Result<String> {
+        let staged_path = format!("{}.snapshot-{name}", self.file_path);
+        if let Err(source) = self
+            .accelerator
+            .snapshot_to(&self.dataset, &staged_path)
+            .await
+        {
+            tokio::fs::remove_file(&staged_path).await.ok();
+            return Err(Error::UnableToCopyAcceleration { source });
+        }
+
+        Ok(staged_path)
+    }
+
+    async fn upload(&self, name: &str, staged_path: &str) -> Result<()> {
+        let object = self.location.prefix.child(name).child(SNAPSHOT_FILE_NAME);
+        self.location.upload(staged_path, &object).await
+    }
+
+    /// Deletes all but the newest `retain` complete snapshots. Incomplete snapshots are deleted once
+    /// they're older than the snapshots that are kept, as newer ones may still be uploading.
+    async fn prune(&self) -> Result<()> {
+        let mut kept = 0;
+        for snapshot in self.location.list().await?.iter().rev() {
+            let is_complete = self.location.is_complete(snapshot).await?;
+            if kept < self.retain {
+                kept += usize::from(is_complete);
+                continue;
+            }
+
+            self.location.delete(snapshot).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use async_trait::async_trait;
+    use datafusion::{datasource::TableProvider, logical_expr::CreateExternalTable};
+    use url::Url;
+
+    use super::*;
+    use crate::{
+        component::dataset::acceleration::{Acceleration, Mode},
+        parameters::ParameterSpec,
+    };
+
+    /// An accelerator whose acceleration is a plain file, copied as-is for snapshots.
+    struct FileAccelerator {
+        file_path: String,
+    }
+
+    #[async_trait]
+    impl DataAccelerator for FileAccelerator {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        async fn create_external_table(
+            &self,
+            _cmd: &CreateExternalTable,
+            _dataset: Option<&Dataset>,
+        ) -> Result<Arc<dyn TableProvider>, Box<dyn std::error::Error + Send + Sync>> {
+            Err("not supported".into())
+        }
+
+        fn name(&self) -> &'static str {
+            "file"
+        }
+
+        fn prefix(&self) -> &'static str {
+            "file"
+        }
+
+        fn parameters(&self) -> &'static [ParameterSpec] {
+            &[]
+        }
+
+        fn file_path(&self, _dataset: &Dataset) -> Option<String> {
+            Some(self.file_path.clone())
+        }
+
+        async fn snapshot_to(
+            &self,
+            _dataset: &Dataset,
+            path: &str,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            tokio::fs::copy(&self.file_path, path).await?;
+            Ok(())
+        }
+    }
+
+    fn test_dataset(location: &std::path::Path, retain: usize) -> Dataset {
+        let mut dataset =
+            Dataset::try_new("test".to_string(), "snapshotted").expect("a valid dataset");
+        dataset.acceleration = Some(Acceleration {
+            mode: Mode::File,
+            snapshots: Some(Snapshots {
+                location: Url::from_directory_path(location).expect("an absolute path"),
+                interval: None,
+                retain,
+                bootstrap: true,
+            }),
+            ..Default::default()
+        });
+        dataset
+    }
+
+    fn test_directory() -> std::path::PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("spice-snapshots-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).expect("directory is created");
+        directory
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_bootstrap_round_trip() {
+        let directory = test_directory();
+        let dataset = test_dataset(&directory.join("snapshots"), 2);
+        let file_path = directory
+            .join("acceleration.db")
+            .to_string_lossy()
+            .to_string();
+
+        let accelerator = Arc::new(FileAccelerator {
+            file_path: file_path.clone(),
+        });
+        let snapshotter = Snapshotter::try_new(&dataset, accelerator)
+            .expect("valid snapshots location")
+            .expect("snapshots are configured");
+
+        for (name, contents) in [("1", "first"), ("2", "second"), ("3", "third")] {
+            std::fs::write(&file_path, contents).expect("acceleration is written");
+            let staged_path = snapshotter.stage(name).await.expect("snapshot is staged");
+            snapshotter
+                .upload(name, &staged_path)
+                .await
+                .expect("snapshot is uploaded");
+            std::fs::remove_file(staged_path).expect("staged snapshot is removed");
+        }
+
+        // Incomplete snapshots, without the acceleration file, don't count towards `retain`.
+        for name in ["0", "4"] {
+            let snapshot = directory.join("snapshots/snapshotted").join(name);
+            std::fs::create_dir_all(&snapshot).expect("directory is created");
+            std::fs::write(snapshot.join("other"), "partial").expect("file is written");
+        }
+        snapshotter
+            .prune()
+            .await
+            .expect("old snapshots are removed");
+        let snapshots = directory.join("snapshots/snapshotted");
+        for (file, kept) in [
+            ("0/other", false),
+            ("1/db", false),
+            ("2/db", true),
+            ("3/db", true),
+            ("4/other", true),
+        ] {
+            assert_eq!(snapshots.join(file).exists(), kept, "{file}");
+        }
+
+        // The incomplete snapshot is skipped.
+
+        let restored_path = directory.join("restored.db").to_string_lossy().to_string();
+        let restored = bootstrap(
+            &dataset,
+            &FileAccelerator {
+                file_path: restored_path.clone(),
+            },
+        )
+        .await
+        .expect("snapshot is restored");
+        assert!(restored);
+        assert_eq!(
+            std::fs::read_to_string(&restored_path).expect("restored acceleration is read"),
+            "third"
+        );
+
+        // An existing acceleration isn't overwritten.
+        let restored = bootstrap(
+            &dataset,
+            &FileAccelerator {
+                file_path: restored_path,
+            },
+        )
+        .await
+        .expect("bootstrap is skipped");
+        assert!(!restored);
+
+        std::fs::remove_dir_all(directory).ok();
+    }
+}
@@ -21,15 +21,21 @@ use crate::parameters::ParameterSpec;
 use crate::parameters::Parameters;
 use crate::secrets::Secrets;
 use crate::Runtime;
-use arrow::datatypes::SchemaRef;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
+use data_components::arrow::stream_format::ArrowStreamFormat;
 use data_components::cdc::ChangesStream;
+use data_components::object::document::extractor_for_format;
 use data_components::object::metadata::ObjectStoreMetadataTable;
 use data_components::object::text::ObjectStoreTextTable;
 use datafusion::catalog::CatalogProvider;
+use datafusion::common::GetExt;
 use datafusion::dataframe::DataFrame;
+use datafusion::datasource::file_format::arrow::ArrowFormat;
+use datafusion::datasource::file_format::avro::AvroFormat;
 use datafusion::datasource::file_format::csv::CsvFormat;
 use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::file_format::json::JsonFormat;
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::file_format::FileFormat;
 use datafusion::datasource::listing::{
@@ -42,6 +48,7 @@ use datafusion::execution::context::SessionContext;
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::logical_expr::{Expr, LogicalPlanBuilder};
 use datafusion::sql::TableReference;
+use futures::{StreamExt, TryStreamExt};
 use object_store::ObjectStore;
 use secrecy::SecretString;
 use snafu::prelude::*;
@@ -78,6 +85,10 @@ pub mod ftp;
 pub mod github;
 pub mod graphql;
 pub mod https;
+#[cfg(feature = "iceberg")]
+pub mod iceberg;
+#[cfg(feature = "kafka")]
+pub mod kafka;
 #[cfg(feature = "mysql")]
 pub mod mysql;
 #[cfg(feature = "odbc")]
@@ -297,6 +308,8 @@ pub async fn register_all() {
     register_connector_factory("http", https::HttpsFactory::new_arc()).await;
     register_connector_factory("https", https::HttpsFactory::new_arc()).await;
     register_connector_factory("github", github::GithubFactory::new_arc()).await;
+    #[cfg(feature = "iceberg")]
+    register_connector_factory("iceberg", iceberg::IcebergFactory::new_arc()).await;
     #[cfg(feature = "ftp")]
     register_connector_factory("sftp", sftp::SFTPFactory::new_arc()).await;
     register_connector_factory("spiceai", spiceai::SpiceAIFactory::new_arc()).await;
@@ -317,6 +330,8 @@ pub async fn register_all() {
     register_connector_factory("snowflake", snowflake::SnowflakeFactory::new_arc()).await;
     #[cfg(feature = "debezium")]
     register_connector_factory("debezium", debezium::DebeziumFactory::new_arc()).await;
+    #[cfg(feature = "kafka")]
+    register_connector_factory("kafka", kafka::KafkaFactory::new_arc()).await;
     #[cfg(feature = "delta_lake")]
     register_connector_factory(
         "unity_catalog",
@@ -440,6 +455,12 @@ pub trait ListingTableConnector: DataConnector {
 
     fn get_params(&self) -> &Parameters;
 
+    /// Whether datasets in `read_write` mode can write new Parquet files under their path, i.e.
+    /// when the object store supports writes.
+    fn supports_writes(&self) -> bool {
+        false
+    }
+
     #[must_use]
     fn get_session_context() -> SessionContext {
         SessionContext::new_with_config_rt(
@@ -499,11 +520,16 @@ pub trait ListingTableConnector: DataConnector {
     /// unstructured formats. It supports the following tabular formats:
     ///  - parquet
     ///  - csv
+    ///  - json (newline-delimited, detected from `.json`, `.jsonl` and `.ndjson` extensions)
+    ///  - avro
+    ///  - arrow (Arrow IPC file format, detected from the `.arrow` extension)
+    ///  - `arrow_stream` (Arrow IPC streaming format, detected from the `.arrows` extension)
     ///
     /// For tabular formats, file options can also be specified in the [`Dataset`]'s `param`s.
     ///
-    /// For unstructured text formats, the [`Dataset`]'s `file_format` param key must be set. `Ok`
-    /// responses, are always of the format `Ok((None, String))`. The data must be UTF8 compatible.
+    /// For unstructured formats, the [`Dataset`]'s `file_format` param key must be set. `Ok`
+    /// responses, are always of the format `Ok((None, String))`. The text of `pdf`, `docx` and
+    /// `html` documents is extracted, other data must be UTF8 compatible.
     fn get_file_format_and_extension(
         &self,
         dataset: &Dataset,
@@ -527,8 +553,55 @@ pub trait ListingTableConnector: DataConnector {
                 Some(Arc::new(ParquetFormat::default())),
                 extension.unwrap_or(".parquet".to_string()),
             )),
+            Some("json") => {
+                let (default_extension, compression_type) =
+                    match detect_json_extension(&dataset.path()) {
+                        Some((detected_extension, compression_type)) => (
+                            detected_extension,
+                            self.get_json_compression_type(params, compression_type)?,
+                        ),
+                        None => {
+                            let compression_type = self.get_json_compression_type(
+                                params,
+                                FileCompressionType::UNCOMPRESSED,
+                            )?;
+                            (
+                                format!(".json{}", compression_type.get_ext()),
+                                compression_type,
+                            )
+                        }
+                    };
+
+                Ok((
+                    Some(get_json_format(params, compression_type)),
+                    extension.unwrap_or(default_extension),
+                ))
+            }
+            Some("avro") => Ok((
+                Some(Arc::new(AvroFormat)),
+                extension.unwrap_or(".avro".to_string()),
+            )),
+            Some("arrow") => Ok((
+                Some(Arc::new(ArrowFormat)),
+                extension.unwrap_or(".arrow".to_string()),
+            )),
+            Some("arrow_stream") => Ok((
+                Some(Arc::new(ArrowStreamFormat::default())),
+                extension.unwrap_or(".arrows".to_string()),
+            )),
             Some(format) => Ok((None, format!(".{format}"))),
             None => {
+                if let Some((detected_extension, compression_type)) =
+                    detect_json_extension(&dataset.path())
+                {
+                    let compression_type =
+                        self.get_json_compression_type(params, compression_type)?;
+                    return Ok((
+                        Some(get_json_format(params, compression_type)),
+                        extension.unwrap_or(detected_extension),
+                    ));
+                }
+
                 if let Some(ext) = std::path::Path::new(dataset.path().as_str()).extension() {
                     if ext.eq_ignore_ascii_case("csv") {
                         return Ok((
@@ -542,6 +615,24 @@ pub trait ListingTableConnector: DataConnector {
                             extension.unwrap_or(".parquet".to_string()),
                         ));
                     }
+                    if ext.eq_ignore_ascii_case("avro") {
+                        return Ok((
+                            Some(Arc::new(AvroFormat)),
+                            extension.unwrap_or(".avro".to_string()),
+                        ));
+                    }
+                    if ext.eq_ignore_ascii_case("arrow") {
+                        return Ok((
+                            Some(Arc::new(ArrowFormat)),
+                            extension.unwrap_or(".arrow".to_string()),
+                        ));
+                    }
+                    if ext.eq_ignore_ascii_case("arrows") {
+                        return Ok((
+                            Some(Arc::new(ArrowStreamFormat::default())),
+                            extension.unwrap_or(".arrows".to_string()),
+                        ));
+                    }
                 }
 
                 Err(DataConnectorError::InvalidConfiguration {
@@ -605,6 +696,239 @@ pub trait ListingTableConnector: DataConnector {
                 ),
         ))
     }
+
+    /// Whether partition columns are inferred from Hive-style paths (i.e. `year=2024/month=09/`),
+    /// via the `hive_partitioning_enabled` param.
+    fn hive_partitioning_enabled(&self) -> bool {
+        self.get_params()
+            .get("hive_partitioning_enabled")
+            .expose()
+            .ok()
+            .is_some_and(|f| f.eq_ignore_ascii_case("true"))
+    }
+
+    /// Parses the explicit partition column types of the `hive_partitioning_column_types` param,
+    /// i.e. `year:int,month:int,region:string`.
+    fn get_hive_partition_column_types(&self) -> DataConnectorResult<HashMap<String, DataType>>
+    where
+        Self: Display,
+    {
+        Ok(self
+            .get_column_types("hive_partitioning_column_types")?
+            .into_iter()
+            .collect())
+    }
+
+    /// Parses the declared schema of the files of the `file_schema` param, i.e.
+    /// `id:bigint,name:string`. It is used instead of inferring the schema from the files, so that
+    /// datasets whose path has no files yet can be written to.
+    fn get_file_schema(&self) -> DataConnectorResult<Option<SchemaRef>>
+    where
+        Self: Display,
+    {
+        if self.get_params().get("file_schema").expose().ok().is_none() {
+            return Ok(None);
+        }
+
+        let fields = self
+            .get_column_types("file_schema")?
+            .into_iter()
+            .map(|(column, data_type)| Field::new(column, data_type, true))
+            .collect::<Vec<_>>();
+        Ok(Some(Arc::new(Schema::new(fields))))
+    }
+
+    /// Parses the comma separated `<column>:<type>` pairs of the `param` param, in order.
+    fn get_column_types(&self, param: &str) -> DataConnectorResult<Vec<(String, DataType)>>
+    where
+        Self: Display,
+    {
+        let Some(column_types) = self.get_params().get(param).expose().ok() else {
+            return Ok(vec![]);
+        };
+
+        column_types
+            .split(',')
+            .filter(|column_type| !column_type.trim().is_empty())
+            .map(|column_type| {
+                let (column, data_type) = column_type.split_once(':').unwrap_or((column_type, ""));
+                let data_type = parse_column_data_type(data_type.trim()).context(
+                    InvalidConfigurationSnafu {
+                        dataconnector: format!("{self}"),
+                        message: format!("Invalid {param}: {column_type}, expected comma separated <column>:<type> pairs"),
+                    },
+                )?;
+                Ok((column.trim().to_string(), data_type))
+            })
+            .collect()
+    }
+
+    /// The compression of JSON files, from the `file_compression_type` param if set, otherwise
+    /// `detected_compression_type` (i.e. from the file extension).
+    fn get_json_compression_type(
+        &self,
+        params: &Parameters,
+        detected_compression_type: FileCompressionType,
+    ) -> DataConnectorResult<FileCompressionType>
+    where
+        Self: Display,
+    {
+        match params.get("file_compression_type").expose().ok() {
+            Some(compression_type) => FileCompressionType::from_str(compression_type)
+                .boxed()
+                .context(InvalidConfigurationSnafu {
+                    dataconnector: format!("{self}"),
+                    message: format!("Invalid JSON compression_type: {compression_type}, supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
+                }),
+            None => Ok(detected_compression_type),
+        }
+    }
+}
+
+fn get_json_format(params: &Parameters, compression_type: FileCompressionType) -> Arc<JsonFormat> {
+    let schema_infer_max_rec = params
+        .get("json_schema_infer_max_records")
+        .expose()
+        .ok()
+        .map_or_else(|| 1000, |f| usize::from_str(f).map_or(1000, |f| f));
+
+    Arc::new(
+        JsonFormat::default()
+            .with_schema_infer_max_rec(schema_infer_max_rec)
+            .with_file_compression_type(compression_type),
+    )
+}
+
+/// The number of files whose paths are sampled to infer Hive-style partition columns.
+const HIVE_PARTITION_INFERENCE_SAMPLE_SIZE: usize = 100;
+
+/// The partition value Hive uses for `NULL`s.
+const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Infers the Hive-style partition columns of the files under `table_path`, from the
+/// `<column>=<value>` directories of the first file found.
+///
+/// Columns without an explicit type in `column_types` are `Int64` if all of their values in the
+/// sampled paths are integers, otherwise `Utf8`.
+async fn infer_hive_partition_cols(
+    store: &Arc<dyn ObjectStore>,
+    table_path: &ListingTableUrl,
+    extension: &str,
+    column_types: &HashMap<String, DataType>,
+) -> Result<Vec<(String, DataType)>, object_store::Error> {
+    let prefix = table_path.prefix().as_ref().to_string();
+    let files: Vec<_> = store
+        .list(Some(table_path.prefix()))
+        .try_filter(|meta| futures::future::ready(meta.location.as_ref().ends_with(extension)))
+        .take(HIVE_PARTITION_INFERENCE_SAMPLE_SIZE)
+        .map_ok(|meta| {
+            let location = meta.location.as_ref();
+            location
+                .strip_prefix(&prefix)
+                .unwrap_or(location)
+                .to_string()
+        })
+        .try_collect()
+        .await?;
+
+    let Some(first_file) = files.first() else {
+        return Ok(vec![]);
+    };
+
+    let partition_cols = parse_hive_partitions(first_file)
+        .into_iter()
+        .map(|(column, _)| {
+            let data_type = column_types.get(column).cloned().unwrap_or_else(|| {
+                let all_integers = files.iter().all(|file| {
+                    parse_hive_partitions(file)
+                        .into_iter()
+                        .filter(|(name, _)| *name == column)
+                        .all(|(_, value)| {
+                            value == HIVE_DEFAULT_PARTITION || value.parse::<i64>().is_ok()
+                        })
+                });
+                if all_integers {
+                    DataType::Int64
+                } else {
+                    DataType::Utf8
+                }
+            });
+            (column.to_string(), data_type)
+        })
+        .collect::<Vec<_>>();
+
+    for column in column_types.keys() {
+        if !partition_cols.iter().any(|(name, _)| name == column) {
+            tracing::warn!("Partition column {column} has an explicit type, but was not found in the paths under {table_path}");
+        }
+    }
+
+    Ok(partition_cols)
+}
+
+/// Parses the `<column>=<value>` directories of a file path, relative to the table path.
+fn parse_hive_partitions(path: &str) -> Vec<(&str, &str)> {
+    let mut segments = path.split('/').collect::<Vec<_>>();
+    // The last segment is the file name.
+    segments.pop();
+
+    segments
+        .into_iter()
+        .filter_map(|segment| segment.split_once('='))
+        .filter(|(column, _)| !column.is_empty())
+        .collect()
+}
+
+/// Parses a column type, either a common SQL type name or an Arrow type (i.e. `Int16`).
+fn parse_column_data_type(data_type: &str) -> Result<DataType, arrow::error::ArrowError> {
+    match data_type.to_ascii_lowercase().as_str() {
+        "string" | "text" | "varchar" => Ok(DataType::Utf8),
+        "int" | "integer" => Ok(DataType::Int32),
+        "bigint" | "long" => Ok(DataType::Int64),
+        "smallint" => Ok(DataType::Int16),
+        "tinyint" => Ok(DataType::Int8),
+        "boolean" | "bool" => Ok(DataType::Boolean),
+        "double" => Ok(DataType::Float64),
+        "float" | "real" => Ok(DataType::Float32),
+        "date" => Ok(DataType::Date32),
+        _ => DataType::from_str(data_type),
+    }
+}
+
+/// Detects newline-delimited JSON from the extension of `path`, optionally followed by a
+/// compression extension (i.e. `events.ndjson.gz`).
+///
+/// Returns the full extension to list files with, and the compression it implies.
+fn detect_json_extension(path: &str) -> Option<(String, FileCompressionType)> {
+    let path = std::path::Path::new(path);
+    let mut extension = path.extension()?.to_str()?;
+    let mut compression_extension = String::new();
+    let mut compression_type = FileCompressionType::UNCOMPRESSED;
+
+    let detected_compression_type = match extension.to_ascii_lowercase().as_str() {
+        "gz" => Some(FileCompressionType::GZIP),
+        "bz2" => Some(FileCompressionType::BZIP2),
+        "xz" => Some(FileCompressionType::XZ),
+        "zst" => Some(FileCompressionType::ZSTD),
+        _ => None,
+    };
+    if let Some(detected_compression_type) = detected_compression_type {
+        compression_type = detected_compression_type;
+        compression_extension = format!(".{extension}");
+        extension = std::path::Path::new(path.file_stem()?)
+            .extension()?
+            .to_str()?;
+    }
+
+    ["json", "jsonl", "ndjson"]
+        .iter()
+        .any(|json_extension| extension.eq_ignore_ascii_case(json_extension))
+        .then(|| {
+            (
+                format!(".{extension}{compression_extension}"),
+                compression_type,
+            )
+        })
 }
 
 #[async_trait]
@@ -627,6 +951,65 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
         )
     }
 
+    /// Writes to the listing table add new Parquet files under the dataset's path, partitioned by
+    /// its Hive-style partition columns (if enabled). The path can be empty if the schema of its
+    /// files is declared with the `file_schema` param.
+    async fn read_write_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<DataConnectorResult<Arc<dyn TableProvider>>> {
+        if !self.supports_writes() {
+            return None;
+        }
+
+        let writable = self.get_file_format_and_extension(dataset).and_then(|(format, _)| {
+            if !format.is_some_and(|format| format.as_any().is::<ParquetFormat>()) {
+                return Err(DataConnectorError::InvalidConfiguration {
+                    dataconnector: format!("{self}"),
+                    message: format!(
+                        "Dataset {} can only be configured as read_write with the parquet file_format.",
+                        dataset.name
+                    ),
+                    source: "Unsupported file format for writes".into(),
+                });
+            }
+
+            if !self.get_object_store_url(dataset)?.path().ends_with('/') {
+                return Err(DataConnectorError::InvalidConfiguration {
+                    dataconnector: format!("{self}"),
+                    message: format!(
+                        "Dataset {} can only be configured as read_write when its path is a directory, ending with '/'.",
+                        dataset.name
+                    ),
+                    source: "Not a directory".into(),
+                });
+            }
+
+            Ok(())
+        });
+
+        let table = match writable {
+            Ok(()) => self.read_provider(dataset).await,
+            Err(e) => Err(e),
+        };
+
+        // Without files to infer it from, the schema of new files has to be declared.
+        Some(table.and_then(|table| {
+            if table.schema().fields().is_empty() {
+                return Err(DataConnectorError::InvalidConfiguration {
+                    dataconnector: format!("{self}"),
+                    message: format!(
+                        "Dataset {} has no Parquet files to infer its schema from. Declare the schema of its files with the file_schema param, i.e. id:bigint,name:string, to write to it.",
+                        dataset.name
+                    ),
+                    source: "Unknown schema".into(),
+                });
+            }
+
+            Ok(table)
+        }))
+    }
+
     async fn read_provider(
         &self,
         dataset: &Dataset,
@@ -645,11 +1028,20 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
         let (file_format_opt, extension) = self.get_file_format_and_extension(dataset)?;
         match file_format_opt {
             None => {
-                // Assume its unstructured text data. Use a [`ObjectStoreTextTable`].
+                // Assume its unstructured data. Use a [`ObjectStoreTextTable`], with the extractor
+                // for documents of the `file_format` (i.e. pdf, docx, html), otherwise UTF-8 text.
+                let extractor = extractor_for_format(
+                    self.get_params()
+                        .get("file_format")
+                        .expose()
+                        .ok()
+                        .unwrap_or_default(),
+                );
                 Ok(ObjectStoreTextTable::try_new(
                     self.get_object_store(dataset)?,
                     &url.clone(),
                     Some(extension.clone()),
+                    extractor,
                 )
                 .context(InvalidConfigurationSnafu {
                     dataconnector: format!("{self}"),
@@ -660,15 +1052,49 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
                 })?)
             }
             Some(file_format) => {
-                let options = ListingOptions::new(file_format).with_file_extension(&extension);
-
-                let resolved_schema = options
-                    .infer_schema(&ctx.state(), &table_path)
+                let mut options = ListingOptions::new(file_format).with_file_extension(&extension);
+
+                if self.hive_partitioning_enabled() {
+                    let column_types = self.get_hive_partition_column_types()?;
+                    let partition_cols = infer_hive_partition_cols(
+                        &self.get_object_store(dataset)?,
+                        &table_path,
+                        &extension,
+                        &column_types,
+                    )
                     .await
                     .boxed()
                     .context(UnableToConnectInternalSnafu {
                         dataconnector: format!("{self}"),
                     })?;
+                    options = options.with_table_partition_cols(partition_cols);
+                }
+
+                // A declared schema is used as is, so that paths without files yet (i.e. that are
+                // only written to) don't need a file to infer it from. Partition columns are read
+                // from the paths of the files rather than their contents.
+                let resolved_schema = match self.get_file_schema()? {
+                    Some(schema) => Arc::new(Schema::new(
+                        schema
+                            .fields()
+                            .iter()
+                            .filter(|field| {
+                                !options
+                                    .table_partition_cols
+                                    .iter()
+                                    .any(|(column, _)| column == field.name())
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>(),
+                    )),
+                    None => options
+                        .infer_schema(&ctx.state(), &table_path)
+                        .await
+                        .boxed()
+                        .context(UnableToConnectInternalSnafu {
+                            dataconnector: format!("{self}"),
+                        })?,
+                };
 
                 let config = ListingTableConfig::new(table_path)
                     .with_listing_options(options)
@@ -764,7 +1190,10 @@ mod tests {
         ParameterSpec::runtime("csv_escape"),
         ParameterSpec::runtime("csv_schema_infer_max_records"),
         ParameterSpec::runtime("csv_delimiter"),
+        ParameterSpec::runtime("json_schema_infer_max_records"),
         ParameterSpec::runtime("file_compression_type"),
+        ParameterSpec::runtime("hive_partitioning_enabled"),
+        ParameterSpec::runtime("hive_partitioning_column_types"),
     ];
 
     fn setup_connector(path: String, params: HashMap<String, String>) -> (TestConnector, Dataset) {
@@ -849,6 +1278,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_file_format_and_extension_detect_json_extension() {
+        for (path, expected_extension) in [
+            ("test:test.json", ".json"),
+            ("test:test.jsonl", ".jsonl"),
+            ("test:test.NDJSON", ".NDJSON"),
+            ("test:test.ndjson.gz", ".ndjson.gz"),
+        ] {
+            let (connector, dataset) = setup_connector(path.to_string(), HashMap::new());
+
+            if let Ok((Some(file_format), extension)) =
+                connector.get_file_format_and_extension(&dataset)
+            {
+                assert_eq!(extension, expected_extension);
+                assert!(file_format.as_any().is::<JsonFormat>());
+            } else {
+                panic!("Unexpected error for {path}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_file_format_and_extension_detect_avro_and_arrow_extensions() {
+        for (path, expected_extension) in [
+            ("test:test.avro", ".avro"),
+            ("test:test.arrow", ".arrow"),
+            ("test:test.arrows", ".arrows"),
+        ] {
+            let (connector, dataset) = setup_connector(path.to_string(), HashMap::new());
+
+            if let Ok((Some(_file_format), extension)) =
+                connector.get_file_format_and_extension(&dataset)
+            {
+                assert_eq!(extension, expected_extension);
+            } else {
+                panic!("Unexpected error for {path}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_file_format_and_extension_json_from_params() {
+        let mut params = HashMap::new();
+        params.insert("file_format".to_string(), "json".to_string());
+        params.insert("file_compression_type".to_string(), "zstd".to_string());
+        let (connector, dataset) = setup_connector("test:test/".to_string(), params);
+
+        if let Ok((Some(_file_format), extension)) =
+            connector.get_file_format_and_extension(&dataset)
+        {
+            assert_eq!(extension, ".json.zst");
+        } else {
+            panic!("Unexpected error");
+        }
+    }
+
+    #[test]
+    fn test_parse_hive_partitions() {
+        assert_eq!(
+            parse_hive_partitions("events/year=2024/month=09/day=01/part-0.parquet"),
+            vec![("year", "2024"), ("month", "09"), ("day", "01")]
+        );
+        assert_eq!(
+            parse_hive_partitions("year=2024/raw/part-0.parquet"),
+            vec![("year", "2024")]
+        );
+        assert!(parse_hive_partitions("part-0.parquet").is_empty());
+    }
+
+    #[test]
+    fn test_get_hive_partition_column_types() {
+        let mut params = HashMap::new();
+        params.insert(
+            "hive_partitioning_column_types".to_string(),
+            "year:int, month:Int16,region:string".to_string(),
+        );
+        let (connector, _) = setup_connector("test:test/".to_string(), params);
+
+        let column_types = connector
+            .get_hive_partition_column_types()
+            .expect("valid column types");
+        assert_eq!(column_types.get("year"), Some(&DataType::Int32));
+        assert_eq!(column_types.get("month"), Some(&DataType::Int16));
+        assert_eq!(column_types.get("region"), Some(&DataType::Utf8));
+
+        let mut params = HashMap::new();
+        params.insert(
+            "hive_partitioning_column_types".to_string(),
+            "year".to_string(),
+        );
+        let (connector, _) = setup_connector("test:test/".to_string(), params);
+        assert!(connector.get_hive_partition_column_types().is_err());
+    }
+
     #[test]
     fn test_build_fragments() {
         let mut params = HashMap::new();
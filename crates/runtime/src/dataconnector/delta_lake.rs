@@ -14,33 +14,141 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::component::dataset::acceleration::{Acceleration, Engine, RefreshMode};
 use crate::component::dataset::Dataset;
+use crate::dataaccelerator::metadata::AcceleratedMetadata;
 use async_trait::async_trait;
-use data_components::delta_lake::DeltaTableFactory;
+use data_components::cdc::{ChangesStream, Checkpoint};
+use data_components::delta_lake::changes::DeltaChangeDataFeed;
+use data_components::delta_lake::{DeltaTableFactory, VersionSelection};
 use data_components::Read;
 use datafusion::datasource::TableProvider;
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::any::Any;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 
-use super::{DataConnector, DataConnectorFactory, ParameterSpec, Parameters};
+use super::{
+    DataConnector, DataConnectorError, DataConnectorFactory, DataConnectorResult, ParameterSpec,
+    Parameters,
+};
 
 pub struct DeltaLake {
     delta_table_factory: DeltaTableFactory,
 }
 
 impl DeltaLake {
-    #[must_use]
     #[allow(clippy::needless_pass_by_value)]
-    pub fn new(params: Parameters) -> Self {
-        Self {
-            delta_table_factory: DeltaTableFactory::new(params.to_secret_map()),
+    pub fn new(params: Parameters) -> DataConnectorResult<Self> {
+        let version = version_selection(&params)?;
+        Ok(Self {
+            delta_table_factory: DeltaTableFactory::new(params.to_secret_map())
+                .with_version(version),
+        })
+    }
+
+    /// A provider that streams the changes of the table, to refresh accelerations incrementally.
+    async fn changes_provider(
+        &self,
+        dataset: &Dataset,
+        acceleration: &Acceleration,
+    ) -> DataConnectorResult<Arc<dyn TableProvider>> {
+        ensure!(
+            acceleration.engine != Engine::Arrow,
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "delta_lake",
+                message: "The 'changes' refresh mode of the Delta Lake data connector only works with non-Arrow acceleration engines.",
+            }
+        );
+        let primary_keys = acceleration
+            .primary_key
+            .as_ref()
+            .map(|primary_key| {
+                primary_key
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        ensure!(
+            !primary_keys.is_empty(),
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "delta_lake",
+                message: "The 'changes' refresh mode of the Delta Lake data connector requires a primary_key in the acceleration, to apply updates and deletes.",
+            }
+        );
+
+        let dataset_name = dataset.name.to_string();
+        let table_location = dataset.path();
+
+        let start_version = match get_metadata_from_accelerator(dataset).await {
+            Some(metadata) => {
+                ensure!(
+                    table_location == metadata.table_location,
+                    super::InvalidConfigurationNoSourceSnafu {
+                        dataconnector: "delta_lake",
+                        message: format!("The table location has changed from {} to {table_location} for dataset {dataset_name}. The existing accelerator data may be out of date.", metadata.table_location),
+                    }
+                );
+                tracing::debug!(
+                    "Resuming changes of dataset {dataset_name} after Delta table version {}",
+                    metadata.version
+                );
+                Some(metadata.version)
+            }
+            None => None,
+        };
+
+        let delta_table = self
+            .delta_table_factory
+            .create_table(table_location.clone())
+            .await
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "delta_lake",
+            })?;
+
+        let mut change_data_feed = DeltaChangeDataFeed::new(delta_table, primary_keys)
+            .with_start_version(start_version)
+            .with_checkpoint(version_checkpoint(dataset.clone(), table_location));
+        if let Some(check_interval) = dataset.refresh_check_interval() {
+            change_data_feed = change_data_feed.with_poll_interval(check_interval);
         }
+
+        Ok(Arc::new(change_data_feed))
     }
 }
 
+fn version_selection(params: &Parameters) -> DataConnectorResult<VersionSelection> {
+    if let Some(version) = params.get("version").expose().ok() {
+        let version = version.trim().parse().map_err(|_| {
+            DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "delta_lake".to_string(),
+                message: format!("The version {version} is not a valid Delta table version."),
+            }
+        })?;
+        return Ok(VersionSelection::Version(version));
+    }
+
+    if let Some(as_of) = params.get("as_of").expose().ok() {
+        let as_of = as_of.trim();
+        let timestamp_ms = match chrono::DateTime::parse_from_rfc3339(as_of) {
+            Ok(timestamp) => timestamp.timestamp_millis(),
+            Err(_) => as_of.parse().map_err(|_| {
+                DataConnectorError::InvalidConfigurationNoSource {
+                    dataconnector: "delta_lake".to_string(),
+                    message: format!("The as_of {as_of} is not an RFC 3339 timestamp or milliseconds since the epoch."),
+                }
+            })?,
+        };
+        return Ok(VersionSelection::AsOf(timestamp_ms));
+    }
+
+    Ok(VersionSelection::Latest)
+}
+
 #[derive(Default, Copy, Clone)]
 pub struct DeltaLakeFactory {}
 
@@ -59,6 +167,11 @@ impl DeltaLakeFactory {
 const PARAMETERS: &[ParameterSpec] = &[
     ParameterSpec::runtime("client_timeout")
         .description("The timeout setting for object store client."),
+    ParameterSpec::runtime("version")
+        .description("The version of the Delta table to read, instead of the latest version."),
+    ParameterSpec::runtime("as_of")
+        .description("Read the latest version of the Delta table at a point in time, as an RFC 3339 timestamp or milliseconds since the epoch.")
+        .examples(&["2024-09-01T00:00:00Z"]),
     // S3 storage options
     ParameterSpec::connector("aws_region")
         .description("The AWS region to use for S3 storage.")
@@ -103,7 +216,7 @@ impl DataConnectorFactory for DeltaLakeFactory {
         params: Parameters,
     ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
         Box::pin(async move {
-            let delta = DeltaLake::new(params);
+            let delta = DeltaLake::new(params)?;
             Ok(Arc::new(delta) as Arc<dyn DataConnector>)
         })
     }
@@ -127,6 +240,14 @@ impl DataConnector for DeltaLake {
         &self,
         dataset: &Dataset,
     ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        if let Some(acceleration) = &dataset.acceleration {
+            if dataset.is_accelerated()
+                && self.resolve_refresh_mode(acceleration.refresh_mode) == RefreshMode::Changes
+            {
+                return self.changes_provider(dataset, acceleration).await;
+            }
+        }
+
         Ok(Read::table_provider(
             &self.delta_table_factory,
             dataset.path().into(),
@@ -137,4 +258,41 @@ impl DataConnector for DeltaLake {
             dataconnector: "delta_lake",
         })?)
     }
+
+    fn supports_changes_stream(&self) -> bool {
+        true
+    }
+
+    fn changes_stream(&self, table_provider: Arc<dyn TableProvider>) -> Option<ChangesStream> {
+        let change_data_feed = table_provider
+            .as_any()
+            .downcast_ref::<DeltaChangeDataFeed>()?;
+
+        Some(change_data_feed.stream_changes())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeltaLakeMetadata {
+    table_location: String,
+    /// The last version of the Delta table whose changes are applied to the accelerator.
+    version: u64,
+}
+
+async fn get_metadata_from_accelerator(dataset: &Dataset) -> Option<DeltaLakeMetadata> {
+    let accelerated_metadata = AcceleratedMetadata::new(dataset).await?;
+    let metadata = accelerated_metadata.get_metadata().await?;
+    Some(metadata)
+}
+
+/// Saves the applied versions to the accelerator, to resume after the last one on restarts.
+fn version_checkpoint(dataset: Dataset, table_location: String) -> Checkpoint {
+    let writer = AcceleratedMetadata::spawn_writer(dataset);
+
+    Arc::new(move |version| {
+        writer.send_replace(Some(DeltaLakeMetadata {
+            table_location: table_location.clone(),
+            version,
+        }));
+    })
 }
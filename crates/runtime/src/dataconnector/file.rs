@@ -64,8 +64,16 @@ const PARAMETERS: &[ParameterSpec] = &[
         .description("Set a limit in terms of records to scan to infer the schema."),
     ParameterSpec::runtime("csv_delimiter")
         .description("The character separating values within a row."),
+    ParameterSpec::runtime("json_schema_infer_max_records")
+        .description("Set a limit in terms of records to scan to infer the schema of JSON files."),
     ParameterSpec::runtime("file_compression_type")
         .description("The type of compression used on the file. Supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
+    ParameterSpec::runtime("hive_partitioning_enabled")
+        .description("Set true to infer partition columns from Hive-style paths, i.e. year=2024/month=09/."),
+    ParameterSpec::runtime("hive_partitioning_column_types")
+        .description("Explicit types of partition columns, as comma separated <column>:<type> pairs, i.e. year:int,region:string."),
+    ParameterSpec::runtime("file_schema")
+        .description("The schema of the files, as comma separated <column>:<type> pairs, i.e. id:bigint,name:string. Used instead of inferring it from the files, i.e. to write to an empty path."),
 ];
 
 impl DataConnectorFactory for FileFactory {
@@ -94,6 +102,10 @@ impl ListingTableConnector for File {
         &self.params
     }
 
+    fn supports_writes(&self) -> bool {
+        true
+    }
+
     fn get_object_store_url(&self, dataset: &Dataset) -> DataConnectorResult<Url> {
         let clean_from = dataset.from.replace("file://", "file:/");
 
@@ -105,3 +117,90 @@ impl ListingTableConnector for File {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use arrow::util::pretty::pretty_format_batches;
+    use datafusion::prelude::SessionContext;
+    use datafusion_table_providers::util::secrets::to_secret_map;
+
+    use super::*;
+
+    fn connector(params: &[(&str, &str)]) -> File {
+        File {
+            params: Parameters::new(
+                to_secret_map(
+                    params
+                        .iter()
+                        .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+                        .collect::<HashMap<_, _>>(),
+                )
+                .into_iter()
+                .collect(),
+                "file",
+                PARAMETERS,
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_into_empty_directory() {
+        let dir = std::env::temp_dir().join(format!("spice-file-writes-{}", uuid::Uuid::new_v4()));
+        let dataset =
+            Dataset::try_new(format!("file:{}/", dir.display()), "test").expect("valid dataset");
+
+        let undeclared = connector(&[("file_format", "parquet")]);
+        std::fs::create_dir_all(&dir).expect("directory created");
+        let Some(Err(e)) = undeclared.read_write_provider(&dataset).await else {
+            panic!("an empty directory without a declared schema isn't writable");
+        };
+        assert!(e.to_string().contains("no Parquet files"), "{e}");
+        std::fs::remove_dir_all(&dir).expect("directory removed");
+
+        // The directory doesn't exist until the first write
+        let declared = connector(&[
+            ("file_format", "parquet"),
+            ("file_schema", "id:bigint,name:string"),
+        ]);
+        let table = declared
+            .read_write_provider(&dataset)
+            .await
+            .expect("writes are supported")
+            .expect("writable table");
+
+        let ctx = SessionContext::new();
+        ctx.register_table("written", table)
+            .expect("table registered");
+        ctx.sql("INSERT INTO written VALUES (1, 'a'), (2, 'b')")
+            .await
+            .expect("valid insert")
+            .collect()
+            .await
+            .expect("rows inserted");
+
+        // The schema is inferred from the written file once there is one
+        let table = undeclared
+            .read_provider(&dataset)
+            .await
+            .expect("readable table");
+        ctx.register_table("read_back", table)
+            .expect("table registered");
+        let batches = ctx
+            .sql("SELECT id, name FROM read_back ORDER BY id")
+            .await
+            .expect("valid query")
+            .collect()
+            .await
+            .expect("rows read");
+
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(
+            pretty_format_batches(&batches)
+                .expect("printable batches")
+                .to_string(),
+            "+----+------+\n| id | name |\n+----+------+\n| 1  | a    |\n| 2  | b    |\n+----+------+"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::component::catalog::Catalog;
+use crate::component::dataset::Dataset;
+use crate::object_store_registry::default_runtime_env;
+use crate::Runtime;
+use async_trait::async_trait;
+use data_components::iceberg::metadata::SnapshotSelection;
+use data_components::iceberg::rest::{IcebergCatalogProvider, RestCatalog};
+use data_components::iceberg::IcebergTableFactory;
+use data_components::Read;
+use datafusion::catalog::CatalogProvider;
+use datafusion::datasource::TableProvider;
+use datafusion::sql::TableReference;
+use secrecy::SecretString;
+use snafu::prelude::*;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::{
+    DataConnector, DataConnectorError, DataConnectorFactory, DataConnectorResult, ParameterSpec,
+    Parameters,
+};
+
+/// The storage parameters that are passed to the object store registry, i.e. for S3.
+const STORAGE_OPTIONS: &[&str] = &["region", "endpoint", "key", "secret", "client_timeout"];
+
+pub struct Iceberg {
+    params: Parameters,
+}
+
+#[derive(Default, Copy, Clone)]
+pub struct IcebergFactory {}
+
+impl IcebergFactory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    #[must_use]
+    pub fn new_arc() -> Arc<dyn DataConnectorFactory> {
+        Arc::new(Self {}) as Arc<dyn DataConnectorFactory>
+    }
+}
+
+const PARAMETERS: &[ParameterSpec] = &[
+    ParameterSpec::connector("region")
+        .description("The AWS region to use for S3 storage.")
+        .secret(),
+    ParameterSpec::connector("endpoint")
+        .description("The S3 compatible endpoint to use for S3 storage.")
+        .secret(),
+    ParameterSpec::connector("key")
+        .description("The AWS access key ID to use for S3 storage.")
+        .secret(),
+    ParameterSpec::connector("secret")
+        .description("The AWS secret access key to use for S3 storage.")
+        .secret(),
+    ParameterSpec::runtime("client_timeout")
+        .description("The timeout setting for object store client."),
+    ParameterSpec::runtime("snapshot_id")
+        .description("The ID of the Iceberg snapshot to read, instead of the current snapshot."),
+    ParameterSpec::runtime("as_of")
+        .description("Read the latest Iceberg snapshot at a point in time, as an RFC 3339 timestamp or milliseconds since the epoch.")
+        .examples(&["2024-09-01T00:00:00Z"]),
+    // REST catalog
+    ParameterSpec::connector("token")
+        .description("The bearer token used to authenticate against the Iceberg REST catalog.")
+        .secret(),
+    ParameterSpec::connector("warehouse")
+        .description("The warehouse to request from the Iceberg REST catalog."),
+];
+
+impl DataConnectorFactory for IcebergFactory {
+    fn create(
+        &self,
+        params: Parameters,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move { Ok(Arc::new(Iceberg { params }) as Arc<dyn DataConnector>) })
+    }
+
+    fn prefix(&self) -> &'static str {
+        "iceberg"
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        PARAMETERS
+    }
+}
+
+impl Iceberg {
+    fn storage_options(&self) -> HashMap<String, SecretString> {
+        STORAGE_OPTIONS
+            .iter()
+            .filter_map(|key| {
+                let value = self.params.get(key).ok()?;
+                Some(((*key).to_string(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn snapshot_selection(&self) -> DataConnectorResult<SnapshotSelection> {
+        if let Some(snapshot_id) = self.params.get("snapshot_id").expose().ok() {
+            let snapshot_id = snapshot_id.trim().parse().map_err(|_| {
+                DataConnectorError::InvalidConfigurationNoSource {
+                    dataconnector: "iceberg".to_string(),
+                    message: format!("The snapshot_id {snapshot_id} is not a valid snapshot ID."),
+                }
+            })?;
+            return Ok(SnapshotSelection::Id(snapshot_id));
+        }
+
+        if let Some(as_of) = self.params.get("as_of").expose().ok() {
+            let as_of = as_of.trim();
+            let timestamp_ms = match chrono::DateTime::parse_from_rfc3339(as_of) {
+                Ok(timestamp) => timestamp.timestamp_millis(),
+                Err(_) => as_of.parse().map_err(|_| {
+                    DataConnectorError::InvalidConfigurationNoSource {
+                        dataconnector: "iceberg".to_string(),
+                        message: format!("The as_of {as_of} is not an RFC 3339 timestamp or milliseconds since the epoch."),
+                    }
+                })?,
+            };
+            return Ok(SnapshotSelection::AsOf(timestamp_ms));
+        }
+
+        Ok(SnapshotSelection::Current)
+    }
+}
+
+#[async_trait]
+impl DataConnector for Iceberg {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let table_factory = IcebergTableFactory::new(
+            default_runtime_env(),
+            self.storage_options(),
+            self.snapshot_selection()?,
+        );
+
+        Read::table_provider(&table_factory, TableReference::bare(dataset.path()), None)
+            .await
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "iceberg",
+            })
+    }
+
+    async fn catalog_provider(
+        self: Arc<Self>,
+        _runtime: &Runtime,
+        catalog: &Catalog,
+    ) -> Option<super::DataConnectorResult<Arc<dyn CatalogProvider>>> {
+        // The catalog_id for the iceberg provider is the URL of the REST catalog, i.e.
+        // `iceberg:http://localhost:8181`
+        let Some(endpoint) = catalog.catalog_id.clone() else {
+            return Some(Err(DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "iceberg".into(),
+                message:
+                    "The Iceberg REST catalog URL is required, i.e. iceberg:http://localhost:8181"
+                        .into(),
+            }));
+        };
+
+        let client = match RestCatalog::try_new(
+            &endpoint,
+            self.params.get("warehouse").expose().ok(),
+            self.params.get("token").ok().cloned(),
+        )
+        .await
+        {
+            Ok(client) => Arc::new(client),
+            Err(e) => {
+                return Some(Err(DataConnectorError::UnableToGetCatalogProvider {
+                    dataconnector: "iceberg".to_string(),
+                    source: Box::new(e),
+                }))
+            }
+        };
+
+        let table_creator = Arc::new(IcebergTableFactory::new(
+            default_runtime_env(),
+            self.storage_options(),
+            SnapshotSelection::Current,
+        )) as Arc<dyn Read>;
+
+        let catalog_provider =
+            match IcebergCatalogProvider::try_new(client, table_creator, catalog.include.clone())
+                .await
+            {
+                Ok(provider) => provider,
+                Err(e) => {
+                    return Some(Err(DataConnectorError::UnableToGetCatalogProvider {
+                        dataconnector: "iceberg".to_string(),
+                        source: Box::new(e),
+                    }))
+                }
+            };
+
+        Some(Ok(Arc::new(catalog_provider) as Arc<dyn CatalogProvider>))
+    }
+}
@@ -0,0 +1,590 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::component::dataset::acceleration::RefreshMode;
+use crate::component::dataset::Dataset;
+use crate::dataaccelerator::metadata::AcceleratedMetadata;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use data_components::cdc::ChangesStream;
+use data_components::kafka::{KafkaConsumer, StartOffset};
+use data_components::kafka_topic::{self, InvalidMessagePolicy, KafkaTopic, MessageDecoder};
+use datafusion::datasource::TableProvider;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{DataConnector, DataConnectorFactory, ParameterSpec, Parameters};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Missing required parameter: kafka_bootstrap_servers"))]
+    MissingBootstrapServers,
+
+    #[snafu(display("Invalid value for kafka_format: '{format}'. Valid values: 'json', 'avro'"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display(
+        "Invalid value for kafka_start_offset: '{start_offset}'. Valid values: 'earliest', 'latest'"
+    ))]
+    InvalidStartOffset { start_offset: String },
+
+    #[snafu(display(
+        "Invalid value for kafka_invalid_messages: '{invalid_messages}'. Valid values: 'stop', 'skip'"
+    ))]
+    InvalidInvalidMessages { invalid_messages: String },
+
+    #[snafu(display(
+        "The 'avro' format requires either kafka_schema_registry_url or kafka_avro_schema"
+    ))]
+    MissingAvroSchema,
+
+    #[snafu(display("Invalid value for kafka_avro_schema: {source}"))]
+    InvalidAvroSchema { source: kafka_topic::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The most messages the schema of a JSON topic is inferred from.
+const MAX_SAMPLE_MESSAGES: usize = 100;
+
+/// How long to wait for more messages to infer the schema from, once one was received.
+const SAMPLE_WAIT: Duration = Duration::from_secs(1);
+
+enum MessageFormat {
+    Json,
+    Avro {
+        schema_registry_url: Option<String>,
+        schema: Option<String>,
+    },
+}
+
+pub struct Kafka {
+    brokers: String,
+    format: MessageFormat,
+    start_offset: StartOffset,
+    consumer_group_id: Option<String>,
+    invalid_messages: InvalidMessagePolicy,
+}
+
+impl Kafka {
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn new(params: Parameters) -> Result<Self> {
+        let brokers = params
+            .get("bootstrap_servers")
+            .expose()
+            .ok()
+            .context(MissingBootstrapServersSnafu)?;
+
+        let format = match params.get("format").expose().ok().unwrap_or("json") {
+            "json" => MessageFormat::Json,
+            "avro" => {
+                let schema_registry_url = params
+                    .get("schema_registry_url")
+                    .expose()
+                    .ok()
+                    .map(str::to_string);
+                let schema = params.get("avro_schema").expose().ok().map(str::to_string);
+                ensure!(
+                    schema_registry_url.is_some() || schema.is_some(),
+                    MissingAvroSchemaSnafu
+                );
+                MessageFormat::Avro {
+                    schema_registry_url,
+                    schema,
+                }
+            }
+            format => return InvalidFormatSnafu { format }.fail(),
+        };
+
+        let start_offset = match params.get("start_offset").expose().ok() {
+            None | Some("earliest") => StartOffset::Earliest,
+            Some("latest") => StartOffset::Latest,
+            Some(start_offset) => return InvalidStartOffsetSnafu { start_offset }.fail(),
+        };
+
+        let invalid_messages = match params.get("invalid_messages").expose().ok() {
+            None | Some("stop") => InvalidMessagePolicy::Stop,
+            Some("skip") => InvalidMessagePolicy::Skip,
+            Some(invalid_messages) => {
+                return InvalidInvalidMessagesSnafu { invalid_messages }.fail()
+            }
+        };
+
+        let kafka = Self {
+            brokers: brokers.to_string(),
+            format,
+            start_offset,
+            consumer_group_id: params
+                .get("consumer_group_id")
+                .expose()
+                .ok()
+                .map(str::to_string),
+            invalid_messages,
+        };
+
+        // Validate the Avro schema before any dataset is loaded.
+        kafka.decoder()?;
+
+        Ok(kafka)
+    }
+
+    fn decoder(&self) -> Result<MessageDecoder> {
+        match &self.format {
+            MessageFormat::Json => Ok(MessageDecoder::Json),
+            MessageFormat::Avro {
+                schema_registry_url: Some(url),
+                ..
+            } => Ok(MessageDecoder::avro_schema_registry(url)),
+            MessageFormat::Avro {
+                schema: Some(schema),
+                ..
+            } => MessageDecoder::avro(schema).context(InvalidAvroSchemaSnafu),
+            MessageFormat::Avro { .. } => MissingAvroSchemaSnafu.fail(),
+        }
+    }
+}
+
+#[derive(Default, Copy, Clone)]
+pub struct KafkaFactory {}
+
+impl KafkaFactory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    #[must_use]
+    pub fn new_arc() -> Arc<dyn DataConnectorFactory> {
+        Arc::new(Self {}) as Arc<dyn DataConnectorFactory>
+    }
+}
+
+const PARAMETERS: &[ParameterSpec] = &[
+    ParameterSpec::connector("bootstrap_servers")
+        .required()
+        .description(
+            "A list of host/port pairs for establishing the initial Kafka cluster connection.",
+        ),
+    ParameterSpec::connector("format")
+        .default("json")
+        .description(
+            "The format of the messages in the topic, either json or avro. The default is json.",
+        ),
+    ParameterSpec::connector("start_offset")
+        .default("earliest")
+        .description(
+            "Where a new consumer group starts reading the topic, either earliest or latest.",
+        ),
+    ParameterSpec::connector("invalid_messages")
+        .default("stop")
+        .description(
+            "What to do with messages that can't be read, either stop to stop reading the topic before them, or skip to skip them with a warning.",
+        ),
+    ParameterSpec::connector("consumer_group_id").description(
+        "The consumer group to read the topic with. A group is generated for the dataset if not set.",
+    ),
+    ParameterSpec::connector("schema_registry_url")
+        .description("The URL of the schema registry that holds the schemas of Avro messages."),
+    ParameterSpec::connector("avro_schema").description(
+        "The schema all Avro messages are written with, when not using a schema registry.",
+    ),
+];
+
+impl DataConnectorFactory for KafkaFactory {
+    fn create(
+        &self,
+        params: Parameters,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let kafka = Kafka::new(params)?;
+            Ok(Arc::new(kafka) as Arc<dyn DataConnector>)
+        })
+    }
+
+    fn prefix(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        PARAMETERS
+    }
+}
+
+#[async_trait]
+impl DataConnector for Kafka {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn resolve_refresh_mode(&self, refresh_mode: Option<RefreshMode>) -> RefreshMode {
+        refresh_mode.unwrap_or(RefreshMode::Append)
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        ensure!(
+            dataset.is_accelerated(),
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "kafka",
+                message: "The Kafka data connector only works with accelerated datasets.",
+            }
+        );
+        let Some(ref acceleration) = dataset.acceleration else {
+            unreachable!("we just checked above that the dataset is accelerated");
+        };
+        ensure!(
+            self.resolve_refresh_mode(acceleration.refresh_mode) == RefreshMode::Append,
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "kafka",
+                message: "The Kafka data connector only works with 'append' refresh mode.",
+            }
+        );
+        ensure!(
+            dataset.time_column.is_none(),
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "kafka",
+                message: "The Kafka data connector doesn't support a time_column.",
+            }
+        );
+
+        let dataset_name = dataset.name.to_string();
+        let topic = dataset.path();
+
+        let metadata = get_metadata_from_accelerator(dataset).await;
+        if let Some(metadata) = &metadata {
+            ensure!(
+                topic == metadata.topic,
+                super::InvalidConfigurationNoSourceSnafu {
+                    dataconnector: "kafka",
+                    message: format!("The topic has changed from {} to {topic} for dataset {dataset_name}. The existing accelerator data may be out of date.", metadata.topic),
+                }
+            );
+        }
+
+        let consumer_group_id = self
+            .consumer_group_id
+            .clone()
+            .or_else(|| metadata.as_ref().map(|m| m.consumer_group_id.clone()))
+            .unwrap_or_else(|| KafkaConsumer::generate_group_id(&dataset_name));
+
+        let kafka_consumer = KafkaConsumer::create_with_start_offset(
+            consumer_group_id,
+            self.brokers.clone(),
+            self.start_offset,
+        )
+        .boxed()
+        .context(super::UnableToGetReadProviderSnafu {
+            dataconnector: "kafka",
+        })?;
+        kafka_consumer
+            .subscribe(&topic)
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "kafka",
+            })?;
+
+        let decoder = self
+            .decoder()
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "kafka",
+            })?;
+
+        let (schema, samples, avro_schema_id) = topic_schema(
+            &kafka_consumer,
+            &decoder,
+            &topic,
+            &dataset_name,
+            metadata.as_ref(),
+        )
+        .await?;
+
+        let new_metadata = KafkaMetadata {
+            consumer_group_id: kafka_consumer.group_id().to_string(),
+            topic,
+            samples,
+            avro_schema_id,
+        };
+        if dataset.is_file_accelerated() {
+            if metadata.as_ref() != Some(&new_metadata) {
+                set_metadata_to_accelerator(dataset, &new_metadata)
+                    .await
+                    .context(super::UnableToGetReadProviderSnafu {
+                        dataconnector: "kafka",
+                    })?;
+            }
+        } else if self.consumer_group_id.is_none() {
+            tracing::warn!(
+                "Dataset {dataset_name} is not file accelerated. The topic is read again from the {} offset on restarts.",
+                match self.start_offset {
+                    StartOffset::Earliest => "earliest",
+                    StartOffset::Latest => "latest",
+                }
+            );
+        }
+
+        Ok(Arc::new(
+            KafkaTopic::new(schema, decoder, kafka_consumer)
+                .with_invalid_message_policy(self.invalid_messages),
+        ))
+    }
+
+    fn supports_append_stream(&self) -> bool {
+        true
+    }
+
+    fn append_stream(&self, table_provider: Arc<dyn TableProvider>) -> Option<ChangesStream> {
+        let kafka_topic = table_provider.as_any().downcast_ref::<KafkaTopic>()?;
+
+        Some(kafka_topic.stream_appends())
+    }
+}
+
+/// The consumer group of a dataset and what its schema was inferred from, so that restarts resume
+/// from the committed offsets with the same schema.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct KafkaMetadata {
+    consumer_group_id: String,
+    topic: String,
+    /// The messages the schema of a JSON topic was inferred from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    samples: Vec<serde_json::Value>,
+    /// The registered schema the schema of an Avro topic was derived from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avro_schema_id: Option<u32>,
+}
+
+async fn get_metadata_from_accelerator(dataset: &Dataset) -> Option<KafkaMetadata> {
+    let accelerated_metadata = AcceleratedMetadata::new(dataset).await?;
+    let metadata = accelerated_metadata.get_metadata().await?;
+    Some(metadata)
+}
+
+async fn set_metadata_to_accelerator(
+    dataset: &Dataset,
+    metadata: &KafkaMetadata,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let accelerated_metadata = AcceleratedMetadata::new_create_if_not_exists(dataset).await?;
+    accelerated_metadata.set_metadata(metadata).await
+}
+
+/// The schema of a topic, with the samples or registered schema id it was derived from.
+///
+/// Avro topics have the schema of their Avro schema, and the schema of JSON topics is inferred from
+/// sample messages.
+async fn topic_schema(
+    kafka_consumer: &KafkaConsumer,
+    decoder: &MessageDecoder,
+    topic: &str,
+    dataset_name: &str,
+    metadata: Option<&KafkaMetadata>,
+) -> super::DataConnectorResult<(SchemaRef, Vec<serde_json::Value>, Option<u32>)> {
+    Ok(match decoder {
+        MessageDecoder::Avro(avro_schema) => (
+            kafka_topic::avro_to_arrow_schema(avro_schema)
+                .boxed()
+                .context(super::UnableToGetReadProviderSnafu {
+                    dataconnector: "kafka",
+                })?,
+            vec![],
+            None,
+        ),
+        MessageDecoder::AvroSchemaRegistry(registry) => {
+            let id = match metadata.and_then(|m| m.avro_schema_id) {
+                Some(id) => id,
+                None => {
+                    let payloads = sample_payloads(kafka_consumer, topic, dataset_name, 1).await?;
+                    kafka_topic::schema_id(payloads.first().map(Vec::as_slice).unwrap_or_default())
+                        .boxed()
+                        .context(super::UnableToGetReadProviderSnafu {
+                            dataconnector: "kafka",
+                        })?
+                }
+            };
+            let schema = registry.arrow_schema(id).await.boxed().context(
+                super::UnableToGetReadProviderSnafu {
+                    dataconnector: "kafka",
+                },
+            )?;
+            (schema, vec![], Some(id))
+        }
+        MessageDecoder::Json => {
+            let samples = match metadata {
+                Some(metadata) if !metadata.samples.is_empty() => metadata.samples.clone(),
+                _ => {
+                    let payloads =
+                        sample_payloads(kafka_consumer, topic, dataset_name, MAX_SAMPLE_MESSAGES)
+                            .await?;
+                    let mut samples = Vec::with_capacity(payloads.len());
+                    for payload in &payloads {
+                        samples.push(decoder.decode(payload).await.boxed().context(
+                            super::UnableToGetReadProviderSnafu {
+                                dataconnector: "kafka",
+                            },
+                        )?);
+                    }
+                    samples
+                }
+            };
+            let schema = kafka_topic::infer_schema(&samples).boxed().context(
+                super::UnableToGetReadProviderSnafu {
+                    dataconnector: "kafka",
+                },
+            )?;
+            (schema, samples, None)
+        }
+    })
+}
+
+/// Receives the next messages of the topic to infer the schema from, and moves the consumer back so
+/// that the messages are also appended to the dataset.
+///
+/// Waits for the first message, then receives up to `max_messages` that are already available.
+async fn sample_payloads(
+    kafka_consumer: &KafkaConsumer,
+    topic: &str,
+    dataset_name: &str,
+    max_messages: usize,
+) -> super::DataConnectorResult<Vec<Vec<u8>>> {
+    tracing::info!(
+        "Waiting for a message on Kafka topic {topic} to infer the schema of dataset {dataset_name}"
+    );
+
+    let mut payloads = vec![];
+    // The offset of the first sampled message of each partition.
+    let mut offsets: HashMap<i32, i64> = HashMap::new();
+    let mut messages = Box::pin(kafka_consumer.stream_payloads());
+    while payloads.len() < max_messages {
+        let msg = if payloads.is_empty() {
+            messages.next().await
+        } else {
+            match tokio::time::timeout(SAMPLE_WAIT, messages.next()).await {
+                Ok(msg) => msg,
+                Err(_) => break,
+            }
+        };
+
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => {
+                return Err(e).boxed().context(super::UnableToGetReadProviderSnafu {
+                    dataconnector: "kafka",
+                });
+            }
+            None if payloads.is_empty() => {
+                return Err(super::DataConnectorError::UnableToGetReadProvider {
+                    dataconnector: "kafka".to_string(),
+                    source: "No message received from Kafka".into(),
+                });
+            }
+            None => break,
+        };
+
+        offsets.entry(msg.partition()).or_insert(msg.offset());
+        payloads.push(msg.value().clone());
+    }
+    drop(messages);
+
+    for (partition, offset) in offsets {
+        kafka_consumer
+            .seek(topic, partition, offset)
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "kafka",
+            })?;
+    }
+
+    Ok(payloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion_table_providers::util::secrets::to_secret_map;
+    use std::collections::HashMap;
+
+    fn params(values: &[(&str, &str)]) -> Parameters {
+        let params: HashMap<String, String> = values
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        Parameters::new(
+            to_secret_map(params).into_iter().collect(),
+            "kafka",
+            PARAMETERS,
+        )
+    }
+
+    #[test]
+    fn test_parameters() {
+        let kafka = Kafka::new(params(&[("bootstrap_servers", "localhost:9092")]))
+            .expect("valid parameters");
+        assert!(matches!(kafka.format, MessageFormat::Json));
+        assert_eq!(kafka.start_offset, StartOffset::Earliest);
+        assert!(kafka.consumer_group_id.is_none());
+
+        let kafka = Kafka::new(params(&[
+            ("bootstrap_servers", "localhost:9092"),
+            ("start_offset", "latest"),
+            ("consumer_group_id", "spice"),
+            ("format", "avro"),
+            ("schema_registry_url", "http://localhost:8081"),
+        ]))
+        .expect("valid parameters");
+        assert_eq!(kafka.start_offset, StartOffset::Latest);
+        assert_eq!(kafka.consumer_group_id.as_deref(), Some("spice"));
+        assert!(matches!(
+            kafka.decoder(),
+            Ok(MessageDecoder::AvroSchemaRegistry(_))
+        ));
+
+        assert!(matches!(
+            Kafka::new(params(&[])),
+            Err(Error::MissingBootstrapServers)
+        ));
+        assert!(matches!(
+            Kafka::new(params(&[
+                ("bootstrap_servers", "localhost:9092"),
+                ("start_offset", "beginning"),
+            ])),
+            Err(Error::InvalidStartOffset { .. })
+        ));
+        assert!(matches!(
+            Kafka::new(params(&[
+                ("bootstrap_servers", "localhost:9092"),
+                ("format", "avro"),
+            ])),
+            Err(Error::MissingAvroSchema)
+        ));
+        assert!(matches!(
+            Kafka::new(params(&[
+                ("bootstrap_servers", "localhost:9092"),
+                ("format", "avro"),
+                ("avro_schema", "{"),
+            ])),
+            Err(Error::InvalidAvroSchema { .. })
+        ));
+    }
+}
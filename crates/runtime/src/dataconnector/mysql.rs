@@ -14,9 +14,13 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::component::catalog::Catalog;
 use crate::component::dataset::Dataset;
+use crate::Runtime;
 use async_trait::async_trait;
+use data_components::catalog::{query_table_names, LazyCatalogProvider};
 use data_components::Read;
+use datafusion::catalog::CatalogProvider;
 use datafusion::datasource::TableProvider;
 use datafusion_table_providers::mysql::MySQLTableFactory;
 use datafusion_table_providers::sql::db_connection_pool::mysqlpool::MySQLConnectionPool;
@@ -40,8 +44,18 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+type MySQLPool =
+    dyn DbConnectionPool<mysql_async::Conn, &'static (dyn ToValue + Sync)> + Send + Sync;
+
+/// Lists the tables and views of all databases, for catalogs. System databases are excluded.
+const LIST_TABLES_QUERY: &str =
+    "SELECT CAST(table_schema AS CHAR), CAST(table_name AS CHAR) FROM information_schema.tables
+WHERE table_schema NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')
+AND table_type IN ('BASE TABLE', 'VIEW')";
+
 pub struct MySQL {
-    mysql_factory: MySQLTableFactory,
+    pool: Arc<MySQLPool>,
+    mysql_factory: Arc<MySQLTableFactory>,
 }
 
 #[derive(Default, Copy, Clone)]
@@ -76,19 +90,18 @@ impl DataConnectorFactory for MySQLFactory {
         params: Parameters,
     ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
         Box::pin(async move {
-            let pool: Arc<
-                dyn DbConnectionPool<mysql_async::Conn, &'static (dyn ToValue + Sync)>
-                    + Send
-                    + Sync,
-            > = Arc::new(
+            let pool: Arc<MySQLPool> = Arc::new(
                 MySQLConnectionPool::new(params.to_secret_map())
                     .await
                     .context(UnableToCreateMySQLConnectionPoolSnafu)?,
             );
 
-            let mysql_factory = MySQLTableFactory::new(pool);
+            let mysql_factory = Arc::new(MySQLTableFactory::new(Arc::clone(&pool)));
 
-            Ok(Arc::new(MySQL { mysql_factory }) as Arc<dyn DataConnector>)
+            Ok(Arc::new(MySQL {
+                pool,
+                mysql_factory,
+            }) as Arc<dyn DataConnector>)
         })
     }
 
@@ -111,12 +124,57 @@ impl DataConnector for MySQL {
         &self,
         dataset: &Dataset,
     ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
-        Ok(
-            Read::table_provider(&self.mysql_factory, dataset.path().into(), dataset.schema())
-                .await
-                .context(super::UnableToGetReadProviderSnafu {
-                    dataconnector: "mysql",
-                })?,
+        Ok(Read::table_provider(
+            self.mysql_factory.as_ref(),
+            dataset.path().into(),
+            dataset.schema(),
         )
+        .await
+        .context(super::UnableToGetReadProviderSnafu {
+            dataconnector: "mysql",
+        })?)
+    }
+
+    async fn catalog_provider(
+        self: Arc<Self>,
+        _runtime: &Runtime,
+        catalog: &Catalog,
+    ) -> Option<super::DataConnectorResult<Arc<dyn CatalogProvider>>> {
+        if catalog.catalog_id.is_some() {
+            return Some(Err(super::DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "mysql".into(),
+                message: "Catalog ID is not supported for the MySQL data connector. The catalog includes the tables of all databases, as schemas.".into(),
+            }));
+        }
+
+        let tables = match self.list_tables().await {
+            Ok(tables) => tables,
+            Err(source) => {
+                return Some(Err(super::DataConnectorError::UnableToGetCatalogProvider {
+                    dataconnector: "mysql".to_string(),
+                    source,
+                }))
+            }
+        };
+
+        let table_creator = Arc::clone(&self.mysql_factory) as Arc<dyn Read>;
+        Some(Ok(Arc::new(LazyCatalogProvider::new(
+            tables,
+            table_creator,
+            catalog.include.as_ref(),
+        )) as Arc<dyn CatalogProvider>))
+    }
+}
+
+impl MySQL {
+    async fn list_tables(
+        &self,
+    ) -> std::result::Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.pool.connect().await?;
+        let Some(conn) = conn.as_async() else {
+            return Err("Unable to list tables, the MySQL connection is not async".into());
+        };
+
+        query_table_names(conn, LIST_TABLES_QUERY).await
     }
 }
@@ -14,21 +14,35 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::component::catalog::Catalog;
+use crate::component::dataset::acceleration::{Acceleration, Engine, RefreshMode};
 use crate::component::dataset::Dataset;
+use crate::dataaccelerator::metadata::AcceleratedMetadata;
+use crate::Runtime;
 use async_trait::async_trait;
+use data_components::catalog::{query_table_names, LazyCatalogProvider};
+use data_components::cdc::{ChangesStream, Checkpoint};
+use data_components::postgres::replication::{
+    create_replication_slot_if_not_exists, has_full_replica_identity, PostgresReplication,
+};
 use data_components::Read;
+use datafusion::catalog::CatalogProvider;
 use datafusion::datasource::TableProvider;
+use datafusion::sql::TableReference;
 use datafusion_table_providers::postgres::PostgresTableFactory;
 use datafusion_table_providers::sql::db_connection_pool::dbconnection;
 use datafusion_table_providers::sql::db_connection_pool::{
     postgrespool::{self, PostgresConnectionPool},
-    Error as DbConnectionPoolError,
+    DbConnectionPool, Error as DbConnectionPoolError,
 };
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::any::Any;
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
 
 use super::{DataConnector, DataConnectorError, DataConnectorFactory, ParameterSpec, Parameters};
 
@@ -38,8 +52,63 @@ pub enum Error {
     UnableToCreatePostgresConnectionPool { source: DbConnectionPoolError },
 }
 
+/// Lists the tables and views of the database, for catalogs. Tables in system schemas (i.e.
+/// `pg_catalog`) are excluded.
+const LIST_TABLES_QUERY: &str = r"SELECT table_schema::text, table_name::text FROM information_schema.tables
+WHERE table_schema <> 'information_schema' AND table_schema NOT LIKE 'pg\_%'
+AND table_type IN ('BASE TABLE', 'VIEW', 'FOREIGN')";
+
+/// The dataset that streams changes from each replication slot, and the id of its claim. A slot
+/// has a single position, so datasets that stream changes from the same slot would acknowledge
+/// each other's changes.
+static REPLICATION_SLOTS: LazyLock<Mutex<HashMap<String, (String, u64)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_REPLICATION_SLOT_CLAIM: AtomicU64 = AtomicU64::new(0);
+
+/// Reserves a replication slot for a dataset, until the connector that claimed it is dropped.
+struct ReplicationSlotClaim {
+    slot: String,
+    id: u64,
+}
+
+impl ReplicationSlotClaim {
+    /// Reserves `slot` for `dataset`, or returns the name of the other dataset that uses it. A
+    /// dataset that is reloaded claims its slot again before its previous connector is dropped.
+    fn try_claim(slot: &str, dataset: &str) -> Result<Self, String> {
+        let mut slots = REPLICATION_SLOTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((other, _)) = slots.get(slot).filter(|(other, _)| other != dataset) {
+            return Err(other.clone());
+        }
+
+        let id = NEXT_REPLICATION_SLOT_CLAIM.fetch_add(1, Ordering::Relaxed);
+        slots.insert(slot.to_string(), (dataset.to_string(), id));
+        Ok(Self {
+            slot: slot.to_string(),
+            id,
+        })
+    }
+}
+
+impl Drop for ReplicationSlotClaim {
+    fn drop(&mut self) {
+        let mut slots = REPLICATION_SLOTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if slots.get(&self.slot).is_some_and(|(_, id)| *id == self.id) {
+            slots.remove(&self.slot);
+        }
+    }
+}
+
 pub struct Postgres {
-    postgres_factory: PostgresTableFactory,
+    pool: Arc<PostgresConnectionPool>,
+    postgres_factory: Arc<PostgresTableFactory>,
+    replication_slot: Option<String>,
+    publication: Option<String>,
+    replication_slot_claim: Mutex<Option<ReplicationSlotClaim>>,
 }
 
 #[derive(Default, Copy, Clone)]
@@ -69,6 +138,12 @@ const PARAMETERS: &[ParameterSpec] = &[
     ParameterSpec::runtime("connection_pool_size")
         .description("The maximum number of connections created in the connection pool")
         .default("10"),
+    // Logical replication, for the 'changes' refresh mode
+    ParameterSpec::connector("replication_slot")
+        .description("The logical replication slot to stream changes from, with the 'changes' refresh mode. The slot is created with the pgoutput plugin if it doesn't exist."),
+    ParameterSpec::connector("publication")
+        .description("The publication that includes the table, to stream its changes with the 'changes' refresh mode.")
+        .examples(&["spice_publication"]),
 ];
 
 impl DataConnectorFactory for PostgresFactory {
@@ -77,10 +152,24 @@ impl DataConnectorFactory for PostgresFactory {
         params: Parameters,
     ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
         Box::pin(async move {
+            let replication_slot = params
+                .get("replication_slot")
+                .expose()
+                .ok()
+                .map(str::to_string);
+            let publication = params.get("publication").expose().ok().map(str::to_string);
+
             match PostgresConnectionPool::new(params.to_secret_map()).await {
                 Ok(pool) => {
-                    let postgres_factory = PostgresTableFactory::new(Arc::new(pool));
-                    Ok(Arc::new(Postgres { postgres_factory }) as Arc<dyn DataConnector>)
+                    let pool = Arc::new(pool);
+                    let postgres_factory = Arc::new(PostgresTableFactory::new(Arc::clone(&pool)));
+                    Ok(Arc::new(Postgres {
+                        pool,
+                        postgres_factory,
+                        replication_slot,
+                        publication,
+                        replication_slot_claim: Mutex::new(None),
+                    }) as Arc<dyn DataConnector>)
                 }
                 Err(e) => match e {
                     postgrespool::Error::InvalidUsernameOrPassword { .. } => Err(
@@ -129,9 +218,72 @@ impl DataConnector for Postgres {
     async fn read_provider(
         &self,
         dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let provider = self.table_provider(dataset).await?;
+
+        if let Some(acceleration) = &dataset.acceleration {
+            if dataset.is_accelerated()
+                && self.resolve_refresh_mode(acceleration.refresh_mode) == RefreshMode::Changes
+            {
+                return self
+                    .replication_provider(dataset, acceleration, provider)
+                    .await;
+            }
+        }
+
+        Ok(provider)
+    }
+
+    fn supports_changes_stream(&self) -> bool {
+        true
+    }
+
+    fn changes_stream(&self, table_provider: Arc<dyn TableProvider>) -> Option<ChangesStream> {
+        let replication = table_provider
+            .as_any()
+            .downcast_ref::<PostgresReplication>()?;
+
+        Some(replication.stream_changes())
+    }
+
+    async fn catalog_provider(
+        self: Arc<Self>,
+        _runtime: &Runtime,
+        catalog: &Catalog,
+    ) -> Option<super::DataConnectorResult<Arc<dyn CatalogProvider>>> {
+        if catalog.catalog_id.is_some() {
+            return Some(Err(DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "postgres".into(),
+                message: "Catalog ID is not supported for the Postgres data connector. The catalog includes the tables of the configured database.".into(),
+            }));
+        }
+
+        let tables = match self.list_tables().await {
+            Ok(tables) => tables,
+            Err(source) => {
+                return Some(Err(DataConnectorError::UnableToGetCatalogProvider {
+                    dataconnector: "postgres".to_string(),
+                    source,
+                }))
+            }
+        };
+
+        let table_creator = Arc::clone(&self.postgres_factory) as Arc<dyn Read>;
+        Some(Ok(Arc::new(LazyCatalogProvider::new(
+            tables,
+            table_creator,
+            catalog.include.as_ref(),
+        )) as Arc<dyn CatalogProvider>))
+    }
+}
+
+impl Postgres {
+    async fn table_provider(
+        &self,
+        dataset: &Dataset,
     ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
         match Read::table_provider(
-            &self.postgres_factory,
+            self.postgres_factory.as_ref(),
             dataset.path().into(),
             dataset.schema(),
         )
@@ -160,4 +312,184 @@ impl DataConnector for Postgres {
             }
         }
     }
+
+    /// A provider that streams the changes of the table from a logical replication slot, to
+    /// refresh accelerations incrementally.
+    async fn replication_provider(
+        &self,
+        dataset: &Dataset,
+        acceleration: &Acceleration,
+        provider: Arc<dyn TableProvider>,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let (Some(slot), Some(publication)) = (&self.replication_slot, &self.publication) else {
+            return Err(DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "postgres".to_string(),
+                message: "The 'changes' refresh mode requires the pg_replication_slot and pg_publication parameters.".to_string(),
+            });
+        };
+        ensure!(
+            acceleration.engine != Engine::Arrow,
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "postgres",
+                message: "The 'changes' refresh mode of the Postgres data connector only works with non-Arrow acceleration engines.",
+            }
+        );
+        let primary_keys = acceleration
+            .primary_key
+            .as_ref()
+            .map(|primary_key| {
+                primary_key
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        ensure!(
+            !primary_keys.is_empty(),
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "postgres",
+                message: "The 'changes' refresh mode of the Postgres data connector requires a primary_key in the acceleration, to apply updates and deletes.",
+            }
+        );
+
+        let dataset_name = dataset.name.to_string();
+        let claim = ReplicationSlotClaim::try_claim(slot, &dataset_name).map_err(|other| {
+            DataConnectorError::InvalidConfigurationNoSource {
+                dataconnector: "postgres".to_string(),
+                message: format!("The replication slot {slot} is already used by the dataset {other}. Use a different pg_replication_slot for each dataset."),
+            }
+        })?;
+        *self
+            .replication_slot_claim
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(claim);
+        let table_reference = TableReference::from(dataset.path());
+
+        // Updates only include the unchanged TOASTed values with the old row.
+        let full_replica_identity = has_full_replica_identity(&self.pool, &table_reference)
+            .await
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "postgres",
+            })?;
+        ensure!(
+            full_replica_identity,
+            super::InvalidConfigurationNoSourceSnafu {
+                dataconnector: "postgres",
+                message: format!(
+                    "The 'changes' refresh mode of the Postgres data connector requires the table {table_reference} to have a full replica identity, so updates include unchanged large values. Run `ALTER TABLE {table_reference} REPLICA IDENTITY FULL`."
+                ),
+            }
+        );
+
+        let created = create_replication_slot_if_not_exists(&self.pool, slot)
+            .await
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "postgres",
+            })?;
+
+        let start_lsn = match get_metadata_from_accelerator(dataset).await {
+            Some(metadata) if metadata.replication_slot != *slot => {
+                tracing::warn!(
+                    "The replication slot has changed from {} to {slot} for dataset {dataset_name}. Reading the table again.",
+                    metadata.replication_slot
+                );
+                None
+            }
+            Some(_) if created => {
+                tracing::warn!(
+                    "The replication slot {slot} was created for dataset {dataset_name}, changes since it was dropped are lost. Reading the table again."
+                );
+                None
+            }
+            Some(metadata) => {
+                tracing::debug!(
+                    "Resuming changes of dataset {dataset_name} after LSN {}",
+                    metadata.lsn
+                );
+                Some(metadata.lsn)
+            }
+            None => None,
+        };
+
+        let mut replication = PostgresReplication::new(
+            provider,
+            Arc::clone(&self.pool),
+            table_reference,
+            slot.clone(),
+            publication.clone(),
+            primary_keys,
+        )
+        .with_start_lsn(start_lsn)
+        .with_checkpoint(lsn_checkpoint(dataset.clone(), slot.clone()));
+        if let Some(check_interval) = dataset.refresh_check_interval() {
+            replication = replication.with_poll_interval(check_interval);
+        }
+
+        Ok(Arc::new(replication))
+    }
+
+    async fn list_tables(
+        &self,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.pool.connect().await?;
+        let Some(conn) = conn.as_async() else {
+            return Err("Unable to list tables, the Postgres connection is not async".into());
+        };
+
+        query_table_names(conn, LIST_TABLES_QUERY).await
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PostgresReplicationMetadata {
+    replication_slot: String,
+    /// The LSN of the last transaction whose changes are applied to the accelerator.
+    lsn: u64,
+}
+
+async fn get_metadata_from_accelerator(dataset: &Dataset) -> Option<PostgresReplicationMetadata> {
+    let accelerated_metadata = AcceleratedMetadata::new(dataset).await?;
+    let metadata = accelerated_metadata.get_metadata().await?;
+    Some(metadata)
+}
+
+/// Saves the applied LSNs to the accelerator, to resume after the last one on restarts.
+fn lsn_checkpoint(dataset: Dataset, replication_slot: String) -> Checkpoint {
+    let writer = AcceleratedMetadata::spawn_writer(dataset);
+
+    Arc::new(move |lsn| {
+        writer.send_replace(Some(PostgresReplicationMetadata {
+            replication_slot: replication_slot.clone(),
+            lsn,
+        }));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_slot_claim() {
+        let claim = ReplicationSlotClaim::try_claim("test_slot", "orders").expect("slot is free");
+        assert_eq!(
+            ReplicationSlotClaim::try_claim("test_slot", "customers").err(),
+            Some("orders".to_string()),
+            "the slot is used by another dataset"
+        );
+
+        // A reloaded dataset claims its slot again before its previous connector is dropped
+        let reloaded =
+            ReplicationSlotClaim::try_claim("test_slot", "orders").expect("same dataset");
+        drop(claim);
+        assert!(ReplicationSlotClaim::try_claim("test_slot", "customers").is_err());
+
+        drop(reloaded);
+        assert!(
+            ReplicationSlotClaim::try_claim("test_slot", "customers").is_ok(),
+            "the slot is released with the connector of its dataset"
+        );
+    }
 }
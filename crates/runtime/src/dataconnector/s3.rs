@@ -82,8 +82,16 @@ const PARAMETERS: &[ParameterSpec] = &[
         .description("Set a limit in terms of records to scan to infer the schema."),
     ParameterSpec::runtime("csv_delimiter")
         .description("The character separating values within a row."),
+    ParameterSpec::runtime("json_schema_infer_max_records")
+        .description("Set a limit in terms of records to scan to infer the schema of JSON files."),
     ParameterSpec::runtime("file_compression_type")
         .description("The type of compression used on the file. Supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
+    ParameterSpec::runtime("hive_partitioning_enabled")
+        .description("Set true to infer partition columns from Hive-style paths, i.e. year=2024/month=09/."),
+    ParameterSpec::runtime("hive_partitioning_column_types")
+        .description("Explicit types of partition columns, as comma separated <column>:<type> pairs, i.e. year:int,region:string."),
+    ParameterSpec::runtime("file_schema")
+        .description("The schema of the files, as comma separated <column>:<type> pairs, i.e. id:bigint,name:string. Used instead of inferring it from the files, i.e. to write to an empty path."),
 ];
 
 impl DataConnectorFactory for S3Factory {
@@ -131,6 +139,10 @@ impl ListingTableConnector for S3 {
         &self.params
     }
 
+    fn supports_writes(&self) -> bool {
+        true
+    }
+
     fn get_object_store_url(&self, dataset: &Dataset) -> DataConnectorResult<Url> {
         let mut s3_url =
             Url::parse(&dataset.from)
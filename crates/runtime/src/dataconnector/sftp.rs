@@ -79,8 +79,14 @@ const PARAMETERS: &[ParameterSpec] = &[
         .description("Set a limit in terms of records to scan to infer the schema."),
     ParameterSpec::runtime("csv_delimiter")
         .description("The character separating values within a row."),
+    ParameterSpec::runtime("json_schema_infer_max_records")
+        .description("Set a limit in terms of records to scan to infer the schema of JSON files."),
     ParameterSpec::runtime("file_compression_type")
         .description("The type of compression used on the file. Supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
+    ParameterSpec::runtime("hive_partitioning_enabled")
+        .description("Set true to infer partition columns from Hive-style paths, i.e. year=2024/month=09/."),
+    ParameterSpec::runtime("hive_partitioning_column_types")
+        .description("Explicit types of partition columns, as comma separated <column>:<type> pairs, i.e. year:int,region:string."),
 ];
 
 impl DataConnectorFactory for SFTPFactory {
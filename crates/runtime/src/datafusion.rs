@@ -16,18 +16,21 @@ limitations under the License.
 
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use crate::accelerated_table::refresh;
 use crate::accelerated_table::{refresh::Refresh, AcceleratedTable, Retention};
 use crate::component::dataset::acceleration::RefreshMode;
 use crate::component::dataset::{Dataset, Mode};
-use crate::dataaccelerator::{self, create_accelerator_table};
+use crate::dataaccelerator::{
+    self, create_accelerator_table, get_accelerator_engine, snapshot::Snapshotter,
+};
 use crate::dataconnector::sink::SinkConnector;
 use crate::dataconnector::{DataConnector, DataConnectorError};
 use crate::dataupdate::{
     DataUpdate, StreamingDataUpdate, StreamingDataUpdateExecutionPlan, UpdateType,
 };
+use crate::embeddings::table::EmbeddingTable;
 use crate::object_store_registry::default_runtime_env;
 use crate::secrets::Secrets;
 use crate::{embeddings, view};
@@ -49,8 +52,9 @@ use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
 use datafusion::sql::{sqlparser, TableReference};
 use datafusion_federation::{FederatedTableProviderAdaptor, FederationAnalyzerRule};
 use extension::{bytes_processed::BytesProcessedAnalyzerRule, SpiceQueryPlanner};
-use query::{Protocol, QueryBuilder};
+use query::{jobs::QueryJobs, Protocol, QueryBuilder};
 use snafu::prelude::*;
+use spicepod::component::embeddings::DistanceMetric;
 use tokio::spawn;
 use tokio::sync::oneshot;
 use tokio::sync::RwLock as TokioRwLock;
@@ -227,6 +231,12 @@ pub struct DataFusion {
 
     /// Has the initial load of the data been completed? It is the responsibility of the caller to call `mark_initial_load_complete` when the initial load is complete.
     initial_load_complete: Mutex<bool>,
+
+    query_jobs: Arc<QueryJobs>,
+
+    /// The datasets whose acceleration was restored from a snapshot on startup, until their
+    /// accelerated table is created.
+    bootstrapped_datasets: Mutex<HashSet<TableReference>>,
 }
 
 impl DataFusion {
@@ -268,7 +278,13 @@ impl DataFusion {
         let ctx = SessionContext::new_with_state(state);
         ctx.add_analyzer_rule(Arc::new(FederationAnalyzerRule::new()));
         ctx.add_analyzer_rule(Arc::new(BytesProcessedAnalyzerRule::new()));
-        ctx.register_udf(embeddings::array_distance::ArrayDistance::new().into());
+        for metric in [
+            DistanceMetric::L2,
+            DistanceMetric::Cosine,
+            DistanceMetric::Dot,
+        ] {
+            ctx.register_udf(embeddings::array_distance::ArrayDistance::with_metric(metric).into());
+        }
         ctx.register_udf(crate::datafusion::udf::Greatest::new().into());
         ctx.register_udf(crate::datafusion::udf::Least::new().into());
         let catalog = MemoryCatalogProvider::new();
@@ -305,6 +321,8 @@ impl DataFusion {
             cache_provider: RwLock::new(cache_provider),
             initial_load_complete: Mutex::new(false),
             pending_sink_tables: TokioRwLock::new(Vec::new()),
+            query_jobs: Arc::new(QueryJobs::default()),
+            bootstrapped_datasets: Mutex::new(HashSet::new()),
         }
     }
 
@@ -372,6 +390,28 @@ impl DataFusion {
             .flatten()
     }
 
+    /// Looks up a table of the default catalog synchronously, i.e. while planning. Tables of other
+    /// catalogs may be resolved over the network, and aren't returned.
+    #[must_use]
+    pub fn get_registered_table(
+        &self,
+        table_reference: &TableReference,
+    ) -> Option<Arc<dyn TableProvider>> {
+        if table_reference
+            .catalog()
+            .is_some_and(|catalog| catalog != SPICE_DEFAULT_CATALOG)
+        {
+            return None;
+        }
+
+        self.ctx
+            .catalog(SPICE_DEFAULT_CATALOG)?
+            .schema(table_reference.schema().unwrap_or(SPICE_DEFAULT_SCHEMA))?
+            .as_any()
+            .downcast_ref::<SpiceSchemaProvider>()?
+            .get_table(table_reference.table())
+    }
+
     pub fn register_runtime_table(
         &self,
         table_name: TableReference,
@@ -459,6 +499,23 @@ impl DataFusion {
         Ok(())
     }
 
+    /// Marks the acceleration of `dataset_name` as restored from a snapshot, so that its initial
+    /// refresh is skipped.
+    pub(crate) fn mark_bootstrapped(&self, dataset_name: TableReference) {
+        if let Ok(mut datasets) = self.bootstrapped_datasets.lock() {
+            datasets.insert(dataset_name);
+        }
+    }
+
+    /// Whether the acceleration of `dataset_name` was restored from a snapshot. Only returns `true`
+    /// once per bootstrap.
+    fn take_bootstrapped(&self, dataset_name: &TableReference) -> bool {
+        self.bootstrapped_datasets
+            .lock()
+            .map(|mut datasets| datasets.remove(dataset_name))
+            .unwrap_or(false)
+    }
+
     #[must_use]
     pub fn is_writable(&self, table_reference: &TableReference) -> bool {
         if let Ok(writers) = self.data_writers.read() {
@@ -716,12 +773,27 @@ impl DataFusion {
         if let Some(check_interval) = dataset.refresh_check_interval() {
             refresh = refresh.check_interval(check_interval);
         }
+        if let Some(cron) = dataset.refresh_cron() {
+            refresh = refresh.cron(cron);
+        }
         if let Some(max_jitter) = dataset.refresh_max_jitter() {
             refresh = refresh.max_jitter(max_jitter);
         }
         if let Some(append_overlap) = acceleration_settings.refresh_append_overlap {
             refresh = refresh.append_overlap(append_overlap);
         }
+        if self.take_bootstrapped(&dataset.name) {
+            refresh = refresh.restored_from_snapshot(true);
+        }
+        if refresh_mode == RefreshMode::Upsert {
+            if let Some(primary_key) = &acceleration_settings.primary_key {
+                refresh =
+                    refresh.primary_key(primary_key.iter().map(ToString::to_string).collect());
+            }
+            if let Some(interval) = acceleration_settings.refresh_delete_check_interval {
+                refresh = refresh.delete_check_interval(interval);
+            }
+        }
         refresh
             .validate_time_format(dataset.name.to_string(), &source_schema)
             .context(InvalidTimeColumnTimeFormatSnafu)?;
@@ -748,6 +820,17 @@ impl DataFusion {
             accelerated_table_builder.disable_query_push_down();
         }
 
+        if let Some(accelerator) = get_accelerator_engine(acceleration_settings.engine).await {
+            match Snapshotter::try_new(dataset, accelerator) {
+                Ok(snapshotter) => {
+                    accelerated_table_builder.snapshotter(snapshotter.map(Arc::new));
+                }
+                Err(e) => {
+                    tracing::warn!("Snapshots are disabled for dataset {}: {e}", dataset.name);
+                }
+            }
+        }
+
         if refresh_mode == RefreshMode::Changes {
             let source = Box::leak(Box::new(Arc::clone(&source)));
             let changes_stream = source.changes_stream(Arc::clone(&source_table_provider));
@@ -759,7 +842,7 @@ impl DataFusion {
 
         if refresh_mode == RefreshMode::Append && dataset.time_column.is_none() {
             let source = Box::leak(Box::new(source));
-            let append_stream = source.append_stream(source_table_provider);
+            let append_stream = source.append_stream(Arc::clone(&source_table_provider));
             if let Some(append_stream) = append_stream {
                 accelerated_table_builder.append_stream(append_stream);
             } else {
@@ -770,7 +853,29 @@ impl DataFusion {
             };
         }
 
-        Ok(accelerated_table_builder.build().await)
+        let (accelerated_table, is_ready) = accelerated_table_builder.build().await;
+
+        if let Some(embedding_table) = source_table_provider
+            .as_any()
+            .downcast_ref::<EmbeddingTable>()
+        {
+            for vector_index in embedding_table.get_vector_indexes() {
+                tokio::spawn(vector_index.maintain(
+                    dataset.name.to_string(),
+                    accelerated_table.get_accelerator(),
+                    accelerated_table.refresher().subscribe_to_data_updates(),
+                ));
+            }
+            for full_text_index in embedding_table.get_full_text_indexes() {
+                tokio::spawn(full_text_index.maintain(
+                    dataset.name.to_string(),
+                    accelerated_table.get_accelerator(),
+                    accelerated_table.refresher().subscribe_to_data_updates(),
+                ));
+            }
+        }
+
+        Ok((accelerated_table, is_ready))
     }
 
     pub fn cache_provider(&self) -> Option<Arc<QueryResultsCacheProvider>> {
@@ -781,6 +886,13 @@ impl DataFusion {
         provider.clone()
     }
 
+    /// The queries running in the background, i.e. submitted through Flight `poll_flight_info` or
+    /// the `/v1/sql/jobs` HTTP API.
+    #[must_use]
+    pub fn query_jobs(&self) -> Arc<QueryJobs> {
+        Arc::clone(&self.query_jobs)
+    }
+
     async fn register_accelerated_table(
         &self,
         dataset: Arc<Dataset>,
@@ -824,6 +936,23 @@ impl DataFusion {
         .fail()?
     }
 
+    /// When the next scheduled refresh of an accelerated dataset will start, if one is scheduled.
+    pub async fn next_scheduled_refresh(
+        &self,
+        dataset_name: &TableReference,
+    ) -> Option<SystemTime> {
+        let table = self
+            .get_accelerated_table_provider(&dataset_name.to_string())
+            .await
+            .ok()?;
+
+        table
+            .as_any()
+            .downcast_ref::<AcceleratedTable>()?
+            .refresher()
+            .next_scheduled_refresh()
+    }
+
     pub async fn update_refresh_sql(
         &self,
         dataset_name: TableReference,
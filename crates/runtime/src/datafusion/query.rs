@@ -24,11 +24,14 @@ use arrow::{
     datatypes::{Schema, SchemaRef},
 };
 use arrow_tools::schema::verify_schema;
-use cache::{get_logical_plan_input_tables, to_cached_record_batch_stream, QueryResult};
+use cache::{
+    get_logical_plan_input_tables, to_cached_record_batch_stream, CacheControl, QueryResult,
+};
 use datafusion::{
     error::DataFusionError,
     execution::{context::SQLOptions, SendableRecordBatchStream},
-    physical_plan::{memory::MemoryStream, stream::RecordBatchStreamAdapter},
+    logical_expr::{DmlStatement, LogicalPlan, WriteOp},
+    physical_plan::{execute_stream, memory::MemoryStream, stream::RecordBatchStreamAdapter},
     prelude::DataFrame,
 };
 use error_code::ErrorCode;
@@ -42,6 +45,7 @@ pub mod builder;
 pub mod query_history;
 pub use builder::QueryBuilder;
 pub mod error_code;
+pub mod jobs;
 mod metrics;
 mod tracker;
 
@@ -116,10 +120,32 @@ thread_local! {
     });
 }
 
+/// Verifies that `plan` is allowed by the restricted SQL options. If `allow_writes`, inserts into
+/// `read_write` datasets are allowed too, as long as the query whose results they insert is.
+fn verify_restricted_plan(
+    df: &crate::datafusion::DataFusion,
+    plan: &LogicalPlan,
+    allow_writes: bool,
+) -> Result<(), DataFusionError> {
+    let plan = match plan {
+        LogicalPlan::Dml(DmlStatement {
+            table_name,
+            op: WriteOp::InsertInto | WriteOp::InsertOverwrite,
+            input,
+            ..
+        }) if allow_writes && df.is_writable(table_name) => input.as_ref(),
+        plan => plan,
+    };
+
+    RESTRICTED_SQL_OPTIONS.with(|sql_options| sql_options.verify_plan(plan))
+}
+
 pub struct Query {
     df: Arc<crate::datafusion::DataFusion>,
     sql: Arc<str>,
     restricted_sql_options: bool,
+    allow_writes: bool,
+    cache_control: CacheControl,
     tracker: QueryTracker,
 }
 
@@ -167,16 +193,23 @@ impl Query {
             };
 
             let mut plan_is_cache_enabled = false;
+            let mut cache_bypassed = false;
             let plan_cache_key = cache::key_for_logical_plan(&plan);
 
             if let Some(cache_provider) = &ctx.df.cache_provider() {
-                if let Some(cached_result) = match cache_provider.get(&plan).await {
-                    Ok(Some(v)) => Some(v),
-                    Ok(None) => None,
-                    Err(e) => {
-                        handle_error!(tracker, ErrorCode::InternalError, e, FailedToAccessCache)
+                let cached_result = if ctx.cache_control.skip_read {
+                    None
+                } else {
+                    match cache_provider.get(&plan).await {
+                        Ok(Some(v)) => Some(v),
+                        Ok(None) => None,
+                        Err(e) => {
+                            handle_error!(tracker, ErrorCode::InternalError, e, FailedToAccessCache)
+                        }
                     }
-                } {
+                };
+
+                if let Some(cached_result) = cached_result {
                     tracker = tracker
                         .datasets(cached_result.input_tables)
                         .results_cache_hit(true);
@@ -207,14 +240,14 @@ impl Query {
                     ));
                 }
 
-                plan_is_cache_enabled = cache_provider.cache_is_enabled_for_plan(&plan);
+                plan_is_cache_enabled = !ctx.cache_control.skip_write
+                    && cache_provider.cache_is_enabled_for_plan(&plan);
+                cache_bypassed = ctx.cache_control.bypasses_cache();
                 tracker = tracker.results_cache_hit(false);
             }
 
             if ctx.restricted_sql_options {
-                if let Err(e) =
-                    RESTRICTED_SQL_OPTIONS.with(|sql_options| sql_options.verify_plan(&plan))
-                {
+                if let Err(e) = verify_restricted_plan(&ctx.df, &plan, ctx.allow_writes) {
                     handle_error!(
                         tracker,
                         ErrorCode::QueryPlanningError,
@@ -241,13 +274,27 @@ impl Query {
 
             let df_schema: SchemaRef = Arc::clone(df.schema().inner());
 
-            let res_stream: SendableRecordBatchStream = match df.execute_stream().await {
-                Ok(stream) => stream,
+            let task_ctx = Arc::new(df.task_ctx());
+            let physical_plan = match df.create_physical_plan().await {
+                Ok(physical_plan) => physical_plan,
                 Err(e) => {
                     let error_code = ErrorCode::from(&e);
                     handle_error!(tracker, error_code, e, UnableToExecuteQuery)
                 }
             };
+            let estimated_rows = physical_plan
+                .statistics()
+                .ok()
+                .and_then(|statistics| statistics.num_rows.get_value().copied());
+
+            let res_stream: SendableRecordBatchStream =
+                match execute_stream(physical_plan, task_ctx) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        let error_code = ErrorCode::from(&e);
+                        handle_error!(tracker, error_code, e, UnableToExecuteQuery)
+                    }
+                };
 
             let res_schema = res_stream.schema();
 
@@ -262,19 +309,23 @@ impl Query {
                         res_stream,
                         plan_cache_key,
                         Arc::clone(&tracker.datasets),
+                        ctx.cache_control.ttl,
                     );
 
                     return Ok(QueryResult::new(
                         attach_query_tracker_to_stream(inner_span, tracker, record_batch_stream),
                         Some(false),
-                    ));
+                    )
+                    .with_estimated_rows(estimated_rows));
                 }
             }
 
+            // A query that bypassed the cache is reported as a miss.
             Ok(QueryResult::new(
                 attach_query_tracker_to_stream(inner_span, tracker, res_stream),
-                None,
-            ))
+                cache_bypassed.then_some(false),
+            )
+            .with_estimated_rows(estimated_rows))
         }
         .instrument(span.clone())
         .await;
@@ -16,6 +16,7 @@ limitations under the License.
 
 use std::{collections::HashSet, sync::Arc, time::SystemTime};
 
+use cache::CacheControl;
 use tokio::time::Instant;
 use uuid::Uuid;
 
@@ -29,7 +30,9 @@ pub struct QueryBuilder<'a> {
     query_id: Uuid,
     nsql: Option<&'a str>,
     restricted_sql_options: bool,
+    allow_writes: bool,
     protocol: Protocol,
+    cache_control: CacheControl,
 }
 
 impl<'a> QueryBuilder<'a> {
@@ -40,7 +43,9 @@ impl<'a> QueryBuilder<'a> {
             query_id: Uuid::new_v4(),
             nsql: None,
             restricted_sql_options: false,
+            allow_writes: false,
             protocol,
+            cache_control: CacheControl::default(),
         }
     }
 
@@ -62,12 +67,26 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
+    /// Allows inserts into `read_write` datasets with the restricted SQL options.
+    #[must_use]
+    pub fn allow_writes(mut self, allow_writes: bool) -> Self {
+        self.allow_writes = allow_writes;
+        self
+    }
+
     #[must_use]
     pub fn protocol(mut self, protocol: Protocol) -> Self {
         self.protocol = protocol;
         self
     }
 
+    /// Controls how the results cache is used for this query, i.e. from a `Cache-Control` header.
+    #[must_use]
+    pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = cache_control;
+        self
+    }
+
     #[must_use]
     pub fn build(self) -> Query {
         let sql: Arc<str> = self.sql.into();
@@ -75,6 +94,8 @@ impl<'a> QueryBuilder<'a> {
             df: Arc::clone(&self.df),
             sql: Arc::clone(&sql),
             restricted_sql_options: self.restricted_sql_options,
+            allow_writes: self.allow_writes,
+            cache_control: self.cache_control,
             tracker: QueryTracker {
                 df: self.df,
                 schema: None,
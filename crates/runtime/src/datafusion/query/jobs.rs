@@ -0,0 +1,761 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use dashmap::DashMap;
+use futures::StreamExt;
+use snafu::prelude::*;
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+use crate::datafusion::DataFusion;
+
+use super::{Protocol, QueryBuilder};
+
+/// How long the results of a finished job are kept before they're dropped.
+pub const DEFAULT_JOB_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// The most memory the results of a single job can use. Jobs with larger results fail, and should
+/// be run as regular queries that stream their results instead.
+pub const DEFAULT_MAX_RESULT_BYTES: usize = 512 * 1024 * 1024;
+
+/// The most memory the results of all jobs can use together. Jobs fail once their results don't
+/// fit, until the results of other jobs expire.
+pub const DEFAULT_MAX_TOTAL_RESULT_BYTES: usize = 2 * 1024 * 1024 * 1024;
+
+/// The most jobs that are kept at once, running or finished. At the limit, the oldest finished job
+/// is dropped to make room for a new one.
+pub const DEFAULT_MAX_JOBS: usize = 64;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Too many queries are running in the background, the limit is {max_jobs}. Try again once one of them finishes."
+    ))]
+    TooManyJobs { max_jobs: usize },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryJobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl std::fmt::Display for QueryJobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryJobStatus::Running => write!(f, "running"),
+            QueryJobStatus::Completed => write!(f, "completed"),
+            QueryJobStatus::Failed => write!(f, "failed"),
+            QueryJobStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// A memory limit for the results of jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultsLimit {
+    /// The limit for the results of a single job. Running the query again fails the same way.
+    Job,
+    /// The limit for the results of all jobs together. The query can succeed once the results of
+    /// other jobs expire.
+    Total,
+}
+
+/// The memory used by the results of the jobs of a [`QueryJobs`], which is shared between them.
+#[derive(Debug)]
+struct ResultsBudget {
+    used_bytes: AtomicUsize,
+    max_bytes: usize,
+}
+
+impl ResultsBudget {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            used_bytes: AtomicUsize::new(0),
+            max_bytes,
+        }
+    }
+
+    /// Reserves `bytes` for results. Returns `false` if they don't fit in the budget.
+    fn try_reserve(&self, bytes: usize) -> bool {
+        self.used_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used_bytes| {
+                used_bytes
+                    .checked_add(bytes)
+                    .filter(|used_bytes| *used_bytes <= self.max_bytes)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, bytes: usize) {
+        self.used_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+struct JobState {
+    status: QueryJobStatus,
+    schema: Option<SchemaRef>,
+    /// The number of rows the plan is expected to return, to report progress.
+    estimated_rows: Option<usize>,
+    results: Vec<RecordBatch>,
+    /// The memory used by `results`, which is reserved in `budget` until they're dropped.
+    result_bytes: usize,
+    budget: Arc<ResultsBudget>,
+    exceeded_limit: Option<ResultsLimit>,
+    error: Option<String>,
+    finished_at: Option<Instant>,
+}
+
+impl JobState {
+    /// Moves a running job to its final status. Returns `false` if the job had already finished.
+    fn finish(&mut self, status: QueryJobStatus, error: Option<String>) -> bool {
+        if self.status != QueryJobStatus::Running {
+            return false;
+        }
+
+        self.status = status;
+        self.error = error;
+        self.finished_at = Some(Instant::now());
+        if status != QueryJobStatus::Completed {
+            self.clear_results();
+        }
+
+        true
+    }
+
+    fn clear_results(&mut self) {
+        self.results.clear();
+        self.budget.release(self.result_bytes);
+        self.result_bytes = 0;
+    }
+}
+
+impl Drop for JobState {
+    fn drop(&mut self) {
+        self.clear_results();
+    }
+}
+
+/// Fails a job if the task running it ends before the job finished, i.e. because it panicked or
+/// was aborted, so that the job doesn't stay running forever.
+struct FinishGuard(Arc<QueryJob>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        if self.0.finish(
+            QueryJobStatus::Failed,
+            Some("The query ended unexpectedly".to_string()),
+        ) {
+            tracing::warn!("Query job {} ended unexpectedly", self.0.id());
+        }
+    }
+}
+
+/// A query running in the background, whose results are held in memory until they expire.
+///
+/// The job id is used as the query id, so the job can be correlated with `runtime.query_history`.
+pub struct QueryJob {
+    id: Uuid,
+    sql: Arc<str>,
+    state: RwLock<JobState>,
+    rows_produced: AtomicU64,
+    abort_handle: Mutex<Option<AbortHandle>>,
+}
+
+impl QueryJob {
+    fn new(sql: &str, budget: Arc<ResultsBudget>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            sql: sql.into(),
+            state: RwLock::new(JobState {
+                status: QueryJobStatus::Running,
+                schema: None,
+                estimated_rows: None,
+                results: vec![],
+                result_bytes: 0,
+                budget,
+                exceeded_limit: None,
+                error: None,
+                finished_at: None,
+            }),
+            rows_produced: AtomicU64::new(0),
+            abort_handle: Mutex::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    #[must_use]
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    #[must_use]
+    pub fn status(&self) -> QueryJobStatus {
+        self.state
+            .read()
+            .map_or(QueryJobStatus::Failed, |state| state.status)
+    }
+
+    /// The number of rows the query has produced so far.
+    #[must_use]
+    pub fn rows_produced(&self) -> u64 {
+        self.rows_produced.load(Ordering::Relaxed)
+    }
+
+    /// The estimated fraction of the results produced so far, from the number of rows the plan is
+    /// expected to return. `None` while the query is running if there's no estimate.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn progress(&self) -> Option<f64> {
+        let state = self.state.read().ok()?;
+        match state.status {
+            QueryJobStatus::Running => {
+                let estimated_rows = state.estimated_rows.filter(|rows| *rows > 0)?;
+                // Estimates can be low, so a running query never reports being done.
+                Some((self.rows_produced() as f64 / estimated_rows as f64).min(0.99))
+            }
+            QueryJobStatus::Completed => Some(1.0),
+            QueryJobStatus::Failed | QueryJobStatus::Cancelled => None,
+        }
+    }
+
+    /// Whether the job failed because its results exceeded the memory limit for a job, or didn't fit
+    /// in the memory left for the results of all jobs.
+    #[must_use]
+    pub fn results_too_large(&self) -> bool {
+        self.exceeded_limit().is_some()
+    }
+
+    /// The memory limit the results of the job exceeded, which failed it.
+    #[must_use]
+    pub fn exceeded_limit(&self) -> Option<ResultsLimit> {
+        self.state
+            .read()
+            .ok()
+            .and_then(|state| state.exceeded_limit)
+    }
+
+    /// The schema of the results, available once the query has been planned.
+    #[must_use]
+    pub fn schema(&self) -> Option<SchemaRef> {
+        self.state
+            .read()
+            .ok()
+            .and_then(|state| state.schema.clone())
+    }
+
+    #[must_use]
+    pub fn error(&self) -> Option<String> {
+        self.state.read().ok().and_then(|state| state.error.clone())
+    }
+
+    /// The results of the query, if it has completed.
+    #[must_use]
+    pub fn results(&self) -> Option<Vec<RecordBatch>> {
+        let state = self.state.read().ok()?;
+        if state.status != QueryJobStatus::Completed {
+            return None;
+        }
+
+        Some(state.results.clone())
+    }
+
+    /// Up to `limit` rows of the results starting at row `offset`, if the query has completed.
+    #[must_use]
+    pub fn results_page(&self, offset: usize, limit: usize) -> Option<Vec<RecordBatch>> {
+        let state = self.state.read().ok()?;
+        if state.status != QueryJobStatus::Completed {
+            return None;
+        }
+
+        Some(slice_batches(&state.results, offset, limit))
+    }
+
+    /// Cancels the job if it's still running. Returns `false` if the job had already finished.
+    pub fn cancel(&self) -> bool {
+        if !self.finish(QueryJobStatus::Cancelled, None) {
+            return false;
+        }
+
+        if let Ok(mut abort_handle) = self.abort_handle.lock() {
+            if let Some(abort_handle) = abort_handle.take() {
+                abort_handle.abort();
+            }
+        }
+
+        true
+    }
+
+    fn finished_at(&self) -> Option<Instant> {
+        self.state.read().ok().and_then(|state| state.finished_at)
+    }
+
+    fn is_expired(&self, retention: Duration) -> bool {
+        self.state.read().map_or(true, |state| {
+            state
+                .finished_at
+                .is_some_and(|finished_at| finished_at.elapsed() > retention)
+        })
+    }
+
+    fn set_schema(&self, schema: SchemaRef, estimated_rows: Option<usize>) {
+        if let Ok(mut state) = self.state.write() {
+            state.schema = Some(schema);
+            state.estimated_rows = estimated_rows;
+        }
+    }
+
+    /// Buffers a batch of results. Returns `false` if the job isn't running anymore, including
+    /// when the results exceed `max_result_bytes` or don't fit in the budget for the results of all
+    /// jobs, which fails the job.
+    fn push_batch(&self, batch: RecordBatch, max_result_bytes: usize) -> bool {
+        let Ok(mut state) = self.state.write() else {
+            return false;
+        };
+        if state.status != QueryJobStatus::Running {
+            return false;
+        }
+
+        let batch_bytes = batch.get_array_memory_size();
+        let exceeded = if state.result_bytes + batch_bytes > max_result_bytes {
+            Some((ResultsLimit::Job, format!(
+                "The results of the query exceed the limit of {max_result_bytes} bytes for background queries. Filter or limit the results, or run it as a regular query to stream them."
+            )))
+        } else if !state.budget.try_reserve(batch_bytes) {
+            Some((ResultsLimit::Total, format!(
+                "The results of background queries exceed the limit of {} bytes. Try again once the results of other queries expire, or run it as a regular query to stream its results.",
+                state.budget.max_bytes
+            )))
+        } else {
+            None
+        };
+        if let Some((limit, error)) = exceeded {
+            state.exceeded_limit = Some(limit);
+            state.finish(QueryJobStatus::Failed, Some(error));
+            return false;
+        }
+        state.result_bytes += batch_bytes;
+
+        self.rows_produced
+            .fetch_add(batch.num_rows() as u64, Ordering::Relaxed);
+        state.results.push(batch);
+        true
+    }
+
+    /// Moves a running job to its final status. Returns `false` if the job had already finished.
+    fn finish(&self, status: QueryJobStatus, error: Option<String>) -> bool {
+        self.state
+            .write()
+            .is_ok_and(|mut state| state.finish(status, error))
+    }
+
+    async fn run(&self, df: Arc<DataFusion>, protocol: Protocol, max_result_bytes: usize) {
+        let query = QueryBuilder::new(&self.sql, df, protocol)
+            .query_id(self.id)
+            .use_restricted_sql_options()
+            .build();
+
+        let mut result = match query.run().await {
+            Ok(result) => result,
+            Err(e) => {
+                self.finish(QueryJobStatus::Failed, Some(e.to_string()));
+                return;
+            }
+        };
+
+        self.set_schema(result.data.schema(), result.estimated_rows);
+
+        while let Some(batch) = result.data.next().await {
+            match batch {
+                Ok(batch) => {
+                    if !self.push_batch(batch, max_result_bytes) {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    self.finish(QueryJobStatus::Failed, Some(e.to_string()));
+                    return;
+                }
+            }
+        }
+
+        self.finish(QueryJobStatus::Completed, None);
+    }
+}
+
+fn slice_batches(batches: &[RecordBatch], mut offset: usize, mut limit: usize) -> Vec<RecordBatch> {
+    let mut page = vec![];
+    for batch in batches {
+        if limit == 0 {
+            break;
+        }
+
+        if offset >= batch.num_rows() {
+            offset -= batch.num_rows();
+            continue;
+        }
+
+        let length = limit.min(batch.num_rows() - offset);
+        page.push(batch.slice(offset, length));
+        limit -= length;
+        offset = 0;
+    }
+
+    page
+}
+
+/// Tracks the queries submitted to run in the background, i.e. by Flight `poll_flight_info` or
+/// the `/v1/sql/jobs` HTTP API.
+pub struct QueryJobs {
+    jobs: DashMap<Uuid, Arc<QueryJob>>,
+    retention: Duration,
+    max_jobs: usize,
+    max_result_bytes: usize,
+    budget: Arc<ResultsBudget>,
+}
+
+impl QueryJobs {
+    #[must_use]
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            jobs: DashMap::new(),
+            retention,
+            max_jobs: DEFAULT_MAX_JOBS,
+            max_result_bytes: DEFAULT_MAX_RESULT_BYTES,
+            budget: Arc::new(ResultsBudget::new(DEFAULT_MAX_TOTAL_RESULT_BYTES)),
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_jobs = max_jobs;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_result_bytes(mut self, max_result_bytes: usize) -> Self {
+        self.max_result_bytes = max_result_bytes;
+        self
+    }
+
+    /// Limits the memory used by the results of all jobs together.
+    #[must_use]
+    pub fn with_max_total_result_bytes(mut self, max_total_result_bytes: usize) -> Self {
+        self.budget = Arc::new(ResultsBudget::new(max_total_result_bytes));
+        self
+    }
+
+    /// Starts running `sql` in the background and returns the job tracking it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the maximum number of jobs are already running.
+    pub fn submit(
+        &self,
+        df: Arc<DataFusion>,
+        sql: &str,
+        protocol: Protocol,
+    ) -> Result<Arc<QueryJob>> {
+        self.remove_expired();
+        if self.jobs.len() >= self.max_jobs && !self.remove_oldest_finished() {
+            return TooManyJobsSnafu {
+                max_jobs: self.max_jobs,
+            }
+            .fail();
+        }
+
+        let job = Arc::new(QueryJob::new(sql, Arc::clone(&self.budget)));
+        self.jobs.insert(job.id(), Arc::clone(&job));
+
+        let running_job = Arc::clone(&job);
+        let max_result_bytes = self.max_result_bytes;
+        let handle = tokio::spawn(async move {
+            let _guard = FinishGuard(Arc::clone(&running_job));
+            running_job.run(df, protocol, max_result_bytes).await;
+        });
+
+        if let Ok(mut abort_handle) = job.abort_handle.lock() {
+            *abort_handle = Some(handle.abort_handle());
+        }
+
+        Ok(job)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &Uuid) -> Option<Arc<QueryJob>> {
+        let job = self.jobs.get(id).map(|job| Arc::clone(job.value()))?;
+        if job.is_expired(self.retention) {
+            self.jobs.remove(id);
+            return None;
+        }
+
+        Some(job)
+    }
+
+    fn remove_expired(&self) {
+        self.jobs.retain(|_, job| !job.is_expired(self.retention));
+    }
+
+    /// Drops the job that finished first. Returns `false` if all the jobs are still running.
+    fn remove_oldest_finished(&self) -> bool {
+        let oldest = self
+            .jobs
+            .iter()
+            .filter_map(|job| Some((*job.key(), job.finished_at()?)))
+            .min_by_key(|(_, finished_at)| *finished_at);
+
+        oldest.is_some_and(|(id, _)| self.jobs.remove(&id).is_some())
+    }
+}
+
+impl Default for QueryJobs {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOB_RETENTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use super::*;
+
+    fn batch(values: Vec<i32>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(values))]).expect("valid batch")
+    }
+
+    fn job(sql: &str) -> QueryJob {
+        QueryJob::new(
+            sql,
+            Arc::new(ResultsBudget::new(DEFAULT_MAX_TOTAL_RESULT_BYTES)),
+        )
+    }
+
+    fn values(batches: &[RecordBatch]) -> Vec<i32> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .expect("int32 column")
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slice_batches() {
+        let batches = vec![
+            batch(vec![1, 2, 3]),
+            batch(vec![4, 5]),
+            batch(vec![6, 7, 8]),
+        ];
+
+        assert_eq!(values(&slice_batches(&batches, 0, 2)), vec![1, 2]);
+        assert_eq!(values(&slice_batches(&batches, 2, 4)), vec![3, 4, 5, 6]);
+        assert_eq!(values(&slice_batches(&batches, 5, 10)), vec![6, 7, 8]);
+        assert!(slice_batches(&batches, 8, 10).is_empty());
+        assert!(slice_batches(&batches, 0, 0).is_empty());
+    }
+
+    async fn wait_until_finished(job: &QueryJob) -> QueryJobStatus {
+        for _ in 0..500 {
+            if job.status() != QueryJobStatus::Running {
+                return job.status();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("Job {} didn't finish", job.id());
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_poll() {
+        let df = Arc::new(DataFusion::new());
+        let jobs = QueryJobs::default();
+
+        let job = jobs
+            .submit(
+                Arc::clone(&df),
+                "SELECT * FROM (VALUES (1), (2), (3)) AS t(a)",
+                Protocol::Http,
+            )
+            .expect("job is submitted");
+        assert_eq!(wait_until_finished(&job).await, QueryJobStatus::Completed);
+        assert!(job.progress().is_some_and(|progress| progress >= 1.0));
+
+        let polled = jobs.get(&job.id()).expect("job is tracked");
+        assert_eq!(polled.rows_produced(), 3);
+        assert_eq!(
+            values(&polled.results_page(1, 10).expect("results are available")),
+            vec![2, 3]
+        );
+        assert!(!polled.cancel(), "a completed job can't be cancelled");
+
+        let failed = jobs
+            .submit(df, "SELECT * FROM missing_table", Protocol::Http)
+            .expect("job is submitted");
+        assert_eq!(wait_until_finished(&failed).await, QueryJobStatus::Failed);
+        assert!(failed.error().is_some());
+        assert!(failed.results().is_none());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let job = job("SELECT 1");
+        assert!(job.cancel());
+        assert_eq!(job.status(), QueryJobStatus::Cancelled);
+        assert!(!job.cancel(), "a job is only cancelled once");
+        assert!(job.results().is_none());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let jobs = QueryJobs::new(Duration::ZERO);
+        let running = Arc::new(job("SELECT 1"));
+        let finished = Arc::new(job("SELECT 2"));
+        finished.finish(QueryJobStatus::Completed, None);
+        jobs.jobs.insert(running.id(), Arc::clone(&running));
+        jobs.jobs.insert(finished.id(), Arc::clone(&finished));
+
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(
+            jobs.get(&running.id()).is_some(),
+            "running jobs don't expire"
+        );
+        assert!(jobs.get(&finished.id()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_max_jobs() {
+        let df = Arc::new(DataFusion::new());
+        let jobs = QueryJobs::default().with_max_jobs(1);
+        let running = Arc::new(job("SELECT 1"));
+        jobs.jobs.insert(running.id(), Arc::clone(&running));
+
+        let result = jobs.submit(Arc::clone(&df), "SELECT 2", Protocol::Http);
+        assert!(matches!(result, Err(Error::TooManyJobs { max_jobs: 1 })));
+
+        // A finished job makes room for a new one.
+        running.finish(QueryJobStatus::Completed, None);
+        let job = jobs
+            .submit(df, "SELECT 2", Protocol::Http)
+            .expect("job is submitted");
+        assert!(jobs.get(&running.id()).is_none());
+        assert!(jobs.get(&job.id()).is_some());
+    }
+
+    #[test]
+    fn test_max_result_bytes() {
+        let job = job("SELECT a FROM t");
+        let first = batch(vec![1, 2, 3]);
+        let max_result_bytes = first.get_array_memory_size();
+
+        assert!(job.push_batch(first, max_result_bytes));
+        assert!(!job.push_batch(batch(vec![4, 5]), max_result_bytes));
+        assert_eq!(job.status(), QueryJobStatus::Failed);
+        assert_eq!(job.exceeded_limit(), Some(ResultsLimit::Job));
+        assert_eq!(job.rows_produced(), 3);
+        assert!(job.results().is_none());
+    }
+
+    #[test]
+    fn test_max_total_result_bytes() {
+        let first = batch(vec![1, 2, 3]);
+        let budget = Arc::new(ResultsBudget::new(first.get_array_memory_size()));
+
+        let completed = QueryJob::new("SELECT a FROM t", Arc::clone(&budget));
+        assert!(completed.push_batch(first, DEFAULT_MAX_RESULT_BYTES));
+        completed.finish(QueryJobStatus::Completed, None);
+
+        let job = QueryJob::new("SELECT a FROM u", Arc::clone(&budget));
+        assert!(!job.push_batch(batch(vec![4]), DEFAULT_MAX_RESULT_BYTES));
+        assert_eq!(job.status(), QueryJobStatus::Failed);
+        assert_eq!(job.exceeded_limit(), Some(ResultsLimit::Total));
+
+        // Dropping a job releases the memory of its results
+        drop(completed);
+        let job = QueryJob::new("SELECT a FROM u", Arc::clone(&budget));
+        assert!(job.push_batch(batch(vec![4]), DEFAULT_MAX_RESULT_BYTES));
+        job.cancel();
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_job_fails_when_its_task_ends_abnormally() {
+        let panicked = Arc::new(job("SELECT 1"));
+        let running_job = Arc::clone(&panicked);
+        let result = tokio::spawn(async move {
+            let _guard = FinishGuard(running_job);
+            panic!("the query panicked");
+        })
+        .await;
+        assert!(result.is_err_and(|e| e.is_panic()));
+        assert_eq!(panicked.status(), QueryJobStatus::Failed);
+        assert!(panicked.finished_at().is_some(), "the job expires");
+
+        let aborted = Arc::new(job("SELECT 2"));
+        let running_job = Arc::clone(&aborted);
+        let handle = tokio::spawn(async move {
+            let _guard = FinishGuard(running_job);
+            std::future::pending::<()>().await;
+        });
+        handle.abort();
+        assert!(handle.await.is_err_and(|e| e.is_cancelled()));
+        assert_eq!(aborted.status(), QueryJobStatus::Failed);
+    }
+
+    #[test]
+    fn test_progress() {
+        let job = job("SELECT a FROM t");
+        assert_eq!(job.progress(), None);
+
+        job.set_schema(batch(vec![]).schema(), Some(4));
+        job.push_batch(batch(vec![1, 2]), DEFAULT_MAX_RESULT_BYTES);
+        assert!(job
+            .progress()
+            .is_some_and(|progress| (progress - 0.5).abs() < f64::EPSILON));
+        job.push_batch(batch(vec![3, 4, 5]), DEFAULT_MAX_RESULT_BYTES);
+        assert!(
+            job.progress().is_some_and(|progress| progress < 1.0),
+            "a running job isn't done, even past the estimate"
+        );
+    }
+}
@@ -19,16 +19,20 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use crate::{component::dataset::acceleration::Acceleration, datafusion::SPICE_RUNTIME_SCHEMA};
+use crate::{
+    component::dataset::acceleration::Acceleration,
+    datafusion::{DataFusion, SPICE_RUNTIME_SCHEMA},
+};
 use crate::{component::dataset::TimeFormat, secrets::Secrets};
 use arrow::{
     array::{
-        BooleanArray, Float32Array, Int8Array, RecordBatch, StringArray, TimestampNanosecondArray,
-        UInt64Array,
+        AsArray, BooleanArray, Float32Array, Int8Array, RecordBatch, StringArray,
+        TimestampNanosecondArray, UInt64Array,
     },
-    datatypes::{DataType, Field, Schema, TimeUnit},
+    datatypes::{DataType, Field, Float32Type, Schema, TimeUnit, UInt64Type},
 };
-use datafusion::sql::TableReference;
+use datafusion::{error::DataFusionError, sql::TableReference};
+use uuid::Uuid;
 
 use snafu::{ResultExt, Snafu};
 use tokio::sync::RwLock;
@@ -126,6 +130,55 @@ pub enum Error {
     UnableToGetTableProvider {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[snafu(display("Error reading from query_history table: {source}"))]
+    UnableToReadFromTable { source: DataFusionError },
+}
+
+/// The outcome of a finished query, as recorded in the `query_history` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryHistoryEntry {
+    pub query_duration_secs: f32,
+    pub rows_produced: u64,
+    pub results_cache_hit: bool,
+    pub error_message: Option<String>,
+}
+
+/// Looks up the `query_history` row for `query_id`. Returns `None` if the query hasn't finished
+/// yet, or its row has already been removed by the table retention.
+pub async fn get_query_history_entry(
+    df: &DataFusion,
+    query_id: Uuid,
+) -> Result<Option<QueryHistoryEntry>, Error> {
+    let table = TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_QUERY_HISTORY_TABLE);
+    let sql = format!(
+        "SELECT query_duration_secs, rows_produced, results_cache_hit, error_message FROM {} WHERE query_id = '{query_id}' LIMIT 1",
+        table.to_quoted_string()
+    );
+
+    let batches = df
+        .ctx
+        .sql(&sql)
+        .await
+        .context(UnableToReadFromTableSnafu)?
+        .collect()
+        .await
+        .context(UnableToReadFromTableSnafu)?;
+
+    let Some(batch) = batches.iter().find(|batch| batch.num_rows() > 0) else {
+        return Ok(None);
+    };
+
+    let error_message = batch.column(3).as_string::<i32>();
+
+    Ok(Some(QueryHistoryEntry {
+        query_duration_secs: batch.column(0).as_primitive::<Float32Type>().value(0),
+        rows_produced: batch.column(1).as_primitive::<UInt64Type>().value(0),
+        results_cache_hit: batch.column(2).as_boolean().value(0),
+        error_message: error_message
+            .is_valid(0)
+            .then(|| error_message.value(0).to_string()),
+    }))
 }
 
 /// Checks if a required field is missing in a [`QueryHistory`] struct. Adds field name to missing fields vector if field is None.
@@ -42,6 +42,12 @@ impl SpiceSchemaProvider {
             tables: DashMap::new(),
         }
     }
+
+    /// The table registered as `name`, without going through the async [`SchemaProvider::table`].
+    #[must_use]
+    pub fn get_table(&self, name: &str) -> Option<Arc<dyn TableProvider>> {
+        self.tables.get(name).map(|table| Arc::clone(table.value()))
+    }
 }
 
 impl Default for SpiceSchemaProvider {
@@ -64,7 +70,7 @@ impl SchemaProvider for SpiceSchemaProvider {
     }
 
     async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>, DataFusionError> {
-        Ok(self.tables.get(name).map(|table| Arc::clone(table.value())))
+        Ok(self.get_table(name))
     }
 
     fn register_table(
@@ -25,14 +25,35 @@ use datafusion::{
     logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, TypeSignature, Volatility},
 };
 use itertools::Itertools;
+use spicepod::component::embeddings::DistanceMetric;
 use std::{any::Any, sync::Arc};
 
 // See: https://github.com/apache/datafusion/blob/888504a8da6d20f9caf3ecb6cd1a6b7d1956e23e/datafusion/expr/src/signature.rs#L36
 pub const FIXED_SIZE_LIST_WILDCARD: i32 = i32::MIN;
 
+/// The name of the UDF that computes `metric`.
+#[must_use]
+pub fn metric_udf_name(metric: DistanceMetric) -> &'static str {
+    match metric {
+        DistanceMetric::L2 => "array_distance",
+        DistanceMetric::Cosine => "cosine_distance",
+        DistanceMetric::Dot => "dot_product",
+    }
+}
+
+/// The SQL sort order that puts the embeddings most similar to a query first for `metric`.
+#[must_use]
+pub fn nearest_first(metric: DistanceMetric) -> &'static str {
+    match metric {
+        DistanceMetric::L2 | DistanceMetric::Cosine => "ASC",
+        DistanceMetric::Dot => "DESC",
+    }
+}
+
 #[derive(Debug)]
 pub struct ArrayDistance {
     signature: Signature,
+    metric: DistanceMetric,
 }
 
 impl Default for ArrayDistance {
@@ -41,9 +62,12 @@ impl Default for ArrayDistance {
     }
 }
 
-/// [`ArrayDistance`] is a scalar UDF that calculates the Euclidean distance between elements in
+/// [`ArrayDistance`] is a scalar UDF that calculates a [`DistanceMetric`] between elements in
 /// [`DataType::FixedSizeList`] arrays with a numeric inner type. Limited support for
-/// [`DataType::List`] is also provided.
+/// [`DataType::List`] is also provided. The UDF of each metric is named by [`metric_udf_name`]:
+///   - `array_distance`: the squared Euclidean distance.
+///   - `cosine_distance`: one minus the cosine similarity, null when either input has no length.
+///   - `dot_product`: the dot product, larger for more similar inputs.
 ///
 /// For two [`DataType::FixedSizeList`], the inputs must have the same length, and have compatible
 /// inner types. Compatible inner types are
@@ -64,6 +88,11 @@ impl Default for ArrayDistance {
 impl ArrayDistance {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_metric(DistanceMetric::L2)
+    }
+
+    #[must_use]
+    pub fn with_metric(metric: DistanceMetric) -> Self {
         let valid_types = [true, false]
             .iter()
             .cartesian_product([DataType::Float32, DataType::Float64])
@@ -94,6 +123,39 @@ impl ArrayDistance {
 
         Self {
             signature: Signature::one_of(valid_signatures, Volatility::Immutable),
+            metric,
+        }
+    }
+
+    /// Sums `op` over the pairs of elements of `a` and `b`.
+    fn sum_pairs(
+        a: &Float64Array,
+        b: &Float64Array,
+        op: impl Fn(f64, f64) -> f64,
+    ) -> DataFusionResult<Option<f64>> {
+        let z: Float64Array =
+            binary(a, b, op).map_err(|e| DataFusionError::Internal(e.to_string()))?;
+        Ok(sum(&z))
+    }
+
+    /// Computes the metric between two vectors.
+    fn compute(&self, a: &ArrayRef, b: &ArrayRef) -> DataFusionResult<Option<f64>> {
+        let a = Self::cast_to_float64_array(a)?;
+        let b = Self::cast_to_float64_array(b)?;
+        match self.metric {
+            DistanceMetric::L2 => Self::sum_pairs(&a, &b, |x, y| (x - y).powi(2)),
+            DistanceMetric::Dot => Self::sum_pairs(&a, &b, |x, y| x * y),
+            DistanceMetric::Cosine => {
+                let (Some(dot), Some(norm_a), Some(norm_b)) = (
+                    Self::sum_pairs(&a, &b, |x, y| x * y)?,
+                    Self::sum_pairs(&a, &a, |x, y| x * y)?,
+                    Self::sum_pairs(&b, &b, |x, y| x * y)?,
+                ) else {
+                    return Ok(None);
+                };
+                let norm = (norm_a * norm_b).sqrt();
+                Ok((norm > 0.0).then_some(1.0 - dot / norm))
+            }
         }
     }
 
@@ -210,7 +272,7 @@ impl ScalarUDFImpl for ArrayDistance {
         self
     }
     fn name(&self) -> &str {
-        "array_distance"
+        metric_udf_name(self.metric)
     }
     fn signature(&self) -> &Signature {
         &self.signature
@@ -218,7 +280,7 @@ impl ScalarUDFImpl for ArrayDistance {
 
     fn return_type(&self, args: &[DataType]) -> DataFusionResult<DataType> {
         if args.len() != 2 {
-            return plan_err!("array_distance takes exactly two arguments");
+            return plan_err!("{} takes exactly two arguments", self.name());
         }
 
         match (args[0].clone(), args[1].clone()) {
@@ -233,7 +295,7 @@ impl ScalarUDFImpl for ArrayDistance {
             | (DataType::List(f1) | DataType::LargeList(f1), DataType::FixedSizeList(f2, _)) => {
                 Self::least_precise_float_type(f1.data_type(), f2.data_type())
             }
-            _ => plan_err!("Invalid combination of input types for 'array_distance'"),
+            _ => plan_err!("Invalid combination of input types for '{}'", self.name()),
         }
     }
 
@@ -250,15 +312,7 @@ impl ScalarUDFImpl for ArrayDistance {
             .iter()
             .zip(z2.iter())
             .map(|(a, b)| match (a, b) {
-                (Some(a), Some(b)) => {
-                    let z: Float64Array = binary(
-                        &Self::cast_to_float64_array(&a)?,
-                        &Self::cast_to_float64_array(&b)?,
-                        |x, y| (x - y).powi(2),
-                    )
-                    .map_err(|e| DataFusionError::Internal(e.to_string()))?;
-                    Ok(sum(&z))
-                }
+                (Some(a), Some(b)) => self.compute(&a, &b),
                 _ => Ok(None),
             })
             .collect();
@@ -297,6 +351,8 @@ mod tests {
         logical_expr::{ColumnarValue, ScalarUDF},
     };
 
+    use spicepod::component::embeddings::DistanceMetric;
+
     use super::ArrayDistance;
 
     #[allow(clippy::float_cmp)]
@@ -463,4 +519,63 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_metrics() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let field = Arc::new(Field::new("item", DataType::Float64, false));
+        let left = Arc::new(FixedSizeListArray::try_new(
+            Arc::clone(&field),
+            2_i32,
+            Arc::new(Float64Array::try_new(
+                vec![1.0, 0.0, 3.0, 4.0, 0.0, 0.0].into(),
+                None,
+            )?),
+            None,
+        )?) as ArrayRef;
+        let right = Arc::new(FixedSizeListArray::try_new(
+            field,
+            2_i32,
+            Arc::new(Float64Array::try_new(
+                vec![0.0, 2.0, 6.0, 8.0, 1.0, 1.0].into(),
+                None,
+            )?),
+            None,
+        )?) as ArrayRef;
+
+        let compute =
+            |metric| -> Result<Vec<Option<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+                let udf = ScalarUDF::from(ArrayDistance::with_metric(metric));
+                let result = udf.invoke(&[
+                    ColumnarValue::Array(Arc::clone(&left)),
+                    ColumnarValue::Array(Arc::clone(&right)),
+                ])?;
+                let arrays = ColumnarValue::values_to_arrays(&[result])?;
+                Ok(arrays[0]
+                    .as_any()
+                    .downcast_ref::<Float64Array>()
+                    .ok_or("failed downcast of result")?
+                    .iter()
+                    .collect())
+            };
+
+        assert_eq!(
+            compute(DistanceMetric::L2)?,
+            vec![Some(5.0), Some(25.0), Some(2.0)]
+        );
+        assert_eq!(
+            compute(DistanceMetric::Dot)?,
+            vec![Some(0.0), Some(50.0), Some(0.0)]
+        );
+        let cosine = compute(DistanceMetric::Cosine)?;
+        assert!(cosine[0].is_some_and(|d| (d - 1.0).abs() < 1e-9));
+        assert!(cosine[1].is_some_and(|d| d.abs() < 1e-9));
+        assert_eq!(cosine[2], None);
+
+        assert_eq!(
+            ScalarUDF::from(ArrayDistance::with_metric(DistanceMetric::Cosine)).name(),
+            "cosine_distance"
+        );
+
+        Ok(())
+    }
 }
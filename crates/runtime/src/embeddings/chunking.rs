@@ -0,0 +1,437 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Splitting of column values into chunks that are embedded separately.
+//!
+//! A value is first split into segments that should be kept together (markdown sections and
+//! blocks, sentences), splitting segments larger than a chunk further, down to single characters
+//! or tokens. Consecutive segments are then packed into chunks of up to the target size.
+
+use std::ops::Range;
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::Int32Type;
+use spicepod::component::embeddings::{ChunkSizeUnit, ChunkSplitter, EmbeddingChunkConfig};
+
+/// A chunk of a value, with the character offsets of its start and end in the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    pub text: &'a str,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits values into chunks according to an [`EmbeddingChunkConfig`].
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    config: EmbeddingChunkConfig,
+}
+
+impl Chunker {
+    #[must_use]
+    pub fn new(config: EmbeddingChunkConfig) -> Self {
+        Self { config }
+    }
+
+    /// Splits `text` into chunks of at most `target_chunk_size` units each, in order. Consecutive
+    /// chunks share up to `overlap_size` units.
+    #[must_use]
+    pub fn chunks<'a>(&self, text: &'a str) -> Vec<Chunk<'a>> {
+        let target = self.config.target_chunk_size.max(1);
+        let segments = self.segments(text, target);
+        let sizes: Vec<usize> = segments
+            .iter()
+            .map(|segment| self.size(&text[segment.clone()]))
+            .collect();
+
+        let mut ranges = vec![];
+        let mut start = 0;
+        while start < segments.len() {
+            let mut end = start;
+            let mut size = 0;
+            while end < segments.len() && (end == start || size + sizes[end] <= target) {
+                size += sizes[end];
+                end += 1;
+            }
+            ranges.push(segments[start].start..segments[end - 1].end);
+            if end == segments.len() {
+                break;
+            }
+
+            // Start the next chunk with the last segments of this one, always making progress.
+            let mut next = end;
+            let mut overlap = 0;
+            while next > start + 1 && overlap + sizes[next - 1] <= self.config.overlap_size {
+                overlap += sizes[next - 1];
+                next -= 1;
+            }
+            start = next;
+        }
+
+        self.to_chunks(text, ranges)
+    }
+
+    /// Splits `text` into contiguous segments, only splitting the segments of a splitter that are
+    /// larger than `target` with the next finer one.
+    fn segments(&self, text: &str, target: usize) -> Vec<Range<usize>> {
+        let all = 0..text.len();
+        let split_units = |range| self.units(text, range);
+        let split_sentences =
+            |range| self.refine(text, sentences(text, range), target, split_units);
+        match self.config.splitter {
+            ChunkSplitter::Fixed => split_units(all),
+            ChunkSplitter::Sentence => split_sentences(all),
+            ChunkSplitter::Markdown => {
+                let split_blocks = |range| {
+                    self.refine(
+                        text,
+                        markdown_blocks(text, range, false),
+                        target,
+                        split_sentences,
+                    )
+                };
+                self.refine(text, markdown_blocks(text, all, true), target, split_blocks)
+            }
+        }
+    }
+
+    fn refine(
+        &self,
+        text: &str,
+        segments: Vec<Range<usize>>,
+        target: usize,
+        split: impl Fn(Range<usize>) -> Vec<Range<usize>>,
+    ) -> Vec<Range<usize>> {
+        segments
+            .into_iter()
+            .flat_map(|segment| {
+                if self.size(&text[segment.clone()]) > target {
+                    split(segment)
+                } else {
+                    vec![segment]
+                }
+            })
+            .collect()
+    }
+
+    /// The smallest segments: single characters, or tokens with the whitespace that follows them.
+    fn units(&self, text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+        let starts = match self.config.unit {
+            ChunkSizeUnit::Characters => text[range.clone()]
+                .char_indices()
+                .map(|(i, _)| range.start + i)
+                .collect(),
+            ChunkSizeUnit::Tokens => token_starts(&text[range.clone()])
+                .map(|i| range.start + i)
+                .collect(),
+        };
+        split_at(range, starts)
+    }
+
+    fn size(&self, text: &str) -> usize {
+        match self.config.unit {
+            ChunkSizeUnit::Characters => text.chars().count(),
+            ChunkSizeUnit::Tokens => token_starts(text).count(),
+        }
+    }
+
+    /// Converts the byte ranges of chunks, which start in increasing order, to [`Chunk`]s.
+    fn to_chunks<'a>(&self, text: &'a str, ranges: Vec<Range<usize>>) -> Vec<Chunk<'a>> {
+        let mut chunks = Vec::with_capacity(ranges.len());
+        let (mut byte_offset, mut char_offset) = (0, 0);
+        for range in ranges {
+            let mut chunk = &text[range.clone()];
+            let mut start = range.start;
+            if self.config.trim_whitespace {
+                start += chunk.len() - chunk.trim_start().len();
+                chunk = chunk.trim();
+            }
+            if chunk.is_empty() {
+                continue;
+            }
+
+            char_offset += text[byte_offset..start].chars().count();
+            byte_offset = start;
+            chunks.push(Chunk {
+                text: chunk,
+                start: char_offset,
+                end: char_offset + chunk.chars().count(),
+            });
+        }
+        chunks
+    }
+}
+
+/// The `[start, end)` character offsets of each chunk in an array of chunk offsets.
+pub fn chunk_offsets(offsets: &ArrayRef) -> Vec<Option<(usize, usize)>> {
+    let Some(offsets) = offsets.as_fixed_size_list_opt() else {
+        return vec![];
+    };
+
+    (0..offsets.len())
+        .map(|i| {
+            if offsets.is_null(i) {
+                return None;
+            }
+            let offset = offsets.value(i);
+            let offset = offset.as_primitive_opt::<Int32Type>()?.values();
+            Some((
+                usize::try_from(*offset.first()?).ok()?,
+                usize::try_from(*offset.get(1)?).ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// The text of the chunk of `value` between the `start` and `end` character offsets.
+#[must_use]
+pub fn chunk_text(value: &str, start: usize, end: usize) -> &str {
+    let byte_offset = |chars: usize| {
+        value
+            .char_indices()
+            .nth(chars)
+            .map_or(value.len(), |(i, _)| i)
+    };
+    &value[byte_offset(start)..byte_offset(end.max(start))]
+}
+
+/// The byte offsets of the tokens of `text`: runs of alphanumeric characters, and every other
+/// character that isn't whitespace.
+fn token_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut previous: Option<char> = None;
+    text.char_indices().filter_map(move |(i, c)| {
+        let continues_word = c.is_alphanumeric() && previous.is_some_and(char::is_alphanumeric);
+        previous = Some(c);
+        (!c.is_whitespace() && !continues_word).then_some(i)
+    })
+}
+
+/// Sentences, with the whitespace that follows them. A sentence ends with a terminal punctuation
+/// mark that is followed by whitespace, or at a blank line.
+fn sentences(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut starts = vec![];
+    let mut after_terminator = false;
+    let mut at_boundary = false;
+    let mut newlines = 0;
+    for (i, c) in text[range.clone()].char_indices() {
+        if c.is_whitespace() {
+            if c == '\n' {
+                newlines += 1;
+            }
+            at_boundary |= after_terminator || newlines > 1;
+            after_terminator = false;
+            continue;
+        }
+
+        if at_boundary {
+            starts.push(range.start + i);
+            at_boundary = false;
+        }
+        newlines = 0;
+        after_terminator = matches!(c, '.' | '!' | '?' | '。' | '！' | '？')
+            || (after_terminator && matches!(c, '"' | '\'' | ')' | ']' | '”' | '’'));
+    }
+    split_at(range, starts)
+}
+
+/// Markdown sections, which start at headings, or blocks, which are also separated by blank lines.
+/// Blank lines within fenced code blocks don't separate blocks.
+fn markdown_blocks(text: &str, range: Range<usize>, sections: bool) -> Vec<Range<usize>> {
+    let mut starts = vec![];
+    let mut fence: Option<&str> = None;
+    let mut after_blank = false;
+    let mut line_start = range.start;
+    for line in text[range.clone()].split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let line = line.trim_start();
+
+        if let Some(marker) = fence {
+            if line.starts_with(marker) {
+                fence = None;
+                after_blank = true;
+            }
+            continue;
+        }
+        if line.is_empty() {
+            after_blank = true;
+            continue;
+        }
+
+        let heading = line.starts_with('#')
+            && line
+                .trim_start_matches('#')
+                .starts_with(char::is_whitespace);
+        fence = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| line.starts_with(marker));
+        if heading || (!sections && (after_blank || fence.is_some())) {
+            starts.push(start);
+        }
+        after_blank = false;
+    }
+    split_at(range, starts)
+}
+
+/// Splits `range` into contiguous ranges that start at each of `starts`, in increasing order.
+fn split_at(range: Range<usize>, starts: Vec<usize>) -> Vec<Range<usize>> {
+    let mut ranges = Vec::with_capacity(starts.len() + 1);
+    let mut start = range.start;
+    for next in starts {
+        if next > start {
+            ranges.push(start..next);
+            start = next;
+        }
+    }
+    if start < range.end {
+        ranges.push(start..range.end);
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunker(
+        target_chunk_size: usize,
+        overlap_size: usize,
+        unit: ChunkSizeUnit,
+        splitter: ChunkSplitter,
+    ) -> Chunker {
+        Chunker::new(EmbeddingChunkConfig {
+            target_chunk_size,
+            overlap_size,
+            unit,
+            splitter,
+            trim_whitespace: true,
+        })
+    }
+
+    fn chunks(chunker: &Chunker, text: &str) -> Vec<(String, usize, usize)> {
+        chunker
+            .chunks(text)
+            .into_iter()
+            .map(|chunk| (chunk.text.to_string(), chunk.start, chunk.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_characters() {
+        let chunker = chunker(4, 0, ChunkSizeUnit::Characters, ChunkSplitter::Fixed);
+        assert_eq!(
+            chunks(&chunker, "abcdefghij"),
+            vec![
+                ("abcd".to_string(), 0, 4),
+                ("efgh".to_string(), 4, 8),
+                ("ij".to_string(), 8, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlap() {
+        let chunker = chunker(4, 2, ChunkSizeUnit::Characters, ChunkSplitter::Fixed);
+        assert_eq!(
+            chunks(&chunker, "abcdefghij"),
+            vec![
+                ("abcd".to_string(), 0, 4),
+                ("cdef".to_string(), 2, 6),
+                ("efgh".to_string(), 4, 8),
+                ("ghij".to_string(), 6, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixed_tokens() {
+        let chunker = chunker(3, 0, ChunkSizeUnit::Tokens, ChunkSplitter::Fixed);
+        assert_eq!(
+            chunks(&chunker, "one two, three four."),
+            vec![
+                ("one two,".to_string(), 0, 8),
+                ("three four.".to_string(), 9, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentences() {
+        let chunker = chunker(30, 0, ChunkSizeUnit::Characters, ChunkSplitter::Sentence);
+        assert_eq!(
+            chunks(&chunker, "The first sentence. The second one! A third?"),
+            vec![
+                ("The first sentence.".to_string(), 0, 19),
+                ("The second one! A third?".to_string(), 20, 44),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let chunker = chunker(30, 0, ChunkSizeUnit::Characters, ChunkSplitter::Markdown);
+        assert_eq!(
+            chunks(
+                &chunker,
+                "# Title\n\nIntro text.\n\n## Part\n\nBody of the part.\n"
+            ),
+            vec![
+                ("# Title\n\nIntro text.".to_string(), 0, 20),
+                ("## Part\n\nBody of the part.".to_string(), 22, 48),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_code_block() {
+        let chunker = chunker(30, 0, ChunkSizeUnit::Characters, ChunkSplitter::Markdown);
+        let text = "Intro.\n\n```\nfn a() {}\n\nfn b() {}\n```\n";
+        assert_eq!(
+            chunks(&chunker, text)
+                .into_iter()
+                .map(|(text, _, _)| text)
+                .collect::<Vec<_>>(),
+            vec![
+                "Intro.".to_string(),
+                "```\nfn a() {}\n\nfn b() {}\n```".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_character_offsets() {
+        let chunker = chunker(6, 0, ChunkSizeUnit::Characters, ChunkSplitter::Fixed);
+        assert_eq!(
+            chunks(&chunker, "héllo wörld"),
+            vec![("héllo".to_string(), 0, 5), ("wörld".to_string(), 6, 11)]
+        );
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        assert_eq!(chunk_text("héllo wörld", 6, 11), "wörld");
+        assert_eq!(chunk_text("héllo wörld", 1, 2), "é");
+        assert_eq!(chunk_text("héllo", 3, 20), "lo");
+        assert_eq!(chunk_text("héllo", 4, 2), "");
+    }
+
+    #[test]
+    fn test_empty() {
+        let chunker = chunker(4, 0, ChunkSizeUnit::Characters, ChunkSplitter::Sentence);
+        assert!(chunker.chunks("").is_empty());
+        assert!(chunker.chunks(" \n ").is_empty());
+    }
+}
@@ -17,6 +17,7 @@ use crate::component::dataset::Dataset;
 use crate::model::EmbeddingModelStore;
 use async_trait::async_trait;
 use datafusion::datasource::TableProvider;
+use spicepod::component::embeddings::ColumnEmbeddingConfig;
 use std::any::Any;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -25,6 +26,8 @@ use tokio::sync::RwLock;
 use crate::dataconnector::DataConnector;
 use crate::dataconnector::DataConnectorResult;
 
+use super::full_text::FullTextIndex;
+use super::index::VectorIndex;
 use super::table::EmbeddingTable;
 
 pub struct EmbeddingConnector {
@@ -61,17 +64,108 @@ impl EmbeddingConnector {
             .map(|e| (e.column.clone(), e.model.clone()))
             .collect::<HashMap<_, _>>();
 
+        let chunking = dataset
+            .embeddings
+            .iter()
+            .filter_map(|e| Some((e.column.clone(), e.chunking.clone()?)))
+            .collect::<HashMap<_, _>>();
+
+        let distance_metrics = dataset
+            .embeddings
+            .iter()
+            .map(|e| (e.column.clone(), e.metric))
+            .collect::<HashMap<_, _>>();
+
         Ok(Arc::new(
             EmbeddingTable::new(
                 inner_table_provider,
                 embed_columns,
                 Arc::clone(&self.embedding_models),
             )
-            .await,
+            .await
+            .with_chunking(chunking)
+            .with_distance_metrics(distance_metrics)
+            .with_vector_indexes(vector_indexes(dataset))
+            .with_full_text_indexes(full_text_indexes(dataset)),
         ) as Arc<dyn TableProvider>)
     }
 }
 
+/// Create the indexes configured for the embedding columns of a [`Dataset`]. Indexes are built
+/// from the accelerated data, and identify rows by their primary key.
+fn vector_indexes(dataset: &Dataset) -> Vec<Arc<VectorIndex>> {
+    dataset
+        .embeddings
+        .iter()
+        .filter_map(|embedding| {
+            let config = embedding.index.clone()?;
+            let primary_keys = index_primary_keys(dataset, embedding, "vector search")?;
+
+            Some(Arc::new(VectorIndex::new(
+                embedding.column.clone(),
+                primary_keys,
+                config,
+                embedding.metric,
+            )))
+        })
+        .collect()
+}
+
+/// Create the full-text indexes configured for the embedding columns of a [`Dataset`]. Like vector
+/// indexes, they are built from the accelerated data and identify rows by their primary key.
+fn full_text_indexes(dataset: &Dataset) -> Vec<Arc<FullTextIndex>> {
+    dataset
+        .embeddings
+        .iter()
+        .filter_map(|embedding| {
+            let config = embedding.full_text.clone()?;
+            let primary_keys = index_primary_keys(dataset, embedding, "full-text search")?;
+
+            Some(Arc::new(FullTextIndex::new(
+                embedding.column.clone(),
+                primary_keys,
+                embedding.chunking.is_some(),
+                config,
+            )))
+        })
+        .collect()
+}
+
+/// The primary keys that identify the rows of an index over an embedding column, or `None` if the
+/// column can't be indexed.
+fn index_primary_keys(
+    dataset: &Dataset,
+    embedding: &ColumnEmbeddingConfig,
+    purpose: &str,
+) -> Option<Vec<String>> {
+    if !dataset.is_accelerated() {
+        tracing::warn!(
+            "Column {} of dataset {} isn't indexed for {purpose}, as indexes require an accelerated dataset.",
+            embedding.column,
+            dataset.name
+        );
+        return None;
+    }
+
+    let primary_keys = embedding.primary_keys.clone().or_else(|| {
+        dataset
+            .acceleration
+            .as_ref()?
+            .primary_key
+            .as_ref()
+            .map(|pk| pk.iter().map(ToString::to_string).collect())
+    });
+    let Some(primary_keys) = primary_keys.filter(|pks| !pks.is_empty()) else {
+        tracing::warn!(
+            "Column {} of dataset {} isn't indexed for {purpose}, as indexes require a primary key. Set the acceleration primary_key or column_pk.",
+            embedding.column,
+            dataset.name
+        );
+        return None;
+    };
+    Some(primary_keys)
+}
+
 #[async_trait]
 impl DataConnector for EmbeddingConnector {
     fn as_any(&self) -> &dyn Any {
@@ -14,7 +14,10 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use arrow::array::{ArrayRef, FixedSizeListArray, Float32Array, RecordBatch, StringArray};
+use arrow::array::{
+    ArrayRef, FixedSizeListArray, Float32Array, Int32Array, ListArray, RecordBatch, StringArray,
+};
+use arrow::buffer::OffsetBuffer;
 use arrow::datatypes::{DataType, Field, SchemaRef};
 
 use arrow::error::ArrowError;
@@ -34,7 +37,9 @@ use std::{any::Any, sync::Arc};
 use std::fmt;
 use tokio::sync::RwLock;
 
+use crate::embeddings::chunking::Chunker;
 use crate::model::EmbeddingModelStore;
+use llms::embeddings::Embed;
 
 pub struct EmbeddingTableExec {
     projected_schema: SchemaRef,
@@ -45,6 +50,7 @@ pub struct EmbeddingTableExec {
     base_plan: Arc<dyn ExecutionPlan>,
 
     embedded_columns: HashMap<String, String>,
+    chunkers: HashMap<String, Arc<Chunker>>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 }
 
@@ -96,6 +102,7 @@ impl ExecutionPlan for EmbeddingTableExec {
             self.limit,
             Arc::clone(&self.base_plan).with_new_children(children)?,
             self.embedded_columns.clone(),
+            self.chunkers.clone(),
             Arc::clone(&self.embedding_models),
         )) as Arc<dyn ExecutionPlan>)
     }
@@ -112,6 +119,7 @@ impl ExecutionPlan for EmbeddingTableExec {
                 s,
                 Arc::clone(&self.projected_schema),
                 self.embedded_columns.clone(),
+                self.chunkers.clone(),
                 Arc::clone(&self.embedding_models),
             ),
         )))
@@ -126,6 +134,7 @@ impl EmbeddingTableExec {
         limit: Option<usize>,
         base_plan: Arc<dyn ExecutionPlan>,
         embedded_columns: HashMap<String, String>,
+        chunkers: HashMap<String, Arc<Chunker>>,
         embedding_models: Arc<RwLock<EmbeddingModelStore>>,
     ) -> Self {
         Self {
@@ -135,6 +144,7 @@ impl EmbeddingTableExec {
             properties: Self::compute_properties(&base_plan, projected_schema),
             base_plan,
             embedded_columns,
+            chunkers,
             embedding_models,
         }
     }
@@ -154,13 +164,14 @@ fn to_sendable_stream(
     mut base_stream: SendableRecordBatchStream,
     projected_schema: SchemaRef,
     embedded_columns: HashMap<String, String>,
+    chunkers: HashMap<String, Arc<Chunker>>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
     stream! {
         while let Some(batch_result) = base_stream.next().await {
             match batch_result {
                 Ok(batch) => {
-                    match get_embeddings(&batch, &embedded_columns, Arc::clone(&embedding_models)).await {
+                    match get_embeddings(&batch, &embedded_columns, &chunkers, Arc::clone(&embedding_models)).await {
                         Ok(embeddings) => {
 
                             match construct_record_batch(
@@ -205,6 +216,7 @@ fn construct_record_batch(
 async fn get_embeddings(
     rb: &RecordBatch,
     embedded_columns: &HashMap<String, String>,
+    chunkers: &HashMap<String, Arc<Chunker>>,
     embedding_models: Arc<RwLock<EmbeddingModelStore>>,
 ) -> Result<HashMap<String, ArrayRef>, Box<dyn std::error::Error + Send + Sync>> {
     let field = Arc::new(Field::new("item", DataType::Float32, false));
@@ -234,6 +246,13 @@ async fn get_embeddings(
             continue;
         };
 
+        if let Some(chunker) = chunkers.get(col) {
+            let (embeddings, offsets) = get_chunked_embeddings(arr, chunker, &mut **model).await?;
+            embed_arrays.insert(format!("{col}_embedding"), embeddings);
+            embed_arrays.insert(format!("{col}_offset"), offsets);
+            continue;
+        }
+
         let column: Vec<String> = arr
             .iter()
             .filter_map(|s| s.map(ToString::to_string))
@@ -254,3 +273,67 @@ async fn get_embeddings(
     }
     Ok(embed_arrays)
 }
+
+/// Embeds the chunks of each value of a column. Returns the list of the embeddings of each value's
+/// chunks, and the list of the `[start, end)` character offsets of each value's chunks.
+async fn get_chunked_embeddings(
+    arr: &StringArray,
+    chunker: &Chunker,
+    model: &mut dyn Embed,
+) -> Result<(ArrayRef, ArrayRef), Box<dyn std::error::Error + Send + Sync>> {
+    let mut texts = vec![];
+    let mut offsets = vec![];
+    let mut num_chunks = Vec::with_capacity(arr.len());
+    for value in arr.iter() {
+        let chunks = value.map(|v| chunker.chunks(v)).unwrap_or_default();
+        num_chunks.push(chunks.len());
+        for chunk in chunks {
+            texts.push(chunk.text.to_string());
+            offsets.push(i32::try_from(chunk.start)?);
+            offsets.push(i32::try_from(chunk.end)?);
+        }
+    }
+
+    let embedded_data = if texts.is_empty() {
+        vec![]
+    } else {
+        model.embed(EmbeddingInput::StringArray(texts)).await?
+    };
+    let vector_length = embedded_data.first().map_or_else(
+        || model.size(),
+        |v| i32::try_from(v.len()).unwrap_or_default(),
+    );
+    let processed = embedded_data.iter().flatten().copied().collect_vec();
+
+    let embedding_field = Arc::new(Field::new("item", DataType::Float32, false));
+    let embeddings = FixedSizeListArray::try_new(
+        embedding_field,
+        vector_length,
+        Arc::new(Float32Array::try_new(processed.into(), None)?),
+        None,
+    )?;
+    let offset_field = Arc::new(Field::new("item", DataType::Int32, false));
+    let offsets = FixedSizeListArray::try_new(
+        offset_field,
+        2,
+        Arc::new(Int32Array::try_new(offsets.into(), None)?),
+        None,
+    )?;
+
+    let list_offsets = OffsetBuffer::from_lengths(num_chunks);
+    let nulls = arr.nulls().cloned();
+    let embeddings = ListArray::try_new(
+        Arc::new(Field::new("item", embeddings.data_type().clone(), false)),
+        list_offsets.clone(),
+        Arc::new(embeddings),
+        nulls.clone(),
+    )?;
+    let offsets = ListArray::try_new(
+        Arc::new(Field::new("item", offsets.data_type().clone(), false)),
+        list_offsets,
+        Arc::new(offsets),
+        nulls,
+    )?;
+
+    Ok((Arc::new(embeddings), Arc::new(offsets)))
+}
@@ -0,0 +1,473 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Full-text indexes over the embedded columns of accelerated datasets.
+//!
+//! A [`FullTextIndex`] ranks the rows of a column, or the chunks of its rows when the column is
+//! chunked, by how well they match a set of keywords with BM25. The index is updated incrementally
+//! after the data of the accelerator is updated, see [`super::incremental`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use datafusion::common::ScalarValue;
+use datafusion::datasource::TableProvider;
+use snafu::prelude::*;
+use spicepod::component::embeddings::FullTextIndexConfig;
+use tokio::sync::{watch, RwLock};
+
+use super::chunking::{chunk_offsets, chunk_text};
+use super::incremental::{self, IncrementalIndex, IndexEntries, Row, Tracked};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read the values of column {column}: {source}"))]
+    UnableToReadValues {
+        column: String,
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("Unable to convert the values of column {column} to text: {source}"))]
+    UnableToConvertValues {
+        column: String,
+        source: arrow::error::ArrowError,
+    },
+
+    #[snafu(context(false), display("{source}"))]
+    UnableToUpdateIndex { source: incremental::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A row, or a chunk of a row, that matches the keywords of a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullTextMatch {
+    /// The primary key of the row.
+    pub key: Vec<ScalarValue>,
+
+    /// The start and end character offsets of the matching chunk, if the column is chunked.
+    pub offset: Option<(usize, usize)>,
+
+    pub score: f32,
+}
+
+/// A BM25 index over the text of one column.
+pub struct FullTextIndex {
+    column: String,
+    primary_keys: Vec<String>,
+    chunked: bool,
+    config: FullTextIndexConfig,
+    built: RwLock<Option<Tracked<BuiltIndex>>>,
+}
+
+/// A row, or a chunk of a row, in the index.
+struct Document {
+    key: Vec<ScalarValue>,
+    offset: Option<(usize, usize)>,
+    /// The number of terms in the document.
+    length: usize,
+    /// Whether the row was deleted or its value changed since the document was inserted.
+    stale: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct BuiltIndex {
+    documents: Vec<Document>,
+    /// The documents each term occurs in, with the number of times it occurs in them.
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    /// The number of stale documents.
+    stale: usize,
+    /// The number of terms in the documents that aren't stale.
+    total_length: usize,
+}
+
+impl FullTextIndex {
+    #[must_use]
+    pub fn new(
+        column: String,
+        primary_keys: Vec<String>,
+        chunked: bool,
+        config: FullTextIndexConfig,
+    ) -> Self {
+        Self {
+            column,
+            primary_keys,
+            chunked,
+            config,
+            built: RwLock::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    #[must_use]
+    pub fn primary_keys(&self) -> &[String] {
+        &self.primary_keys
+    }
+
+    /// The best matches of the terms in `query`, best first, or `None` if the index isn't built
+    /// yet. Documents without any of the terms never match.
+    pub async fn search(&self, query: &str, limit: usize) -> Option<Vec<FullTextMatch>> {
+        let tracked = self.built.read().await;
+        Some(tracked.as_ref()?.index().search(query, limit, &self.config))
+    }
+
+    /// Updates the index with the values currently in `accelerator`.
+    pub async fn update(self: &Arc<Self>, accelerator: Arc<dyn TableProvider>) -> Result<()> {
+        incremental::update(self, accelerator).await
+    }
+
+    /// Builds the index, and updates it every time the data of the dataset is updated until the
+    /// dataset is removed.
+    pub async fn maintain(
+        self: Arc<Self>,
+        dataset_name: String,
+        accelerator: Arc<dyn TableProvider>,
+        data_updates: watch::Receiver<()>,
+    ) {
+        incremental::maintain(self, dataset_name, accelerator, data_updates).await;
+    }
+}
+
+impl IncrementalIndex for FullTextIndex {
+    type Index = BuiltIndex;
+
+    fn built(&self) -> &RwLock<Option<Tracked<BuiltIndex>>> {
+        &self.built
+    }
+
+    fn new_index(&self) -> BuiltIndex {
+        BuiltIndex::default()
+    }
+
+    /// The primary keys and the values of every row, followed by their chunk offsets if the column
+    /// is chunked.
+    fn columns(&self) -> Vec<String> {
+        self.primary_keys
+            .iter()
+            .cloned()
+            .chain(Some(self.column.clone()))
+            .chain(self.chunked.then(|| format!("{}_offset", self.column)))
+            .collect()
+    }
+
+    fn read_rows(&self, batch: &RecordBatch) -> Result<Vec<Row<BuiltIndex>>> {
+        read_rows(batch, self.primary_keys.len(), self.chunked)
+    }
+
+    fn description(&self) -> String {
+        format!("full-text index of column {}", self.column)
+    }
+}
+
+/// Reads the rows with values from a batch of primary key columns followed by the text column and,
+/// if the column is chunked, its chunk offsets.
+fn read_rows(batch: &RecordBatch, num_keys: usize, chunked: bool) -> Result<Vec<Row<BuiltIndex>>> {
+    let column = batch.schema().field(num_keys).name().clone();
+    let values =
+        cast(batch.column(num_keys), &DataType::Utf8).context(UnableToConvertValuesSnafu {
+            column: column.clone(),
+        })?;
+    let values = values.as_string::<i32>();
+    let offsets = if chunked {
+        batch.column(num_keys + 1).as_list_opt::<i32>()
+    } else {
+        None
+    };
+
+    let mut rows = vec![];
+    for row in 0..batch.num_rows() {
+        if values.is_null(row) {
+            continue;
+        }
+        let key = batch.columns()[..num_keys]
+            .iter()
+            .map(|column| ScalarValue::try_from_array(column, row))
+            .collect::<Result<Vec<_>, _>>()
+            .context(UnableToReadValuesSnafu {
+                column: column.clone(),
+            })?;
+
+        let value = values.value(row);
+        let documents = match offsets {
+            Some(offsets) if offsets.is_valid(row) => chunk_offsets(&offsets.value(row))
+                .into_iter()
+                .flatten()
+                .map(|(start, end)| {
+                    (
+                        Some((start, end)),
+                        chunk_text(value, start, end).to_string(),
+                    )
+                })
+                .collect(),
+            Some(_) => vec![],
+            None => vec![(None, value.to_string())],
+        };
+
+        let mut hasher = DefaultHasher::new();
+        documents.hash(&mut hasher);
+        rows.push(Row {
+            key,
+            entries: documents,
+            hash: hasher.finish(),
+        });
+    }
+    Ok(rows)
+}
+
+impl BuiltIndex {
+    /// Adds a document, and returns its id.
+    fn insert_document(
+        &mut self,
+        key: Vec<ScalarValue>,
+        offset: Option<(usize, usize)>,
+        text: &str,
+    ) -> usize {
+        let id = self.documents.len();
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        let mut length = 0;
+        for term in terms(text) {
+            *frequencies.entry(term).or_default() += 1;
+            length += 1;
+        }
+
+        for (term, frequency) in frequencies {
+            self.postings.entry(term).or_default().push((id, frequency));
+        }
+        self.documents.push(Document {
+            key,
+            offset,
+            length,
+            stale: false,
+        });
+        self.total_length += length;
+        id
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        config: &FullTextIndexConfig,
+    ) -> Vec<FullTextMatch> {
+        let num_documents = self.documents.len() - self.stale;
+        let average_length = average(self.total_length, num_documents);
+        let num_documents = num_documents as f32;
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+
+        for term in terms(query).collect::<HashSet<_>>() {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let postings = postings
+                .iter()
+                .filter(|(id, _)| !self.documents[*id].stale)
+                .collect::<Vec<_>>();
+            if postings.is_empty() {
+                continue;
+            }
+
+            let document_frequency = postings.len() as f32;
+            let idf = (1.0
+                + (num_documents - document_frequency + 0.5) / (document_frequency + 0.5))
+                .ln();
+            for &&(id, frequency) in &postings {
+                let frequency = frequency as f32;
+                let length = self.documents[id].length as f32 / average_length;
+                *scores.entry(id).or_default() += idf * frequency * (config.k1 + 1.0)
+                    / (frequency + config.k1 * (1.0 - config.b + config.b * length));
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(id, score)| FullTextMatch {
+                key: self.documents[id].key.clone(),
+                offset: self.documents[id].offset,
+                score,
+            })
+            .collect()
+    }
+}
+
+impl IndexEntries for BuiltIndex {
+    /// The chunk offsets and the text of the value of the row, or of each of its chunks.
+    type Entries = Vec<(Option<(usize, usize)>, String)>;
+    type Error = Error;
+
+    fn num_entries(documents: &Self::Entries) -> usize {
+        documents.len()
+    }
+
+    fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn insert(&mut self, key: &[ScalarValue], documents: Self::Entries) -> Result<Vec<usize>> {
+        Ok(documents
+            .into_iter()
+            .map(|(offset, text)| self.insert_document(key.to_vec(), offset, &text))
+            .collect())
+    }
+
+    fn remove(&mut self, id: usize) {
+        let document = &mut self.documents[id];
+        if !document.stale {
+            document.stale = true;
+            self.total_length -= document.length;
+            self.stale += 1;
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn average(total: usize, count: usize) -> f32 {
+    if count == 0 {
+        return 0.0;
+    }
+    total as f32 / count as f32
+}
+
+/// The lowercase terms of `text`: runs of alphanumeric characters and underscores. Identifiers
+/// such as `ERR-1234` are split into several terms, which queries for them are split into too.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, FixedSizeListArray, Int64Array, ListArray, StringArray};
+    use arrow::buffer::OffsetBuffer;
+    use arrow::datatypes::{Field, Int32Type};
+
+    fn documents(values: Vec<Option<&str>>) -> RecordBatch {
+        let ids = (0..i64::try_from(values.len()).expect("few values")).collect::<Vec<_>>();
+        RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(Int64Array::from(ids)) as ArrayRef),
+            ("text", Arc::new(StringArray::from(values)) as ArrayRef),
+        ])
+        .expect("valid batch")
+    }
+
+    fn build_index(batches: &[RecordBatch], num_keys: usize, chunked: bool) -> Result<BuiltIndex> {
+        let mut index = BuiltIndex::default();
+        for batch in batches {
+            for row in read_rows(batch, num_keys, chunked)? {
+                index.insert(&row.key, row.entries)?;
+            }
+        }
+        Ok(index)
+    }
+
+    fn ids(matches: &[FullTextMatch]) -> Vec<i64> {
+        matches
+            .iter()
+            .filter_map(|m| match m.key.first() {
+                Some(ScalarValue::Int64(Some(id))) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(
+            terms("Error ERR-1234: the_value, é!").collect::<Vec<_>>(),
+            vec!["error", "err", "1234", "the_value", "é"]
+        );
+    }
+
+    #[test]
+    fn test_rare_terms_rank_first() {
+        let batch = documents(vec![
+            Some("the quick brown fox"),
+            Some("the lazy dog"),
+            Some("the fox jumps over the dog"),
+            None,
+            Some("nothing in common"),
+        ]);
+        let index = build_index(&[batch], 1, false).expect("index");
+        assert_eq!(index.documents.len(), 4);
+
+        let config = FullTextIndexConfig::default();
+        assert_eq!(ids(&index.search("quick fox", 10, &config)), vec![0, 2]);
+        assert_eq!(ids(&index.search("THE dog", 10, &config)), vec![1, 2, 0]);
+        assert_eq!(ids(&index.search("dog", 1, &config)), vec![1]);
+        assert!(index.search("cat", 10, &config).is_empty());
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let batch = documents(vec![
+            Some("Order failed with ERR-1234, please retry"),
+            Some("Order failed with ERR-5678, please retry"),
+            Some("SKU AB-99 is out of stock"),
+        ]);
+        let index = build_index(&[batch], 1, false).expect("index");
+
+        let config = FullTextIndexConfig::default();
+        assert_eq!(ids(&index.search("err-5678", 1, &config)), vec![1]);
+        assert_eq!(ids(&index.search("ab-99", 10, &config)), vec![2]);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let offsets = FixedSizeListArray::from_iter_primitive::<Int32Type, _, _>(
+            vec![
+                Some(vec![Some(0), Some(9)]),
+                Some(vec![Some(10), Some(20)]),
+                Some(vec![Some(0), Some(6)]),
+            ],
+            2,
+        );
+        let offsets = ListArray::new(
+            Arc::new(Field::new("item", offsets.data_type().clone(), false)),
+            OffsetBuffer::from_lengths([2, 1]),
+            Arc::new(offsets),
+            None,
+        );
+        let batch = RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(Int64Array::from(vec![10, 20])) as ArrayRef),
+            (
+                "text",
+                Arc::new(StringArray::from(vec!["red apple green pear", "a plum"])) as ArrayRef,
+            ),
+            ("text_offset", Arc::new(offsets) as ArrayRef),
+        ])
+        .expect("valid batch");
+        let index = build_index(&[batch], 1, true).expect("index");
+        assert_eq!(index.documents.len(), 3);
+
+        let matches = index.search("pear", 10, &FullTextIndexConfig::default());
+        assert_eq!(ids(&matches), vec![10]);
+        assert_eq!(matches.first().and_then(|m| m.offset), Some((10, 20)));
+    }
+}
@@ -0,0 +1,583 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Incremental updates of the indexes over the columns of accelerated datasets.
+//!
+//! An index holds one or more entries for every row of the accelerator, i.e. the vectors of its
+//! embeddings or the documents of its text. After the data of the accelerator is updated, its
+//! batches are streamed and compared with the index one at a time: the entries of inserted and
+//! changed rows are added to the index in the background, and the entries of changed and deleted
+//! rows become stale, which excludes them from its results. The index is only rebuilt from scratch
+//! once most of its entries are stale.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::RecordBatch;
+use datafusion::common::ScalarValue;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::logical_expr::col;
+use datafusion::prelude::SessionContext;
+use futures::StreamExt;
+use snafu::prelude::*;
+use tokio::sync::{watch, RwLock};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read column {column}: {source}"))]
+    UnableToReadColumn {
+        column: String,
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("Unable to update the index: {source}"))]
+    UnableToUpdateIndex { source: tokio::task::JoinError },
+}
+
+/// Data updates within this interval of a rebuild are batched into the next rebuild, so that
+/// frequently changing datasets don't rebuild their indexes continuously.
+pub(crate) const MIN_REBUILD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The number of rows inserted into the index at a time, so that searches aren't blocked for the
+/// whole update.
+pub(crate) const UPDATE_BATCH_SIZE: usize = 256;
+
+/// The entries of an index, i.e. the graph of an approximate nearest neighbour index.
+pub(crate) trait IndexEntries: Send + Sync + 'static {
+    /// The entries of one row.
+    type Entries: Send + 'static;
+    type Error: From<Error> + Display + Send + 'static;
+
+    fn num_entries(entries: &Self::Entries) -> usize;
+
+    /// The number of entries in the index, including stale ones.
+    fn len(&self) -> usize;
+
+    /// Whether `entries` can't be added to the index, which has to be rebuilt instead.
+    fn is_incompatible(&self, _entries: &Self::Entries) -> bool {
+        false
+    }
+
+    /// Adds the entries of the row with `key`, and returns their ids.
+    fn insert(
+        &mut self,
+        key: &[ScalarValue],
+        entries: Self::Entries,
+    ) -> Result<Vec<usize>, Self::Error>;
+
+    /// Excludes the entry `id` from the results of the index.
+    fn remove(&mut self, id: usize);
+}
+
+/// An index over the rows of an accelerator, which is updated incrementally when its data changes.
+pub(crate) trait IncrementalIndex: Send + Sync + 'static {
+    type Index: IndexEntries;
+
+    fn built(&self) -> &RwLock<Option<Tracked<Self::Index>>>;
+
+    /// A new, empty index.
+    fn new_index(&self) -> Self::Index;
+
+    /// The columns to read from the accelerator: the primary keys followed by the indexed columns.
+    fn columns(&self) -> Vec<String>;
+
+    /// Reads the rows with entries from a batch of [`IncrementalIndex::columns`].
+    fn read_rows(&self, batch: &RecordBatch) -> Result<Vec<Row<Self::Index>>, ErrorOf<Self>>;
+
+    /// Describes the index in logs, i.e. `vector index of column text`.
+    fn description(&self) -> String;
+}
+
+pub(crate) type ErrorOf<T> = <<T as IncrementalIndex>::Index as IndexEntries>::Error;
+
+/// The entries of a row of the accelerator.
+pub(crate) struct Row<I: IndexEntries> {
+    pub(crate) key: Vec<ScalarValue>,
+    pub(crate) entries: I::Entries,
+    /// A hash of the indexed values of the row, to detect changes.
+    pub(crate) hash: u64,
+}
+
+/// An index and the rows it holds entries for.
+pub(crate) struct Tracked<I> {
+    index: I,
+    /// The ids of the entries of each row, and a hash of its indexed values.
+    rows: HashMap<Vec<ScalarValue>, IndexedRow>,
+    /// The number of stale entries.
+    stale: usize,
+    /// Incremented by every update, to find the rows that weren't seen by it.
+    generation: u64,
+}
+
+struct IndexedRow {
+    ids: Vec<usize>,
+    hash: u64,
+    /// The last update the row was seen by.
+    generation: u64,
+}
+
+impl<I: IndexEntries> Tracked<I> {
+    pub(crate) fn new(index: I) -> Self {
+        Self {
+            index,
+            rows: HashMap::new(),
+            stale: 0,
+            generation: 0,
+        }
+    }
+
+    pub(crate) fn index(&self) -> &I {
+        &self.index
+    }
+
+    /// The number of stale entries, which are still in the index but excluded from its results.
+    pub(crate) fn stale(&self) -> usize {
+        self.stale
+    }
+
+    /// Marks `row` as seen by the update `generation` if its entries are in the index, and returns
+    /// whether they are.
+    fn mark_seen(&mut self, row: &Row<I>, generation: u64) -> bool {
+        match self.rows.get_mut(&row.key) {
+            Some(indexed) if indexed.hash == row.hash => {
+                indexed.generation = generation;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the index should be rebuilt rather than updated with the `changed` and `deleted`
+    /// rows: when the entries of a changed row are incompatible with the index, or when most of its
+    /// entries would be stale.
+    fn needs_rebuild(&self, changed: &[Row<I>], deleted: &[Vec<ScalarValue>]) -> bool {
+        if self.index.len() > 0
+            && changed
+                .iter()
+                .any(|row| self.index.is_incompatible(&row.entries))
+        {
+            return true;
+        }
+
+        let stale = self.stale
+            + changed
+                .iter()
+                .map(|row| &row.key)
+                .chain(deleted)
+                .filter_map(|key| self.rows.get(key))
+                .map(|indexed| indexed.ids.len())
+                .sum::<usize>();
+        let inserted = changed
+            .iter()
+            .map(|row| I::num_entries(&row.entries))
+            .sum::<usize>();
+        stale * 2 > self.index.len() + inserted
+    }
+
+    /// Adds the entries of `row`, replacing the entries of the row with the same key.
+    fn upsert(&mut self, row: Row<I>) -> Result<(), I::Error> {
+        self.remove(&row.key);
+        let ids = self.index.insert(&row.key, row.entries)?;
+        self.rows.insert(
+            row.key,
+            IndexedRow {
+                ids,
+                hash: row.hash,
+                generation: self.generation,
+            },
+        );
+        Ok(())
+    }
+
+    /// Excludes the entries of the row with `key` from the results of the index.
+    fn remove(&mut self, key: &[ScalarValue]) {
+        if let Some(indexed) = self.rows.remove(key) {
+            for id in indexed.ids {
+                self.index.remove(id);
+                self.stale += 1;
+            }
+        }
+    }
+}
+
+/// Updates `index` with the rows currently in `accelerator`.
+///
+/// The batches of `accelerator` are compared with the index one at a time: the entries of rows that
+/// are new or changed are inserted into the existing index, and rows that weren't seen once all
+/// batches are read are removed from its results. The index is rebuilt from scratch if it isn't
+/// built yet, or once most of its entries are stale.
+pub(crate) async fn update<T: IncrementalIndex>(
+    index: &Arc<T>,
+    accelerator: Arc<dyn TableProvider>,
+) -> Result<(), ErrorOf<T>> {
+    let generation = index.built().write().await.as_mut().map(|built| {
+        built.generation += 1;
+        built.generation
+    });
+    let Some(generation) = generation else {
+        return rebuild(index, accelerator).await;
+    };
+
+    let mut batches = read_batches(index.as_ref(), Arc::clone(&accelerator)).await?;
+    let mut num_changed = 0;
+    while let Some(batch) = batches.next().await {
+        let batch = batch.context(UnableToReadColumnSnafu {
+            column: index.columns().join(", "),
+        })?;
+        let updated = Arc::clone(index);
+        let changed =
+            tokio::task::spawn_blocking(move || apply(updated.as_ref(), &batch, generation))
+                .await
+                .context(UnableToUpdateIndexSnafu)??;
+        match changed {
+            Some(changed) => num_changed += changed,
+            None => return rebuild(index, accelerator).await,
+        }
+    }
+
+    let updated = Arc::clone(index);
+    let deleted = tokio::task::spawn_blocking(move || remove_unseen(updated.as_ref(), generation))
+        .await
+        .context(UnableToUpdateIndexSnafu)?;
+    let Some(num_deleted) = deleted else {
+        return rebuild(index, accelerator).await;
+    };
+
+    if num_changed > 0 || num_deleted > 0 {
+        tracing::debug!(
+            "Updated {} with {num_changed} changed and {num_deleted} deleted rows",
+            index.description()
+        );
+    }
+    Ok(())
+}
+
+/// Builds a new index from the rows in `accelerator`, which replaces the current one once it is
+/// built.
+async fn rebuild<T: IncrementalIndex>(
+    index: &Arc<T>,
+    accelerator: Arc<dyn TableProvider>,
+) -> Result<(), ErrorOf<T>> {
+    let mut built = Tracked::new(index.new_index());
+    let mut batches = read_batches(index.as_ref(), accelerator).await?;
+    while let Some(batch) = batches.next().await {
+        let batch = batch.context(UnableToReadColumnSnafu {
+            column: index.columns().join(", "),
+        })?;
+        let rebuilt = Arc::clone(index);
+        built = tokio::task::spawn_blocking(move || {
+            for row in rebuilt.read_rows(&batch)? {
+                built.upsert(row)?;
+            }
+            Ok::<_, ErrorOf<T>>(built)
+        })
+        .await
+        .context(UnableToUpdateIndexSnafu)??;
+    }
+
+    tracing::debug!(
+        "Built {} with {} entries",
+        index.description(),
+        built.index.len()
+    );
+    *index.built().write().await = Some(built);
+    Ok(())
+}
+
+/// Streams the indexed columns of every row of `accelerator`.
+async fn read_batches<T: IncrementalIndex>(
+    index: &T,
+    accelerator: Arc<dyn TableProvider>,
+) -> Result<SendableRecordBatchStream, Error> {
+    let column_names = index.columns();
+    let columns = column_names
+        .iter()
+        .map(|column| col(format!(r#""{column}""#)))
+        .collect::<Vec<_>>();
+
+    SessionContext::new()
+        .read_table(accelerator)
+        .and_then(|df| df.select(columns))
+        .context(UnableToReadColumnSnafu {
+            column: column_names.join(", "),
+        })?
+        .execute_stream()
+        .await
+        .context(UnableToReadColumnSnafu {
+            column: column_names.join(", "),
+        })
+}
+
+/// Updates the index with a batch of rows, and marks them as seen by the update `generation`.
+/// Blocks while entries are inserted.
+///
+/// Returns the number of changed rows, or `None` if the index should be rebuilt instead.
+fn apply<T: IncrementalIndex>(
+    index: &T,
+    batch: &RecordBatch,
+    generation: u64,
+) -> Result<Option<usize>, ErrorOf<T>> {
+    let rows = index.read_rows(batch)?;
+
+    let changed: Vec<Row<T::Index>> = {
+        let mut built = index.built().blocking_write();
+        let Some(built) = built.as_mut() else {
+            return Ok(None);
+        };
+        let changed: Vec<Row<T::Index>> = rows
+            .into_iter()
+            .filter(|row| !built.mark_seen(row, generation))
+            .collect();
+        if built.needs_rebuild(&changed, &[]) {
+            return Ok(None);
+        }
+        changed
+    };
+
+    let num_changed = changed.len();
+    let mut changed = changed.into_iter().peekable();
+    while changed.peek().is_some() {
+        let mut built = index.built().blocking_write();
+        let Some(built) = built.as_mut() else {
+            return Ok(None);
+        };
+        for row in changed.by_ref().take(UPDATE_BATCH_SIZE) {
+            built.upsert(row)?;
+        }
+    }
+
+    Ok(Some(num_changed))
+}
+
+/// Removes the rows that weren't seen by the update `generation`, i.e. that were deleted.
+///
+/// Returns the number of deleted rows, or `None` if the index should be rebuilt instead.
+fn remove_unseen<T: IncrementalIndex>(index: &T, generation: u64) -> Option<usize> {
+    let mut built = index.built().blocking_write();
+    let built = built.as_mut()?;
+    let deleted: Vec<Vec<ScalarValue>> = built
+        .rows
+        .iter()
+        .filter(|(_, indexed)| indexed.generation != generation)
+        .map(|(key, _)| key.clone())
+        .collect();
+    if built.needs_rebuild(&[], &deleted) {
+        return None;
+    }
+
+    for key in &deleted {
+        built.remove(key);
+    }
+    Some(deleted.len())
+}
+
+/// Builds `index`, and updates it every time the data of the dataset is updated until the dataset
+/// is removed.
+pub(crate) async fn maintain<T: IncrementalIndex>(
+    index: Arc<T>,
+    dataset_name: String,
+    accelerator: Arc<dyn TableProvider>,
+    mut data_updates: watch::Receiver<()>,
+) {
+    loop {
+        if let Err(e) = update(&index, Arc::clone(&accelerator)).await {
+            tracing::warn!(
+                "Unable to build the {} in dataset {dataset_name}: {e}",
+                index.description()
+            );
+        }
+
+        tokio::time::sleep(MIN_REBUILD_INTERVAL).await;
+        if data_updates.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use arrow::array::{ArrayRef, AsArray, Int64Array, StringArray};
+    use arrow::datatypes::Int64Type;
+    use datafusion::datasource::MemTable;
+
+    use super::*;
+
+    /// An index with the value of every row as its only entry.
+    #[derive(Default)]
+    struct Values(Vec<Option<String>>);
+
+    impl IndexEntries for Values {
+        type Entries = String;
+        type Error = Error;
+
+        fn num_entries(_entries: &String) -> usize {
+            1
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn insert(&mut self, _key: &[ScalarValue], entries: String) -> Result<Vec<usize>, Error> {
+            self.0.push(Some(entries));
+            Ok(vec![self.0.len() - 1])
+        }
+
+        fn remove(&mut self, id: usize) {
+            self.0[id] = None;
+        }
+    }
+
+    struct ValuesIndex {
+        built: RwLock<Option<Tracked<Values>>>,
+    }
+
+    impl IncrementalIndex for ValuesIndex {
+        type Index = Values;
+
+        fn built(&self) -> &RwLock<Option<Tracked<Values>>> {
+            &self.built
+        }
+
+        fn new_index(&self) -> Values {
+            Values::default()
+        }
+
+        fn columns(&self) -> Vec<String> {
+            vec!["id".to_string(), "value".to_string()]
+        }
+
+        fn read_rows(&self, batch: &RecordBatch) -> Result<Vec<Row<Values>>, Error> {
+            let ids = batch.column(0).as_primitive::<Int64Type>();
+            let values = batch.column(1).as_string::<i32>();
+            Ok((0..batch.num_rows())
+                .map(|row| {
+                    let mut hasher = DefaultHasher::new();
+                    values.value(row).hash(&mut hasher);
+                    Row {
+                        key: vec![ScalarValue::Int64(Some(ids.value(row)))],
+                        entries: values.value(row).to_string(),
+                        hash: hasher.finish(),
+                    }
+                })
+                .collect())
+        }
+
+        fn description(&self) -> String {
+            "test index".to_string()
+        }
+    }
+
+    fn table(rows: &[(i64, &str)]) -> Arc<dyn TableProvider> {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "id",
+                Arc::new(Int64Array::from_iter_values(rows.iter().map(|(id, _)| *id))) as ArrayRef,
+            ),
+            (
+                "value",
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(_, value)| *value),
+                )) as ArrayRef,
+            ),
+        ])
+        .expect("valid batch");
+        Arc::new(MemTable::try_new(batch.schema(), vec![vec![batch]]).expect("valid table"))
+    }
+
+    async fn live_values(index: &ValuesIndex) -> Vec<String> {
+        let built = index.built.read().await;
+        let mut values: Vec<String> = built
+            .as_ref()
+            .expect("index built")
+            .index()
+            .0
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[tokio::test]
+    async fn test_update_inserts_changed_rows() {
+        let index = Arc::new(ValuesIndex {
+            built: RwLock::new(None),
+        });
+        let initial: Vec<(i64, String)> = (0..10).map(|id| (id, format!("value {id}"))).collect();
+        let rows = |rows: &[(i64, String)]| {
+            table(
+                &rows
+                    .iter()
+                    .map(|(id, value)| (*id, value.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        update(&index, rows(&initial)).await.expect("index built");
+        assert_eq!(live_values(&index).await.len(), 10);
+
+        // Row 3 changes, row 5 is deleted and row 100 is appended
+        let mut updated: Vec<(i64, String)> = initial
+            .iter()
+            .filter(|(id, _)| *id != 5)
+            .map(|(id, value)| {
+                if *id == 3 {
+                    (*id, "changed".to_string())
+                } else {
+                    (*id, value.clone())
+                }
+            })
+            .collect();
+        updated.push((100, "new".to_string()));
+        update(&index, rows(&updated)).await.expect("index updated");
+
+        {
+            let built = index.built.read().await;
+            let built = built.as_ref().expect("index built");
+            assert_eq!(
+                built.index().len(),
+                12,
+                "index should be updated, not rebuilt"
+            );
+            assert_eq!(built.stale(), 2);
+            assert_eq!(built.rows.len(), 10);
+        }
+        let values = live_values(&index).await;
+        assert!(values.contains(&"changed".to_string()));
+        assert!(values.contains(&"new".to_string()));
+        assert!(!values.contains(&"value 3".to_string()), "row 3 changed");
+        assert!(
+            !values.contains(&"value 5".to_string()),
+            "row 5 was deleted"
+        );
+
+        // Most entries are stale after deleting most rows, so the index is rebuilt
+        update(&index, rows(&updated[..2]))
+            .await
+            .expect("index rebuilt");
+        let built = index.built.read().await;
+        let built = built.as_ref().expect("index built");
+        assert_eq!(built.index().len(), 2);
+        assert_eq!(built.stale(), 0);
+    }
+}
@@ -0,0 +1,878 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Approximate nearest neighbour indexes over the embedding columns of accelerated datasets.
+//!
+//! A [`VectorIndex`] maps the embedding of every row, or of every chunk of a row when the column is
+//! chunked, to the row's primary key. Vector search uses
+//! it to narrow the rows it computes exact distances for down to a few candidates. The index is
+//! updated incrementally after the data of the accelerator is updated, see [`super::incremental`].
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Float32Array, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use datafusion::common::ScalarValue;
+use datafusion::datasource::TableProvider;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use snafu::prelude::*;
+use spicepod::component::embeddings::{DistanceMetric, VectorIndexConfig};
+use tokio::sync::{watch, RwLock};
+
+use super::incremental::{self, IncrementalIndex, IndexEntries, Row, Tracked};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read the embeddings of column {column}: {source}"))]
+    UnableToReadEmbeddings {
+        column: String,
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("Column {column} doesn't contain embeddings of type {data_type}"))]
+    InvalidEmbeddingType { column: String, data_type: DataType },
+
+    #[snafu(display("Unable to convert embeddings to Float32: {source}"))]
+    UnableToConvertEmbeddings { source: arrow::error::ArrowError },
+
+    #[snafu(display("Embeddings have {actual} dimensions, expected {expected}"))]
+    DimensionMismatch { expected: usize, actual: usize },
+
+    #[snafu(context(false), display("{source}"))]
+    UnableToUpdateIndex { source: incremental::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An approximate nearest neighbour index over the embeddings of one column.
+pub struct VectorIndex {
+    column: String,
+    primary_keys: Vec<String>,
+    config: VectorIndexConfig,
+    metric: DistanceMetric,
+    built: RwLock<Option<Tracked<BuiltIndex>>>,
+}
+
+pub(crate) struct BuiltIndex {
+    params: HnswParams,
+    hnsw: Hnsw,
+    /// The primary key of the row of each vector in `hnsw`, or `None` once the row is deleted or
+    /// its embeddings change. Rows with several chunks have several vectors.
+    keys: Vec<Option<Vec<ScalarValue>>>,
+}
+
+impl VectorIndex {
+    #[must_use]
+    pub fn new(
+        column: String,
+        primary_keys: Vec<String>,
+        config: VectorIndexConfig,
+        metric: DistanceMetric,
+    ) -> Self {
+        Self {
+            column,
+            primary_keys,
+            config,
+            metric,
+            built: RwLock::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    #[must_use]
+    pub fn primary_keys(&self) -> &[String] {
+        &self.primary_keys
+    }
+
+    /// The primary keys of the rows that are likely closest to `query`, closest first, or `None`
+    /// if the index isn't built yet.
+    ///
+    /// At least `limit` candidates are returned when the index has enough vectors, and up to
+    /// `ef_search` to leave room for filters that exclude some of them.
+    pub async fn search(&self, query: &[f32], limit: usize) -> Option<Vec<Vec<ScalarValue>>> {
+        let tracked = self.built.read().await;
+        let tracked = tracked.as_ref()?;
+        let built = tracked.index();
+        if built.hnsw.dimension() != query.len() {
+            return None;
+        }
+
+        // Stale vectors are skipped, so more of them are searched to return as many candidates.
+        let ef = self.config.ef_search.max(limit) + tracked.stale().min(self.config.ef_search);
+        let mut seen = HashSet::new();
+        Some(
+            built
+                .hnsw
+                .search(query, ef, ef)
+                .into_iter()
+                .filter_map(|(id, _)| built.keys.get(id)?.as_ref())
+                .filter(|key| seen.insert(*key))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Updates the index with the embeddings currently in `accelerator`.
+    pub async fn update(self: &Arc<Self>, accelerator: Arc<dyn TableProvider>) -> Result<()> {
+        incremental::update(self, accelerator).await
+    }
+
+    /// Builds the index, and updates it every time the data of the dataset is updated until the
+    /// dataset is removed.
+    pub async fn maintain(
+        self: Arc<Self>,
+        dataset_name: String,
+        accelerator: Arc<dyn TableProvider>,
+        data_updates: watch::Receiver<()>,
+    ) {
+        incremental::maintain(self, dataset_name, accelerator, data_updates).await;
+    }
+
+    fn embedding_column(&self) -> String {
+        format!("{}_embedding", self.column)
+    }
+}
+
+impl IncrementalIndex for VectorIndex {
+    type Index = BuiltIndex;
+
+    fn built(&self) -> &RwLock<Option<Tracked<BuiltIndex>>> {
+        &self.built
+    }
+
+    fn new_index(&self) -> BuiltIndex {
+        BuiltIndex::new(HnswParams::from(&self.config).with_metric(self.metric))
+    }
+
+    fn columns(&self) -> Vec<String> {
+        self.primary_keys
+            .iter()
+            .cloned()
+            .chain(Some(self.embedding_column()))
+            .collect()
+    }
+
+    fn read_rows(&self, batch: &RecordBatch) -> Result<Vec<Row<BuiltIndex>>> {
+        read_rows(std::slice::from_ref(batch), self.primary_keys.len())
+    }
+
+    fn description(&self) -> String {
+        format!("vector index of column {}", self.column)
+    }
+}
+
+impl BuiltIndex {
+    fn new(params: HnswParams) -> Self {
+        Self {
+            params,
+            hnsw: Hnsw::new(0, params),
+            keys: vec![],
+        }
+    }
+}
+
+impl IndexEntries for BuiltIndex {
+    /// The embedding of the row, or of each of its chunks.
+    type Entries = Vec<Vec<f32>>;
+    type Error = Error;
+
+    fn num_entries(vectors: &Vec<Vec<f32>>) -> usize {
+        vectors.len()
+    }
+
+    fn len(&self) -> usize {
+        self.hnsw.len()
+    }
+
+    /// Vectors of another dimension than the embeddings in the index can't be added to it.
+    fn is_incompatible(&self, vectors: &Vec<Vec<f32>>) -> bool {
+        vectors
+            .iter()
+            .any(|vector| vector.len() != self.hnsw.dimension())
+    }
+
+    fn insert(&mut self, key: &[ScalarValue], vectors: Vec<Vec<f32>>) -> Result<Vec<usize>> {
+        if self.hnsw.len() == 0 {
+            if let Some(vector) = vectors.first() {
+                self.hnsw = Hnsw::new(vector.len(), self.params);
+            }
+        }
+        for vector in &vectors {
+            ensure!(
+                vector.len() == self.hnsw.dimension(),
+                DimensionMismatchSnafu {
+                    expected: self.hnsw.dimension(),
+                    actual: vector.len(),
+                }
+            );
+        }
+
+        let mut ids = Vec::with_capacity(vectors.len());
+        for vector in vectors {
+            ids.push(self.hnsw.len());
+            self.hnsw.insert(vector);
+            self.keys.push(Some(key.to_vec()));
+        }
+        Ok(ids)
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.keys[id] = None;
+    }
+}
+
+/// Reads the rows with embeddings from batches of primary key columns followed by the embedding
+/// column.
+fn read_rows(batches: &[RecordBatch], num_keys: usize) -> Result<Vec<Row<BuiltIndex>>> {
+    let mut rows = vec![];
+    for batch in batches {
+        let embeddings = batch.column(num_keys);
+        let column = batch.schema().field(num_keys).name().clone();
+        for row in 0..batch.num_rows() {
+            if embeddings.is_null(row) {
+                continue;
+            }
+            let key = batch.columns()[..num_keys]
+                .iter()
+                .map(|column| ScalarValue::try_from_array(column, row))
+                .collect::<Result<Vec<_>, _>>()
+                .context(UnableToReadEmbeddingsSnafu {
+                    column: column.clone(),
+                })?;
+
+            let vectors = row_embeddings(embeddings, row, &column)?;
+            let mut hasher = DefaultHasher::new();
+            for vector in &vectors {
+                vector.len().hash(&mut hasher);
+                vector.iter().for_each(|v| v.to_bits().hash(&mut hasher));
+            }
+            rows.push(Row {
+                key,
+                entries: vectors,
+                hash: hasher.finish(),
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// The embeddings in `row` of a (fixed size) list array: the embedding of the row, or the embedding
+/// of each of its chunks if the column is chunked.
+fn row_embeddings(embeddings: &ArrayRef, row: usize, column: &str) -> Result<Vec<Vec<f32>>> {
+    let values = list_value(embeddings, row, column)?;
+    if !matches!(
+        values.data_type(),
+        DataType::FixedSizeList(..) | DataType::List(_) | DataType::LargeList(_)
+    ) {
+        return Ok(vec![embedding(&values)?]);
+    }
+
+    (0..values.len())
+        .filter(|&chunk| !values.is_null(chunk))
+        .map(|chunk| embedding(&list_value(&values, chunk, column)?))
+        .collect()
+}
+
+/// The value in `row` of a (fixed size) list array.
+fn list_value(list: &ArrayRef, row: usize, column: &str) -> Result<ArrayRef> {
+    if let Some(list) = list.as_fixed_size_list_opt() {
+        Ok(list.value(row))
+    } else if let Some(list) = list.as_list_opt::<i32>() {
+        Ok(list.value(row))
+    } else if let Some(list) = list.as_list_opt::<i64>() {
+        Ok(list.value(row))
+    } else {
+        InvalidEmbeddingTypeSnafu {
+            column,
+            data_type: list.data_type().clone(),
+        }
+        .fail()
+    }
+}
+
+/// Converts the numeric values of an embedding to Float32.
+fn embedding(values: &ArrayRef) -> Result<Vec<f32>> {
+    let values = cast(values, &DataType::Float32).context(UnableToConvertEmbeddingsSnafu)?;
+    let Some(values) = values.as_any().downcast_ref::<Float32Array>() else {
+        unreachable!("values were cast to Float32");
+    };
+    Ok(values.values().to_vec())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HnswParams {
+    /// The number of neighbours of a node on the upper layers, twice as many on the bottom layer.
+    m: usize,
+    ef_construction: usize,
+    metric: DistanceMetric,
+}
+
+impl HnswParams {
+    fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+}
+
+impl From<&VectorIndexConfig> for HnswParams {
+    fn from(config: &VectorIndexConfig) -> Self {
+        Self {
+            m: config.m.max(2),
+            ef_construction: config.ef_construction.max(1),
+            metric: DistanceMetric::default(),
+        }
+    }
+}
+
+/// A hierarchical navigable small world graph (Malkov & Yashunin, 2016) that orders vectors the
+/// same way as the UDF of its [`DistanceMetric`]. Cosine distance is computed as the squared
+/// Euclidean distance between normalized vectors, and the dot product is negated so that closer
+/// vectors have smaller distances.
+struct Hnsw {
+    params: HnswParams,
+    dimension: usize,
+    /// The vectors of all nodes, `dimension` values each.
+    vectors: Vec<f32>,
+    /// The neighbours of every node on each layer it is on, from the bottom layer up.
+    neighbours: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    rng: StdRng,
+}
+
+/// A node and its distance to the vector being searched for, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    id: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+impl Hnsw {
+    fn new(dimension: usize, params: HnswParams) -> Self {
+        Self {
+            params,
+            dimension,
+            vectors: vec![],
+            neighbours: vec![],
+            entry_point: None,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn len(&self) -> usize {
+        self.neighbours.len()
+    }
+
+    fn vector(&self, id: usize) -> &[f32] {
+        &self.vectors[id * self.dimension..(id + 1) * self.dimension]
+    }
+
+    fn distance(&self, query: &[f32], id: usize) -> f32 {
+        let pairs = query.iter().zip(self.vector(id));
+        match self.params.metric {
+            DistanceMetric::L2 | DistanceMetric::Cosine => {
+                pairs.map(|(a, b)| (a - b) * (a - b)).sum()
+            }
+            DistanceMetric::Dot => -pairs.map(|(a, b)| a * b).sum::<f32>(),
+        }
+    }
+
+    /// Normalizes `vector` to unit length for the cosine distance, leaving zero vectors as is.
+    fn prepare(&self, mut vector: Vec<f32>) -> Vec<f32> {
+        if self.params.metric == DistanceMetric::Cosine {
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+        vector
+    }
+
+    fn candidate(&self, query: &[f32], id: usize) -> Candidate {
+        Candidate {
+            distance: self.distance(query, id),
+            id,
+        }
+    }
+
+    fn top_layer(&self, id: usize) -> usize {
+        self.neighbours[id].len() - 1
+    }
+
+    fn max_neighbours(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.params.m * 2
+        } else {
+            self.params.m
+        }
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn random_layer(&mut self) -> usize {
+        let uniform: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        (-uniform.ln() / (self.params.m as f64).ln()).floor() as usize
+    }
+
+    fn insert(&mut self, vector: Vec<f32>) {
+        let id = self.len();
+        let layer = self.random_layer();
+        let vector = self.prepare(vector);
+        self.vectors.extend(vector);
+        self.neighbours.push(vec![vec![]; layer + 1]);
+
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(id);
+            return;
+        };
+
+        let query = self.vector(id).to_vec();
+        let top_layer = self.top_layer(entry_point);
+        let mut entry_points = vec![self.candidate(&query, entry_point)];
+
+        for current in (layer + 1..=top_layer).rev() {
+            entry_points = self.search_layer(&query, &entry_points, 1, current);
+        }
+
+        for current in (0..=layer.min(top_layer)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.params.ef_construction, current);
+            let max_neighbours = self.max_neighbours(current);
+            let neighbours: Vec<usize> = candidates
+                .iter()
+                .take(max_neighbours)
+                .map(|c| c.id)
+                .collect();
+
+            for &neighbour in &neighbours {
+                let links = &mut self.neighbours[neighbour][current];
+                links.push(id);
+                if links.len() > max_neighbours {
+                    self.prune(neighbour, current, max_neighbours);
+                }
+            }
+            self.neighbours[id][current] = neighbours;
+            entry_points = candidates;
+        }
+
+        if layer > top_layer {
+            self.entry_point = Some(id);
+        }
+    }
+
+    /// Keeps the `max_neighbours` closest neighbours of `id` on `layer`.
+    fn prune(&mut self, id: usize, layer: usize, max_neighbours: usize) {
+        let vector = self.vector(id).to_vec();
+        let mut links: Vec<Candidate> = self.neighbours[id][layer]
+            .iter()
+            .map(|&neighbour| self.candidate(&vector, neighbour))
+            .collect();
+        links.sort();
+        self.neighbours[id][layer] = links
+            .into_iter()
+            .take(max_neighbours)
+            .map(|c| c.id)
+            .collect();
+    }
+
+    /// The `ef` nodes closest to `query` on `layer` reachable from `entry_points`, closest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().map(|c| c.id).collect();
+        let mut candidates: BinaryHeap<std::cmp::Reverse<Candidate>> = entry_points
+            .iter()
+            .copied()
+            .map(std::cmp::Reverse)
+            .collect();
+        let mut nearest: BinaryHeap<Candidate> = entry_points.iter().copied().collect();
+        while nearest.len() > ef {
+            nearest.pop();
+        }
+
+        while let Some(std::cmp::Reverse(closest)) = candidates.pop() {
+            if nearest
+                .peek()
+                .is_some_and(|furthest| closest.distance > furthest.distance && nearest.len() >= ef)
+            {
+                break;
+            }
+
+            for &neighbour in &self.neighbours[closest.id][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let candidate = self.candidate(query, neighbour);
+                if nearest.len() < ef
+                    || nearest
+                        .peek()
+                        .is_some_and(|furthest| candidate.distance < furthest.distance)
+                {
+                    candidates.push(std::cmp::Reverse(candidate));
+                    nearest.push(candidate);
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+
+        nearest.into_sorted_vec()
+    }
+
+    /// The ids of the `k` nodes closest to `query` and their distances, closest first, considering
+    /// `ef` candidates on the bottom layer.
+    fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(usize, f32)> {
+        let Some(entry_point) = self.entry_point else {
+            return vec![];
+        };
+
+        let query = &self.prepare(query.to_vec());
+        let mut entry_points = vec![self.candidate(query, entry_point)];
+        for layer in (1..=self.top_layer(entry_point)).rev() {
+            entry_points = self.search_layer(query, &entry_points, 1, layer);
+        }
+
+        self.search_layer(query, &entry_points, ef.max(k), 0)
+            .into_iter()
+            .take(k)
+            .map(|c| (c.id, c.distance))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{FixedSizeListArray, Int64Array, ListArray};
+    use arrow::buffer::OffsetBuffer;
+    use arrow::datatypes::{Field, Float32Type, Schema};
+    use datafusion::datasource::MemTable;
+
+    fn random_vectors(n: usize, dimension: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| (0..dimension).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn exact_nearest(vectors: &[Vec<f32>], query: &[f32], k: usize) -> Vec<usize> {
+        let mut distances: Vec<(usize, f32)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(id, v)| {
+                (
+                    id,
+                    v.iter().zip(query).map(|(a, b)| (a - b) * (a - b)).sum(),
+                )
+            })
+            .collect();
+        distances.sort_by(|a, b| a.1.total_cmp(&b.1));
+        distances.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    fn build_index(
+        batches: &[RecordBatch],
+        num_keys: usize,
+        params: HnswParams,
+    ) -> Result<BuiltIndex> {
+        let mut built = BuiltIndex::new(params);
+        for row in read_rows(batches, num_keys)? {
+            built.insert(&row.key, row.entries)?;
+        }
+        Ok(built)
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let vectors = random_vectors(2000, 16, 1);
+        let mut hnsw = Hnsw::new(
+            16,
+            HnswParams {
+                m: 16,
+                ef_construction: 100,
+                metric: DistanceMetric::L2,
+            },
+        );
+        for vector in &vectors {
+            hnsw.insert(vector.clone());
+        }
+        assert_eq!(hnsw.len(), 2000);
+
+        let queries = random_vectors(50, 16, 2);
+        let mut found = 0;
+        for query in &queries {
+            let expected = exact_nearest(&vectors, query, 10);
+            let actual: Vec<usize> = hnsw
+                .search(query, 10, 64)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(actual.len(), 10);
+            found += expected.iter().filter(|id| actual.contains(id)).count();
+        }
+
+        // Recall@10 over all queries
+        assert!(found >= 450, "recall too low: {found}/500");
+    }
+
+    #[test]
+    fn test_hnsw_exact_match_and_empty() {
+        let empty = Hnsw::new(3, HnswParams::from(&VectorIndexConfig::default()));
+        assert!(empty.search(&[0.0, 0.0, 0.0], 5, 10).is_empty());
+
+        let mut hnsw = Hnsw::new(3, HnswParams::from(&VectorIndexConfig::default()));
+        for vector in random_vectors(100, 3, 3) {
+            hnsw.insert(vector);
+        }
+        let query = hnsw.vector(42).to_vec();
+        let results = hnsw.search(&query, 3, 16);
+        assert_eq!(results.first().map(|(id, _)| *id), Some(42));
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn test_hnsw_metrics() {
+        let vectors = [
+            vec![1.0, 0.0],
+            vec![10.0, 1.0],
+            vec![100.0, 0.0],
+            vec![0.0, 1.0],
+        ];
+        let nearest = |metric| {
+            let params = HnswParams::from(&VectorIndexConfig::default()).with_metric(metric);
+            let mut hnsw = Hnsw::new(2, params);
+            for vector in &vectors {
+                hnsw.insert(vector.clone());
+            }
+            hnsw.search(&[2.0, 0.2], 1, 16).first().map(|(id, _)| *id)
+        };
+
+        assert_eq!(nearest(DistanceMetric::L2), Some(0));
+        assert_eq!(nearest(DistanceMetric::Cosine), Some(1));
+        assert_eq!(nearest(DistanceMetric::Dot), Some(2));
+    }
+
+    #[test]
+    fn test_build_index_from_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "text_embedding",
+                DataType::new_fixed_size_list(DataType::Float32, 2, false),
+                true,
+            ),
+        ]));
+        let embeddings = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(0.0), Some(0.0)]),
+                None,
+                Some(vec![Some(1.0), Some(1.0)]),
+            ],
+            2,
+        );
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![10, 20, 30])),
+                Arc::new(embeddings),
+            ],
+        )
+        .expect("valid batch");
+
+        let built = build_index(&[batch], 1, HnswParams::from(&VectorIndexConfig::default()))
+            .expect("index");
+        assert_eq!(
+            built.keys,
+            vec![
+                Some(vec![ScalarValue::Int64(Some(10))]),
+                Some(vec![ScalarValue::Int64(Some(30))]),
+            ]
+        );
+
+        let nearest = built.hnsw.search(&[0.9, 0.9], 1, 4);
+        assert_eq!(
+            nearest.first().and_then(|(id, _)| built.keys.get(*id)),
+            Some(&Some(vec![ScalarValue::Int64(Some(30))]))
+        );
+    }
+
+    #[test]
+    fn test_build_index_from_chunked_batches() {
+        let chunks = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(0.0), Some(0.0)]),
+                Some(vec![Some(1.0), Some(1.0)]),
+                Some(vec![Some(5.0), Some(5.0)]),
+            ],
+            2,
+        );
+        let embeddings = ListArray::new(
+            Arc::new(Field::new("item", chunks.data_type().clone(), false)),
+            OffsetBuffer::from_lengths([2, 1]),
+            Arc::new(chunks),
+            None,
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("text_embedding", embeddings.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![10, 20])),
+                Arc::new(embeddings),
+            ],
+        )
+        .expect("valid batch");
+
+        let built = build_index(&[batch], 1, HnswParams::from(&VectorIndexConfig::default()))
+            .expect("index");
+        assert_eq!(
+            built.keys,
+            vec![
+                Some(vec![ScalarValue::Int64(Some(10))]),
+                Some(vec![ScalarValue::Int64(Some(10))]),
+                Some(vec![ScalarValue::Int64(Some(20))]),
+            ]
+        );
+
+        let nearest = built.hnsw.search(&[0.9, 0.9], 1, 4);
+        assert_eq!(
+            nearest.first().and_then(|(id, _)| built.keys.get(*id)),
+            Some(&Some(vec![ScalarValue::Int64(Some(10))]))
+        );
+    }
+
+    /// A table of `rows`, in batches of a few rows so that updates read several batches.
+    fn embeddings_table(rows: &[(i64, Vec<f32>)]) -> Arc<dyn TableProvider> {
+        let dimension = rows.first().map_or(0, |(_, vector)| vector.len());
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "text_embedding",
+                DataType::new_fixed_size_list(
+                    DataType::Float32,
+                    i32::try_from(dimension).expect("small dimension"),
+                    false,
+                ),
+                true,
+            ),
+        ]));
+        let batches = rows
+            .chunks(7)
+            .map(|rows| {
+                let embeddings = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                    rows.iter()
+                        .map(|(_, vector)| Some(vector.iter().copied().map(Some))),
+                    i32::try_from(dimension).expect("small dimension"),
+                );
+                RecordBatch::try_new(
+                    Arc::clone(&schema),
+                    vec![
+                        Arc::new(Int64Array::from_iter_values(rows.iter().map(|(id, _)| *id))),
+                        Arc::new(embeddings),
+                    ],
+                )
+                .expect("valid batch")
+            })
+            .collect();
+        Arc::new(MemTable::try_new(schema, vec![batches]).expect("valid table"))
+    }
+
+    #[tokio::test]
+    async fn test_update_rebuilds_when_the_dimension_changes() {
+        let index = Arc::new(VectorIndex::new(
+            "text".to_string(),
+            vec!["id".to_string()],
+            VectorIndexConfig::default(),
+            DistanceMetric::L2,
+        ));
+        assert!(index.search(&[0.0, 0.0], 1).await.is_none());
+
+        let rows = |dimension: usize| {
+            (0..20u8)
+                .map(|id| (i64::from(id), vec![f32::from(id); dimension]))
+                .collect::<Vec<_>>()
+        };
+        index
+            .update(embeddings_table(&rows(2)))
+            .await
+            .expect("index built");
+        assert_eq!(
+            index
+                .search(&[3.1, 3.1], 1)
+                .await
+                .and_then(|keys| keys.into_iter().next()),
+            Some(vec![ScalarValue::Int64(Some(3))])
+        );
+
+        // The embeddings of every row change, e.g. after switching the embedding model
+        index
+            .update(embeddings_table(&rows(3)))
+            .await
+            .expect("index rebuilt");
+        assert!(index.search(&[3.1, 3.1], 1).await.is_none());
+        assert_eq!(
+            index
+                .search(&[5.1, 5.1, 5.1], 1)
+                .await
+                .and_then(|keys| keys.into_iter().next()),
+            Some(vec![ScalarValue::Int64(Some(5))])
+        );
+        let built = index.built.read().await;
+        let built = built.as_ref().expect("index built");
+        assert_eq!(built.index().hnsw.len(), 20);
+        assert_eq!(built.stale(), 0);
+    }
+}
@@ -16,8 +16,13 @@ limitations under the License.
 #![allow(unused_attributes)] // This is for the `f16_and_f128` feature.
 #![feature(f16_and_f128)]
 pub mod array_distance;
+pub mod chunking;
 pub mod connector;
 pub mod execution_plan;
+pub mod full_text;
+pub mod incremental;
+pub mod index;
+pub mod search_function;
 pub mod table;
 pub mod task;
 pub mod vector_search;
@@ -0,0 +1,463 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The `vector_search` SQL table function, which runs a vector search over a dataset so that its
+//! results can be filtered and joined with other tables:
+//!
+//! ```sql
+//! SELECT * FROM vector_search(reviews, 'broken on arrival', 5) v JOIN orders o ON v.order_id = o.id
+//! ```
+//!
+//! The result has the columns of the dataset without its embeddings, in order of similarity. For
+//! chunked columns, the embedded column contains the matching chunk. Qualified dataset names are
+//! passed as strings, e.g. `vector_search('spice.public.reviews', 'text')`. Only datasets of the
+//! `spice` catalog with a single embedding column can be searched.
+
+use std::any::Any;
+use std::sync::{Arc, Weak};
+
+use app::App;
+use arrow::array::{ArrayRef, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, DataFusionError, Result as DataFusionResult, ScalarValue};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::sql::TableReference;
+use itertools::izip;
+use tokio::sync::RwLock;
+
+use crate::datafusion::{DataFusion, SPICE_DEFAULT_CATALOG};
+use crate::model::EmbeddingModelStore;
+
+use super::vector_search::{
+    default_limit, get_embedding_table, parse_explicit_primary_keys, SearchRequest, VectorSearch,
+    VectorSearchTableResult,
+};
+
+pub const VECTOR_SEARCH_FUNCTION_NAME: &str = "vector_search";
+
+/// A table function that runs a vector search over a dataset:
+/// `vector_search(dataset, 'text', [limit])`.
+pub struct VectorSearchTableFunction {
+    // Weak, as the function is registered in the session context of the [`DataFusion`] itself.
+    df: Weak<DataFusion>,
+    embeddings: Arc<RwLock<EmbeddingModelStore>>,
+    app: Arc<RwLock<Option<Arc<App>>>>,
+}
+
+impl VectorSearchTableFunction {
+    #[must_use]
+    pub fn new(
+        df: Weak<DataFusion>,
+        embeddings: Arc<RwLock<EmbeddingModelStore>>,
+        app: Arc<RwLock<Option<Arc<App>>>>,
+    ) -> Self {
+        Self {
+            df,
+            embeddings,
+            app,
+        }
+    }
+
+    /// Resolves the schema of the results from the embedding table registered for the dataset,
+    /// without blocking.
+    fn resolve(
+        &self,
+        df: Arc<DataFusion>,
+        table: TableReference,
+        text: String,
+        limit: usize,
+    ) -> DataFusionResult<Arc<dyn TableProvider>> {
+        if table
+            .catalog()
+            .is_some_and(|catalog| catalog != SPICE_DEFAULT_CATALOG)
+        {
+            return plan_err!(
+                "{VECTOR_SEARCH_FUNCTION_NAME} only searches datasets of the {SPICE_DEFAULT_CATALOG} catalog, got {table}"
+            );
+        }
+
+        let provider = df
+            .get_registered_table(&table)
+            .ok_or_else(|| DataFusionError::Plan(format!("Dataset {table} does not exist")))?;
+        let embedding_table = get_embedding_table(&provider).ok_or_else(|| {
+            DataFusionError::Plan(format!("Dataset {table} has no embedding columns"))
+        })?;
+        let embedding_column =
+            single_embedding_column(&table, &embedding_table.get_embedding_columns())?;
+
+        let schema = result_schema(
+            &embedding_table.get_base_table_schema(),
+            &embedding_column,
+            embedding_table.is_chunked(&embedding_column),
+        );
+
+        Ok(Arc::new(VectorSearchResultTable {
+            df,
+            embeddings: Arc::clone(&self.embeddings),
+            app: Arc::clone(&self.app),
+            request: SearchRequest::new(text, vec![table.to_string()], limit, None, vec![]),
+            table,
+            embedding_column,
+            schema,
+        }))
+    }
+}
+
+impl TableFunctionImpl for VectorSearchTableFunction {
+    /// Resolves the schema of the results when the query is planned. Planning is synchronous, so
+    /// only datasets registered in the runtime's own catalog are looked up.
+    fn call(&self, args: &[Expr]) -> DataFusionResult<Arc<dyn TableProvider>> {
+        let (table, text, limit) = match args {
+            [table, text] => (
+                table_argument(table)?,
+                text_argument(text)?,
+                default_limit(),
+            ),
+            [table, text, limit] => (
+                table_argument(table)?,
+                text_argument(text)?,
+                limit_argument(limit)?,
+            ),
+            _ => {
+                return plan_err!(
+                    "{VECTOR_SEARCH_FUNCTION_NAME} takes a dataset, the text to search for, and optionally the number of results"
+                )
+            }
+        };
+
+        let Some(df) = self.df.upgrade() else {
+            return plan_err!("The runtime is shutting down");
+        };
+
+        self.resolve(df, table, text, limit)
+    }
+}
+
+/// The embedding column that is searched. Vector searches only support one embedding column per
+/// dataset, so datasets with several are rejected rather than searching an arbitrary one.
+fn single_embedding_column(
+    table: &TableReference,
+    embedding_columns: &[String],
+) -> DataFusionResult<String> {
+    match embedding_columns {
+        [column] => Ok(column.clone()),
+        [] => plan_err!("Dataset {table} has no embedding columns"),
+        columns => plan_err!(
+            "{VECTOR_SEARCH_FUNCTION_NAME} only searches datasets with one embedding column, but {table} has {}",
+            columns.join(", ")
+        ),
+    }
+}
+
+/// The results of a vector search, which is run when the table is scanned.
+struct VectorSearchResultTable {
+    df: Arc<DataFusion>,
+    embeddings: Arc<RwLock<EmbeddingModelStore>>,
+    app: Arc<RwLock<Option<Arc<App>>>>,
+    table: TableReference,
+    embedding_column: String,
+    request: SearchRequest,
+    schema: SchemaRef,
+}
+
+#[async_trait]
+impl TableProvider for VectorSearchResultTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let vector_search = VectorSearch::new(
+            Arc::clone(&self.df),
+            Arc::clone(&self.embeddings),
+            parse_explicit_primary_keys(Arc::clone(&self.app)).await,
+        );
+        let primary_keys = vector_search
+            .table_primary_keys(&self.table)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let mut request = self.request.clone();
+        request.additional_columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .filter(|name| *name != self.embedding_column && !primary_keys.contains(name))
+            .collect();
+
+        let result = vector_search
+            .search(&request)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let batches = match result.into_values().next() {
+            Some(result) => to_batches(&self.schema, &self.embedding_column, &result)?,
+            None => vec![],
+        };
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[batches],
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+/// The columns of the dataset without its embeddings. The values of chunked columns are replaced
+/// by the matching chunk.
+fn result_schema(base_schema: &Schema, embedding_column: &str, chunked: bool) -> SchemaRef {
+    Arc::new(Schema::new(
+        base_schema
+            .fields()
+            .iter()
+            .map(|field| {
+                if chunked && field.name() == embedding_column {
+                    Field::new(field.name(), DataType::Utf8, true)
+                } else {
+                    field.as_ref().clone().with_nullable(true)
+                }
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Reassembles the primary keys, embedded column and additional columns of a search result into
+/// batches of `schema`.
+fn to_batches(
+    schema: &SchemaRef,
+    embedding_column: &str,
+    result: &VectorSearchTableResult,
+) -> DataFusionResult<Vec<RecordBatch>> {
+    izip!(
+        &result.primary_key,
+        &result.embedded_column,
+        &result.additional_columns
+    )
+    .map(|(primary_keys, embedded, additional)| {
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let column = if field.name() == embedding_column {
+                    embedded.columns().first()
+                } else {
+                    primary_keys
+                        .column_by_name(field.name())
+                        .or_else(|| additional.column_by_name(field.name()))
+                };
+                let Some(column) = column else {
+                    return Err(DataFusionError::Internal(format!(
+                        "Column {} is missing from the vector search results",
+                        field.name()
+                    )));
+                };
+
+                if column.data_type() == field.data_type() {
+                    Ok(Arc::clone(column))
+                } else {
+                    Ok(cast(column, field.data_type())?)
+                }
+            })
+            .collect::<DataFusionResult<Vec<ArrayRef>>>()?;
+
+        Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+    })
+    .collect()
+}
+
+/// The dataset to search: an identifier, or a string for qualified names.
+fn table_argument(expr: &Expr) -> DataFusionResult<TableReference> {
+    match expr {
+        Expr::Column(column) => Ok(TableReference::parse_str(&column.flat_name())),
+        Expr::Literal(ScalarValue::Utf8(Some(name)) | ScalarValue::LargeUtf8(Some(name))) => {
+            Ok(TableReference::parse_str(name))
+        }
+        _ => plan_err!(
+            "The first argument of {VECTOR_SEARCH_FUNCTION_NAME} must be a dataset, got {expr}"
+        ),
+    }
+}
+
+fn text_argument(expr: &Expr) -> DataFusionResult<String> {
+    match expr {
+        Expr::Literal(ScalarValue::Utf8(Some(text)) | ScalarValue::LargeUtf8(Some(text))) => {
+            Ok(text.clone())
+        }
+        _ => plan_err!(
+            "The second argument of {VECTOR_SEARCH_FUNCTION_NAME} must be the text to search for, got {expr}"
+        ),
+    }
+}
+
+fn limit_argument(expr: &Expr) -> DataFusionResult<usize> {
+    let limit = match expr {
+        Expr::Literal(ScalarValue::Int64(Some(limit))) => usize::try_from(*limit).ok(),
+        Expr::Literal(ScalarValue::UInt64(Some(limit))) => usize::try_from(*limit).ok(),
+        _ => None,
+    };
+
+    match limit {
+        Some(limit) if limit > 0 => Ok(limit),
+        _ => plan_err!(
+            "The third argument of {VECTOR_SEARCH_FUNCTION_NAME} must be a positive number of results, got {expr}"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray, Int64Array, StringArray};
+    use arrow::datatypes::Int64Type;
+    use datafusion::logical_expr::{col, lit};
+
+    #[test]
+    fn test_arguments() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        assert_eq!(
+            table_argument(&col("reviews"))?,
+            TableReference::bare("reviews")
+        );
+        assert_eq!(
+            table_argument(&lit("spice.public.reviews"))?,
+            TableReference::full("spice", "public", "reviews")
+        );
+        assert!(table_argument(&lit(1)).is_err());
+
+        assert_eq!(text_argument(&lit("broken"))?, "broken");
+        assert!(text_argument(&col("text")).is_err());
+
+        assert_eq!(limit_argument(&lit(5_i64))?, 5);
+        assert!(limit_argument(&lit(0_i64)).is_err());
+        assert!(limit_argument(&lit(-1_i64)).is_err());
+        assert!(limit_argument(&lit("5")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_embedding_column() {
+        let table = TableReference::bare("reviews");
+        assert_eq!(
+            single_embedding_column(&table, &["body".to_string()]).expect("one embedding column"),
+            "body"
+        );
+
+        let err = single_embedding_column(&table, &[])
+            .expect_err("there is no embedding column to search");
+        assert!(err.to_string().contains("no embedding columns"), "{err}");
+
+        let err = single_embedding_column(&table, &["title".to_string(), "body".to_string()])
+            .expect_err("the embedding column to search is ambiguous");
+        assert!(err.to_string().contains("title, body"), "{err}");
+    }
+
+    #[test]
+    fn test_call_without_runtime() {
+        // Planning must not block on a runtime, so this runs outside of one.
+        let df = Arc::new(DataFusion::new());
+        let function = VectorSearchTableFunction::new(
+            Arc::downgrade(&df),
+            Arc::new(RwLock::new(EmbeddingModelStore::new())),
+            Arc::new(RwLock::new(None)),
+        );
+
+        let err = function
+            .call(&[lit("unity.public.reviews"), lit("broken")])
+            .err()
+            .expect("datasets of other catalogs aren't searched");
+        assert!(err.to_string().contains("spice catalog"), "{err}");
+
+        let err = function
+            .call(&[col("reviews"), lit("broken")])
+            .err()
+            .expect("the dataset doesn't exist");
+        assert!(err.to_string().contains("does not exist"), "{err}");
+    }
+
+    #[test]
+    fn test_to_batches() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let base_schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("body", DataType::Utf8, false),
+            Field::new("rating", DataType::Int64, true),
+        ]);
+        let schema = result_schema(&base_schema, "body", true);
+        assert!(schema.fields().iter().all(|f| f.is_nullable()));
+
+        let column = |name: &str, data_type: DataType, array: ArrayRef| {
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![Field::new(name, data_type, true)])),
+                vec![array],
+            )
+        };
+        let result = VectorSearchTableResult {
+            primary_key: vec![column(
+                "id",
+                DataType::Int64,
+                Arc::new(Int64Array::from(vec![2, 1])),
+            )?],
+            embedded_column: vec![column(
+                "body",
+                DataType::Utf8,
+                Arc::new(StringArray::from(vec!["second chunk", "first chunk"])),
+            )?],
+            additional_columns: vec![column(
+                "rating",
+                DataType::Int64,
+                Arc::new(Int64Array::from(vec![Some(4), None])),
+            )?],
+            chunk_offsets: vec![],
+            rerank_scores: vec![],
+        };
+
+        let batches = to_batches(&schema, "body", &result)?;
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), schema);
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>().values(),
+            &[2, 1]
+        );
+        assert_eq!(
+            batches[0].column(1).as_string::<i32>().value(0),
+            "second chunk"
+        );
+        assert!(batches[0].column(2).is_null(1));
+
+        Ok(())
+    }
+}
@@ -17,7 +17,7 @@ limitations under the License.
 use std::collections::HashMap;
 use std::{any::Any, sync::Arc};
 
-use arrow::datatypes::{DataType, Schema, SchemaRef};
+use arrow::datatypes::{DataType, Field, FieldRef, Schema, SchemaRef};
 use async_trait::async_trait;
 use datafusion::catalog::Session;
 use datafusion::common::{project_schema, Constraints, Statistics};
@@ -30,10 +30,14 @@ use datafusion::{
 };
 use itertools::Itertools;
 use snafu::prelude::*;
+use spicepod::component::embeddings::{DistanceMetric, EmbeddingChunkConfig};
 
 use tokio::sync::RwLock;
 
+use crate::embeddings::chunking::Chunker;
 use crate::embeddings::execution_plan::EmbeddingTableExec;
+use crate::embeddings::full_text::FullTextIndex;
+use crate::embeddings::index::VectorIndex;
 use crate::model::EmbeddingModelStore;
 
 #[derive(Debug, Snafu)]
@@ -52,6 +56,18 @@ pub struct EmbeddingTable {
     // Precompute to avoid async lock waits from `embedding_models` data structure.
     // Mapping of column name to the expected size of its embedding.
     embedding_sizes: HashMap<String, i32>,
+
+    // A mapping of column names to the index over their embeddings, if they are indexed.
+    vector_indexes: HashMap<String, Arc<VectorIndex>>,
+
+    // A mapping of column names to the full-text index over their values, if they are indexed.
+    full_text_indexes: HashMap<String, Arc<FullTextIndex>>,
+
+    // A mapping of column names to how their values are split into chunks, if they are chunked.
+    chunkers: HashMap<String, Arc<Chunker>>,
+
+    // A mapping of column names to how the similarity of their embeddings is measured, if not L2.
+    distance_metrics: HashMap<String, DistanceMetric>,
 }
 
 impl EmbeddingTable {
@@ -66,9 +82,94 @@ impl EmbeddingTable {
             embedded_columns,
             embedding_models,
             embedding_sizes: sizes,
+            vector_indexes: HashMap::new(),
+            full_text_indexes: HashMap::new(),
+            chunkers: HashMap::new(),
+            distance_metrics: HashMap::new(),
         }
     }
 
+    /// Split the values of columns into chunks that are embedded separately. The embedding column
+    /// of a chunked column is a list of the embeddings of its chunks, and a `{column}_offset` column
+    /// contains the start and end character offsets of each chunk in the value.
+    #[must_use]
+    pub fn with_chunking(mut self, chunking: HashMap<String, EmbeddingChunkConfig>) -> Self {
+        self.chunkers = chunking
+            .into_iter()
+            .map(|(column, config)| (column, Arc::new(Chunker::new(config))))
+            .collect();
+        self
+    }
+
+    /// Whether the values of a column are split into chunks before they are embedded.
+    #[must_use]
+    pub fn is_chunked(&self, column: &str) -> bool {
+        self.chunkers.contains_key(column)
+    }
+
+    /// Measure the similarity of the embeddings of columns with other metrics than the default.
+    #[must_use]
+    pub fn with_distance_metrics(
+        mut self,
+        distance_metrics: HashMap<String, DistanceMetric>,
+    ) -> Self {
+        self.distance_metrics = distance_metrics;
+        self
+    }
+
+    /// How vector search measures the similarity of the embeddings of a column.
+    #[must_use]
+    pub fn get_distance_metric(&self, column: &str) -> DistanceMetric {
+        self.distance_metrics
+            .get(column)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Index the embeddings of columns, to search them without computing the distance to every row.
+    #[must_use]
+    pub fn with_vector_indexes(mut self, vector_indexes: Vec<Arc<VectorIndex>>) -> Self {
+        self.vector_indexes = vector_indexes
+            .into_iter()
+            .map(|index| (index.column().to_string(), index))
+            .collect();
+        self
+    }
+
+    /// Get the index over the embeddings of a column, if it has one.
+    #[must_use]
+    pub fn get_vector_index(&self, column: &str) -> Option<Arc<VectorIndex>> {
+        self.vector_indexes.get(column).cloned()
+    }
+
+    /// Get the indexes over the embeddings of this table's columns.
+    #[must_use]
+    pub fn get_vector_indexes(&self) -> Vec<Arc<VectorIndex>> {
+        self.vector_indexes.values().cloned().collect()
+    }
+
+    /// Index the values of columns, to search them by keywords.
+    #[must_use]
+    pub fn with_full_text_indexes(mut self, full_text_indexes: Vec<Arc<FullTextIndex>>) -> Self {
+        self.full_text_indexes = full_text_indexes
+            .into_iter()
+            .map(|index| (index.column().to_string(), index))
+            .collect();
+        self
+    }
+
+    /// Get the full-text index over the values of a column, if it has one.
+    #[must_use]
+    pub fn get_full_text_index(&self, column: &str) -> Option<Arc<FullTextIndex>> {
+        self.full_text_indexes.get(column).cloned()
+    }
+
+    /// Get the full-text indexes over the values of this table's columns.
+    #[must_use]
+    pub fn get_full_text_indexes(&self) -> Vec<Arc<FullTextIndex>> {
+        self.full_text_indexes.values().cloned().collect()
+    }
+
     /// Get the names of the embedding models used by this table across its columns.
     #[must_use]
     pub fn get_embedding_models_used(&self) -> Vec<String> {
@@ -110,11 +211,12 @@ impl EmbeddingTable {
     /// Any project index (in `projection`) that is greater than the number of columns in the base
     /// table is an embedding column. The relation of underlying column to embedding column is, for example, as follows:
     ///
-    /// | projection idx | 0 | 1 | 2 | 3 | 4 | 5 |      6      |      7      |
-    /// |  column name   | A | B | C | D | E | F | `B_embedding` | `E_embedding` |
+    /// | projection idx | 0 | 1 | 2 | 3 | 4 | 5 |      6      |      7      |      8     |
+    /// |  column name   | A | B | C | D | E | F | `B_embedding` | `E_embedding` | `E_offset` |
     ///
     ///     - 6 Base columns A, B, C, D, E, F
     ///     - 2 Embedding columns B_embedding, E_embedding
+    ///     - 1 Offset column `E_offset`, as E is chunked
     ///     - Any projection index >=6 is an embedding column.
     ///
     /// The order of embedding columns in [`Self::Schema`] is alphabetical.
@@ -123,23 +225,66 @@ impl EmbeddingTable {
             None => self.embedded_columns.keys().cloned().collect_vec(),
             Some(column_idx) => {
                 let base_cols = self.base_table.schema().fields.len();
-                let x: Vec<_> = self.embedded_columns.keys().sorted().collect();
+                let x = self.embedding_fields();
 
-                // Order of embedding columns in [`Self::Schema`] is alphabetical.
                 column_idx
                     .iter()
                     .filter_map(|&c| {
                         if c >= base_cols {
-                            x.get(c - base_cols).copied()
+                            x.get(c - base_cols).map(|(column, _)| column.clone())
                         } else {
                             None
                         }
                     })
-                    .cloned()
+                    .unique()
                     .collect()
             }
         }
     }
+
+    /// The fields added to the base table's schema, with the column each is computed from.
+    fn embedding_fields(&self) -> Vec<(String, FieldRef)> {
+        let base_schema = self.base_table.schema();
+        self.embedded_columns
+            .keys()
+            .sorted() // Important to be kept alphabetical for fast lookup
+            .filter_map(|k| base_schema.column_with_name(k))
+            .flat_map(|(_, field)| {
+                let embedding_size = self
+                    .embedding_sizes
+                    .get(field.name())
+                    .copied()
+                    .unwrap_or_default();
+                let embedding_type =
+                    DataType::new_fixed_size_list(DataType::Float32, embedding_size, false);
+
+                if !self.is_chunked(field.name()) {
+                    let embedding_field = field
+                        .clone()
+                        .with_data_type(embedding_type)
+                        .with_name(format!("{}_embedding", field.name()));
+                    return vec![(field.name().clone(), Arc::new(embedding_field))];
+                }
+
+                let embedding_field = field
+                    .clone()
+                    .with_data_type(DataType::new_list(embedding_type, false))
+                    .with_name(format!("{}_embedding", field.name()));
+                let offset_field = Field::new(
+                    format!("{}_offset", field.name()),
+                    DataType::new_list(
+                        DataType::new_fixed_size_list(DataType::Int32, 2, false),
+                        false,
+                    ),
+                    field.is_nullable(),
+                );
+                vec![
+                    (field.name().clone(), Arc::new(embedding_field)),
+                    (field.name().clone(), Arc::new(offset_field)),
+                ]
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -181,30 +326,9 @@ impl TableProvider for EmbeddingTable {
             .collect();
 
         let mut embedding_fields: Vec<_> = self
-            .embedded_columns
-            .keys()
-            .sorted() // Important to be kept alphabetical for fast lookup
-            .filter_map(|k| match base_schema.column_with_name(k) {
-                Some((_, field)) => {
-                    let embedding_size = self
-                        .embedding_sizes
-                        .get(field.name())
-                        .copied()
-                        .unwrap_or_default();
-
-                    Some(Arc::new(
-                        field
-                            .clone()
-                            .with_data_type(DataType::new_fixed_size_list(
-                                DataType::Float32,
-                                embedding_size,
-                                false,
-                            ))
-                            .with_name(format!("{}_embedding", field.name())),
-                    ))
-                }
-                None => None,
-            })
+            .embedding_fields()
+            .into_iter()
+            .map(|(_, field)| field)
             .collect();
 
         base_fields.append(&mut embedding_fields);
@@ -250,6 +374,12 @@ impl TableProvider for EmbeddingTable {
             .filter(|(c, _m)| columns_to_embed.contains(c))
             .map(|(c, m)| (c.clone(), m.clone()))
             .collect();
+        let scan_chunkers: HashMap<String, Arc<Chunker>> = self
+            .chunkers
+            .iter()
+            .filter(|(c, _)| scan_embed_columns.contains_key(*c))
+            .map(|(c, chunker)| (c.clone(), Arc::clone(chunker)))
+            .collect();
 
         // Need to ensure base table gets the underlying column for each embedding column specified (as well as everything in the original [`projection`]).
         let projection_for_base_table: Option<Vec<usize>> = match projection.cloned() {
@@ -282,6 +412,7 @@ impl TableProvider for EmbeddingTable {
             limit,
             base_plan,
             scan_embed_columns,
+            scan_chunkers,
             Arc::clone(&self.embedding_models),
         )) as Arc<dyn ExecutionPlan>)
     }
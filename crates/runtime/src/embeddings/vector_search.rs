@@ -14,27 +14,43 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::hash_map::Entry;
 use std::iter::zip;
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::Arc,
+};
 
 use app::App;
-use arrow::array::{RecordBatch, StringArray};
+use arrow::array::{Array, AsArray, FixedSizeListArray, RecordBatch, StringArray, UInt32Array};
+use arrow::compute::{cast, concat_batches, take_record_batch};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema};
 use arrow::error::ArrowError;
 use async_openai::types::EmbeddingInput;
 use datafusion::common::utils::quote_identifier;
+use datafusion::common::ScalarValue;
+use datafusion::logical_expr::lit;
+use datafusion::sql::unparser::expr_to_sql;
 use datafusion::{common::Constraint, datasource::TableProvider, sql::TableReference};
 use datafusion_federation::FederatedTableProviderAdaptor;
 use itertools::Itertools;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use spicepod::component::embeddings::DistanceMetric;
 use tokio::sync::RwLock;
 use tracing::{Instrument, Span};
 
 use crate::accelerated_table::AcceleratedTable;
 use crate::datafusion::query::write_to_json_string;
 use crate::datafusion::{SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA};
+use crate::model::LLMModelStore;
 use crate::{datafusion::DataFusion, model::EmbeddingModelStore};
 
+use super::array_distance::{metric_udf_name, nearest_first};
+use super::chunking::{chunk_offsets, chunk_text};
+use super::full_text::FullTextIndex;
+use super::index::VectorIndex;
 use super::table::EmbeddingTable;
 use snafu::prelude::*;
 
@@ -65,6 +81,12 @@ pub enum Error {
         num_embeddings: usize,
     },
 
+    #[snafu(display("Column {column} of {data_source} has no full-text index for keyword search. Set full_text in its embeddings configuration"))]
+    NoFullTextIndex {
+        data_source: TableReference,
+        column: String,
+    },
+
     #[snafu(display("Embedding model {} not found", model_name))]
     EmbeddingModelNotFound { model_name: String },
 
@@ -72,6 +94,15 @@ pub enum Error {
     EmbeddingError {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[snafu(display("Rerank model {model} not found. Use the name of a cross-encoder embedding model or of a chat model"))]
+    RerankModelNotFound { model: String },
+
+    #[snafu(display("Error reranking search results with {model}: {source}"))]
+    RerankError {
+        model: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -80,6 +111,7 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub struct VectorSearch {
     pub df: Arc<DataFusion>,
     embeddings: Arc<RwLock<EmbeddingModelStore>>,
+    llms: Option<Arc<RwLock<LLMModelStore>>>,
     explicit_primary_keys: HashMap<TableReference, Vec<String>>,
 }
 
@@ -107,7 +139,7 @@ pub struct SearchRequest {
     #[serde(default)]
     pub datasets: Vec<String>,
 
-    /// Number of documents to return for each dataset
+    /// Number of documents to return for each dataset. For datasets with chunked embeddings, the number of chunks, which can be from the same document.
     #[serde(default = "default_limit")]
     pub limit: usize,
 
@@ -118,12 +150,75 @@ pub struct SearchRequest {
     /// Additional columns to return from the dataset.
     #[serde(default)]
     pub additional_columns: Vec<String>,
+
+    /// How documents are ranked. Use 'keyword' or 'hybrid' to find exact identifiers, error codes, product SKUs or names.
+    #[serde(default)]
+    pub mode: SearchMode,
+
+    // Not in the schema of the document similarity tool, as models can't know which rerank models
+    // are loaded.
+    /// Reorder the results of each dataset with a model that scores their relevance to the text.
+    #[serde(default)]
+    #[schemars(skip)]
+    pub rerank: Option<RerankRequest>,
+}
+
+/// A rerank stage that reorders the best candidates of a search with a model that scores their
+/// relevance to the text, which is slower but more accurate than comparing embeddings.
+#[derive(Debug, Clone, PartialEq, Eq, JsonSchema, Serialize, Deserialize)]
+pub struct RerankRequest {
+    /// The name of a cross-encoder embedding model, or of a chat model.
+    pub model: String,
+
+    /// The number of candidates of each dataset that are reranked, of which the best `limit` are
+    /// returned.
+    #[serde(default = "default_rerank_candidates")]
+    pub candidates: usize,
+}
+
+impl RerankRequest {
+    #[must_use]
+    pub fn new(model: String) -> Self {
+        Self {
+            model,
+            candidates: default_rerank_candidates(),
+        }
+    }
+}
+
+fn default_rerank_candidates() -> usize {
+    20
 }
 
-fn default_limit() -> usize {
+pub(crate) fn default_limit() -> usize {
     3
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Rank documents by the similarity of their embeddings to the embedding of the text.
+    #[default]
+    Vector,
+
+    /// Rank documents by how well they match the words of the text, with BM25. Requires a full-text index.
+    Keyword,
+
+    /// Fuse the vector and keyword rankings with reciprocal rank fusion. Requires a full-text index.
+    Hybrid,
+}
+
+/// The number of documents ranked by each search of a hybrid search, and by keyword search, to
+/// leave room for documents excluded by the filter or not in the other ranking.
+const FUSION_CANDIDATES: usize = 50;
+
+/// The most keyword matches that are checked against the filter of a keyword search, which bounds
+/// the number of searches and the size of the primary key filter of their query.
+const MAX_FILTERED_KEYWORD_MATCHES: usize = 1024;
+
+/// Lowers the weight of the top ranks in reciprocal rank fusion, as in the original paper.
+const RRF_K: f64 = 60.0;
+
 impl SearchRequest {
     #[must_use]
     pub fn new(
@@ -139,8 +234,22 @@ impl SearchRequest {
             limit,
             where_cond,
             additional_columns,
+            mode: SearchMode::default(),
+            rerank: None,
         }
     }
+
+    #[must_use]
+    pub fn with_mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    #[must_use]
+    pub fn with_rerank(mut self, rerank: RerankRequest) -> Self {
+        self.rerank = Some(rerank);
+        self
+    }
 }
 
 pub type ModelKey = String;
@@ -148,8 +257,10 @@ pub type ModelKey = String;
 #[derive(Debug)]
 pub struct VectorSearchTableResult {
     pub primary_key: Vec<RecordBatch>,
-    pub embedded_column: Vec<RecordBatch>, // original data, not the embedding vector.
+    pub embedded_column: Vec<RecordBatch>, // original data, not the embedding vector. Only the matching chunk if chunked.
     pub additional_columns: Vec<RecordBatch>,
+    pub chunk_offsets: Vec<RecordBatch>, // empty if the embedded column isn't chunked.
+    pub rerank_scores: Vec<f32>,         // empty if the results weren't reranked.
 }
 
 pub type VectorSearchResult = HashMap<TableReference, VectorSearchTableResult>;
@@ -161,6 +272,16 @@ pub struct Match {
     dataset: String,
     primary_key: HashMap<String, serde_json::Value>,
     metadata: HashMap<String, serde_json::Value>,
+
+    /// The start and end character offsets of `value` in the column, when the column is chunked
+    /// and `value` is the matching chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<(usize, usize)>,
+
+    /// The relevance of `value` to the text according to the rerank model, when results are
+    /// reranked. Higher is more relevant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rerank_score: Option<f32>,
 }
 
 pub fn table_to_matches(
@@ -190,30 +311,43 @@ pub fn table_to_matches(
         vec![]
     };
 
-    let values: Vec<String> = result
-        .embedded_column
+    let values = embedded_values(result)?;
+
+    let offsets: Vec<Option<(usize, usize)>> = result
+        .chunk_offsets
         .iter()
-        .flat_map(|v| {
-            if let Some(col) = v.column(0).as_any().downcast_ref::<StringArray>() {
-                col.iter()
-                    .map(|v| v.unwrap_or_default().to_string())
-                    .collect::<Vec<String>>()
-            } else {
-                vec![]
-            }
-        })
+        .flat_map(|v| chunk_offsets(v.column(0)))
         .collect();
 
     Ok(zip(zip(pks, add_cols), values)
-        .map(|((pks, add_cols), value)| Match {
+        .enumerate()
+        .map(|(i, ((pks, add_cols), value))| Match {
             value,
             dataset: tbl.to_string(),
             primary_key: pks,
             metadata: add_cols,
+            offset: offsets.get(i).copied().flatten(),
+            rerank_score: result.rerank_scores.get(i).copied(),
         })
         .collect::<Vec<Match>>())
 }
 
+/// The values of the embedded column of each row of a result, converted to text. Null values are
+/// empty, so that there is a value for every row.
+fn embedded_values(result: &VectorSearchTableResult) -> Result<Vec<String>> {
+    let mut values = vec![];
+    for batch in &result.embedded_column {
+        let column = cast(batch.column(0), &DataType::Utf8).context(RecordProcessingSnafu)?;
+        values.extend(
+            column
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.unwrap_or_default().to_string()),
+        );
+    }
+    Ok(values)
+}
+
 pub fn to_matches(result: &VectorSearchResult) -> Result<Vec<Match>> {
     let output = result
         .iter()
@@ -232,11 +366,24 @@ impl VectorSearch {
         VectorSearch {
             df,
             embeddings,
+            llms: None,
             explicit_primary_keys,
         }
     }
 
+    /// Allow results to be reranked with chat models, in addition to cross-encoders.
+    #[must_use]
+    pub fn with_llms(mut self, llms: Arc<RwLock<LLMModelStore>>) -> Self {
+        self.llms = Some(llms);
+        self
+    }
+
     /// Perform a single SQL query vector search.
+    ///
+    /// If the embedding column is indexed, distances are only computed for the nearest candidates
+    /// of the index, unless fewer than `n` of them match `where_cond`.
+    ///
+    /// If the embedding column is chunked, the `n` nearest chunks are returned instead of rows.
     #[allow(clippy::too_many_arguments)]
     async fn individual_search(
         &self,
@@ -244,64 +391,363 @@ impl VectorSearch {
         embedding: Vec<f32>,
         primary_keys: &[String],
         embedding_column: &str,
+        chunked: bool,
+        metric: DistanceMetric,
+        vector_index: Option<&VectorIndex>,
         additional_columns: &[String],
         where_cond: Option<&str>,
         n: usize,
     ) -> Result<VectorSearchTableResult> {
-        let projection: Vec<String> = primary_keys
-            .iter()
-            .cloned()
-            .chain(Some(embedding_column.to_string()))
-            .chain(additional_columns.iter().cloned())
-            .collect();
+        let batches = self
+            .vector_search_rows(
+                tbl,
+                embedding,
+                primary_keys,
+                embedding_column,
+                chunked,
+                metric,
+                vector_index,
+                additional_columns,
+                where_cond,
+                n,
+            )
+            .await?;
+
+        to_table_result(
+            &batches,
+            primary_keys.len(),
+            primary_keys.len() + 1 + additional_columns.len(),
+            chunked,
+        )
+    }
 
-        let projection_str = projection.iter().map(|s| quote_identifier(s)).join(", ");
+    /// The `n` rows, or chunks of rows, nearest to `embedding`. Columns are the primary keys, the
+    /// embedding column, the additional columns and, for chunked columns, the chunk offsets.
+    #[allow(clippy::too_many_arguments)]
+    async fn vector_search_rows(
+        &self,
+        tbl: &TableReference,
+        embedding: Vec<f32>,
+        primary_keys: &[String],
+        embedding_column: &str,
+        chunked: bool,
+        metric: DistanceMetric,
+        vector_index: Option<&VectorIndex>,
+        additional_columns: &[String],
+        where_cond: Option<&str>,
+        n: usize,
+    ) -> Result<Vec<RecordBatch>> {
+        let projection_str = projection_sql(primary_keys, embedding_column, additional_columns);
 
         let where_str = where_cond.map_or_else(String::new, |cond| format!("WHERE ({cond})"));
 
-        let query = format!(
-            "SELECT {projection_str} FROM {tbl} {where_str} ORDER BY array_distance({embedding_column}_embedding, {embedding:?}) LIMIT {n}"
+        let embedding_col = quote_identifier(&format!("{embedding_column}_embedding")).to_string();
+        let offset_col = quote_identifier(&format!("{embedding_column}_offset")).to_string();
+        let order_by_str = format!(
+            "ORDER BY {}({embedding_col}, {embedding:?}) {}",
+            metric_udf_name(metric),
+            nearest_first(metric)
         );
-        tracing::trace!("running SQL: {query}");
+
+        // Chunked columns have a list of embeddings per row, which are unnested to a row per chunk.
+        let query = |where_str: &str| {
+            if chunked {
+                format!(
+                    "SELECT {projection_str}, {offset_col} FROM (SELECT {projection_str}, unnest({embedding_col}) AS {embedding_col}, unnest({offset_col}) AS {offset_col} FROM {tbl} {where_str}) {order_by_str} LIMIT {n}"
+                )
+            } else {
+                format!("SELECT {projection_str} FROM {tbl} {where_str} {order_by_str} LIMIT {n}")
+            }
+        };
+
+        if let Some(vector_index) = vector_index {
+            if let Some(candidates) = vector_index.search(&embedding, n).await {
+                if let Some(key_filter) = key_filter_sql(vector_index.primary_keys(), &candidates)?
+                {
+                    let where_str = match where_cond {
+                        Some(cond) => format!("WHERE ({cond}) AND ({key_filter})"),
+                        None => format!("WHERE {key_filter}"),
+                    };
+                    let batches = self.run_query(&query(&where_str)).await?;
+
+                    if batches.iter().map(RecordBatch::num_rows).sum::<usize>() >= n {
+                        return Ok(batches);
+                    }
+                    tracing::debug!("Fewer than {n} candidates from the vector index of {tbl}.{embedding_column} matched, searching all rows");
+                }
+            }
+        }
+
+        self.run_query(&query(&where_str)).await
+    }
+
+    /// Search for the rows, or chunks of rows, that best match the words of `query` with a
+    /// full-text index. For hybrid search, the keyword ranking is fused with the ranking of the
+    /// vector search for `embedding` with reciprocal rank fusion.
+    ///
+    /// Until the full-text index is built, the results of a vector search are returned instead.
+    #[allow(clippy::too_many_arguments)]
+    async fn keyword_search(
+        &self,
+        tbl: &TableReference,
+        query: &str,
+        embedding: Option<Vec<f32>>,
+        embedding_column: &str,
+        chunked: bool,
+        metric: DistanceMetric,
+        full_text_index: &FullTextIndex,
+        vector_index: Option<&VectorIndex>,
+        additional_columns: &[String],
+        where_cond: Option<&str>,
+        n: usize,
+    ) -> Result<VectorSearchTableResult> {
+        let primary_keys = full_text_index.primary_keys();
+        let candidates = n.max(FUSION_CANDIDATES);
+
+        let Some(keyword_ranking) = self
+            .keyword_ranking(tbl, query, full_text_index, where_cond, candidates)
+            .await?
+        else {
+            tracing::warn!(
+                "The full-text index of column {embedding_column} of {tbl} isn't built yet, returning vector search results"
+            );
+            let embedding = match embedding {
+                Some(embedding) => embedding,
+                None => self.query_embedding(tbl, query).await?,
+            };
+            return self
+                .individual_search(
+                    tbl,
+                    embedding,
+                    primary_keys,
+                    embedding_column,
+                    chunked,
+                    metric,
+                    vector_index,
+                    additional_columns,
+                    where_cond,
+                    n,
+                )
+                .await;
+        };
+
+        let ranking = match embedding {
+            None => keyword_ranking,
+            Some(embedding) => {
+                let vector_rows = self
+                    .vector_search_rows(
+                        tbl,
+                        embedding,
+                        primary_keys,
+                        embedding_column,
+                        chunked,
+                        metric,
+                        vector_index,
+                        &[],
+                        where_cond,
+                        candidates,
+                    )
+                    .await?;
+                let vector_ranking = ranked_rows(
+                    &vector_rows,
+                    primary_keys.len(),
+                    chunked.then_some(primary_keys.len() + 1),
+                )?;
+                reciprocal_rank_fusion(&[vector_ranking, keyword_ranking])
+            }
+        };
 
         let batches = self
-            .df
+            .ranked_rows_batches(
+                tbl,
+                primary_keys,
+                embedding_column,
+                chunked,
+                additional_columns,
+                where_cond,
+                &ranking,
+                n,
+            )
+            .await?;
+
+        to_table_result(
+            &batches,
+            primary_keys.len(),
+            primary_keys.len() + 1 + additional_columns.len(),
+            chunked,
+        )
+    }
+
+    /// The ranking of the rows, or chunks of rows, of the full-text index that best match the
+    /// words of `query` and `where_cond`, or `None` if the index isn't built yet.
+    ///
+    /// Rows that don't match `where_cond` are dropped from the ranking, so more matches are taken
+    /// from the index until `candidates` of them match `where_cond`, the index has no more, or
+    /// [`MAX_FILTERED_KEYWORD_MATCHES`] matches were checked.
+    async fn keyword_ranking(
+        &self,
+        tbl: &TableReference,
+        query: &str,
+        full_text_index: &FullTextIndex,
+        where_cond: Option<&str>,
+        candidates: usize,
+    ) -> Result<Option<Vec<RankedRow>>> {
+        let primary_keys = full_text_index.primary_keys();
+        let mut limit = candidates;
+        loop {
+            let Some(matches) = full_text_index.search(query, limit).await else {
+                return Ok(None);
+            };
+            let exhausted = matches.len() < limit;
+            let ranking: Vec<RankedRow> = matches
+                .into_iter()
+                .map(|m| RankedRow {
+                    key: m.key,
+                    offset: m.offset,
+                })
+                .collect();
+
+            let Some(cond) = where_cond else {
+                return Ok(Some(ranking));
+            };
+            let keys = ranking.iter().map(|r| r.key.clone()).unique().collect_vec();
+            let Some(key_filter) = key_filter_sql(primary_keys, &keys)? else {
+                return Ok(Some(vec![]));
+            };
+
+            let projection = primary_keys
+                .iter()
+                .map(|column| quote_identifier(column).to_string())
+                .join(", ");
+            let batches = self
+                .run_query(&format!(
+                    "SELECT {projection} FROM {tbl} WHERE ({cond}) AND ({key_filter})"
+                ))
+                .await?;
+            let mut matching_keys = HashSet::new();
+            for batch in &batches {
+                for row in 0..batch.num_rows() {
+                    matching_keys.insert(row_key(batch, primary_keys.len(), row)?);
+                }
+            }
+
+            let ranking: Vec<RankedRow> = ranking
+                .into_iter()
+                .filter(|ranked| matching_keys.contains(&ranked.key))
+                .collect();
+            if ranking.len() >= candidates || exhausted || limit >= MAX_FILTERED_KEYWORD_MATCHES {
+                return Ok(Some(ranking));
+            }
+            tracing::debug!("Fewer than {candidates} keyword matches of {tbl} matched the filter, searching more matches");
+            limit = limit.saturating_mul(4).min(MAX_FILTERED_KEYWORD_MATCHES);
+        }
+    }
+
+    /// The embedding of `query` for the embedding column of `tbl`.
+    async fn query_embedding(&self, tbl: &TableReference, query: &str) -> Result<Vec<f32>> {
+        let search_vectors = self
+            .calculate_embeddings_per_table(query.to_string(), vec![tbl.clone()])
+            .await?
+            .remove(tbl)
+            .unwrap_or_default();
+        match <[Vec<f32>; 1]>::try_from(search_vectors) {
+            Ok([embedding]) => Ok(embedding),
+            Err(search_vectors) => Err(Error::IncorrectNumberOfEmbeddingColumns {
+                data_source: tbl.clone(),
+                num_embeddings: search_vectors.len(),
+            }),
+        }
+    }
+
+    /// The first `n` rows of `ranking` that match `where_cond`, in order. Columns are the primary
+    /// keys, the embedding column, the additional columns and, for chunked columns, the offsets of
+    /// the ranked chunks.
+    #[allow(clippy::too_many_arguments)]
+    async fn ranked_rows_batches(
+        &self,
+        tbl: &TableReference,
+        primary_keys: &[String],
+        embedding_column: &str,
+        chunked: bool,
+        additional_columns: &[String],
+        where_cond: Option<&str>,
+        ranking: &[RankedRow],
+        n: usize,
+    ) -> Result<Vec<RecordBatch>> {
+        let keys = ranking.iter().map(|r| r.key.clone()).unique().collect_vec();
+        let Some(key_filter) = key_filter_sql(primary_keys, &keys)? else {
+            return Ok(vec![]);
+        };
+
+        let projection_str = projection_sql(primary_keys, embedding_column, additional_columns);
+        let where_str = match where_cond {
+            Some(cond) => format!("WHERE ({cond}) AND ({key_filter})"),
+            None => format!("WHERE {key_filter}"),
+        };
+        let batches = self
+            .run_query(&format!("SELECT {projection_str} FROM {tbl} {where_str}"))
+            .await?;
+        let Some(schema) = batches.first().map(RecordBatch::schema) else {
+            return Ok(vec![]);
+        };
+        let batch = concat_batches(&schema, &batches).context(RecordProcessingSnafu)?;
+
+        let mut rows_by_key = HashMap::new();
+        for row in 0..batch.num_rows() {
+            rows_by_key
+                .entry(row_key(&batch, primary_keys.len(), row)?)
+                .or_insert(row);
+        }
+
+        let mut indices = vec![];
+        let mut offsets = vec![];
+        for ranked in ranking {
+            if indices.len() == n {
+                break;
+            }
+            if let Some(&row) = rows_by_key.get(&ranked.key) {
+                indices.push(u32::try_from(row).boxed().context(FormattingSnafu)?);
+                offsets.push(ranked.offset);
+            }
+        }
+        let batch = take_record_batch(&batch, &UInt32Array::from(indices))
+            .context(RecordProcessingSnafu)?;
+        if !chunked {
+            return Ok(vec![batch]);
+        }
+
+        let offsets = FixedSizeListArray::from_iter_primitive::<Int32Type, _, _>(
+            offsets.into_iter().map(|offset| {
+                offset.map(|(start, end)| vec![i32::try_from(start).ok(), i32::try_from(end).ok()])
+            }),
+            2,
+        );
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            format!("{embedding_column}_offset"),
+            offsets.data_type().clone(),
+            true,
+        )));
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(offsets));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .map(|batch| vec![batch])
+            .context(RecordProcessingSnafu)
+    }
+
+    async fn run_query(&self, query: &str) -> Result<Vec<RecordBatch>> {
+        tracing::trace!("running SQL: {query}");
+
+        self.df
             .ctx
-            .sql(&query)
+            .sql(query)
             .await
             .boxed()
             .context(DataFusionSnafu)?
             .collect()
             .await
             .boxed()
-            .context(DataFusionSnafu)?;
-
-        let primary_key_projection = (0..primary_keys.len()).collect_vec();
-        let embedding_projection = (primary_keys.len()..=primary_keys.len()).collect_vec();
-        let additional_columns_projection =
-            (primary_keys.len() + 1..projection.len()).collect_vec();
-
-        let primary_keys_records = batches
-            .iter()
-            .map(|s| s.project(&primary_key_projection))
-            .collect::<std::result::Result<Vec<_>, ArrowError>>()
-            .context(RecordProcessingSnafu)?;
-        let embedding_records = batches
-            .iter()
-            .map(|s| s.project(&embedding_projection))
-            .collect::<std::result::Result<Vec<_>, ArrowError>>()
-            .context(RecordProcessingSnafu)?;
-        let primary_keys = batches
-            .iter()
-            .map(|s| s.project(&additional_columns_projection))
-            .collect::<std::result::Result<Vec<_>, ArrowError>>()
-            .context(RecordProcessingSnafu)?;
-
-        Ok(VectorSearchTableResult {
-            primary_key: primary_keys_records,
-            embedded_column: embedding_records,
-            additional_columns: primary_keys,
-        })
+            .context(DataFusionSnafu)
     }
 
     pub async fn search(&self, req: &SearchRequest) -> Result<VectorSearchResult> {
@@ -311,8 +757,15 @@ impl VectorSearch {
             limit,
             where_cond,
             additional_columns,
+            mode,
+            rerank,
         } = req;
 
+        // Rerank the best candidates, of which the best `limit` are kept.
+        let candidates = rerank
+            .as_ref()
+            .map_or(*limit, |rerank| rerank.candidates.max(*limit));
+
         let tables: Vec<TableReference> = data_source.iter().map(TableReference::from).collect();
         let tables_not_found: Vec<TableReference> = tables
             .iter()
@@ -338,9 +791,12 @@ impl VectorSearch {
         let vector_search_result = async {
             tracing::info!(target: "task_history", tables = tables.iter().join(","), limit = %limit, "labels");
 
-            let per_table_embeddings = self
-                .calculate_embeddings_per_table(query.clone(), tables.clone())
-                .await?;
+            let per_table_embeddings = if *mode == SearchMode::Keyword {
+                HashMap::new()
+            } else {
+                self.calculate_embeddings_per_table(query.clone(), tables.clone())
+                    .await?
+            };
 
             let table_primary_keys = self
                 .get_primary_keys_with_overrides(&self.explicit_primary_keys, tables.clone())
@@ -348,7 +804,7 @@ impl VectorSearch {
 
             let mut response: VectorSearchResult = HashMap::new();
 
-            for (tbl, search_vectors) in per_table_embeddings {
+            for tbl in tables.iter().cloned() {
                 tracing::debug!("Running vector search for table {:#?}", tbl.clone());
                 let primary_keys = table_primary_keys.get(&tbl).cloned().unwrap_or(vec![]);
 
@@ -361,35 +817,83 @@ impl VectorSearch {
                         data_source: vec![tbl.clone()],
                     })?;
 
-                let embedding_column = get_embedding_table(&table_provider)
-                    .and_then(|e| e.get_embedding_columns().first().cloned())
+                let embedding_table =
+                    get_embedding_table(&table_provider).ok_or(Error::NoEmbeddingColumns {
+                        data_source: tbl.clone(),
+                    })?;
+                let embedding_column = embedding_table
+                    .get_embedding_columns()
+                    .first()
+                    .cloned()
                     .ok_or(Error::NoEmbeddingColumns {
                         data_source: tbl.clone(),
                     })?;
+                let chunked = embedding_table.is_chunked(&embedding_column);
+                let metric = embedding_table.get_distance_metric(&embedding_column);
+                let vector_index = embedding_table.get_vector_index(&embedding_column);
 
-                if search_vectors.len() != 1 {
-                    return Err(Error::IncorrectNumberOfEmbeddingColumns {
-                        data_source: tbl.clone(),
-                        num_embeddings: search_vectors.len(),
-                    });
-                }
-                match search_vectors.first() {
-                    None => unreachable!(),
-                    Some(embedding) => {
-                        let result = self
-                            .individual_search(
-                                &tbl,
-                                embedding.clone(),
-                                &primary_keys,
-                                &embedding_column,
-                                additional_columns,
-                                where_cond.as_deref(),
-                                *limit,
-                            )
-                            .await?;
-                        response.insert(tbl.clone(), result);
+                let embedding = if *mode == SearchMode::Keyword {
+                    vec![]
+                } else {
+                    let search_vectors = per_table_embeddings.get(&tbl).cloned().unwrap_or_default();
+                    match search_vectors.as_slice() {
+                        [embedding] => embedding.clone(),
+                        _ => {
+                            return Err(Error::IncorrectNumberOfEmbeddingColumns {
+                                data_source: tbl.clone(),
+                                num_embeddings: search_vectors.len(),
+                            })
+                        }
                     }
                 };
+
+                let result = match mode {
+                    SearchMode::Vector => {
+                        self.individual_search(
+                            &tbl,
+                            embedding,
+                            &primary_keys,
+                            &embedding_column,
+                            chunked,
+                            metric,
+                            vector_index.as_deref(),
+                            additional_columns,
+                            where_cond.as_deref(),
+                            candidates,
+                        )
+                        .await?
+                    }
+                    SearchMode::Keyword | SearchMode::Hybrid => {
+                        let full_text_index = embedding_table
+                            .get_full_text_index(&embedding_column)
+                            .context(NoFullTextIndexSnafu {
+                                data_source: tbl.clone(),
+                                column: embedding_column.clone(),
+                            })?;
+                        self.keyword_search(
+                            &tbl,
+                            query,
+                            (*mode == SearchMode::Hybrid).then_some(embedding),
+                            &embedding_column,
+                            chunked,
+                            metric,
+                            &full_text_index,
+                            vector_index.as_deref(),
+                            additional_columns,
+                            where_cond.as_deref(),
+                            candidates,
+                        )
+                        .await?
+                    }
+                };
+                let result = match rerank {
+                    Some(rerank) => {
+                        self.rerank(query, &rerank.model, result, *limit)
+                            .await?
+                    }
+                    None => result,
+                };
+                response.insert(tbl.clone(), result);
             }
             tracing::info!(target: "task_history", truncated_output = ?response);
             Ok(response)
@@ -484,6 +988,93 @@ impl VectorSearch {
         Ok(tbl_to_pks)
     }
 
+    /// Reorders the rows of a result by the relevance of their embedded column to `query`
+    /// according to `model`, and keeps the best `limit`.
+    async fn rerank(
+        &self,
+        query: &str,
+        model: &str,
+        result: VectorSearchTableResult,
+        limit: usize,
+    ) -> Result<VectorSearchTableResult> {
+        let documents = embedded_values(&result)?;
+        if documents.is_empty() {
+            return Ok(result);
+        }
+
+        let scores = self.rerank_scores(query, model, &documents).await?;
+        if scores.len() != documents.len() {
+            return Err(Error::RerankError {
+                model: model.to_string(),
+                source: string_to_boxed_err(format!(
+                    "{} scores returned for {} results",
+                    scores.len(),
+                    documents.len()
+                )),
+            });
+        }
+
+        let order = rerank_order(&scores, limit);
+        let indices = order
+            .iter()
+            .map(|&row| u32::try_from(row))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .boxed()
+            .context(FormattingSnafu)?;
+        let indices = UInt32Array::from(indices);
+
+        Ok(VectorSearchTableResult {
+            primary_key: take_rows(&result.primary_key, &indices)?,
+            embedded_column: take_rows(&result.embedded_column, &indices)?,
+            additional_columns: take_rows(&result.additional_columns, &indices)?,
+            chunk_offsets: take_rows(&result.chunk_offsets, &indices)?,
+            rerank_scores: order.iter().map(|&row| scores[row]).collect(),
+        })
+    }
+
+    /// The relevance of each of `documents` to `query`, with a cross-encoder embedding model or a
+    /// chat model named `model`.
+    async fn rerank_scores(
+        &self,
+        query: &str,
+        model: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>> {
+        let embeddings = self.embeddings.read().await;
+        if let Some(cross_encoder) = embeddings.get(model) {
+            return cross_encoder
+                .write()
+                .await
+                .rerank(query, documents)
+                .await
+                .boxed()
+                .context(RerankSnafu { model });
+        }
+        drop(embeddings);
+
+        let Some(llms) = &self.llms else {
+            return RerankModelNotFoundSnafu { model }.fail();
+        };
+        let llms = llms.read().await;
+        let chat = llms
+            .get(model)
+            .context(RerankModelNotFoundSnafu { model })?
+            .read()
+            .await;
+        llms::chat::rerank::rerank(&**chat, query, documents)
+            .await
+            .boxed()
+            .context(RerankSnafu { model })
+    }
+
+    /// The primary keys of a table, from its constraints or else the spicepod configuration.
+    pub(crate) async fn table_primary_keys(&self, table: &TableReference) -> Result<Vec<String>> {
+        let mut primary_keys = self
+            .get_primary_keys_with_overrides(&self.explicit_primary_keys, vec![table.clone()])
+            .await?;
+        Ok(primary_keys.remove(table).unwrap_or_default())
+    }
+
     /// Embed the input text using the specified embedding model.
     async fn embed(&self, input: &str, embedding_model: &str) -> Result<Vec<f32>> {
         self.embeddings
@@ -552,7 +1143,7 @@ impl VectorSearch {
 
 /// If a [`TableProvider`] is an [`EmbeddingTable`], return the [`EmbeddingTable`].
 /// This includes if the [`TableProvider`] is an [`AcceleratedTable`] with a [`EmbeddingTable`] underneath.
-fn get_embedding_table(tbl: &Arc<dyn TableProvider>) -> Option<Arc<EmbeddingTable>> {
+pub(crate) fn get_embedding_table(tbl: &Arc<dyn TableProvider>) -> Option<Arc<EmbeddingTable>> {
     if let Some(embedding_table) = tbl.as_any().downcast_ref::<EmbeddingTable>() {
         return Some(Arc::new(embedding_table.clone()));
     }
@@ -575,6 +1166,206 @@ fn get_embedding_table(tbl: &Arc<dyn TableProvider>) -> Option<Arc<EmbeddingTabl
     None
 }
 
+/// Splits the rows of a search into a [`VectorSearchTableResult`]. Columns of `batches` are the
+/// primary keys, the embedding column, the additional columns and, for chunked columns, the chunk
+/// offsets.
+fn to_table_result(
+    batches: &[RecordBatch],
+    num_primary_keys: usize,
+    num_columns: usize,
+    chunked: bool,
+) -> Result<VectorSearchTableResult> {
+    let primary_key_projection = (0..num_primary_keys).collect_vec();
+    let embedding_projection = (num_primary_keys..=num_primary_keys).collect_vec();
+    let additional_columns_projection = (num_primary_keys + 1..num_columns).collect_vec();
+
+    let primary_keys_records = batches
+        .iter()
+        .map(|s| s.project(&primary_key_projection))
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()
+        .context(RecordProcessingSnafu)?;
+    let embedding_records = if chunked {
+        batches
+            .iter()
+            .map(|s| chunk_values(s, num_primary_keys, num_columns))
+            .collect::<std::result::Result<Vec<_>, ArrowError>>()
+            .context(RecordProcessingSnafu)?
+    } else {
+        batches
+            .iter()
+            .map(|s| s.project(&embedding_projection))
+            .collect::<std::result::Result<Vec<_>, ArrowError>>()
+            .context(RecordProcessingSnafu)?
+    };
+    let chunk_offset_records = if chunked {
+        batches
+            .iter()
+            .map(|s| s.project(&[num_columns]))
+            .collect::<std::result::Result<Vec<_>, ArrowError>>()
+            .context(RecordProcessingSnafu)?
+    } else {
+        vec![]
+    };
+    let additional_columns_records = batches
+        .iter()
+        .map(|s| s.project(&additional_columns_projection))
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()
+        .context(RecordProcessingSnafu)?;
+
+    Ok(VectorSearchTableResult {
+        primary_key: primary_keys_records,
+        embedded_column: embedding_records,
+        additional_columns: additional_columns_records,
+        chunk_offsets: chunk_offset_records,
+        rerank_scores: vec![],
+    })
+}
+
+/// The quoted columns of the rows of a search: the primary keys, the embedding column and the
+/// additional columns.
+fn projection_sql(
+    primary_keys: &[String],
+    embedding_column: &str,
+    additional_columns: &[String],
+) -> String {
+    primary_keys
+        .iter()
+        .map(String::as_str)
+        .chain(Some(embedding_column))
+        .chain(additional_columns.iter().map(String::as_str))
+        .map(quote_identifier)
+        .join(", ")
+}
+
+/// A row, or a chunk of a row, in the ranking of a search.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RankedRow {
+    key: Vec<ScalarValue>,
+    offset: Option<(usize, usize)>,
+}
+
+/// The ranking of the rows of a search, in order. The primary keys are the first `num_keys`
+/// columns, and the chunk offsets are at `offset_idx` for chunked columns.
+fn ranked_rows(
+    batches: &[RecordBatch],
+    num_keys: usize,
+    offset_idx: Option<usize>,
+) -> Result<Vec<RankedRow>> {
+    let mut ranking = vec![];
+    for batch in batches {
+        let offsets = offset_idx.map(|idx| chunk_offsets(batch.column(idx)));
+        for row in 0..batch.num_rows() {
+            ranking.push(RankedRow {
+                key: row_key(batch, num_keys, row)?,
+                offset: offsets
+                    .as_ref()
+                    .and_then(|offsets| offsets.get(row).copied().flatten()),
+            });
+        }
+    }
+    Ok(ranking)
+}
+
+/// The primary key of a row, from the first `num_keys` columns.
+fn row_key(batch: &RecordBatch, num_keys: usize, row: usize) -> Result<Vec<ScalarValue>> {
+    batch.columns()[..num_keys]
+        .iter()
+        .map(|column| ScalarValue::try_from_array(column, row))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .boxed()
+        .context(DataFusionSnafu)
+}
+
+/// Fuses rankings by scoring each row with the sum of `1 / (RRF_K + rank)` over the rankings it is
+/// in. Ties keep the order in which rows first appear.
+#[allow(clippy::cast_precision_loss)]
+fn reciprocal_rank_fusion(rankings: &[Vec<RankedRow>]) -> Vec<RankedRow> {
+    let mut scores: HashMap<&RankedRow, f64> = HashMap::new();
+    let mut rows = vec![];
+    for ranking in rankings {
+        for (rank, row) in ranking.iter().enumerate() {
+            let score = 1.0 / (RRF_K + (rank + 1) as f64);
+            match scores.entry(row) {
+                Entry::Occupied(mut entry) => *entry.get_mut() += score,
+                Entry::Vacant(entry) => {
+                    entry.insert(score);
+                    rows.push(row);
+                }
+            }
+        }
+    }
+
+    rows.sort_by(|a, b| scores[b].total_cmp(&scores[a]));
+    rows.into_iter().cloned().collect()
+}
+
+/// A batch with the text of the chunk of each row, from the column values at `value_idx` and the
+/// chunk offsets at `offset_idx`.
+fn chunk_values(
+    batch: &RecordBatch,
+    value_idx: usize,
+    offset_idx: usize,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let values = cast(batch.column(value_idx), &DataType::Utf8)?;
+    let values = values.as_string::<i32>();
+    let offsets = chunk_offsets(batch.column(offset_idx));
+
+    let chunks: StringArray = (0..batch.num_rows())
+        .map(|row| {
+            if values.is_null(row) {
+                return None;
+            }
+            let (start, end) = offsets.get(row).copied().flatten()?;
+            Some(chunk_text(values.value(row), start, end))
+        })
+        .collect();
+
+    let field = Field::new(batch.schema().field(value_idx).name(), DataType::Utf8, true);
+    RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![Arc::new(chunks)])
+}
+
+/// The indices of the `limit` highest scores, highest first. Equal scores keep their order.
+fn rerank_order(scores: &[f32], limit: usize) -> Vec<usize> {
+    let mut order = (0..scores.len()).collect_vec();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    order.truncate(limit);
+    order
+}
+
+/// The rows of `batches` at `indices`, in order.
+fn take_rows(batches: &[RecordBatch], indices: &UInt32Array) -> Result<Vec<RecordBatch>> {
+    let Some(schema) = batches.first().map(RecordBatch::schema) else {
+        return Ok(vec![]);
+    };
+    let batch = concat_batches(&schema, batches).context(RecordProcessingSnafu)?;
+    take_record_batch(&batch, indices)
+        .map(|batch| vec![batch])
+        .context(RecordProcessingSnafu)
+}
+
+/// A SQL predicate that selects the rows with one of the given primary keys.
+fn key_filter_sql(primary_keys: &[String], keys: &[Vec<ScalarValue>]) -> Result<Option<String>> {
+    let mut key_filters = Vec::with_capacity(keys.len());
+    for key in keys {
+        let key_filter = primary_keys
+            .iter()
+            .zip(key)
+            .map(|(column, value)| {
+                expr_to_sql(&lit(value.clone()))
+                    .map(|value| format!("{} = {value}", quote_identifier(column)))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .boxed()
+            .context(DataFusionSnafu)?;
+        key_filters.push(format!("({})", key_filter.join(" AND ")));
+    }
+
+    if key_filters.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(key_filters.join(" OR ")))
+}
+
 fn string_to_boxed_err(s: String) -> Box<dyn std::error::Error + Send + Sync> {
     Box::<dyn std::error::Error + Send + Sync>::from(s)
 }
@@ -605,14 +1396,247 @@ pub async fn parse_explicit_primary_keys(
 
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use std::collections::{HashMap, HashSet};
+
+    use arrow::array::{
+        ArrayRef, FixedSizeListArray, Int64Array, LargeStringArray, RecordBatch, StringArray,
+        StringViewArray,
+    };
+    use arrow::datatypes::Int32Type;
+    use datafusion::datasource::MemTable;
+    use datafusion::sql::TableReference;
+    use spicepod::component::embeddings::FullTextIndexConfig;
+    use tokio::sync::RwLock;
+
+    use crate::datafusion::DataFusion;
+    use crate::embeddings::full_text::FullTextIndex;
     use schemars::schema_for;
     use snafu::ResultExt;
 
     use crate::embeddings::vector_search::SearchRequest;
 
+    use super::{
+        chunk_values, key_filter_sql, reciprocal_rank_fusion, rerank_order, take_rows, RankedRow,
+        RerankRequest, ScalarValue, SearchMode, VectorSearch, FUSION_CANDIDATES,
+    };
+
     #[tokio::test]
     async fn test_search_request_schema() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         serde_json::to_value(schema_for!(SearchRequest)).boxed()?;
         Ok(())
     }
+
+    #[test]
+    fn test_search_request_mode() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let req: SearchRequest = serde_json::from_str(r#"{"text": "ERR-1234"}"#)?;
+        assert_eq!(req.mode, SearchMode::Vector);
+
+        let req: SearchRequest = serde_json::from_str(r#"{"text": "ERR-1234", "mode": "hybrid"}"#)?;
+        assert_eq!(req.mode, SearchMode::Hybrid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_request_rerank() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let req: SearchRequest = serde_json::from_str(r#"{"text": "refunds"}"#)?;
+        assert_eq!(req.rerank, None);
+
+        let req: SearchRequest =
+            serde_json::from_str(r#"{"text": "refunds", "rerank": {"model": "ms-marco"}}"#)?;
+        assert_eq!(req.rerank, Some(RerankRequest::new("ms-marco".to_string())));
+
+        let schema = serde_json::to_value(schema_for!(SearchRequest))?;
+        assert!(schema["properties"].get("rerank").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded_values() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let batch = |values: ArrayRef| RecordBatch::try_from_iter(vec![("value", values)]);
+        let result = VectorSearchTableResult {
+            primary_key: vec![],
+            embedded_column: vec![
+                batch(Arc::new(StringArray::from(vec![Some("a"), None])))?,
+                batch(Arc::new(LargeStringArray::from(vec!["b"])))?,
+                batch(Arc::new(StringViewArray::from(vec!["c"])))?,
+            ],
+            additional_columns: vec![],
+            chunk_offsets: vec![],
+            rerank_scores: vec![],
+        };
+
+        assert_eq!(embedded_values(&result)?, vec!["a", "", "b", "c"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rerank_order() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let order = rerank_order(&[0.1, 2.5, -1.0, 2.5], 3);
+        assert_eq!(order, vec![1, 3, 0]);
+
+        let batch = RecordBatch::try_from_iter(vec![(
+            "value",
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d"])) as ArrayRef,
+        )])?;
+        let (first, second) = (batch.slice(0, 2), batch.slice(2, 2));
+        let indices = arrow::array::UInt32Array::from(vec![1, 3, 0]);
+        let rows = take_rows(&[first, second], &indices).boxed()?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or("expected strings")?
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("b"), Some("d"), Some("a")]
+        );
+
+        assert!(take_rows(&[], &indices).boxed()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let row = |id: i64| RankedRow {
+            key: vec![ScalarValue::from(id)],
+            offset: None,
+        };
+
+        let fused = reciprocal_rank_fusion(&[vec![row(1), row(2), row(3)], vec![row(3), row(4)]]);
+        assert_eq!(fused, vec![row(3), row(1), row(2), row(4)]);
+    }
+
+    #[test]
+    fn test_key_filter_sql() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        assert_eq!(key_filter_sql(&["id".to_string()], &[]).boxed()?, None);
+
+        let filter = key_filter_sql(
+            &["id".to_string(), "Region".to_string()],
+            &[
+                vec![ScalarValue::from(1_i64), ScalarValue::from("us")],
+                vec![ScalarValue::from(2_i64), ScalarValue::from("eu")],
+            ],
+        )
+        .boxed()?;
+        assert_eq!(
+            filter.as_deref(),
+            Some(r#"(id = 1 AND "Region" = 'us') OR (id = 2 AND "Region" = 'eu')"#)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_values() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let offsets = FixedSizeListArray::from_iter_primitive::<Int32Type, _, _>(
+            vec![
+                Some(vec![Some(6), Some(11)]),
+                Some(vec![Some(0), Some(5)]),
+                None,
+            ],
+            2,
+        );
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "text",
+                Arc::new(StringArray::from(vec!["héllo wörld", "abcdefgh", "xyz"])) as ArrayRef,
+            ),
+            ("text_offset", Arc::new(offsets) as ArrayRef),
+        ])?;
+
+        let chunks = chunk_values(&batch, 0, 1)?;
+        assert_eq!(chunks.schema().field(0).name(), "text");
+        let chunks = chunks
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or("chunks should be strings")?;
+        assert_eq!(
+            chunks.iter().collect::<Vec<_>>(),
+            vec![Some("wörld"), Some("abcde"), None]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keyword_ranking_with_filter(
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // The rows of category "y" match the keyword worse than the first FUSION_CANDIDATES rows
+        let num_rows: i64 = 60;
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "id",
+                Arc::new(Int64Array::from_iter_values(0..num_rows)) as ArrayRef,
+            ),
+            (
+                "text",
+                Arc::new(StringArray::from_iter_values((0..num_rows).map(|id| {
+                    if id < 55 {
+                        "apple apple apple"
+                    } else {
+                        "apple banana cherry"
+                    }
+                }))) as ArrayRef,
+            ),
+            (
+                "category",
+                Arc::new(StringArray::from_iter_values((0..num_rows).map(|id| {
+                    if id < 55 {
+                        "x"
+                    } else {
+                        "y"
+                    }
+                }))) as ArrayRef,
+            ),
+        ])?;
+        let table = Arc::new(MemTable::try_new(batch.schema(), vec![vec![batch]])?);
+
+        let df = Arc::new(DataFusion::new());
+        df.ctx
+            .register_table(TableReference::bare("docs"), Arc::clone(&table) as _)?;
+        let vs = VectorSearch::new(df, Arc::new(RwLock::new(HashMap::new())), HashMap::new());
+        let tbl = TableReference::bare("docs");
+
+        let index = Arc::new(FullTextIndex::new(
+            "text".to_string(),
+            vec!["id".to_string()],
+            false,
+            FullTextIndexConfig::default(),
+        ));
+        assert!(
+            vs.keyword_ranking(&tbl, "apple", &index, None, FUSION_CANDIDATES)
+                .await?
+                .is_none(),
+            "the index isn't built yet"
+        );
+        index.update(table).await?;
+
+        let ranking = vs
+            .keyword_ranking(&tbl, "apple", &index, None, FUSION_CANDIDATES)
+            .await?
+            .ok_or("the index is built")?;
+        assert_eq!(ranking.len(), FUSION_CANDIDATES);
+
+        let ranking = vs
+            .keyword_ranking(
+                &tbl,
+                "apple",
+                &index,
+                Some("category = 'y'"),
+                FUSION_CANDIDATES,
+            )
+            .await?
+            .ok_or("the index is built")?;
+        let ids: HashSet<ScalarValue> = ranking.into_iter().flat_map(|ranked| ranked.key).collect();
+        assert_eq!(
+            ids,
+            (55..num_rows)
+                .map(|id| ScalarValue::Int64(Some(id)))
+                .collect::<HashSet<_>>()
+        );
+        Ok(())
+    }
 }
@@ -14,6 +14,7 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::auth::{Auth, Credentials};
 use crate::datafusion::query::error_code::ErrorCode;
 use crate::datafusion::query::{Protocol, QueryBuilder};
 use crate::datafusion::DataFusion;
@@ -28,6 +29,7 @@ use arrow_flight::encode::FlightDataEncoderBuilder;
 use arrow_flight::{Action, ActionType, Criteria, IpcMessage, PollInfo, SchemaResult};
 use arrow_ipc::writer::IpcWriteOptions;
 use bytes::Bytes;
+use cache::CacheControl;
 use datafusion::error::DataFusionError;
 use datafusion::sql::sqlparser::parser::ParserError;
 use datafusion::sql::TableReference;
@@ -35,10 +37,12 @@ use futures::stream::{self, BoxStream, StreamExt};
 use futures::{Stream, TryStreamExt};
 use secrecy::ExposeSecret;
 use snafu::prelude::*;
+use spicepod::component::runtime::ApiKeyScope;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::broadcast::Sender;
 use tokio::sync::RwLock;
+use tonic::metadata::MetadataMap;
 use tonic::transport::{Identity, Server, ServerTlsConfig};
 use tonic::{Request, Response, Status, Streaming};
 
@@ -50,7 +54,9 @@ mod flightsql;
 mod get_flight_info;
 mod get_schema;
 mod handshake;
+mod list_flights;
 mod metrics;
+mod poll_flight_info;
 mod util;
 
 use arrow_flight::{
@@ -62,6 +68,7 @@ use arrow_flight::{
 pub struct Service {
     datafusion: Arc<DataFusion>,
     channel_map: Arc<RwLock<HashMap<TableReference, Arc<Sender<DataUpdate>>>>>,
+    auth: Option<Arc<Auth>>,
 }
 
 #[tonic::async_trait]
@@ -76,19 +83,19 @@ impl FlightService for Service {
 
     async fn handshake(
         &self,
-        _request: Request<Streaming<HandshakeRequest>>,
+        request: Request<Streaming<HandshakeRequest>>,
     ) -> Result<Response<Self::HandshakeStream>, Status> {
         metrics::HANDSHAKE_REQUESTS.add(1, &[]);
-        handshake::handle()
+        handshake::handle(self, request).await
     }
 
     async fn list_flights(
         &self,
-        _request: Request<Criteria>,
+        request: Request<Criteria>,
     ) -> Result<Response<Self::ListFlightsStream>, Status> {
         metrics::LIST_FLIGHTS_REQUESTS.add(1, &[]);
-        tracing::trace!("list_flights - unimplemented");
-        Err(Status::unimplemented("Not yet implemented"))
+        self.authenticate(&request, ApiKeyScope::Sql)?;
+        Box::pin(list_flights::handle(self, request)).await
     }
 
     async fn get_flight_info(
@@ -97,14 +104,17 @@ impl FlightService for Service {
     ) -> Result<Response<FlightInfo>, Status> {
         let _guard = TimeMeasurement::new(&metrics::GET_FLIGHT_INFO_REQUEST_DURATION_MS, vec![]);
         metrics::GET_FLIGHT_INFO_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         Box::pin(get_flight_info::handle(self, request)).await
     }
 
     async fn poll_flight_info(
         &self,
-        _request: Request<FlightDescriptor>,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<PollInfo>, Status> {
-        Err(Status::unimplemented("Not yet implemented"))
+        metrics::POLL_FLIGHT_INFO_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
+        Box::pin(poll_flight_info::handle(self, request)).await
     }
 
     async fn get_schema(
@@ -112,6 +122,7 @@ impl FlightService for Service {
         request: Request<FlightDescriptor>,
     ) -> Result<Response<SchemaResult>, Status> {
         metrics::GET_SCHEMA_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         get_schema::handle(self, request).await
     }
 
@@ -120,6 +131,7 @@ impl FlightService for Service {
         request: Request<Ticket>,
     ) -> Result<Response<Self::DoGetStream>, Status> {
         metrics::DO_GET_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         Box::pin(do_get::handle(self, request)).await
     }
 
@@ -128,6 +140,7 @@ impl FlightService for Service {
         request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoPutStream>, Status> {
         metrics::DO_PUT_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Write)?;
         do_put::handle(self, request).await
     }
 
@@ -136,6 +149,7 @@ impl FlightService for Service {
         request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoExchangeStream>, Status> {
         metrics::DO_EXCHANGE_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         do_exchange::handle(self, request).await
     }
 
@@ -144,19 +158,44 @@ impl FlightService for Service {
         request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
         metrics::DO_ACTION_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         Box::pin(actions::do_action(self, request)).await
     }
 
     async fn list_actions(
         &self,
-        _request: Request<arrow_flight::Empty>,
+        request: Request<arrow_flight::Empty>,
     ) -> Result<Response<Self::ListActionsStream>, Status> {
         metrics::LIST_ACTIONS_REQUESTS.add(1, &[]);
+        self.authenticate(&request, ApiKeyScope::Sql)?;
         Ok(actions::list())
     }
 }
 
 impl Service {
+    /// Rejects the request if auth is enabled and it doesn't carry a valid API key, basic auth
+    /// credentials or a bearer token issued by the handshake that grants `scope`.
+    ///
+    /// Queries, subscriptions and prepared statements need the `sql` scope, while `DoPut` writes
+    /// to datasets and needs the `write` scope.
+    fn authenticate<T>(&self, request: &Request<T>, scope: ApiKeyScope) -> Result<(), Status> {
+        let Some(auth) = &self.auth else {
+            return Ok(());
+        };
+
+        let Some(credentials) = request_credentials(request.metadata())? else {
+            return Err(Status::unauthenticated("No authorization header provided"));
+        };
+
+        match auth.validate_request(&credentials) {
+            Some(grant) if grant.allows(scope) => Ok(()),
+            Some(_) => Err(Status::permission_denied(format!(
+                "The provided credentials don't grant the `{scope}` scope"
+            ))),
+            None => Err(Status::unauthenticated("Invalid or expired credentials")),
+        }
+    }
+
     async fn get_arrow_schema(
         datafusion: Arc<DataFusion>,
         sql: &str,
@@ -189,10 +228,12 @@ impl Service {
         datafusion: Arc<DataFusion>,
         sql: &str,
         protocol: Protocol,
+        cache_control: CacheControl,
     ) -> Result<(BoxStream<'static, Result<FlightData, Status>>, Option<bool>), Status> {
         let query = QueryBuilder::new(sql, Arc::clone(&datafusion), protocol)
             .use_restricted_sql_options()
             .protocol(protocol)
+            .cache_control(cache_control)
             .build();
 
         let query_result = query.run().await.map_err(to_tonic_err)?;
@@ -260,6 +301,21 @@ fn record_batches_to_flight_stream(
         .map_err(to_tonic_err)
 }
 
+/// Parses the `authorization` header from the request metadata, if present.
+fn request_credentials(metadata: &MetadataMap) -> Result<Option<Credentials>, Status> {
+    let Some(value) = metadata.get("authorization") else {
+        return Ok(None);
+    };
+
+    let value = value
+        .to_str()
+        .map_err(|_| Status::unauthenticated("Invalid authorization header"))?;
+
+    Credentials::from_header(value)
+        .map(Some)
+        .ok_or_else(|| Status::unauthenticated("Unsupported authorization scheme"))
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn to_tonic_err<E>(e: E) -> Status
 where
@@ -316,10 +372,12 @@ pub async fn start(
     bind_address: std::net::SocketAddr,
     df: Arc<DataFusion>,
     tls_config: Option<Arc<TlsConfig>>,
+    auth: Option<Arc<Auth>>,
 ) -> Result<()> {
     let service = Service {
         datafusion: Arc::clone(&df),
         channel_map: Arc::new(RwLock::new(HashMap::new())),
+        auth,
     };
     let svc = FlightServiceServer::new(service);
 
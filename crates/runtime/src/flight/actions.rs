@@ -21,19 +21,24 @@ use prost::Message;
 use tonic::{Request, Response, Status};
 
 use crate::{
-    flight::{flightsql::prepared_statement_query, metrics, to_tonic_err, Service},
+    datafusion::query::jobs::QueryJobStatus,
+    flight::{
+        flightsql::prepared_statement_query, metrics, poll_flight_info, to_tonic_err, Service,
+    },
     timing::{TimeMeasurement, TimedStream},
 };
 
 use arrow_flight::{
     flight_service_server::FlightService,
     sql::{self, Any, ProstMessageExt},
-    Action, ActionType as FlightActionType,
+    Action, ActionType as FlightActionType, CancelFlightInfoRequest, CancelFlightInfoResult,
+    CancelStatus,
 };
 
 enum ActionType {
     CreatePreparedStatement,
     ClosePreparedStatement,
+    CancelFlightInfo,
     Unknown,
 }
 
@@ -42,6 +47,7 @@ impl ActionType {
         match s {
             "CreatePreparedStatement" => ActionType::CreatePreparedStatement,
             "ClosePreparedStatement" => ActionType::ClosePreparedStatement,
+            "CancelFlightInfo" => ActionType::CancelFlightInfo,
             _ => ActionType::Unknown,
         }
     }
@@ -50,6 +56,7 @@ impl ActionType {
         match self {
             ActionType::CreatePreparedStatement => "CreatePreparedStatement",
             ActionType::ClosePreparedStatement => "ClosePreparedStatement",
+            ActionType::CancelFlightInfo => "CancelFlightInfo",
             ActionType::Unknown => "Unknown",
         }
     }
@@ -77,9 +84,17 @@ pub(crate) fn list() -> Response<<Service as FlightService>::ListActionsStream>
             Response Message: N/A"
             .into(),
     };
+    let cancel_flight_info_action_type = FlightActionType {
+        r#type: ActionType::CancelFlightInfo.to_string(),
+        description: "Cancels a query started by PollFlightInfo.\n
+            Request Message: CancelFlightInfoRequest\n
+            Response Message: CancelFlightInfoResult"
+            .into(),
+    };
     let actions: Vec<Result<FlightActionType, Status>> = vec![
         Ok(create_prepared_statement_action_type),
         Ok(close_prepared_statement_action_type),
+        Ok(cancel_flight_info_action_type),
     ];
 
     let output = TimedStream::new(futures::stream::iter(actions), || {
@@ -123,6 +138,15 @@ pub(crate) async fn do_action(
             tracing::trace!("do_action: ClosePreparedStatement");
             futures::stream::iter(vec![Ok(arrow_flight::Result::default())])
         }
+        ActionType::CancelFlightInfo => {
+            tracing::trace!("do_action: CancelFlightInfo");
+            let cancel_request =
+                CancelFlightInfoRequest::decode(&*request.get_ref().body).map_err(to_tonic_err)?;
+            let result = cancel_flight_info(flight_svc, &cancel_request)?;
+            futures::stream::iter(vec![Ok(arrow_flight::Result {
+                body: result.encode_to_vec().into(),
+            })])
+        }
         ActionType::Unknown => return Err(Status::invalid_argument("Unknown action type")),
     };
 
@@ -131,3 +155,52 @@ pub(crate) async fn do_action(
         move || start,
     ))))
 }
+
+/// Cancels the background query referenced by the `FlightInfo` returned from `poll_flight_info`,
+/// either through its descriptor or one of its endpoint tickets.
+fn cancel_flight_info(
+    flight_svc: &Service,
+    request: &CancelFlightInfoRequest,
+) -> Result<CancelFlightInfoResult, Status> {
+    let Some(info) = &request.info else {
+        return Err(Status::invalid_argument(
+            "CancelFlightInfoRequest is missing the FlightInfo to cancel",
+        ));
+    };
+
+    let descriptor_reference = info
+        .flight_descriptor
+        .as_ref()
+        .map(|descriptor| descriptor.cmd.as_ref());
+    let ticket_references = info
+        .endpoint
+        .iter()
+        .filter_map(|endpoint| endpoint.ticket.as_ref())
+        .map(|ticket| ticket.ticket.as_ref());
+
+    let Some(id) = descriptor_reference
+        .into_iter()
+        .chain(ticket_references)
+        .find_map(poll_flight_info::parse_job_reference)
+    else {
+        return Err(Status::invalid_argument(
+            "The FlightInfo doesn't reference a query started by PollFlightInfo",
+        ));
+    };
+
+    let Some(job) = flight_svc.datafusion.query_jobs().get(&id) else {
+        return Err(Status::not_found(format!(
+            "Query {id} not found or expired"
+        )));
+    };
+
+    let status = if job.cancel() || job.status() == QueryJobStatus::Cancelled {
+        CancelStatus::Cancelled
+    } else {
+        CancelStatus::NotCancellable
+    };
+
+    Ok(CancelFlightInfoResult {
+        status: status as i32,
+    })
+}
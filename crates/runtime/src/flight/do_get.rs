@@ -17,37 +17,57 @@ limitations under the License.
 use std::sync::Arc;
 
 use arrow_flight::{
+    encode::FlightDataEncoderBuilder,
     flight_service_server::FlightService,
     sql::{Any, Command},
     Ticket,
 };
+use cache::CacheControl;
+use futures::{stream, TryStreamExt};
 use prost::Message;
 use tonic::{Request, Response, Status};
+use uuid::Uuid;
 
 use crate::{
-    datafusion::query::Protocol,
-    flight::{metrics, util::attach_cache_metadata},
+    datafusion::query::{jobs::QueryJobStatus, Protocol},
+    flight::{
+        metrics,
+        util::{attach_cache_metadata, cache_control},
+    },
     timing::{TimeMeasurement, TimedStream},
 };
 
-use super::{flightsql, to_tonic_err, Service};
+use super::{flightsql, poll_flight_info, to_tonic_err, Service};
 
 pub(crate) async fn handle(
     flight_svc: &Service,
     request: Request<Ticket>,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
+    if let Some(id) = poll_flight_info::parse_job_reference(&request.get_ref().ticket) {
+        return do_get_query_job(flight_svc, id);
+    }
+
+    let cache_control = cache_control(request.metadata());
+
     let msg: Any = match Message::decode(&*request.get_ref().ticket) {
         Ok(msg) => msg,
-        Err(_) => return Box::pin(do_get_simple(flight_svc, request)).await,
+        Err(_) => return Box::pin(do_get_simple(flight_svc, request, cache_control)).await,
     };
 
     match Command::try_from(msg).map_err(to_tonic_err)? {
         Command::CommandStatementQuery(command) => {
-            Box::pin(flightsql::statement_query::do_get(flight_svc, command)).await
+            Box::pin(flightsql::statement_query::do_get(
+                flight_svc,
+                command,
+                cache_control,
+            ))
+            .await
         }
         Command::CommandPreparedStatementQuery(command) => {
             Box::pin(flightsql::prepared_statement_query::do_get(
-                flight_svc, command,
+                flight_svc,
+                command,
+                cache_control,
             ))
             .await
         }
@@ -72,6 +92,7 @@ pub(crate) async fn handle(
 async fn do_get_simple(
     flight_svc: &Service,
     request: Request<Ticket>,
+    cache_control: CacheControl,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
     let ticket = request.into_inner();
@@ -83,6 +104,7 @@ async fn do_get_simple(
                 datafusion,
                 sql,
                 Protocol::Flight,
+                cache_control,
             ))
             .await?;
 
@@ -98,3 +120,49 @@ async fn do_get_simple(
         Err(e) => Err(Status::invalid_argument(format!("Invalid ticket: {e}"))),
     }
 }
+
+/// Streams the results of a query started by `poll_flight_info`.
+fn do_get_query_job(
+    flight_svc: &Service,
+    id: Uuid,
+) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
+    tracing::trace!("do_get_query_job: {id}");
+    let start = TimeMeasurement::new(&metrics::DO_GET_QUERY_JOB_DURATION_MS, vec![]);
+
+    let job = flight_svc
+        .datafusion
+        .query_jobs()
+        .get(&id)
+        .ok_or_else(|| Status::not_found(format!("Query {id} not found or expired")))?;
+
+    match job.status() {
+        QueryJobStatus::Completed => {}
+        QueryJobStatus::Running => {
+            return Err(Status::failed_precondition(format!(
+                "Query {id} is still running"
+            )))
+        }
+        QueryJobStatus::Failed => return Err(poll_flight_info::job_error(&job)),
+        QueryJobStatus::Cancelled => {
+            return Err(Status::cancelled(format!("Query {id} was cancelled")))
+        }
+    }
+
+    let (Some(schema), Some(results)) = (job.schema(), job.results()) else {
+        return Err(Status::not_found(format!(
+            "Query {id} not found or expired"
+        )));
+    };
+
+    // The schema is sent explicitly so that queries without any rows still return it.
+    let output = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(stream::iter(results.into_iter().map(Ok)))
+        .map_err(to_tonic_err);
+
+    let timed_output = TimedStream::new(output, move || start);
+
+    Ok(Response::new(
+        Box::pin(timed_output) as <Service as FlightService>::DoGetStream
+    ))
+}
@@ -21,6 +21,7 @@ use arrow_flight::{
     sql::{self, ProstMessageExt},
     FlightDescriptor, FlightEndpoint, FlightInfo, Ticket,
 };
+use cache::CacheControl;
 use prost::Message;
 use tonic::{Request, Response, Status};
 
@@ -94,6 +95,7 @@ pub(crate) async fn get_flight_info(
 pub(crate) async fn do_get(
     flight_svc: &Service,
     query: sql::CommandPreparedStatementQuery,
+    cache_control: CacheControl,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
     tracing::trace!("do_get: {query:?}");
@@ -107,6 +109,7 @@ pub(crate) async fn do_get(
                 datafusion,
                 sql,
                 Protocol::FlightSQL,
+                cache_control,
             ))
             .await?;
             let timed_output = TimedStream::new(output, move || start);
@@ -21,6 +21,7 @@ use arrow_flight::{
     sql::{self, ProstMessageExt},
     FlightDescriptor, FlightEndpoint, FlightInfo, Ticket,
 };
+use cache::CacheControl;
 use prost::Message;
 use tonic::{Request, Response, Status};
 
@@ -63,6 +64,7 @@ pub(crate) async fn get_flight_info(
 pub(crate) async fn do_get(
     flight_svc: &Service,
     cmd: sql::CommandStatementQuery,
+    cache_control: CacheControl,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
     tracing::trace!("do_get_statement: {cmd:?}");
@@ -74,6 +76,7 @@ pub(crate) async fn do_get(
         datafusion,
         &cmd.query,
         Protocol::FlightSQL,
+        cache_control,
     ))
     .await?;
     let timed_output = TimedStream::new(output, move || start);
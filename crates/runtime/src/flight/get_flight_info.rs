@@ -17,20 +17,27 @@ limitations under the License.
 use std::sync::Arc;
 
 use arrow_flight::{
+    flight_descriptor::DescriptorType,
     sql::{Any, Command},
     FlightDescriptor, FlightEndpoint, FlightInfo, Ticket,
 };
+use datafusion::sql::TableReference;
 use prost::Message;
 use tonic::{Request, Response, Status};
 
 use crate::datafusion::query::Protocol;
 
-use super::{flightsql, to_tonic_err, Service};
+use super::{flightsql, list_flights, to_tonic_err, Service};
 
 pub(crate) async fn handle(
     flight_svc: &Service,
     request: Request<FlightDescriptor>,
 ) -> Result<Response<FlightInfo>, Status> {
+    // Path descriptors (i.e. returned by `list_flights`) reference a dataset to fetch in full.
+    if request.get_ref().r#type == DescriptorType::Path as i32 {
+        return get_flight_info_path(flight_svc, request).await;
+    }
+
     let Ok(message) = Any::decode(&*request.get_ref().cmd) else {
         return get_flight_info_simple(flight_svc, request).await;
     };
@@ -64,6 +71,23 @@ pub(crate) async fn handle(
     }
 }
 
+async fn get_flight_info_path(
+    flight_svc: &Service,
+    request: Request<FlightDescriptor>,
+) -> Result<Response<FlightInfo>, Status> {
+    tracing::trace!("get_flight_info_path: {request:?}");
+
+    let fd = request.into_inner();
+    if fd.path.is_empty() {
+        return Err(Status::invalid_argument("No path provided"));
+    }
+
+    let table = TableReference::parse_str(&fd.path.join("."));
+    let info = list_flights::table_flight_info(&flight_svc.datafusion, &table).await?;
+
+    Ok(Response::new(info))
+}
+
 async fn get_flight_info_simple(
     flight_svc: &Service,
     request: Request<FlightDescriptor>,
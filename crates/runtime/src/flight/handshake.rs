@@ -16,22 +16,38 @@ limitations under the License.
 
 use std::pin::Pin;
 
-use arrow_flight::HandshakeResponse;
+use arrow_flight::{BasicAuth, HandshakeRequest, HandshakeResponse};
 use futures::Stream;
-use tonic::{metadata::MetadataValue, Response, Status};
+use prost::Message;
+use tonic::{metadata::MetadataValue, Request, Response, Status, Streaming};
 use uuid::Uuid;
 
-use crate::timing::{TimeMeasurement, TimedStream};
+use crate::{
+    auth::Credentials,
+    timing::{TimeMeasurement, TimedStream},
+};
 
-use super::metrics;
+use super::{metrics, Service};
 
 type HandshakeResponseStream =
     Pin<Box<dyn Stream<Item = Result<HandshakeResponse, Status>> + Send>>;
 
-pub(crate) fn handle() -> Result<Response<HandshakeResponseStream>, Status> {
-    // THIS IS PLACEHOLDER NO-OP AUTH THAT DOES NOT CHECK THE PROVIDED TOKEN AND SIMPLY RETURNS A UUID.
-    // TODO: Implement proper auth.
-    let token = Uuid::new_v4().to_string();
+pub(crate) async fn handle(
+    flight_svc: &Service,
+    request: Request<Streaming<HandshakeRequest>>,
+) -> Result<Response<HandshakeResponseStream>, Status> {
+    let token = match &flight_svc.auth {
+        Some(auth) => {
+            let credentials = handshake_credentials(request).await?;
+            let Some(grant) = auth.validate_login(&credentials) else {
+                return Err(Status::unauthenticated("Invalid credentials"));
+            };
+            auth.issue_token(grant)
+        }
+        // Auth isn't enabled, any token will be accepted by the other endpoints.
+        None => Uuid::new_v4().to_string(),
+    };
+
     let result = HandshakeResponse {
         protocol_version: 0,
         payload: token.as_bytes().to_vec().into(),
@@ -47,3 +63,35 @@ pub(crate) fn handle() -> Result<Response<HandshakeResponseStream>, Status> {
     resp.metadata_mut().insert("authorization", md);
     Ok(resp)
 }
+
+/// Reads the credentials for the handshake, either from the `authorization` header or from the
+/// payload of the first handshake message (a `BasicAuth` message, or a raw API key).
+async fn handshake_credentials(
+    request: Request<Streaming<HandshakeRequest>>,
+) -> Result<Credentials, Status> {
+    if let Some(credentials) = super::request_credentials(request.metadata())? {
+        return Ok(credentials);
+    }
+
+    let mut stream = request.into_inner();
+    let Some(message) = stream.message().await? else {
+        return Err(Status::unauthenticated("No credentials provided"));
+    };
+
+    if message.payload.is_empty() {
+        return Err(Status::unauthenticated("No credentials provided"));
+    }
+
+    if let Ok(basic_auth) = BasicAuth::decode(&*message.payload) {
+        if !basic_auth.username.is_empty() {
+            return Ok(Credentials::Basic {
+                username: basic_auth.username,
+                password: basic_auth.password,
+            });
+        }
+    }
+
+    let api_key = std::str::from_utf8(&message.payload)
+        .map_err(|_| Status::unauthenticated("Invalid credentials"))?;
+    Ok(Credentials::Bearer(api_key.to_string()))
+}
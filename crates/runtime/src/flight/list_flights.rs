@@ -0,0 +1,300 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow_flight::{
+    flight_service_server::FlightService, Criteria, FlightDescriptor, FlightEndpoint, FlightInfo,
+    Ticket,
+};
+use datafusion::sql::TableReference;
+use globset::{Glob, GlobMatcher};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    datafusion::DataFusion,
+    timing::{TimeMeasurement, TimedStream},
+};
+
+use super::{metrics, to_tonic_err, Service};
+
+/// Lists one `FlightInfo` per dataset and view in the public schema.
+///
+/// A non-empty `Criteria` expression is treated as a glob pattern on the table name, i.e. `sales_*`.
+pub(crate) async fn handle(
+    flight_svc: &Service,
+    request: Request<Criteria>,
+) -> Result<Response<<Service as FlightService>::ListFlightsStream>, Status> {
+    let start = TimeMeasurement::new(&metrics::LIST_FLIGHTS_DURATION_MS, vec![]);
+    let criteria = request.into_inner();
+    tracing::trace!("list_flights: {criteria:?}");
+
+    let flights = list_table_flights(&flight_svc.datafusion, &criteria.expression)
+        .await?
+        .into_iter()
+        .map(Ok::<_, Status>)
+        .collect::<Vec<_>>();
+
+    let output = TimedStream::new(futures::stream::iter(flights), move || start);
+
+    Ok(Response::new(
+        Box::pin(output) as <Service as FlightService>::ListFlightsStream
+    ))
+}
+
+/// The `FlightInfo` of each table in the public schema whose name matches the glob pattern in
+/// `expression`, or of every table if it is empty.
+async fn list_table_flights(
+    datafusion: &DataFusion,
+    expression: &[u8],
+) -> Result<Vec<FlightInfo>, Status> {
+    let name_filter = name_filter(expression)?;
+    let table_names = datafusion.get_public_table_names().map_err(to_tonic_err)?;
+
+    let mut flights = vec![];
+    for table_name in table_names {
+        if let Some(name_filter) = &name_filter {
+            if !name_filter.is_match(&table_name) {
+                continue;
+            }
+        }
+
+        let table = TableReference::bare(table_name);
+        match table_flight_info(datafusion, &table).await {
+            Ok(info) => flights.push(info),
+            Err(e) => tracing::debug!("Unable to list flight for {table}: {e}"),
+        }
+    }
+
+    Ok(flights)
+}
+
+/// Builds the `FlightInfo` for fetching an entire table, with a row count estimate if the table
+/// provides one.
+pub(crate) async fn table_flight_info(
+    datafusion: &DataFusion,
+    table: &TableReference,
+) -> Result<FlightInfo, Status> {
+    let Some(table_provider) = datafusion.get_table(table.clone()).await else {
+        return Err(Status::not_found(format!("Table {table} not found")));
+    };
+
+    let total_records = table_provider
+        .statistics()
+        .and_then(|statistics| statistics.num_rows.get_value().copied())
+        .and_then(|num_rows| i64::try_from(num_rows).ok())
+        .unwrap_or(-1);
+
+    let sql = format!("SELECT * FROM {}", table.to_quoted_string());
+
+    FlightInfo {
+        flight_descriptor: Some(FlightDescriptor::new_path(table.to_vec())),
+        endpoint: vec![FlightEndpoint {
+            ticket: Some(Ticket {
+                ticket: sql.into_bytes().into(),
+            }),
+            ..Default::default()
+        }],
+        total_records,
+        total_bytes: -1,
+        ..Default::default()
+    }
+    .try_with_schema(table_provider.schema().as_ref())
+    .map_err(to_tonic_err)
+}
+
+fn name_filter(expression: &[u8]) -> Result<Option<GlobMatcher>, Status> {
+    let pattern = std::str::from_utf8(expression)
+        .map_err(|_| Status::invalid_argument("The criteria expression must be valid UTF-8"))?
+        .trim();
+
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+
+    let glob = Glob::new(pattern).map_err(|e| {
+        Status::invalid_argument(format!("Invalid table name pattern '{pattern}': {e}"))
+    })?;
+
+    Ok(Some(glob.compile_matcher()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::sync::Arc;
+
+    use arrow::array::{Int64Array, RecordBatch};
+    use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+    use async_trait::async_trait;
+    use datafusion::catalog::Session;
+    use datafusion::common::stats::Precision;
+    use datafusion::common::Statistics;
+    use datafusion::datasource::{MemTable, TableProvider, TableType};
+    use datafusion::logical_expr::Expr;
+    use datafusion::physical_plan::ExecutionPlan;
+    use tonic::Code;
+
+    use super::*;
+
+    /// A table with exact statistics, like an accelerated table.
+    struct CountedTable {
+        table: MemTable,
+        num_rows: usize,
+    }
+
+    #[async_trait]
+    impl TableProvider for CountedTable {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn schema(&self) -> SchemaRef {
+            self.table.schema()
+        }
+
+        fn table_type(&self) -> TableType {
+            TableType::Base
+        }
+
+        fn statistics(&self) -> Option<Statistics> {
+            let mut statistics = Statistics::new_unknown(&self.schema());
+            statistics.num_rows = Precision::Exact(self.num_rows);
+            Some(statistics)
+        }
+
+        async fn scan(
+            &self,
+            state: &dyn Session,
+            projection: Option<&Vec<usize>>,
+            filters: &[Expr],
+            limit: Option<usize>,
+        ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+            self.table.scan(state, projection, filters, limit).await
+        }
+    }
+
+    fn mem_table(num_rows: i64) -> MemTable {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from_iter_values(0..num_rows))],
+        )
+        .expect("valid batch");
+        MemTable::try_new(schema, vec![vec![batch]]).expect("valid table")
+    }
+
+    fn datafusion() -> DataFusion {
+        let df = DataFusion::new();
+        for (name, table) in [
+            (
+                "sales_2023",
+                Arc::new(CountedTable {
+                    table: mem_table(3),
+                    num_rows: 3,
+                }) as Arc<dyn TableProvider>,
+            ),
+            ("sales_2024", Arc::new(mem_table(2))),
+            ("customers", Arc::new(mem_table(1))),
+        ] {
+            df.ctx
+                .register_table(TableReference::bare(name), table)
+                .expect("table registered");
+        }
+        df
+    }
+
+    fn table_names(flights: &[FlightInfo]) -> Vec<String> {
+        let mut names: Vec<String> = flights
+            .iter()
+            .filter_map(|flight| flight.flight_descriptor.as_ref())
+            .flat_map(|descriptor| descriptor.path.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_list_flights_filters_by_criteria() {
+        let df = datafusion();
+
+        let flights = list_table_flights(&df, b"").await.expect("flights listed");
+        assert_eq!(
+            table_names(&flights),
+            vec!["customers", "sales_2023", "sales_2024"]
+        );
+
+        let flights = list_table_flights(&df, b"sales_*")
+            .await
+            .expect("flights listed");
+        assert_eq!(table_names(&flights), vec!["sales_2023", "sales_2024"]);
+
+        let flights = list_table_flights(&df, b" orders ")
+            .await
+            .expect("flights listed");
+        assert!(flights.is_empty());
+
+        for invalid in [&b"sales_["[..], &[0xff, 0xfe][..]] {
+            let status = list_table_flights(&df, invalid)
+                .await
+                .expect_err("invalid criteria");
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_flight_info() {
+        let df = datafusion();
+
+        let info = table_flight_info(&df, &TableReference::bare("sales_2023"))
+            .await
+            .expect("flight info");
+        assert_eq!(info.total_records, 3);
+        assert_eq!(info.total_bytes, -1);
+        assert_eq!(
+            info.try_decode_schema().expect("valid schema"),
+            Schema::new(vec![Field::new("id", DataType::Int64, false)])
+        );
+
+        // The ticket is a query for the whole table
+        let ticket = info
+            .endpoint
+            .first()
+            .and_then(|endpoint| endpoint.ticket.as_ref())
+            .expect("endpoint with a ticket");
+        let sql = std::str::from_utf8(&ticket.ticket).expect("UTF-8 ticket");
+        let batches = df
+            .ctx
+            .sql(sql)
+            .await
+            .expect("valid query")
+            .collect()
+            .await
+            .expect("query results");
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 3);
+
+        let info = table_flight_info(&df, &TableReference::bare("sales_2024"))
+            .await
+            .expect("flight info");
+        assert_eq!(
+            info.total_records, -1,
+            "the table has no row count estimate"
+        );
+
+        let status = table_flight_info(&df, &TableReference::bare("orders"))
+            .await
+            .expect_err("unknown table");
+        assert_eq!(status.code(), Code::NotFound);
+    }
+}
@@ -36,6 +36,13 @@ pub(crate) static HANDSHAKE_REQUEST_DURATION_MS: LazyLock<Histogram<f64>> = Lazy
 pub(crate) static LIST_FLIGHTS_REQUESTS: LazyLock<Counter<u64>> =
     LazyLock::new(|| METER.u64_counter("flight_list_flights_requests").init());
 
+pub(crate) static LIST_FLIGHTS_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    METER
+        .f64_histogram("flight_list_flights_duration_ms")
+        .with_unit("ms")
+        .init()
+});
+
 pub(crate) static GET_FLIGHT_INFO_REQUESTS: LazyLock<Counter<u64>> =
     LazyLock::new(|| METER.u64_counter("flight_get_flight_info_requests").init());
 
@@ -47,6 +54,16 @@ pub(crate) static GET_FLIGHT_INFO_REQUEST_DURATION_MS: LazyLock<Histogram<f64>>
             .init()
     });
 
+pub(crate) static POLL_FLIGHT_INFO_REQUESTS: LazyLock<Counter<u64>> =
+    LazyLock::new(|| METER.u64_counter("flight_poll_flight_info_requests").init());
+
+pub(crate) static POLL_FLIGHT_INFO_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    METER
+        .f64_histogram("flight_poll_flight_info_duration_ms")
+        .with_unit("ms")
+        .init()
+});
+
 pub(crate) static GET_SCHEMA_REQUESTS: LazyLock<Counter<u64>> =
     LazyLock::new(|| METER.u64_counter("flight_get_schema_requests").init());
 
@@ -60,6 +77,13 @@ pub(crate) static DO_GET_SIMPLE_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock
         .init()
 });
 
+pub(crate) static DO_GET_QUERY_JOB_DURATION_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    METER
+        .f64_histogram("flight_do_get_query_job_duration_ms")
+        .with_unit("ms")
+        .init()
+});
+
 pub(crate) static DO_PUT_REQUESTS: LazyLock<Counter<u64>> =
     LazyLock::new(|| METER.u64_counter("flight_do_put_requests").init());
 
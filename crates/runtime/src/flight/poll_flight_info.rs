@@ -0,0 +1,164 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use arrow_flight::{
+    sql::{Any, Command},
+    FlightDescriptor, FlightEndpoint, FlightInfo, PollInfo, Ticket,
+};
+use bytes::Bytes;
+use prost::Message;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::{
+    datafusion::query::{
+        jobs::{QueryJob, QueryJobStatus},
+        Protocol,
+    },
+    timing::TimeMeasurement,
+};
+
+use super::{metrics, to_tonic_err, Service};
+
+/// Prefix of the descriptor commands and tickets that reference a background query job.
+const JOB_REFERENCE_PREFIX: &[u8] = b"spice.query_job:";
+
+pub(crate) fn job_reference(id: Uuid) -> Bytes {
+    let mut reference = JOB_REFERENCE_PREFIX.to_vec();
+    reference.extend_from_slice(id.to_string().as_bytes());
+    reference.into()
+}
+
+/// Returns the job id if `bytes` is a descriptor command or ticket created by [`job_reference`].
+pub(crate) fn parse_job_reference(bytes: &[u8]) -> Option<Uuid> {
+    let id = bytes.strip_prefix(JOB_REFERENCE_PREFIX)?;
+    Uuid::try_parse_ascii(id).ok()
+}
+
+/// Starts a query in the background, or reports the progress of one started by a previous call.
+///
+/// Until the query completes, the response carries a retry descriptor that the client passes to
+/// the next `poll_flight_info` call. Once it completes, the endpoints to fetch the results with
+/// `do_get` are returned.
+pub(crate) async fn handle(
+    flight_svc: &Service,
+    request: Request<FlightDescriptor>,
+) -> Result<Response<PollInfo>, Status> {
+    let _start = TimeMeasurement::new(&metrics::POLL_FLIGHT_INFO_DURATION_MS, vec![]);
+    let descriptor = request.into_inner();
+    tracing::trace!("poll_flight_info: {descriptor:?}");
+
+    let query_jobs = flight_svc.datafusion.query_jobs();
+
+    let job = if let Some(id) = parse_job_reference(&descriptor.cmd) {
+        query_jobs
+            .get(&id)
+            .ok_or_else(|| Status::not_found(format!("Query {id} not found or expired")))?
+    } else {
+        let (sql, protocol) = descriptor_sql(&descriptor)?;
+        query_jobs
+            .submit(Arc::clone(&flight_svc.datafusion), &sql, protocol)
+            .map_err(|e| Status::resource_exhausted(e.to_string()))?
+    };
+
+    poll_info(&job).map(Response::new)
+}
+
+/// Reads the SQL to run from either a Flight SQL `CommandStatementQuery` or a raw UTF-8 command.
+fn descriptor_sql(descriptor: &FlightDescriptor) -> Result<(String, Protocol), Status> {
+    let Ok(message) = Any::decode(&*descriptor.cmd) else {
+        let sql = std::str::from_utf8(&descriptor.cmd)
+            .map_err(|e| Status::invalid_argument(format!("Invalid SQL command: {e}")))?;
+        return Ok((sql.to_string(), Protocol::Flight));
+    };
+
+    match Command::try_from(message).map_err(to_tonic_err)? {
+        Command::CommandStatementQuery(command) => Ok((command.query, Protocol::FlightSQL)),
+        _ => Err(Status::unimplemented(
+            "poll_flight_info only supports statement queries",
+        )),
+    }
+}
+
+/// The error of a failed job. Jobs whose results are too large to hold in memory report the
+/// exhausted resource rather than an internal error.
+pub(crate) fn job_error(job: &QueryJob) -> Status {
+    let message = job
+        .error()
+        .unwrap_or_else(|| "Query failed to execute".to_string());
+    if job.results_too_large() {
+        Status::resource_exhausted(message)
+    } else {
+        Status::internal(message)
+    }
+}
+
+fn poll_info(job: &QueryJob) -> Result<PollInfo, Status> {
+    let reference = job_reference(job.id());
+
+    match job.status() {
+        QueryJobStatus::Running => {
+            let mut info = FlightInfo {
+                flight_descriptor: Some(FlightDescriptor::new_cmd(reference.clone())),
+                total_records: -1,
+                total_bytes: -1,
+                ..Default::default()
+            };
+            if let Some(schema) = job.schema() {
+                info = info.try_with_schema(&schema).map_err(to_tonic_err)?;
+            }
+
+            Ok(PollInfo {
+                info: Some(info),
+                flight_descriptor: Some(FlightDescriptor::new_cmd(reference)),
+                progress: job.progress(),
+                expiration_time: None,
+            })
+        }
+        QueryJobStatus::Completed => {
+            let Some(schema) = job.schema() else {
+                return Err(Status::internal("Completed query is missing a schema"));
+            };
+
+            let info = FlightInfo {
+                flight_descriptor: Some(FlightDescriptor::new_cmd(reference.clone())),
+                endpoint: vec![FlightEndpoint {
+                    ticket: Some(Ticket { ticket: reference }),
+                    ..Default::default()
+                }],
+                total_records: i64::try_from(job.rows_produced()).unwrap_or(-1),
+                total_bytes: -1,
+                ..Default::default()
+            }
+            .try_with_schema(&schema)
+            .map_err(to_tonic_err)?;
+
+            Ok(PollInfo {
+                info: Some(info),
+                flight_descriptor: None,
+                progress: Some(1.0),
+                expiration_time: None,
+            })
+        }
+        QueryJobStatus::Failed => Err(job_error(job)),
+        QueryJobStatus::Cancelled => Err(Status::cancelled(format!(
+            "Query {} was cancelled",
+            job.id()
+        ))),
+    }
+}
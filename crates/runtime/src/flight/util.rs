@@ -15,13 +15,24 @@ limitations under the License.
 */
 
 use arrow_flight::flight_service_server::FlightService;
+use cache::CacheControl;
 use tonic::{
-    metadata::{Ascii, MetadataValue},
+    metadata::{Ascii, MetadataMap, MetadataValue},
     Response,
 };
 
 use crate::flight::Service;
 
+/// Reads the per-query results cache control from the `cache-control` request metadata, using
+/// the same directives as the HTTP `Cache-Control` header.
+pub fn cache_control(metadata: &MetadataMap) -> CacheControl {
+    metadata
+        .get("cache-control")
+        .and_then(|value| value.to_str().ok())
+        .map(CacheControl::parse)
+        .unwrap_or_default()
+}
+
 pub fn attach_cache_metadata(
     response: &mut Response<<Service as FlightService>::DoGetStream>,
     from_cache: Option<bool>,
@@ -32,6 +32,7 @@ use tokio::{
 use tokio_rustls::TlsAcceptor;
 
 use crate::{
+    auth::Auth,
     config,
     datafusion::DataFusion,
     embeddings::vector_search::{self, parse_explicit_primary_keys},
@@ -40,6 +41,7 @@ use crate::{
     tls::TlsConfig,
 };
 
+mod auth;
 mod metrics;
 mod routes;
 mod v1;
@@ -66,15 +68,19 @@ pub(crate) async fn start<A>(
     config: Arc<config::Config>,
     with_metrics: Option<SocketAddr>,
     tls_config: Option<Arc<TlsConfig>>,
+    auth: Option<Arc<Auth>>,
 ) -> Result<()>
 where
     A: ToSocketAddrs + Debug,
 {
-    let vsearch = Arc::new(vector_search::VectorSearch::new(
-        Arc::clone(&df),
-        Arc::clone(&embeddings),
-        parse_explicit_primary_keys(Arc::clone(&app)).await,
-    ));
+    let vsearch = Arc::new(
+        vector_search::VectorSearch::new(
+            Arc::clone(&df),
+            Arc::clone(&embeddings),
+            parse_explicit_primary_keys(Arc::clone(&app)).await,
+        )
+        .with_llms(Arc::clone(&llms)),
+    );
     let routes = routes::routes(
         app,
         df,
@@ -84,6 +90,7 @@ where
         config,
         with_metrics,
         vsearch,
+        auth,
     );
 
     let listener = TcpListener::bind(&bind_address)
@@ -0,0 +1,229 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use spicepod::component::runtime::ApiKeyScope;
+
+use crate::auth::{Auth, Credentials};
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Endpoints that can be called without credentials, i.e. by health and readiness probes.
+const PUBLIC_PATHS: [&str; 2] = ["/health", "/v1/ready"];
+
+/// The scopes needed to call an endpoint, all of which must be granted. Metadata endpoints need no
+/// scope, only valid credentials.
+///
+/// Returns `None` for endpoints that aren't listed, which are denied so that new endpoints aren't
+/// callable without a scope by accident.
+fn required_scopes(path: &str) -> Option<&'static [ApiKeyScope]> {
+    match path {
+        "/v1/status" | "/v1/catalogs" | "/v1/datasets" | "/v1/spicepods" | "/v1/models" => {
+            Some(&[])
+        }
+        "/v1/sql" | "/v1/sql/jobs" | "/v1/sql/jobs/:id" | "/v1/sql/jobs/:id/results" => {
+            Some(&[ApiKeyScope::Sql])
+        }
+        "/v1/datasets/:name/acceleration/refresh" | "/v1/datasets/:name/acceleration" => {
+            Some(&[ApiKeyScope::Refresh])
+        }
+        // Generates SQL with a model and runs it
+        "/v1/nsql" => Some(&[ApiKeyScope::Sql, ApiKeyScope::Inference]),
+        "/v1/models/:name/predict"
+        | "/v1/predict"
+        | "/v1/chat/completions"
+        | "/v1/embeddings"
+        | "/v1/search" => Some(&[ApiKeyScope::Inference]),
+        _ => None,
+    }
+}
+
+/// Reads the credentials from the `Authorization` header (`Bearer` or `Basic`), or an API key from
+/// the `X-API-Key` header.
+fn request_credentials(headers: &HeaderMap) -> Option<Credentials> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        return value.to_str().ok().and_then(Credentials::from_header);
+    }
+
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|key| Credentials::Bearer(key.trim().to_string()))
+}
+
+fn unauthorized(message: &'static str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        message,
+    )
+        .into_response()
+}
+
+/// Authenticates requests, and adds their [`Grant`](crate::auth::Grant) to the request extensions
+/// for endpoints whose behavior depends on it.
+pub(crate) async fn authenticate(
+    Extension(auth): Extension<Option<Arc<Auth>>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(auth) = auth else {
+        return next.run(req).await;
+    };
+
+    let path = if let Some(matched_path) = req.extensions().get::<MatchedPath>() {
+        matched_path.as_str().to_owned()
+    } else {
+        req.uri().path().to_owned()
+    };
+
+    if PUBLIC_PATHS.contains(&path.as_str()) {
+        return next.run(req).await;
+    }
+
+    let Some(credentials) = request_credentials(req.headers()) else {
+        return unauthorized("No credentials provided");
+    };
+
+    let Some(grant) = auth.validate_request(&credentials) else {
+        return unauthorized("Invalid or expired credentials");
+    };
+
+    let Some(scopes) = required_scopes(&path) else {
+        return (
+            StatusCode::FORBIDDEN,
+            "The endpoint can't be called when auth is enabled",
+        )
+            .into_response();
+    };
+
+    if let Some(scope) = scopes.iter().find(|scope| !grant.allows(**scope)) {
+        return (
+            StatusCode::FORBIDDEN,
+            format!("The provided credentials don't grant the `{scope}` scope"),
+        )
+            .into_response();
+    }
+
+    req.extensions_mut().insert(grant);
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use axum::{middleware, routing::get, Router};
+    use secrecy::SecretString;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::auth::{ApiKey, Grant};
+
+    fn test_router() -> Router {
+        let auth = Auth::new(
+            vec![
+                ApiKey::new(SecretString::new("all-key".to_string()), Grant::all()),
+                ApiKey::new(
+                    SecretString::new("sql-key".to_string()),
+                    Grant::new([ApiKeyScope::Sql]),
+                ),
+                ApiKey::new(
+                    SecretString::new("inference-key".to_string()),
+                    Grant::new([ApiKeyScope::Inference]),
+                ),
+            ],
+            HashMap::new(),
+            Duration::from_secs(60),
+        );
+
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/v1/status", get(|| async { "ok" }))
+            .route("/v1/sql", get(|| async { "ok" }))
+            .route("/v1/nsql", get(|| async { "ok" }))
+            .route("/v1/unlisted", get(|| async { "ok" }))
+            .layer(middleware::from_fn(authenticate))
+            .layer(Extension(Some(Arc::new(auth))))
+    }
+
+    async fn status(path: &str, api_key: Option<&str>) -> StatusCode {
+        let mut request = Request::get(path);
+        if let Some(api_key) = api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        let request = request.body(Body::empty()).expect("valid request");
+
+        test_router()
+            .oneshot(request)
+            .await
+            .expect("infallible router")
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_public_paths() {
+        assert_eq!(status("/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_missing_or_invalid_credentials() {
+        assert_eq!(status("/v1/sql", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/v1/status", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status("/v1/sql", Some("wrong-key")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scopes() {
+        assert_eq!(status("/v1/sql", Some("sql-key")).await, StatusCode::OK);
+        assert_eq!(status("/v1/status", Some("sql-key")).await, StatusCode::OK);
+        assert_eq!(
+            status("/v1/sql", Some("inference-key")).await,
+            StatusCode::FORBIDDEN
+        );
+
+        // nsql needs both the sql and inference scopes
+        assert_eq!(
+            status("/v1/nsql", Some("inference-key")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status("/v1/nsql", Some("sql-key")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status("/v1/nsql", Some("all-key")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_paths_are_denied() {
+        assert_eq!(
+            status("/v1/unlisted", Some("all-key")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -14,6 +14,7 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::auth::Auth;
 use crate::embeddings::vector_search;
 use crate::model::EmbeddingModelStore;
 use crate::model::LLMModelStore;
@@ -36,7 +37,7 @@ use axum::{
 };
 use tokio::{sync::RwLock, time::Instant};
 
-use super::{metrics, v1};
+use super::{auth, metrics, v1};
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn routes(
@@ -48,10 +49,17 @@ pub(crate) fn routes(
     config: Arc<config::Config>,
     with_metrics: Option<SocketAddr>,
     vector_search: Arc<vector_search::VectorSearch>,
+    auth: Option<Arc<Auth>>,
 ) -> Router {
     let mut router = Router::new()
         .route("/health", get(|| async { "ok\n" }))
         .route("/v1/sql", post(v1::query::post))
+        .route("/v1/sql/jobs", post(v1::sql_jobs::post))
+        .route(
+            "/v1/sql/jobs/:id",
+            get(v1::sql_jobs::get).delete(v1::sql_jobs::delete),
+        )
+        .route("/v1/sql/jobs/:id/results", get(v1::sql_jobs::results))
         .route("/v1/status", get(v1::status::get))
         .route("/v1/catalogs", get(v1::catalogs::get))
         .route("/v1/datasets", get(v1::datasets::get))
@@ -83,6 +91,8 @@ pub(crate) fn routes(
     }
 
     router = router
+        .layer(middleware::from_fn(auth::authenticate))
+        .layer(Extension(auth))
         .layer(Extension(app))
         .layer(Extension(df))
         .layer(Extension(with_metrics))
@@ -24,10 +24,10 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use chrono::{DateTime, Utc};
 use datafusion::sql::TableReference;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
-use tract_core::tract_data::itertools::Itertools;
 
 use crate::{datafusion::DataFusion, status::ComponentStatus};
 
@@ -57,6 +57,10 @@ pub(crate) struct DatasetResponseItem {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<ComponentStatus>,
+
+    /// When the next scheduled acceleration refresh will start, in RFC 3339 format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_refresh: Option<String>,
 }
 
 pub(crate) async fn get(
@@ -83,20 +87,31 @@ pub(crate) async fn get(
         None => valid_datasets,
     };
 
-    let resp = datasets
-        .iter()
-        .map(|d| DatasetResponseItem {
+    let mut resp = Vec::with_capacity(datasets.len());
+    for d in &datasets {
+        let acceleration_enabled = d.acceleration.as_ref().is_some_and(|f| f.enabled);
+        let (status, next_refresh) = if params.status {
+            let next_refresh = if acceleration_enabled {
+                df.next_scheduled_refresh(&d.name)
+                    .await
+                    .map(|next| DateTime::<Utc>::from(next).to_rfc3339())
+            } else {
+                None
+            };
+            (Some(dataset_status(&df, d)), next_refresh)
+        } else {
+            (None, None)
+        };
+
+        resp.push(DatasetResponseItem {
             from: d.from.clone(),
             name: d.name.to_quoted_string(),
             replication_enabled: d.replication.as_ref().is_some_and(|f| f.enabled),
-            acceleration_enabled: d.acceleration.as_ref().is_some_and(|f| f.enabled),
-            status: if params.status {
-                Some(dataset_status(&df, d))
-            } else {
-                None
-            },
-        })
-        .collect_vec();
+            acceleration_enabled,
+            status,
+            next_refresh,
+        });
+    }
 
     match params.format {
         Format::Json => (status::StatusCode::OK, Json(resp)).into_response(),
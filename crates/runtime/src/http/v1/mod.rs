@@ -24,6 +24,7 @@ pub mod query;
 pub mod ready;
 pub mod search;
 pub mod spicepods;
+pub mod sql_jobs;
 pub mod status;
 
 use std::sync::Arc;
@@ -34,9 +35,10 @@ use crate::{
 };
 use arrow::array::RecordBatch;
 use axum::{
-    http::{HeaderMap, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
+use cache::CacheControl;
 use csv::Writer;
 use serde::{Deserialize, Serialize};
 
@@ -69,12 +71,30 @@ fn dataset_status(df: &DataFusion, ds: &Dataset) -> ComponentStatus {
     }
 }
 
-// Runs query and converts query results to HTTP response (as JSON).
-pub async fn sql_to_http_response(df: Arc<DataFusion>, sql: &str, nsql: Option<&str>) -> Response {
+/// Reads the per-query results cache control from the `Cache-Control` request header.
+fn cache_control(headers: &HeaderMap) -> CacheControl {
+    headers
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .map(CacheControl::parse)
+        .unwrap_or_default()
+}
+
+// Runs query and converts query results to HTTP response (as JSON). Inserts into `read_write`
+// datasets are only allowed if `allow_writes`.
+pub async fn sql_to_http_response(
+    df: Arc<DataFusion>,
+    sql: &str,
+    nsql: Option<&str>,
+    cache_control: CacheControl,
+    allow_writes: bool,
+) -> Response {
     let query = QueryBuilder::new(sql, Arc::clone(&df), Protocol::Http)
         .use_restricted_sql_options()
+        .allow_writes(allow_writes)
         .nsql(nsql)
         .protocol(Protocol::Http)
+        .cache_control(cache_control)
         .build();
 
     let (data, is_data_from_cache) = match query.run().await {
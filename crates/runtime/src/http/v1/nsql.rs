@@ -26,6 +26,7 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use cache::CacheControl;
 use datafusion_table_providers::sql::arrow_sql_gen::statement::CreateTableBuilder;
 use llms::{
     chat::{Error as ChatError, Result as ChatResult},
@@ -132,7 +133,14 @@ pub(crate) async fn post(
             let cleaned_query = clean_model_based_sql(&model_sql_query);
             tracing::trace!("Running query:\n{cleaned_query}");
 
-            sql_to_http_response(Arc::clone(&df), &cleaned_query, Some(&nsql_query)).await
+            sql_to_http_response(
+                Arc::clone(&df),
+                &cleaned_query,
+                Some(&nsql_query),
+                CacheControl::default(),
+                false,
+            )
+            .await
         }
         Ok(None) => {
             tracing::trace!("No query produced from NSQL model");
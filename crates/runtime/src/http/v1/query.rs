@@ -17,16 +17,29 @@ use std::sync::Arc;
 
 use axum::{
     body::Bytes,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Extension,
 };
 
-use crate::datafusion::DataFusion;
+use spicepod::component::runtime::ApiKeyScope;
 
-use super::sql_to_http_response;
+use crate::{auth::Grant, datafusion::DataFusion};
 
-pub(crate) async fn post(Extension(df): Extension<Arc<DataFusion>>, body: Bytes) -> Response {
+use super::{cache_control, sql_to_http_response};
+
+/// Runs a SQL query. The results cache can be bypassed or its TTL overridden with a
+/// `Cache-Control` header: `no-cache` to refresh the cached result, `no-store` to skip the cache
+/// entirely, or `max-age=<seconds>` to cache the result for a custom duration.
+///
+/// `INSERT INTO` statements can write to `read_write` datasets, if auth is disabled or the
+/// credentials grant the `write` scope.
+pub(crate) async fn post(
+    Extension(df): Extension<Arc<DataFusion>>,
+    grant: Option<Extension<Grant>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
     let query = match String::from_utf8(body.to_vec()) {
         Ok(query) => query,
         Err(e) => {
@@ -35,5 +48,6 @@ pub(crate) async fn post(Extension(df): Extension<Arc<DataFusion>>, body: Bytes)
         }
     };
 
-    sql_to_http_response(df, &query, None).await
+    let allow_writes = grant.map_or(true, |Extension(grant)| grant.allows(ApiKeyScope::Write));
+    sql_to_http_response(df, &query, None, cache_control(&headers), allow_writes).await
 }
@@ -0,0 +1,326 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use arrow::{array::RecordBatch, csv, datatypes::SchemaRef};
+use arrow_ipc::writer::StreamWriter;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::datafusion::{
+    query::{
+        jobs::{QueryJob, QueryJobStatus, ResultsLimit},
+        query_history::{self, QueryHistoryEntry},
+        Protocol,
+    },
+    DataFusion,
+};
+
+const DEFAULT_PAGE_SIZE: usize = 10_000;
+
+const TOTAL_ROWS_HEADER: &str = "X-Total-Rows";
+const NEXT_OFFSET_HEADER: &str = "X-Next-Offset";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ResultsFormat {
+    #[default]
+    Json,
+    Csv,
+    Arrow,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResultsQueryParams {
+    #[serde(default)]
+    format: ResultsFormat,
+
+    #[serde(default)]
+    offset: usize,
+
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct JobResponse {
+    job_id: String,
+    status: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sql: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows_produced: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_duration_secs: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    results_cache_hit: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl JobResponse {
+    fn new(job: &QueryJob, history: Option<QueryHistoryEntry>) -> Self {
+        Self {
+            job_id: job.id().to_string(),
+            status: job.status().to_string(),
+            sql: Some(job.sql().to_string()),
+            rows_produced: Some(job.rows_produced()),
+            query_duration_secs: history.as_ref().map(|entry| entry.query_duration_secs),
+            results_cache_hit: history.as_ref().map(|entry| entry.results_cache_hit),
+            error: job.error(),
+        }
+    }
+
+    /// Describes a job whose results have expired, from what was recorded in `query_history`.
+    fn from_history(job_id: Uuid, history: QueryHistoryEntry) -> Self {
+        let status = if history.error_message.is_some() {
+            QueryJobStatus::Failed
+        } else {
+            QueryJobStatus::Completed
+        };
+
+        Self {
+            job_id: job_id.to_string(),
+            status: status.to_string(),
+            sql: None,
+            rows_produced: Some(history.rows_produced),
+            query_duration_secs: Some(history.query_duration_secs),
+            results_cache_hit: Some(history.results_cache_hit),
+            error: history.error_message,
+        }
+    }
+}
+
+/// Submits a query to run in the background and returns the id to poll its status with.
+pub(crate) async fn post(Extension(df): Extension<Arc<DataFusion>>, body: Bytes) -> Response {
+    let query = match String::from_utf8(body.to_vec()) {
+        Ok(query) => query,
+        Err(e) => {
+            tracing::debug!("Error reading query: {e}");
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+
+    match df
+        .query_jobs()
+        .submit(Arc::clone(&df), &query, Protocol::Http)
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(JobResponse::new(&job, None))).into_response(),
+        Err(e) => (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+    }
+}
+
+pub(crate) async fn get(
+    Extension(df): Extension<Arc<DataFusion>>,
+    Path(job_id): Path<String>,
+) -> Response {
+    let job_id = match parse_job_id(&job_id) {
+        Ok(job_id) => job_id,
+        Err(response) => return response,
+    };
+    let job = df.query_jobs().get(&job_id);
+
+    if let Some(job) = &job {
+        if job.status() == QueryJobStatus::Running {
+            return (StatusCode::OK, Json(JobResponse::new(job, None))).into_response();
+        }
+    }
+
+    let history = match query_history::get_query_history_entry(&df, job_id).await {
+        Ok(history) => history,
+        Err(e) => {
+            tracing::debug!("Unable to read query_history for job {job_id}: {e}");
+            None
+        }
+    };
+
+    match (job, history) {
+        (Some(job), history) => {
+            (StatusCode::OK, Json(JobResponse::new(&job, history))).into_response()
+        }
+        (None, Some(history)) => (
+            StatusCode::OK,
+            Json(JobResponse::from_history(job_id, history)),
+        )
+            .into_response(),
+        (None, None) => not_found(job_id),
+    }
+}
+
+/// Cancels a running job.
+pub(crate) async fn delete(
+    Extension(df): Extension<Arc<DataFusion>>,
+    Path(job_id): Path<String>,
+) -> Response {
+    let job_id = match parse_job_id(&job_id) {
+        Ok(job_id) => job_id,
+        Err(response) => return response,
+    };
+    let Some(job) = df.query_jobs().get(&job_id) else {
+        return not_found(job_id);
+    };
+
+    if !job.cancel() {
+        return (
+            StatusCode::CONFLICT,
+            format!("Job {job_id} is already {}", job.status()),
+        )
+            .into_response();
+    }
+
+    (StatusCode::OK, Json(JobResponse::new(&job, None))).into_response()
+}
+
+/// Returns a page of the results of a completed job. The total number of rows is returned in the
+/// `X-Total-Rows` header, and the offset of the next page (if any) in `X-Next-Offset`.
+///
+/// A completed job holds all of its results in memory, so the total is exact. Jobs whose results
+/// exceed the memory limit for a job fail instead, and their results are rejected with
+/// `413 Payload Too Large`. Jobs whose results didn't fit in the memory left for the results of all
+/// jobs are rejected with `503 Service Unavailable`, as they can succeed once other results expire.
+pub(crate) async fn results(
+    Extension(df): Extension<Arc<DataFusion>>,
+    Path(job_id): Path<String>,
+    Query(params): Query<ResultsQueryParams>,
+) -> Response {
+    let job_id = match parse_job_id(&job_id) {
+        Ok(job_id) => job_id,
+        Err(response) => return response,
+    };
+    let Some(job) = df.query_jobs().get(&job_id) else {
+        return not_found(job_id);
+    };
+
+    match job.status() {
+        QueryJobStatus::Completed => {}
+        QueryJobStatus::Failed => {
+            // Jobs keep all their results in memory, and fail once they exceed a limit.
+            let status = match job.exceeded_limit() {
+                Some(ResultsLimit::Job) => StatusCode::PAYLOAD_TOO_LARGE,
+                Some(ResultsLimit::Total) => StatusCode::SERVICE_UNAVAILABLE,
+                None => StatusCode::BAD_REQUEST,
+            };
+            return (
+                status,
+                job.error()
+                    .unwrap_or_else(|| format!("Job {job_id} failed")),
+            )
+                .into_response();
+        }
+        status @ (QueryJobStatus::Running | QueryJobStatus::Cancelled) => {
+            return (StatusCode::CONFLICT, format!("Job {job_id} is {status}")).into_response();
+        }
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let (Some(schema), Some(page)) = (job.schema(), job.results_page(params.offset, limit)) else {
+        return not_found(job_id);
+    };
+
+    let body = match params.format {
+        ResultsFormat::Json => batches_to_json(&page),
+        ResultsFormat::Csv => batches_to_csv(&page),
+        ResultsFormat::Arrow => batches_to_arrow(&schema, &page),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::debug!("Error converting results of job {job_id}: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+
+    let total_rows = job.rows_produced();
+    let page_rows: usize = page.iter().map(RecordBatch::num_rows).sum();
+    let next_offset = (params.offset + page_rows) as u64;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(match params.format {
+            ResultsFormat::Json => "application/json",
+            ResultsFormat::Csv => "text/csv",
+            ResultsFormat::Arrow => "application/vnd.apache.arrow.stream",
+        }),
+    );
+    headers.insert(TOTAL_ROWS_HEADER, HeaderValue::from(total_rows));
+    if page_rows > 0 && next_offset < total_rows {
+        headers.insert(NEXT_OFFSET_HEADER, HeaderValue::from(next_offset));
+    }
+
+    (StatusCode::OK, headers, body).into_response()
+}
+
+fn parse_job_id(job_id: &str) -> Result<Uuid, Response> {
+    Uuid::parse_str(job_id).map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid job id '{job_id}'"),
+        )
+            .into_response()
+    })
+}
+
+fn not_found(job_id: Uuid) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Job {job_id} not found or expired"),
+    )
+        .into_response()
+}
+
+fn batches_to_json(batches: &[RecordBatch]) -> Result<Vec<u8>, arrow::error::ArrowError> {
+    if batches.is_empty() {
+        return Ok(b"[]".to_vec());
+    }
+
+    let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+    writer.write_batches(batches.iter().collect::<Vec<&RecordBatch>>().as_slice())?;
+    writer.finish()?;
+    Ok(writer.into_inner())
+}
+
+fn batches_to_csv(batches: &[RecordBatch]) -> Result<Vec<u8>, arrow::error::ArrowError> {
+    let mut writer = csv::Writer::new(Vec::new());
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    Ok(writer.into_inner())
+}
+
+fn batches_to_arrow(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<Vec<u8>, arrow::error::ArrowError> {
+    let mut writer = StreamWriter::try_new(Vec::new(), schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    writer.into_inner()
+}
@@ -68,6 +68,7 @@ use util::{retry, RetryError};
 
 use crate::extension::Extension;
 pub mod accelerated_table;
+pub mod auth;
 mod builder;
 pub mod component;
 pub mod config;
@@ -115,6 +116,9 @@ pub enum Error {
     #[snafu(display("Unable to start Flight server: {source}"))]
     UnableToStartFlightServer { source: flight::Error },
 
+    #[snafu(display("Unable to configure auth: {source}"))]
+    UnableToConfigureAuth { source: auth::Error },
+
     #[snafu(display("Unable to start OpenTelemetry server: {source}"))]
     UnableToStartOpenTelemetryServer { source: opentelemetry::Error },
 
@@ -358,6 +362,8 @@ impl Runtime {
         self.register_metrics_table(self.prometheus_registry.is_some())
             .await?;
 
+        let auth = self.load_auth().await?;
+
         let http_server_future = tokio::spawn(http::start(
             config.http_bind_address,
             Arc::clone(&self.app),
@@ -368,6 +374,7 @@ impl Runtime {
             config.clone().into(),
             self.metrics_endpoint,
             tls_config.clone(),
+            auth.clone(),
         ));
 
         // Spawn the metrics server in the background
@@ -387,6 +394,7 @@ impl Runtime {
             config.flight_bind_address,
             Arc::clone(&self.df),
             tls_config.clone(),
+            auth.clone(),
         ));
         let open_telemetry_server_future = tokio::spawn(opentelemetry::start(
             config.open_telemetry_bind_address,
@@ -654,6 +662,17 @@ impl Runtime {
                     }
                 };
 
+                match dataaccelerator::snapshot::bootstrap(ds, accelerator.as_ref()).await {
+                    Ok(true) => self.df.mark_bootstrapped(ds.name.clone()),
+                    Ok(false) => {}
+                    Err(err) => {
+                        tracing::warn!(
+                            "Unable to bootstrap dataset {} from a snapshot, loading it from the source instead: {err}",
+                            ds.name
+                        );
+                    }
+                }
+
                 match accelerator
                     .init(ds)
                     .await
@@ -1565,6 +1584,25 @@ impl Runtime {
         Ok(())
     }
 
+    /// Loads the endpoint auth configured in the spicepod, if enabled.
+    async fn load_auth(&self) -> Result<Option<Arc<auth::Auth>>> {
+        let app = self.app.read().await;
+        let Some(auth_config) = app.as_ref().and_then(|app| app.runtime.auth.as_ref()) else {
+            return Ok(None);
+        };
+
+        let secrets = self.secrets.read().await;
+        let auth = auth::Auth::try_from_config(auth_config, &secrets)
+            .await
+            .context(UnableToConfigureAuthSnafu)?;
+
+        if auth.is_some() {
+            tracing::info!("Endpoints secured with authentication");
+        }
+
+        Ok(auth.map(Arc::new))
+    }
+
     pub async fn init_results_cache(&self) {
         let app = self.app.read().await;
         let Some(app) = app.as_ref() else { return };
@@ -89,7 +89,8 @@ impl SpiceModelTool for DocumentSimilarityTool {
                 rt.datafusion(),
                 Arc::clone(&rt.embeds),
                 parse_explicit_primary_keys(Arc::clone(&rt.app)).await,
-            );
+            )
+            .with_llms(Arc::clone(&rt.llms));
 
             // If model provides a `where` keyword in their [`where_cond`] field, strip it.
             if let Some(cond) = &req.where_cond {
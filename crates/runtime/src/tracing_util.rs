@@ -66,9 +66,14 @@ fn dataset_acceleration_info(
         RefreshMode::Changes => {
             info.push_str(", changes");
         }
+        RefreshMode::Upsert => {
+            info.push_str(", upsert");
+        }
     }
 
-    if let Some(refresh_interval) = &acceleration.refresh_check_interval {
+    if let Some(refresh_cron) = &acceleration.refresh_cron {
+        info.push_str(&format!(", '{refresh_cron}' refresh"));
+    } else if let Some(refresh_interval) = &acceleration.refresh_check_interval {
         info.push_str(&format!(", {refresh_interval:#?} refresh"));
     }
     if let Some(retention_check_interval) = &acceleration.retention_check_interval {
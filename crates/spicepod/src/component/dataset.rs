@@ -157,6 +157,8 @@ pub mod acceleration {
         Full,
         Append,
         Changes,
+        /// Upserts the rows updated since the last refresh into the acceleration on its `primary_key`
+        Upsert,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -253,6 +255,12 @@ pub mod acceleration {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub refresh_check_interval: Option<String>,
 
+        /// A cron expression to refresh the acceleration on, in UTC, i.e. `0 6 * * *` to refresh
+        /// every day at 06:00. Days of the week are numbered from 0 (Sunday) to 6 (Saturday), as in
+        /// standard cron. Takes precedence over `refresh_check_interval`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refresh_cron: Option<String>,
+
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub refresh_sql: Option<String>,
 
@@ -274,6 +282,11 @@ pub mod acceleration {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub refresh_jitter_max: Option<String>,
 
+        /// How often to remove rows that were deleted from the source, by comparing its primary
+        /// keys with the acceleration. Only applies to the `upsert` refresh mode.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refresh_delete_check_interval: Option<String>,
+
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub params: Option<Params>,
 
@@ -297,6 +310,34 @@ pub mod acceleration {
 
         #[serde(default, skip_serializing_if = "HashMap::is_empty")]
         pub on_conflict: HashMap<String, OnConflictBehavior>,
+
+        /// Periodically snapshot a file-mode `duckdb` or `sqlite` acceleration to an object store,
+        /// and bootstrap new replicas from the newest snapshot instead of loading from the source.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub snapshots: Option<Snapshots>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    #[cfg_attr(feature = "schemars", derive(JsonSchema))]
+    pub struct Snapshots {
+        /// The object store URL to write snapshots to, i.e. `s3://my-bucket/snapshots/` or
+        /// `file:///mnt/snapshots/`
+        pub location: String,
+
+        /// The minimum time between snapshots, which are taken after a successful refresh. If not
+        /// set, a snapshot is taken after every refresh.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub interval: Option<String>,
+
+        /// How many snapshots to keep. Defaults to 3.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub retain: Option<usize>,
+
+        /// Whether to bootstrap the acceleration from the newest snapshot on startup when there's
+        /// no local acceleration file. Defaults to `true`.
+        #[serde(default = "default_true")]
+        pub bootstrap: bool,
     }
 
     #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -316,6 +357,7 @@ pub mod acceleration {
                 engine: None,
                 refresh_mode: None,
                 refresh_check_interval: None,
+                refresh_cron: None,
                 refresh_sql: None,
                 refresh_data_window: None,
                 refresh_append_overlap: None,
@@ -323,6 +365,7 @@ pub mod acceleration {
                 refresh_retry_max_attempts: None,
                 refresh_jitter_enabled: false,
                 refresh_jitter_max: None,
+                refresh_delete_check_interval: None,
                 params: None,
                 retention_period: None,
                 retention_check_interval: None,
@@ -331,6 +374,7 @@ pub mod acceleration {
                 indexes: HashMap::default(),
                 primary_key: None,
                 on_conflict: HashMap::default(),
+                snapshots: None,
             }
         }
     }
@@ -148,4 +148,196 @@ pub struct ColumnEmbeddingConfig {
 
     #[serde(rename = "column_pk", skip_serializing_if = "Option::is_none")]
     pub primary_keys: Option<Vec<String>>,
+
+    /// How vector search measures the similarity of the column's embeddings to a query.
+    #[serde(default)]
+    pub metric: DistanceMetric,
+
+    /// An approximate nearest neighbour index over the embeddings of an accelerated column, used by
+    /// vector search instead of computing the distance to every row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<VectorIndexConfig>,
+
+    /// A full-text index over the values of an accelerated column, used by keyword and hybrid
+    /// search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_text: Option<FullTextIndexConfig>,
+
+    /// Split long values of the column into chunks that are embedded separately, instead of
+    /// embedding each value as a single vector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunking: Option<EmbeddingChunkConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetric {
+    /// The squared Euclidean distance.
+    #[default]
+    L2,
+
+    /// One minus the cosine of the angle between embeddings, which ignores their magnitude.
+    Cosine,
+
+    /// The dot product, where larger values are more similar. Equivalent to cosine for normalized
+    /// embeddings, and cheaper to compute.
+    Dot,
+}
+
+impl Display for DistanceMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DistanceMetric::L2 => write!(f, "l2"),
+            DistanceMetric::Cosine => write!(f, "cosine"),
+            DistanceMetric::Dot => write!(f, "dot"),
+        }
+    }
+}
+
+/// The parameters of the BM25 ranking function used by a full-text index.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct FullTextIndexConfig {
+    /// How quickly the score of a document saturates as a term repeats in it.
+    #[serde(default = "default_k1")]
+    pub k1: f32,
+
+    /// How much the score of a document is normalized by its length, from 0 (not at all) to 1
+    /// (fully).
+    #[serde(default = "default_b")]
+    pub b: f32,
+}
+
+impl Default for FullTextIndexConfig {
+    fn default() -> Self {
+        Self {
+            k1: default_k1(),
+            b: default_b(),
+        }
+    }
+}
+
+const fn default_k1() -> f32 {
+    1.2
+}
+
+const fn default_b() -> f32 {
+    0.75
+}
+
+/// How the values of a column are split into chunks before they are embedded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct EmbeddingChunkConfig {
+    /// The maximum size of a chunk, in `unit`s. Chunks are only larger when a single character or
+    /// token is.
+    pub target_chunk_size: usize,
+
+    /// The size, in `unit`s, of the end of a chunk that is repeated at the start of the next one.
+    #[serde(default)]
+    pub overlap_size: usize,
+
+    #[serde(default)]
+    pub unit: ChunkSizeUnit,
+
+    #[serde(default)]
+    pub splitter: ChunkSplitter,
+
+    /// Remove the whitespace around chunks.
+    #[serde(default = "default_trim_whitespace")]
+    pub trim_whitespace: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkSizeUnit {
+    #[default]
+    Characters,
+
+    /// Words and punctuation marks, which approximate the tokens of most embedding models.
+    Tokens,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkSplitter {
+    /// Split anywhere once a chunk reaches the target size.
+    #[default]
+    Fixed,
+
+    /// Keep sentences together unless they are larger than a chunk.
+    Sentence,
+
+    /// Keep markdown sections, paragraphs, lists and code blocks together unless they are larger
+    /// than a chunk, then keep sentences together.
+    Markdown,
+}
+
+const fn default_trim_whitespace() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum VectorIndexType {
+    /// Hierarchical navigable small world graph.
+    #[default]
+    Hnsw,
+}
+
+impl Display for VectorIndexType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VectorIndexType::Hnsw => write!(f, "hnsw"),
+        }
+    }
+}
+
+/// Recall and latency trade-offs of a vector index. Higher values improve recall at the cost of
+/// memory, build time or search latency.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct VectorIndexConfig {
+    #[serde(rename = "type", default)]
+    pub index_type: VectorIndexType,
+
+    /// The number of neighbours each vector is linked to in the graph.
+    #[serde(default = "default_m")]
+    pub m: usize,
+
+    /// The number of candidates considered when inserting a vector.
+    #[serde(default = "default_ef_construction")]
+    pub ef_construction: usize,
+
+    /// The number of candidates considered when searching. At least as many candidates as results
+    /// requested are always considered.
+    #[serde(default = "default_ef_search")]
+    pub ef_search: usize,
+}
+
+impl Default for VectorIndexConfig {
+    fn default() -> Self {
+        Self {
+            index_type: VectorIndexType::default(),
+            m: default_m(),
+            ef_construction: default_ef_construction(),
+            ef_search: default_ef_search(),
+        }
+    }
+}
+
+const fn default_m() -> usize {
+    16
+}
+
+const fn default_ef_construction() -> usize {
+    200
+}
+
+const fn default_ef_search() -> usize {
+    64
 }
@@ -17,6 +17,7 @@ limitations under the License.
 #[cfg(feature = "schemars")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[cfg_attr(feature = "schemars", derive(JsonSchema))]
@@ -31,6 +32,9 @@ pub struct Runtime {
     pub tracing: Option<TracingConfig>,
 
     pub telemetry: Option<TelemetryConfig>,
+
+    /// If set, the runtime will require clients to authenticate
+    pub auth: Option<AuthConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -41,6 +45,23 @@ pub struct ResultsCache {
     pub cache_max_size: Option<String>,
     pub item_ttl: Option<String>,
     pub eviction_policy: Option<String>,
+
+    /// Where cached results are stored. Defaults to `memory`.
+    pub backend: Option<ResultsCacheBackend>,
+
+    /// The directory to store cached results in, for the `disk` backend.
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub enum ResultsCacheBackend {
+    /// Results are kept in memory, and lost on restart
+    #[default]
+    Memory,
+    /// Results are persisted to local disk as Arrow IPC files, and survive a restart
+    Disk,
 }
 
 const fn default_true() -> bool {
@@ -54,6 +75,8 @@ impl Default for ResultsCache {
             cache_max_size: None,
             item_ttl: None,
             eviction_policy: None,
+            backend: None,
+            path: None,
         }
     }
 }
@@ -92,3 +115,66 @@ pub struct TracingConfig {
 pub struct TelemetryConfig {
     pub enabled: bool,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct AuthConfig {
+    /// If set, the runtime will reject requests that don't carry valid credentials
+    pub enabled: bool,
+
+    /// API keys accepted by the runtime, i.e. `key: ${secrets:my_api_key}`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<ApiKey>,
+
+    /// Users that can authenticate with a username and password
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub basic_auth: Vec<BasicAuthUser>,
+
+    /// How long a token issued by the Flight handshake remains valid, i.e. `1h`
+    pub token_ttl: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct ApiKey {
+    pub key: String,
+
+    /// The endpoints this key can call. If not set, the key can call all endpoints.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<ApiKeyScope>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub enum ApiKeyScope {
+    /// Run read-only SQL queries, i.e. `/v1/sql` and the Flight query endpoints
+    Sql,
+    /// Trigger and configure dataset acceleration refreshes
+    Refresh,
+    /// Call models, i.e. `/v1/chat/completions`, `/v1/embeddings` and `/v1/search`
+    Inference,
+    /// Write data to datasets, i.e. with the Flight `DoPut` endpoint
+    Write,
+}
+
+impl Display for ApiKeyScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiKeyScope::Sql => write!(f, "sql"),
+            ApiKeyScope::Refresh => write!(f, "refresh"),
+            ApiKeyScope::Inference => write!(f, "inference"),
+            ApiKeyScope::Write => write!(f, "write"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct BasicAuthUser {
+    pub username: String,
+    pub password: String,
+}
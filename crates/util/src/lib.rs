@@ -21,6 +21,7 @@ use std::{
 
 pub mod fibonacci_backoff;
 pub use backoff::future::retry;
+pub use backoff::future::retry_notify;
 pub use backoff::Error as RetryError;
 
 #[allow(clippy::cast_precision_loss)]